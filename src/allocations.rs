@@ -0,0 +1,141 @@
+// Keeps drawing from allocating. The overlay draws up to every frame while it animates, so once a
+// frame has been drawn, drawing the next into the same state and canvas mustn't touch the heap,
+// and neither must the rest of a turn of the main loop where nothing new came in.
+//
+// Allocations are counted by a global allocator that only the tests use, per thread, so the tests
+// running next to this one don't count.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    os::{fd::AsRawFd, unix::net::UnixStream},
+};
+
+use dbus::Path;
+use fprint_prompt::rpc;
+
+use crate::{
+    ipc::socket::{RpcSocket, Version},
+    prompts::{Prompts, SessionKind},
+    render,
+    snapshots::{cases, state},
+    theme::Style,
+    wait_readable,
+};
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Counting;
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+fn count() {
+    // Gone while the thread exits.
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// How many times `f` allocated on this thread.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn drawing_again_does_not_allocate() {
+    for style in Style::ALL {
+        for case in cases() {
+            let mut state = state(&case, style, 2);
+            // With spans to merge, and the ruler over it.
+            state.pos.notch = 6;
+            state.pos.segments = vec![(0, 20), (10, 30), (40, 60)];
+            state.ruler = Some((state.pos.clone(), 0xFF00FF00));
+            let shown = state.pos.clone();
+
+            let mut canvas =
+                vec![0; state.pos.win_width() as usize * state.pos.win_height() as usize * 4];
+            render(&mut canvas, &mut state);
+            let allocated = allocations(|| {
+                state.pos.clone_from(&shown);
+                render(&mut canvas, &mut state);
+            });
+            assert_eq!(
+                allocated,
+                0,
+                "drawing {} again in the {} style allocated",
+                case.name,
+                style.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn a_quiet_turn_does_not_allocate() {
+    let path = std::env::temp_dir().join(format!(
+        "fprint-prompt-allocations-{}.sock",
+        std::process::id()
+    ));
+    let mut socket = RpcSocket::bind(path).unwrap();
+    let (compositor, _) = UnixStream::pair().unwrap();
+    let device = Path::from("/net/reactivated/Fprint/Device/0");
+    let mut prompts = Prompts::default();
+    prompts.start(
+        device.clone(),
+        SessionKind::Verify,
+        "Scan your right index finger".to_string(),
+    );
+    let version = |prompts: &Prompts| Version {
+        visible: true,
+        prompts: prompts.generation(),
+        surfaces: 1,
+        surface_count: 1,
+    };
+
+    let case = &cases()[0];
+    let mut frame = state(case, Style::Bar, 1);
+    let drawn = state(case, Style::Bar, 1);
+    let mut canvas = vec![0; frame.pos.win_width() as usize * frame.pos.win_height() as usize * 4];
+    let mut pollfds = vec![];
+
+    // What the turn before did.
+    wait_readable(&mut pollfds, std::iter::once(compositor.as_raw_fd()), 0);
+    socket.update(version(&prompts), rpc::State::default);
+    socket.poll();
+    render(&mut canvas, &mut frame);
+
+    let allocated = allocations(|| {
+        wait_readable(&mut pollfds, std::iter::once(compositor.as_raw_fd()), 0);
+        prompts.touch(&device, SessionKind::Verify);
+        socket.update(version(&prompts), || panic!("the state was built again"));
+        socket.poll();
+        if !frame.looks_like(&drawn) {
+            render(&mut canvas, &mut frame);
+        }
+    });
+    assert_eq!(allocated, 0);
+}
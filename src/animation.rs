@@ -0,0 +1,209 @@
+// Time-based effects on the bar. The surfaces redraw on every frame callback while one of these is
+// going, so they only need to know how long something has been going on.
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::{Attention, BarSize, BarState},
+    prompts::Outcome,
+    shape::{Mark, Shape},
+};
+
+const FLASH_PERIOD: Duration = Duration::from_millis(400);
+const BLINK_PERIOD: Duration = Duration::from_millis(150);
+/// How long the outcome of a verification stays on screen.
+pub const OUTCOME_DURATION: Duration = Duration::from_millis(900);
+
+/// Opacity of the bar `elapsed` into a verification: fading out and back in a few times, then
+/// staying steady.
+pub fn flash_opacity(attention: &Attention, elapsed: Duration) -> f32 {
+    let periods = elapsed.as_secs_f32() / FLASH_PERIOD.as_secs_f32();
+    if periods >= attention.flashes as f32 {
+        return 1.;
+    }
+
+    // Down and back up once per period.
+    let depth = 1. - (periods.fract() * 2. - 1.).abs();
+    1. - depth * attention.intensity
+}
+
+/// Whether the bar is still flashing `elapsed` into a verification.
+pub fn is_flashing(attention: &Attention, elapsed: Duration) -> bool {
+    elapsed.as_secs_f32() / FLASH_PERIOD.as_secs_f32() < attention.flashes as f32
+}
+
+/// How much of a verification's time is left `elapsed` into it, from 1 down to 0, or None when
+/// the timeout isn't shown.
+pub fn countdown(timeout: u32, elapsed: Duration) -> Option<f32> {
+    (timeout > 0).then(|| (1. - elapsed.as_secs_f32() / timeout as f32).clamp(0., 1.))
+}
+
+/// Opacity of the bar `elapsed` after a verification finished. Success and failure are told apart
+/// by more than their colors: a match holds and then fades out, a failure blinks, and being sent
+/// to the password holds until it's over.
+pub fn outcome_opacity(outcome: Outcome, elapsed: Duration) -> f32 {
+    let progress = elapsed.as_secs_f32() / OUTCOME_DURATION.as_secs_f32();
+
+    match outcome {
+        Outcome::Success => (1. - (progress - 0.5).max(0.) * 2.).max(0.),
+        Outcome::Failure => {
+            let blinks = elapsed.as_millis() / BLINK_PERIOD.as_millis();
+            if blinks.is_multiple_of(2) {
+                1.
+            } else {
+                0.
+            }
+        }
+        Outcome::Fallback | Outcome::Busy | Outcome::Disconnected => 1.,
+    }
+}
+
+/// Eases the bar from one size to another when its state changes.
+pub struct Resize {
+    from: BarSize,
+    to: BarSize,
+    started: Instant,
+}
+
+impl Resize {
+    const DURATION: Duration = Duration::from_millis(150);
+
+    pub fn new(size: BarSize) -> Self {
+        Resize {
+            from: size,
+            to: size,
+            started: Instant::now(),
+        }
+    }
+
+    /// Heads for a new size, starting from wherever the bar is now.
+    pub fn retarget(&mut self, to: BarSize) {
+        if to != self.to {
+            self.from = self.current();
+            self.to = to;
+            self.started = Instant::now();
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.started.elapsed() >= Self::DURATION
+    }
+
+    pub fn current(&self) -> BarSize {
+        let t = (self.started.elapsed().as_secs_f32() / Self::DURATION.as_secs_f32()).min(1.);
+        let lerp =
+            |from: u32, to: u32| (from as f32 + (to as f32 - from as f32) * t).round() as u32;
+
+        BarSize {
+            length: lerp(self.from.length, self.to.length),
+            thickness: lerp(self.from.thickness, self.to.thickness),
+        }
+    }
+}
+
+/// Crossfades the indicators when the bar's state changes, like from scanning to a retry and back,
+/// instead of cutting from one to the other. Colors blend, and a mark fades out before the next one
+/// fades in.
+#[derive(Default)]
+pub struct Handoff {
+    /// What the bar is handing off to, to tell when that changes.
+    state: Option<BarState>,
+    shapes: Vec<Option<Shape>>,
+    /// What was on screen when it did.
+    from_colors: Vec<u32>,
+    from_marks: Vec<Option<Mark>>,
+    started: Option<Instant>,
+    /// What the last frame showed, to hand off from.
+    last_colors: Vec<u32>,
+    last_marks: Vec<Option<Mark>>,
+}
+
+impl Handoff {
+    const DURATION: Duration = Duration::from_millis(150);
+
+    pub fn is_done(&self) -> bool {
+        self.started
+            .is_none_or(|started| started.elapsed() >= Self::DURATION)
+    }
+
+    /// Blends a frame's colors and marks with those shown before its state, starting a handoff when
+    /// the state just changed.
+    pub fn apply(
+        &mut self,
+        state: Option<BarState>,
+        colors: &mut [u32],
+        marks: &mut [Option<Mark>],
+    ) {
+        let shapes = marks.iter().map(|mark| mark.map(|mark| mark.shape));
+        if state != self.state || !shapes.clone().eq(self.shapes.iter().copied()) {
+            self.state = state;
+            self.shapes.clear();
+            self.shapes.extend(shapes);
+            // From nothing at all, the prompt appears as it always has.
+            if !self.last_colors.is_empty() {
+                self.from_colors.clone_from(&self.last_colors);
+                self.from_marks.clone_from(&self.last_marks);
+                self.started = Some(Instant::now());
+            }
+        }
+
+        if let Some(started) = self.started.filter(|_| !self.is_done()) {
+            let t = ease(started.elapsed().as_secs_f32() / Self::DURATION.as_secs_f32());
+            if self.from_colors.len() == colors.len() {
+                for (color, from) in colors.iter_mut().zip(&self.from_colors) {
+                    *color = mix(*from, *color, t);
+                }
+            }
+            if self.from_marks.len() == marks.len() {
+                for (mark, from) in marks.iter_mut().zip(&self.from_marks) {
+                    *mark = hand_off(*from, *mark, t);
+                }
+            }
+        }
+
+        self.last_colors.clear();
+        self.last_colors.extend_from_slice(colors);
+        self.last_marks.clear();
+        self.last_marks.extend_from_slice(marks);
+    }
+}
+
+/// Slow at both ends.
+fn ease(t: f32) -> f32 {
+    let t = t.clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+/// Partway from one premultiplied color to another, channel by channel.
+fn mix(from: u32, to: u32, t: f32) -> u32 {
+    let (from, to) = (from.to_le_bytes(), to.to_le_bytes());
+    u32::from_le_bytes(std::array::from_fn(|channel| {
+        (from[channel] as f32 + (to[channel] as f32 - from[channel] as f32) * t).round() as u8
+    }))
+}
+
+/// The mark partway from one to another. A mark that stays keeps its own opacity, one that
+/// appears or goes fades, and one that's swapped fades out for the first half and in for the
+/// second.
+fn hand_off(from: Option<Mark>, to: Option<Mark>, t: f32) -> Option<Mark> {
+    let faded = |mark: Mark, opacity: f32| Mark {
+        opacity: mark.opacity * opacity,
+        ..mark
+    };
+    match (from, to) {
+        (Some(from), Some(to)) if from.shape == to.shape => Some(to),
+        (Some(from), None) => Some(faded(from, 1. - t)),
+        (None, Some(to)) => Some(faded(to, t)),
+        (Some(from), Some(_)) if t < 0.5 => Some(faded(from, 1. - t * 2.)),
+        (Some(_), Some(to)) => Some(faded(to, t * 2. - 1.)),
+        (None, None) => None,
+    }
+}
+
+/// Scales every channel, since the buffer holds premultiplied alpha.
+pub fn fade(argb: u32, opacity: f32) -> u32 {
+    u32::from_le_bytes(
+        argb.to_le_bytes()
+            .map(|channel| (channel as f32 * opacity).round() as u8),
+    )
+}
@@ -0,0 +1,90 @@
+// Telling which application a verification is for, for the `[app."id"]` sections. fprintd doesn't
+// say who claimed the reader, so it's taken to be the window that has focus as the verification
+// starts, which wlr-foreign-toplevel-management tells us about. A sudo in a terminal counts as the
+// terminal's. The compositor is only asked about windows once there are `[app]` sections.
+
+use std::collections::HashMap;
+
+use smithay_client_toolkit::reexports::protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1,
+};
+use wayland_client::{backend::ObjectId, globals::GlobalList, Proxy, QueueHandle};
+
+use crate::SimpleLayer;
+
+#[derive(Default)]
+pub struct Toplevels {
+    _manager: Option<ZwlrForeignToplevelManagerV1>,
+    /// Whether the manager was bound, or tried to be.
+    bound: bool,
+    windows: HashMap<ObjectId, Window>,
+}
+
+#[derive(Default)]
+struct Window {
+    app_id: String,
+    focused: bool,
+    /// Sent since the last done, which applies them all at once.
+    pending_app_id: Option<String>,
+    pending_focused: Option<bool>,
+}
+
+impl Toplevels {
+    /// Starts following windows the first time there are `apps` to pick from. Without a manager,
+    /// no app is ever known.
+    pub fn follow(&mut self, apps: &[String], globals: &GlobalList, qh: &QueueHandle<SimpleLayer>) {
+        if apps.is_empty() || self.bound {
+            return;
+        }
+        self.bound = true;
+        self._manager = globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(qh, 1..=3, ())
+            .map_err(|e| {
+                log::warn!("can't tell which app a verification is for, ignoring [app]: {e}")
+            })
+            .ok();
+    }
+
+    pub fn handle(
+        &mut self,
+        toplevel: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::{Event, State};
+
+        let window = self.windows.entry(toplevel.id()).or_default();
+        match event {
+            Event::AppId { app_id } => window.pending_app_id = Some(app_id),
+            Event::State { state } => {
+                let activated = State::Activated as u32;
+                window.pending_focused = Some(
+                    state
+                        .chunks_exact(4)
+                        .any(|state| state == activated.to_ne_bytes()),
+                );
+            }
+            Event::Done => {
+                if let Some(app_id) = window.pending_app_id.take() {
+                    window.app_id = app_id;
+                }
+                if let Some(focused) = window.pending_focused.take() {
+                    window.focused = focused;
+                }
+            }
+            Event::Closed => {
+                self.windows.remove(&toplevel.id());
+                toplevel.destroy();
+            }
+            _ => (),
+        }
+    }
+
+    /// The app id of the window with focus, if there is one and the compositor says.
+    pub fn focused(&self) -> Option<&str> {
+        self.windows
+            .values()
+            .find(|window| window.focused && !window.app_id.is_empty())
+            .map(|window| window.app_id.as_str())
+    }
+}
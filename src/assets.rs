@@ -0,0 +1,120 @@
+// Assets installed next to the binary rather than built into it: themes, icons and sounds under
+// `fprint-prompt/` in the XDG data directories, so distro packages and users can add them without
+// recompiling. The defaults are compiled in below, so the binary works with nothing installed, and
+// are used whenever nothing is installed under the same name.
+
+use std::{borrow::Cow, path::PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Color themes, `<name>.theme`, picked with `palette` in `[colors]`.
+    Themes,
+    /// Icons for the tray, which replace the one from the icon theme.
+    Icons,
+    /// Sounds, which replace the ones from the sound theme.
+    Sounds,
+}
+
+impl Kind {
+    fn dir_name(self) -> &'static str {
+        match self {
+            Kind::Themes => "themes",
+            Kind::Icons => "icons",
+            Kind::Sounds => "sounds",
+        }
+    }
+}
+
+const EMBEDDED: &[(Kind, &str, &[u8])] = &[
+    (
+        Kind::Themes,
+        "high-contrast.theme",
+        include_bytes!("../assets/themes/high-contrast.theme"),
+    ),
+    // 32x32 ARGB in network byte order, as StatusNotifierItem's IconPixmap wants it.
+    (
+        Kind::Icons,
+        "fprint-prompt-32.argb",
+        include_bytes!("../assets/icons/fprint-prompt-32.argb"),
+    ),
+];
+
+/// `fprint-prompt/<kind>` in every data directory, most important first: the user's own, then
+/// `$XDG_DATA_DIRS`.
+pub fn dirs(kind: Kind) -> Vec<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        });
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    data_home
+        .into_iter()
+        .chain(
+            data_dirs
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        )
+        .map(|dir| dir.join("fprint-prompt").join(kind.dir_name()))
+        .collect()
+}
+
+/// The first installed file with this name.
+pub fn find(kind: Kind, name: &str) -> Option<PathBuf> {
+    // Names come from the config, and shouldn't reach outside the asset directories.
+    if name.contains('/') || name.starts_with('.') {
+        return None;
+    }
+    dirs(kind)
+        .into_iter()
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Every installed file, by name. Where several data directories have the same name, the file
+/// [`find`] would pick.
+pub fn installed(kind: Kind) -> Vec<(String, PathBuf)> {
+    let mut files: Vec<(String, PathBuf)> = vec![];
+    for dir in dirs(kind) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if entry.path().is_file() && !files.iter().any(|(seen, _)| *seen == name) {
+                files.push((name, entry.path()));
+            }
+        }
+    }
+    files
+}
+
+/// The compiled-in files, by name.
+pub fn embedded(kind: Kind) -> impl Iterator<Item = (&'static str, &'static [u8])> {
+    EMBEDDED
+        .iter()
+        .filter(move |(embedded_kind, _, _)| *embedded_kind == kind)
+        .map(|(_, name, data)| (*name, *data))
+}
+
+/// An installed file, or the compiled-in one if none is installed.
+pub fn load(kind: Kind, name: &str) -> Option<Cow<'static, [u8]>> {
+    if let Some(path) = find(kind, name) {
+        match std::fs::read(&path) {
+            Ok(data) => return Some(Cow::Owned(data)),
+            Err(e) => log::warn!("could not read {}: {e}", path.display()),
+        }
+    }
+
+    embedded(kind)
+        .find(|(embedded_name, _)| *embedded_name == name)
+        .map(|(_, data)| Cow::Borrowed(data))
+}
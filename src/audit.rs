@@ -0,0 +1,187 @@
+// An audit trail of verifications, with `audit = true`: each one ends up in the systemd journal as
+// an entry of its own, with how it ended and which process asked for it, so they can be looked back
+// on with `journalctl MESSAGE_ID=c6fbc44ce43a453baec258836f19d3f3`. Entries are sent over the
+// journal's native protocol, so the fields can be matched on without parsing the message.
+//
+// fprintd doesn't say who asked, so the requester is the process the overlay guessed it was. The
+// entry says so, with `FPRINT_REQUESTER_GUESSED=1` and "probably" in the message.
+
+use std::{fmt::Write as _, io::Write as _, os::unix::net::UnixDatagram, time::Duration};
+
+use dbus::Path;
+
+use crate::{prompts::Outcome, requester::Process};
+
+const SOCKET: &str = "/run/systemd/journal/socket";
+const MESSAGE_ID: &str = "c6fbc44ce43a453baec258836f19d3f3";
+/// Syslog priorities.
+const WARNING: u8 = 4;
+const NOTICE: u8 = 5;
+const INFO: u8 = 6;
+
+/// How a verification ended, as far as the journal is concerned.
+#[derive(Clone, Copy)]
+pub enum Ending {
+    Ended(Outcome),
+    /// The process that asked for it exited before it ended.
+    Abandoned,
+}
+
+impl Ending {
+    fn name(self) -> &'static str {
+        match self {
+            Ending::Ended(Outcome::Success) => "match",
+            Ending::Ended(Outcome::Failure) => "no-match",
+            Ending::Ended(Outcome::Fallback) => "fallback",
+            Ending::Ended(Outcome::Busy) => "busy",
+            Ending::Ended(Outcome::Disconnected) => "disconnected",
+            Ending::Abandoned => "abandoned",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Ending::Ended(Outcome::Success) => "matched",
+            Ending::Ended(Outcome::Failure) => "didn't match",
+            Ending::Ended(Outcome::Fallback) => "fell back to the password",
+            Ending::Ended(Outcome::Busy) => "was skipped, the reader was busy",
+            Ending::Ended(Outcome::Disconnected) => "was cut off, the reader went away",
+            Ending::Abandoned => "was abandoned",
+        }
+    }
+
+    fn priority(self) -> u8 {
+        match self {
+            Ending::Ended(Outcome::Success) => INFO,
+            Ending::Ended(Outcome::Busy) | Ending::Abandoned => NOTICE,
+            Ending::Ended(Outcome::Failure | Outcome::Fallback | Outcome::Disconnected) => WARNING,
+        }
+    }
+}
+
+/// A verification to write down.
+pub struct Entry<'a> {
+    /// None when the reader is unknown, like when it was busy.
+    pub device: Option<&'a Path<'a>>,
+    pub ending: Ending,
+    /// What fprintd said last, like `verify-match`.
+    pub result: Option<&'a str>,
+    /// Left out with `privacy = true`.
+    pub finger: Option<&'a str>,
+    pub duration: Option<Duration>,
+    /// Guessed, see [`requester`](crate::requester).
+    pub requester: Option<&'a Process>,
+}
+
+#[derive(Default)]
+pub struct Journal {
+    /// Opened with the first entry.
+    socket: Option<UnixDatagram>,
+}
+
+impl Journal {
+    pub fn write(&mut self, entry: &Entry) {
+        let mut message = "Fingerprint verification".to_string();
+        if let Some(device) = entry.device {
+            let _ = write!(message, " on {device}");
+        }
+        let _ = write!(message, " {}", entry.ending.describe());
+        if let Some(requester) = entry.requester {
+            match &requester.command {
+                Some(command) => {
+                    let _ = write!(message, ", probably for {command} (pid {})", requester.pid);
+                }
+                None => {
+                    let _ = write!(message, ", probably for pid {}", requester.pid);
+                }
+            }
+        }
+
+        let mut fields = vec![
+            ("MESSAGE", message),
+            ("MESSAGE_ID", MESSAGE_ID.to_string()),
+            ("PRIORITY", entry.ending.priority().to_string()),
+            ("SYSLOG_IDENTIFIER", "fprint-prompt".to_string()),
+            ("FPRINT_OUTCOME", entry.ending.name().to_string()),
+        ];
+        if let Some(device) = entry.device {
+            fields.push(("FPRINT_DEVICE", device.to_string()));
+        }
+        if let Some(result) = entry.result {
+            fields.push(("FPRINT_RESULT", result.to_string()));
+        }
+        if let Some(finger) = entry.finger {
+            fields.push(("FPRINT_FINGER", finger.to_string()));
+        }
+        if let Some(duration) = entry.duration {
+            fields.push(("FPRINT_DURATION_MS", duration.as_millis().to_string()));
+        }
+        if let Some(requester) = entry.requester {
+            fields.push(("FPRINT_REQUESTER_GUESSED", "1".to_string()));
+            fields.push(("FPRINT_REQUESTER_PID", requester.pid.to_string()));
+            fields.push(("FPRINT_REQUESTER_UID", requester.uid.to_string()));
+            if let Some(command) = &requester.command {
+                fields.push(("FPRINT_REQUESTER_COMM", command.clone()));
+            }
+        }
+
+        if let Err(e) = self.send(&encode(&fields)) {
+            log::warn!("could not write the audit entry to the journal: {e}");
+        }
+    }
+
+    fn send(&mut self, datagram: &[u8]) -> std::io::Result<()> {
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            socket => socket.insert(UnixDatagram::unbound()?),
+        };
+        socket.send_to(datagram, SOCKET)?;
+        Ok(())
+    }
+}
+
+/// The journal's native format: `KEY=value` lines, or for values with newlines in them, the key on
+/// a line of its own and the value after its length as a little-endian u64.
+fn encode(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut datagram = vec![];
+    for (key, value) in fields {
+        if value.contains('\n') {
+            let _ = writeln!(datagram, "{key}");
+            datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            datagram.extend_from_slice(value.as_bytes());
+            datagram.push(b'\n');
+        } else {
+            let _ = writeln!(datagram, "{key}={value}");
+        }
+    }
+    datagram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_fields() {
+        let datagram = encode(&[
+            ("MESSAGE", "Fingerprint verification matched".to_string()),
+            ("PRIORITY", "6".to_string()),
+        ]);
+        assert_eq!(
+            datagram,
+            b"MESSAGE=Fingerprint verification matched\nPRIORITY=6\n"
+        );
+    }
+
+    #[test]
+    fn length_prefixes_values_with_newlines() {
+        let datagram = encode(&[
+            ("FPRINT_RESULT", "two\nlines".to_string()),
+            ("PRIORITY", "4".to_string()),
+        ]);
+        let mut expected = b"FPRINT_RESULT\n".to_vec();
+        expected.extend_from_slice(&9u64.to_le_bytes());
+        expected.extend_from_slice(b"two\nlines\nPRIORITY=4\n");
+        assert_eq!(datagram, expected);
+    }
+}
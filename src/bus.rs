@@ -0,0 +1,78 @@
+// Which bus fprintd is on. Normally the system bus, but containers and test harnesses can point
+// elsewhere, and a mock fprintd during development is easiest to run on the session bus.
+
+use std::{fmt, os::fd::RawFd, sync::OnceLock};
+
+use dbus::{
+    channel::{self, Channel},
+    ffidisp::{BusType, Connection as DbusConnection},
+};
+
+static SELECTED: OnceLock<Bus> = OnceLock::new();
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bus {
+    /// The system bus, at `DBUS_SYSTEM_BUS_ADDRESS` if that is set.
+    System,
+    Session,
+    /// A bus at a D-Bus address, like `unix:path=/run/test/bus`.
+    Address(String),
+}
+
+impl Bus {
+    pub fn connect(&self) -> Result<DbusConnection, dbus::Error> {
+        match self {
+            Bus::System => DbusConnection::get_private(BusType::System),
+            Bus::Session => DbusConnection::get_private(BusType::Session),
+            Bus::Address(address) => {
+                let dbus = DbusConnection::open_private(address)?;
+                dbus.register()?;
+                Ok(dbus)
+            }
+        }
+    }
+
+    /// A bare channel, for reading messages straight off the bus without anything answering
+    /// them, like a monitor has to.
+    pub fn channel(&self) -> Result<Channel, dbus::Error> {
+        match self {
+            Bus::System => Channel::get_private(channel::BusType::System),
+            Bus::Session => Channel::get_private(channel::BusType::Session),
+            Bus::Address(address) => {
+                let mut channel = Channel::open_private(address)?;
+                channel.register()?;
+                Ok(channel)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bus::System => write!(f, "the system bus"),
+            Bus::Session => write!(f, "the session bus"),
+            Bus::Address(address) => write!(f, "the bus at {address}"),
+        }
+    }
+}
+
+/// The file descriptors to wait on for reading from `dbus`. They stay the same for as long as the
+/// connection is open, so they can be looked up once.
+pub fn readable_fds(dbus: &DbusConnection) -> Vec<RawFd> {
+    dbus.watch_fds()
+        .into_iter()
+        .filter(|watch| watch.readable())
+        .map(|watch| watch.fd())
+        .collect()
+}
+
+/// Makes [`selected`] return `bus`. Only the first call has an effect.
+pub fn select(bus: Bus) {
+    let _ = SELECTED.set(bus);
+}
+
+/// The bus picked with [`select`], or the system bus.
+pub fn selected() -> &'static Bus {
+    SELECTED.get_or_init(|| Bus::System)
+}
@@ -0,0 +1,128 @@
+// Telling when a verification couldn't start because another process had claimed the reader.
+// pam_fprintd then goes straight to the password, and fprintd only says so in its error reply to
+// it, so nothing would be shown at all.
+//
+// Replies to others can only be seen by a monitor, which the system bus only lets root become, so
+// there this is only on for an overlay running as root, like some greeters'. Users' own overlays
+// don't try, as fprintd has no signal or property that says a reader is claimed. Other buses let
+// whoever runs them monitor them, like a session bus with a mock fprintd.
+//
+// Only claims are followed, so a reader being busy when something else is asked of it, like
+// enrolling from the settings, doesn't show up.
+
+use std::{collections::VecDeque, os::fd::RawFd, time::Duration};
+
+use dbus::{blocking::Connection, MessageType};
+use fprint_prompt::{
+    bus::{self, Bus},
+    fprint::{DEVICE_INTERFACE, SERVICE},
+};
+
+const DBUS: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What fprintd answers a claim on a reader that another process has.
+const ALREADY_IN_USE: &str = "net.reactivated.Fprint.Error.AlreadyInUse";
+
+/// How many claims are waited on for their reply at most, as not every reply is seen.
+const PENDING: usize = 16;
+
+pub struct ClaimErrors {
+    conn: Connection,
+    /// The claims fprintd hasn't turned down yet, by who made them and their serial.
+    pending: VecDeque<(String, u32)>,
+    /// Set once the bus went away, so it isn't complained about every turn.
+    lost: bool,
+}
+
+impl ClaimErrors {
+    /// None where the bus doesn't let us become a monitor.
+    pub fn new() -> Option<ClaimErrors> {
+        let bus = bus::selected();
+        if *bus == Bus::System && unsafe { libc::geteuid() } != 0 {
+            log::info!("Can't tell when the reader is busy, which needs root on the system bus");
+            return None;
+        }
+        let conn = bus
+            .channel()
+            .map(Connection::from)
+            .map_err(|e| log::warn!("could not connect to {bus} to watch for busy readers: {e}"))
+            .ok()?;
+
+        let rules = vec![
+            format!(
+                "type='method_call',destination='{SERVICE}',\
+                 interface='{DEVICE_INTERFACE}',member='Claim'"
+            ),
+            format!("type='error',sender='{SERVICE}'"),
+        ];
+        let monitoring: Result<(), _> = conn.with_proxy(DBUS, DBUS_PATH, TIMEOUT).method_call(
+            "org.freedesktop.DBus.Monitoring",
+            "BecomeMonitor",
+            (rules, 0u32),
+        );
+        match monitoring {
+            Ok(()) => Some(ClaimErrors {
+                conn,
+                pending: VecDeque::new(),
+                lost: false,
+            }),
+            Err(e) => {
+                log::info!("Can't tell when the reader is busy, which needs a monitor: {e}");
+                None
+            }
+        }
+    }
+
+    /// Whether a claim was turned down for the reader being busy since the last poll.
+    pub fn poll(&mut self) -> bool {
+        let channel = self.conn.channel();
+        if self.lost {
+            return false;
+        }
+        if let Err(e) = channel.read_write(Some(Duration::ZERO)) {
+            log::warn!("lost the bus while watching for busy readers: {e:?}");
+            self.lost = true;
+            return false;
+        }
+
+        let mut busy = false;
+        while let Some(mut msg) = channel.pop_message() {
+            match msg.msg_type() {
+                MessageType::MethodCall => {
+                    let (Some(sender), Some(serial)) = (msg.sender(), msg.get_serial()) else {
+                        continue;
+                    };
+                    if self.pending.len() == PENDING {
+                        self.pending.pop_front();
+                    }
+                    self.pending.push_back((sender.to_string(), serial));
+                }
+                MessageType::Error => {
+                    let (Some(destination), Some(serial)) =
+                        (msg.destination(), msg.get_reply_serial())
+                    else {
+                        continue;
+                    };
+                    let Some(claim) = self.pending.iter().position(|(sender, claimed)| {
+                        *sender == *destination && *claimed == serial
+                    }) else {
+                        continue;
+                    };
+                    self.pending.remove(claim);
+                    if let Err(e) = msg.as_result() {
+                        busy |= e.name() == Some(ALREADY_IN_USE);
+                    }
+                }
+                _ => (),
+            }
+        }
+        busy
+    }
+
+    /// To wake up for, when fprintd turns something down.
+    pub fn watch_fd(&self) -> RawFd {
+        self.conn.channel().watch().fd
+    }
+}
@@ -0,0 +1,2013 @@
+// Configuration file handling.
+//
+// The config lives at `$XDG_CONFIG_HOME/fprint-prompt/config.toml` and uses a small subset of
+// TOML: `[section]` headers, `key = value` pairs and `#` comments. Values can be strings, integers,
+// floats or booleans.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::RangeInclusive,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use fprint_prompt::bus::Bus;
+use smithay_client_toolkit::shell::wlr_layer::{Anchor, KeyboardInteractivity, Layer};
+
+use crate::{
+    exit, instance,
+    quirks::{QuirkOverrides, ScaleStrategy},
+    reader_quirks::ReaderQuirkOverrides,
+    sinks::Sink,
+    theme::{Palette, Style, Theme, ThemeRegistry},
+};
+
+/// Bigger than any sensible bar, small enough to catch typos that would cover the screen.
+const MAX_LENGTH: u32 = 4096;
+const MAX_THICKNESS: u32 = 256;
+pub const MAX_FLASHES: u32 = 10;
+const MAX_ESCALATE_AFTER: u32 = 3600;
+const MAX_TIMEOUT: u32 = 3600;
+const MAX_TRIES: u32 = 100;
+const MAX_FALLBACK_LINGER: u32 = 60;
+const MAX_WATCHDOG: u32 = 3600;
+const MAX_SHADOW: u32 = 32;
+const MAX_MILLIMETERS: f32 = 1000.;
+const MAX_SCALE: f64 = 8.;
+
+pub struct Config {
+    /// Also show prompts for verifications that belong to other users, e.g. a greeter on another VT.
+    pub show_other_users: bool,
+    /// Never tell which fingers are enrolled or asked for, on screen, in the status stream, over
+    /// D-Bus or in the log.
+    pub privacy: bool,
+    /// Where prompts are shown. The overlay can also be left out with `display = "none"`.
+    pub sinks: Vec<Sink>,
+    /// Run through `sh -c` by the hook sink.
+    pub hook: Option<String>,
+    /// Under /sys/class/leds, lit by the led sink.
+    pub led: Option<String>,
+    /// Show an icon in the StatusNotifierItem tray.
+    pub tray: bool,
+    /// Bind global shortcuts through the desktop portal.
+    pub shortcuts: bool,
+    /// Pause while the screen is being cast through the portal, e.g. recorded or streamed.
+    pub pause_during_screencast: bool,
+    /// Write a report to the state dir when crashing.
+    pub crash_reports: bool,
+    /// Write every verification to the systemd journal, with who asked for it.
+    pub audit: bool,
+    /// Say so while a verification is for systemd-homed unlocking the home directory.
+    pub homed: bool,
+    /// Seconds the main loop may be stuck before starting over, or 0 to wait forever.
+    pub watchdog: u32,
+    /// Switch to the lock screen layout while the screen is locked.
+    pub lock_screen: bool,
+    /// Whether the prompt can take keyboard focus while it's shown, e.g. for Escape to close it.
+    pub keyboard: KeyboardInteractivity,
+    /// Keep the screen from dimming or locking while a verification waits for a finger.
+    pub inhibit_idle: bool,
+    /// Seats that fingerprint readers are attached to, by fprintd device name. Set with
+    /// `[device."Name"]` sections containing `seat = "seat1"`.
+    pub device_seats: HashMap<String, String>,
+    /// How readers behave, by fprintd device name. Set in the same sections with `retries` and
+    /// `timeout`.
+    pub device_quirks: HashMap<String, ReaderQuirkOverrides>,
+    pub position: PositionInfo,
+    /// Bar sizes that differ from the geometry while in a certain state. Set with
+    /// `[geometry.verify]` and so on, containing `length` and `thickness`.
+    pub state_sizes: StateSizes,
+    /// The most of an output the bar may cover in any state, as a fraction of its area. Geometry
+    /// that covers more, or doesn't fit on the output, is shrunk.
+    pub max_area: f32,
+    pub style: Style,
+    /// Mark how scans went with a shape over the indicator too.
+    pub shapes: bool,
+    pub colors: Colors,
+    pub attention: Attention,
+    pub night: Night,
+    pub dock: Dock,
+    /// Themes to switch to by themselves. Set in `[theme.schedule]`.
+    pub theme_schedule: ThemeSchedule,
+    /// The scheduled theme this config was loaded with, in place of `colors.palette`.
+    pub theme: Option<String>,
+    pub shadow: Shadow,
+    /// Fixes for how the compositor was detected or treated. Set in `[quirks]`.
+    pub quirks: QuirkOverrides,
+    /// Overrides for specific outputs, by name. Set with `[output."eDP-1"]` sections.
+    pub outputs: HashMap<String, OutputProfile>,
+    /// Names of the `[profile.name]` sections, which can be switched to at runtime.
+    pub profiles: Vec<String>,
+    /// The profile this config was loaded with.
+    pub profile: Option<String>,
+    /// Names of the `[app."id"]` sections, by the app ids of the windows they're for.
+    pub apps: Vec<String>,
+    /// The app whose section this config was loaded with, for the verification going on.
+    pub app: Option<String>,
+    /// Profiles to switch to as outputs come and go, in the order they're tried.
+    pub profile_rules: Vec<ProfileRule>,
+    /// Signals that show and hide prompts besides fprintd's, by name. Set with `[signal.name]`
+    /// sections.
+    pub signal_rules: BTreeMap<String, SignalRule>,
+}
+
+/// What's picked on top of the config file, which a reload keeps.
+#[derive(Clone, Copy, Default)]
+pub struct Selection<'a> {
+    /// A `[profile.name]` section.
+    pub profile: Option<&'a str>,
+    /// A theme from `[theme.schedule]`, in place of the palette.
+    pub theme: Option<&'a str>,
+    /// An `[app."id"]` section, on top of the profile.
+    pub app: Option<&'a str>,
+}
+
+/// When to switch to a profile by itself. Set in `[profile.name.when]`.
+pub struct ProfileRule {
+    pub profile: String,
+    /// An output that has to be connected, by name.
+    pub outputs_include: String,
+}
+
+pub struct OutputProfile {
+    /// Whether to show the prompt on this output at all.
+    pub enabled: bool,
+    /// The global geometry with this output's overrides applied.
+    pub position: PositionInfo,
+    /// Used in place of the scale the compositor reports, for those that get it wrong.
+    pub scale_override: Option<f32>,
+}
+
+/// ARGB colors of the indicators.
+#[derive(Clone)]
+pub struct Colors {
+    /// Waiting for a finger to verify.
+    pub prompt: u32,
+    /// Waiting for a finger to enroll.
+    pub enroll: u32,
+    /// Briefly shown after a verification matched.
+    pub success: u32,
+    /// Briefly shown after a verification failed.
+    pub failure: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            show_other_users: false,
+            privacy: false,
+            sinks: vec![Sink::Overlay, Sink::Sound],
+            hook: None,
+            led: None,
+            tray: false,
+            // Both go through the desktop portal, which works from inside the sandbox.
+            shortcuts: cfg!(feature = "flatpak"),
+            pause_during_screencast: cfg!(feature = "flatpak"),
+            crash_reports: false,
+            audit: false,
+            homed: false,
+            watchdog: 30,
+            lock_screen: true,
+            keyboard: KeyboardInteractivity::None,
+            inhibit_idle: true,
+            device_seats: HashMap::new(),
+            device_quirks: HashMap::new(),
+            position: PositionInfo {
+                thickness: 8,
+                length: 138,
+                edge: Anchor::RIGHT,
+                close_to: Anchor::TOP,
+                offset: 100,
+                notch: 0,
+                segments: vec![],
+                millimeters: Millimeters::default(),
+            },
+            state_sizes: StateSizes::default(),
+            max_area: 0.25,
+            style: Style::Bar,
+            shapes: false,
+            colors: Palette::Default.colors(),
+            attention: Attention {
+                flashes: 3,
+                intensity: 1.,
+                escalate_after: 10,
+                escalated_thickness: 24,
+                chime_after: 0,
+                timeout: 30,
+                max_tries: 3,
+                fallback_linger: 3,
+            },
+            night: Night {
+                start: 21 * 60,
+                end: 7 * 60,
+                warmth: 0.,
+                follow_gnome: false,
+            },
+            dock: Dock {
+                enabled: false,
+                color: 0x40808080,
+            },
+            theme_schedule: ThemeSchedule::default(),
+            theme: None,
+            shadow: Shadow {
+                style: ShadowStyle::None,
+                size: 3,
+                color: 0x80000000,
+            },
+            quirks: QuirkOverrides::default(),
+            outputs: HashMap::new(),
+            profiles: vec![],
+            profile: None,
+            apps: vec![],
+            app: None,
+            profile_rules: vec![],
+            signal_rules: BTreeMap::new(),
+        }
+    }
+}
+
+/// Themes picked by the time of day, or by whether the desktop prefers a dark style.
+#[derive(Clone, Default)]
+pub struct ThemeSchedule {
+    /// From a time, in minutes after midnight, until the next one. Sorted, and the last one lasts
+    /// past midnight until the first.
+    pub times: Vec<(u32, String)>,
+    /// While the desktop prefers a dark style, or doesn't. These go before the times.
+    pub dark: Option<String>,
+    pub light: Option<String>,
+}
+
+impl ThemeSchedule {
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty() && self.dark.is_none() && self.light.is_none()
+    }
+
+    /// Whether the desktop's style has to be known to pick a theme.
+    pub fn follows_dark_style(&self) -> bool {
+        self.dark.is_some() || self.light.is_some()
+    }
+
+    /// The theme for a time in minutes after midnight, with the desktop's style if it's known.
+    pub fn pick(&self, minutes: u32, dark: Option<bool>) -> Option<&str> {
+        let by_style = match dark {
+            Some(true) => self.dark.as_deref(),
+            Some(false) => self.light.as_deref(),
+            None => None,
+        };
+
+        by_style.or_else(|| {
+            let before = self.times.partition_point(|(time, _)| *time <= minutes);
+            let (_, theme) = before
+                .checked_sub(1)
+                .and_then(|last| self.times.get(last))
+                .or(self.times.last())?;
+            Some(theme.as_str())
+        })
+    }
+
+    fn apply(&mut self, key: &str, entry: &Entry) -> Result<(), ParseError> {
+        // Checked here, so a broken theme is told about when the config is loaded rather than
+        // whenever the schedule gets to it.
+        entry.palette()?;
+        let theme = entry.string()?;
+
+        match key {
+            "dark" => self.dark = Some(theme),
+            "light" => self.light = Some(theme),
+            time => {
+                let minutes = parse_time(time).ok_or_else(|| {
+                    entry.key_error(format!(
+                        "theme.schedule keys should be \"dark\", \"light\" or a time like \
+                         \"21:30\", not {time:?}"
+                    ))
+                })?;
+                let at = self.times.partition_point(|(time, _)| *time < minutes);
+                self.times.insert(at, (minutes, theme));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A dark edge around the bar, so a light bar stays visible on light content.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub style: ShadowStyle,
+    /// How far it reaches from the bar, in pixels.
+    pub size: u32,
+    pub color: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ShadowStyle {
+    None,
+    /// Fades out with the distance from the bar.
+    Drop,
+    /// Solid up to `size` pixels from the bar.
+    Outline,
+}
+
+impl Shadow {
+    /// How much room the shadow needs around the bar.
+    pub fn margin(&self) -> u32 {
+        match self.style {
+            ShadowStyle::None => 0,
+            ShadowStyle::Drop | ShadowStyle::Outline => self.size,
+        }
+    }
+}
+
+/// Warmer colors at night, so a white bar doesn't glare in a dark room.
+#[derive(Clone)]
+pub struct Night {
+    /// In minutes after midnight, local time.
+    pub start: u32,
+    pub end: u32,
+    /// How far to shift the colors towards orange, from 0 (off) to 1.
+    pub warmth: f32,
+    /// Use GNOME's night light instead of the schedule.
+    pub follow_gnome: bool,
+}
+
+/// Docking the prompt as a strip along the whole edge that's always there, with room kept for it
+/// so windows never go under it.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Dock {
+    pub enabled: bool,
+    /// ARGB, shown while nothing is waiting.
+    pub color: u32,
+}
+
+/// A D-Bus signal that shows or hides a prompt, for authentication that doesn't go through
+/// fprintd, like a custom PAM module or a remote auth daemon.
+#[derive(Clone, PartialEq)]
+pub struct SignalRule {
+    /// A match rule, like "type='signal',interface='org.example.Auth',member='Waiting'".
+    pub rule: String,
+    pub bus: Bus,
+    pub action: SignalAction,
+    /// For `show`, with `{0}`, `{1}` and so on replaced by the signal's arguments.
+    pub text: String,
+    /// Which prompt is shown or hidden, so one rule can hide what another showed. The rule's own
+    /// name if not set.
+    pub prompt: Option<String>,
+}
+
+impl Default for SignalRule {
+    fn default() -> Self {
+        SignalRule {
+            rule: String::new(),
+            bus: Bus::System,
+            action: SignalAction::Show,
+            text: "Waiting for authentication".to_string(),
+            prompt: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignalAction {
+    Show,
+    Hide,
+    /// Shows the prompt if the signal's argument at this index is true, non-zero or a string
+    /// that isn't empty, and hides it otherwise.
+    Argument(usize),
+}
+
+/// Flashing the bar when a verification starts, so it's noticed while looking elsewhere.
+#[derive(Clone)]
+pub struct Attention {
+    /// How many times to flash. 0 turns it off.
+    pub flashes: u32,
+    /// How far the bar fades out during a flash, from 0 (not at all) to 1 (fully transparent).
+    pub intensity: f32,
+    /// Seconds without the reader being touched before the bar grows to get noticed. 0 turns it off.
+    pub escalate_after: u32,
+    /// Thickness of the bar while it's being ignored.
+    pub escalated_thickness: u32,
+    /// Seconds without the reader being touched before a chime reminds the user, sounding more
+    /// often and louder until it is. 0 turns it off.
+    pub chime_after: u32,
+    /// Seconds a verification waits for a finger before PAM gives up, counted down along the bar.
+    /// 0 turns it off.
+    pub timeout: u32,
+    /// Verifications in a row that don't match before PAM asks for the password instead. 0 never
+    /// counts them.
+    pub max_tries: u32,
+    /// Seconds the password fallback is shown for.
+    pub fallback_linger: u32,
+}
+
+/// What the bar is showing, for picking its size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BarState {
+    Verify,
+    Enroll,
+    Success,
+    Failure,
+}
+
+impl BarState {
+    const ALL: [BarState; 4] = [
+        BarState::Verify,
+        BarState::Enroll,
+        BarState::Success,
+        BarState::Failure,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            BarState::Verify => "verify",
+            BarState::Enroll => "enroll",
+            BarState::Success => "success",
+            BarState::Failure => "failure",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarSize {
+    pub length: u32,
+    pub thickness: u32,
+}
+
+/// Overrides of the geometry's length and thickness for each [`BarState`].
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateSizes {
+    overrides: [(Option<u32>, Option<u32>); 4],
+}
+
+impl StateSizes {
+    /// The size of the bar in a state, on top of the geometry.
+    pub fn size(&self, pos: &PositionInfo, state: BarState) -> BarSize {
+        let (length, thickness) = self.overrides[state as usize];
+        BarSize {
+            length: length.unwrap_or(pos.length),
+            thickness: thickness.unwrap_or(pos.thickness),
+        }
+    }
+
+    /// The geometry grown to fit the bar in every state.
+    pub fn fit(&self, pos: &PositionInfo) -> PositionInfo {
+        let mut pos = pos.clone();
+        for state in BarState::ALL {
+            let size = self.size(&pos, state);
+            pos.length = pos.length.max(size.length);
+            pos.thickness = pos.thickness.max(size.thickness);
+        }
+        pos
+    }
+
+    /// With no override longer or thicker than `most`.
+    fn limited(&self, most: BarSize) -> StateSizes {
+        StateSizes {
+            overrides: self.overrides.map(|(length, thickness)| {
+                (
+                    length.map(|length| length.min(most.length)),
+                    thickness.map(|thickness| thickness.min(most.thickness)),
+                )
+            }),
+        }
+    }
+
+    fn apply(&mut self, state: &str, key: &str, entry: &Entry) -> Result<(), ParseError> {
+        let Some(state) = BarState::ALL.into_iter().find(|s| s.name() == state) else {
+            return Err(entry.unknown_key());
+        };
+        let (length, thickness) = &mut self.overrides[state as usize];
+
+        match key {
+            "length" => *length = Some(entry.u32_in(1..=MAX_LENGTH)?),
+            "thickness" => *thickness = Some(entry.u32_in(1..=MAX_THICKNESS)?),
+            _ => return Err(entry.unknown_key()),
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the prompt goes: a bar of `thickness` by `length` along `edge`, `offset` pixels away from
+/// the `close_to` end of it. An empty `close_to` centers the bar on the edge.
+#[derive(PartialEq)]
+pub struct PositionInfo {
+    pub thickness: u32,
+    pub length: u32,
+    pub edge: Anchor,
+    pub close_to: Anchor,
+    pub offset: u32,
+    /// A gap in the middle of the bar, so it frames a camera notch or hinge cut-out instead of
+    /// drawing through it. 0 for none.
+    pub notch: u32,
+    /// The pieces of the bar that are drawn, as offsets and lengths from its `close_to` end, to
+    /// mark more than one place along the edge. Empty for the whole bar.
+    pub segments: Vec<(u32, u32)>,
+    /// The offset and length in millimeters, which take the place of the pixels on outputs that
+    /// report their physical size.
+    pub millimeters: Millimeters,
+}
+
+/// Set with `offset_mm` and `length_mm`, so the bar stays next to the reader at any resolution or
+/// scale.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Millimeters {
+    pub offset: Option<f32>,
+    pub length: Option<f32>,
+}
+
+impl Clone for PositionInfo {
+    fn clone(&self) -> Self {
+        PositionInfo {
+            segments: self.segments.clone(),
+            ..*self
+        }
+    }
+
+    /// Keeps the room `segments` has, for copying the geometry into a frame without allocating.
+    fn clone_from(&mut self, source: &Self) {
+        self.segments.clone_from(&source.segments);
+        *self = PositionInfo {
+            segments: std::mem::take(&mut self.segments),
+            ..*source
+        };
+    }
+}
+
+impl PositionInfo {
+    /// There's nothing to offset from when centered.
+    fn offset(&self) -> u32 {
+        if self.close_to.is_empty() {
+            0
+        } else {
+            self.offset
+        }
+    }
+
+    pub fn win_width(&self) -> u32 {
+        match self.edge {
+            Anchor::TOP | Anchor::BOTTOM => self.length + self.offset(),
+            Anchor::LEFT | Anchor::RIGHT => self.thickness,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn win_height(&self) -> u32 {
+        match self.edge {
+            Anchor::TOP | Anchor::BOTTOM => self.thickness,
+            Anchor::LEFT | Anchor::RIGHT => self.length + self.offset(),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn size(&self) -> BarSize {
+        BarSize {
+            length: self.length,
+            thickness: self.thickness,
+        }
+    }
+
+    /// Where a bar of `size` starts, measured from the `close_to` end. It hugs the offset, or the
+    /// middle of the window when centered.
+    fn bar_start(&self, size: BarSize) -> u32 {
+        if self.close_to.is_empty() {
+            self.length.saturating_sub(size.length) / 2
+        } else {
+            self.offset
+        }
+    }
+
+    /// The rectangle of a bar of `size` within the window, as x, y, width and height.
+    pub fn bar_rect(&self, size: BarSize) -> (u32, u32, u32, u32) {
+        let start = if self.close_to == Anchor::TOP || self.close_to == Anchor::LEFT {
+            self.bar_start(size)
+        } else {
+            self.length + self.offset() - self.bar_start(size) - size.length
+        };
+        // The bar hugs the screen edge.
+        let depth = match self.edge {
+            Anchor::TOP | Anchor::LEFT => 0,
+            _ => self.thickness - size.thickness,
+        };
+
+        match self.edge {
+            Anchor::TOP | Anchor::BOTTOM => (start, depth, size.length, size.thickness),
+            _ => (depth, start, size.thickness, size.length),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("fprint-prompt").join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to the defaults for anything that's missing or broken.
+    pub fn load() -> Config {
+        Self::load_profile(None)
+    }
+
+    /// Loads the config file with a profile's settings on top.
+    pub fn load_profile(profile: Option<&str>) -> Config {
+        Self::load_selected(Selection {
+            profile,
+            ..Selection::default()
+        })
+    }
+
+    /// Loads the config file with what's selected on top.
+    pub fn load_selected(selection: Selection) -> Config {
+        let Some(path) = Self::path() else {
+            return Config::default();
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Config::default(),
+            Err(e) => {
+                log::warn!("could not read {}: {e}", path.display());
+                return Config::default();
+            }
+        };
+
+        let (config, errors) = Self::parse_selected(&text, selection);
+        for error in errors {
+            log::warn!("{}:{error}", path.display());
+        }
+
+        config
+    }
+
+    /// Parses as much of the config as possible, returning everything that was wrong with it.
+    pub fn parse(text: &str) -> (Config, Vec<ParseError>) {
+        Self::parse_profile(text, None)
+    }
+
+    /// Like [`Config::parse`], with the settings of a `[profile.name]` section on top. Every
+    /// profile is checked, whichever is used.
+    pub fn parse_profile(text: &str, profile: Option<&str>) -> (Config, Vec<ParseError>) {
+        Self::parse_selected(
+            text,
+            Selection {
+                profile,
+                ..Selection::default()
+            },
+        )
+    }
+
+    /// Like [`Config::parse_profile`], with an app's settings on top of the profile's, and a
+    /// scheduled theme in place of the palette. Settings given next to the palette still go on top
+    /// of the theme.
+    fn parse_selected(text: &str, selection: Selection) -> (Config, Vec<ParseError>) {
+        // Anything wrong with it was reported when the schedule was read.
+        let scheduled = selection
+            .theme
+            .and_then(|name| ThemeRegistry::discover().resolve(name)?.ok());
+        let (entries, mut errors) = parse_entries(text);
+
+        let mut seen = HashMap::new();
+        for entry in &entries {
+            let dotted = entry.key.join(".");
+            if let Some(line) = seen.insert(dotted.clone(), entry.line) {
+                errors.push(entry.key_error(format!("{dotted} was already set on line {line}")));
+            }
+        }
+
+        // `[profile.docked]`, `[app."org.keepassxc.KeePassXC"]` and `[instance.fido]` hold any of
+        // the other settings, so the name is cut off the key.
+        let mut base = vec![];
+        let mut profiles: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+        let mut apps: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+        let mut instances: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+        let mut profile_rules = vec![];
+        for mut entry in entries {
+            match entry.key.as_slice() {
+                [section, name, when, ..] if section == "profile" && when == "when" => {
+                    let name = name.clone();
+                    entry.key.drain(..2);
+                    profiles.entry(name.clone()).or_default();
+                    match entry.profile_rule(name) {
+                        Ok(rule) => profile_rules.push(rule),
+                        Err(e) => errors.push(e),
+                    }
+                }
+                [section, name, _, ..]
+                    if section == "profile" || section == "app" || section == "instance" =>
+                {
+                    let sections = match section.as_str() {
+                        "profile" => &mut profiles,
+                        "app" => &mut apps,
+                        _ => &mut instances,
+                    };
+                    let name = name.clone();
+                    entry.key.drain(..2);
+                    sections.entry(name).or_default().push(entry);
+                }
+                _ => base.push(entry),
+            }
+        }
+
+        // The running instance's section is under everything else that's picked, so it goes on
+        // the end of the base. The others are only checked.
+        let own_instance = instance::name().and_then(|name| instances.remove(name));
+        base.extend(own_instance.unwrap_or_default());
+
+        let (mut config, built_errors) = Self::build(&base, &[], scheduled.as_ref());
+        errors.extend(built_errors);
+
+        // Anything wrong with the rest of the file was already reported.
+        let mut report = |section: &str, section_errors: Vec<ParseError>| {
+            for mut error in section_errors {
+                // The ones without a line don't say where they came from otherwise.
+                if error.line == 0 {
+                    error.message = format!("{} in {section}", error.message);
+                }
+                if !errors.iter().any(|e| {
+                    (e.line, e.column, &e.message) == (error.line, error.column, &error.message)
+                }) {
+                    errors.push(error);
+                }
+            }
+        };
+
+        let mut profile_entries: &[Entry] = &[];
+        for (name, entries) in &profiles {
+            let (profiled, profile_errors) = Self::build(&base, &[entries], scheduled.as_ref());
+            report(&format!("profile.{name}"), profile_errors);
+            if selection.profile == Some(name.as_str()) {
+                config = profiled;
+                profile_entries = entries;
+            }
+        }
+
+        for (name, entries) in &instances {
+            let (_, instance_errors) = Self::build(&base, &[entries], scheduled.as_ref());
+            report(&format!("instance.{name}"), instance_errors);
+        }
+
+        // Apps are checked without a profile, and only the one in use is built with it.
+        for (name, entries) in &apps {
+            let (_, app_errors) = Self::build(&base, &[entries], scheduled.as_ref());
+            report(&format!("app.{name}"), app_errors);
+        }
+        if let Some(entries) = selection.app.and_then(|app| apps.get(app)) {
+            (config, _) = Self::build(&base, &[profile_entries, entries], scheduled.as_ref());
+        }
+
+        config.profiles = profiles.into_keys().collect();
+        config.profile_rules = profile_rules;
+        config.profile = selection
+            .profile
+            .filter(|profile| config.profiles.iter().any(|name| name == profile))
+            .map(str::to_string);
+        config.theme = selection.theme.map(str::to_string);
+        config.apps = apps.into_keys().collect();
+        config.app = selection
+            .app
+            .filter(|app| config.apps.iter().any(|name| name == app))
+            .map(str::to_string);
+
+        errors.sort_by_key(|e| (e.line, e.column));
+        (config, errors)
+    }
+
+    /// What's selected on top of the file, for reloading it the same way.
+    pub fn selection(&self) -> Selection<'_> {
+        Selection {
+            profile: self.profile.as_deref(),
+            theme: self.theme.as_deref(),
+            app: self.app.as_deref(),
+        }
+    }
+
+    /// The config with `entries` applied, and then the profile's and the app's. A scheduled
+    /// `theme` replaces the palette.
+    fn build(
+        entries: &[Entry],
+        on_top: &[&[Entry]],
+        theme: Option<&Theme>,
+    ) -> (Config, Vec<ParseError>) {
+        let mut config = Config::default();
+        let mut errors = vec![];
+        let mut output_entries = vec![];
+        let entries: Vec<&Entry> = entries
+            .iter()
+            .chain(on_top.iter().copied().flatten())
+            .collect();
+
+        // The palette sets every color, and maybe the style and flashes, so it goes first for the
+        // settings given next to it to override. A profile's palette replaces the one outside it.
+        let palette = entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key == ["colors", "palette"])
+            .and_then(|entry| entry.palette().map_err(|e| errors.push(e)).ok());
+        if let Some(theme) = theme.cloned().or(palette) {
+            config.colors = theme.colors;
+            config.style = theme.style.unwrap_or(config.style);
+            let attention = &mut config.attention;
+            attention.flashes = theme.flashes.unwrap_or(attention.flashes);
+            attention.intensity = theme.intensity.unwrap_or(attention.intensity);
+            config.shapes = theme.shapes.unwrap_or(config.shapes);
+        }
+
+        for entry in entries {
+            // Output overrides go on top of the global geometry, wherever in the file that is.
+            if entry.key.first().is_some_and(|section| section == "output") {
+                output_entries.push(entry);
+            } else if let Err(e) = config.apply(entry) {
+                errors.push(e);
+            }
+        }
+
+        if let Err(e) = config.position.validate("geometry") {
+            errors.push(ParseError {
+                line: 0,
+                column: 0,
+                message: e,
+            });
+            config.position = Config::default().position;
+        }
+
+        for entry in output_entries {
+            if let Err(e) = config.apply_output(entry) {
+                errors.push(e);
+            }
+        }
+
+        config.signal_rules.retain(|name, rule| {
+            if rule.rule.is_empty() {
+                errors.push(ParseError {
+                    line: 0,
+                    column: 0,
+                    message: format!("signal.{name} needs a match rule"),
+                });
+            }
+            !rule.rule.is_empty()
+        });
+
+        for (name, profile) in &mut config.outputs {
+            if let Err(e) = profile.position.validate(&format!("output.{name}")) {
+                errors.push(ParseError {
+                    line: 0,
+                    column: 0,
+                    message: e,
+                });
+                profile.position = config.position.clone();
+            }
+        }
+
+        (config, errors)
+    }
+
+    fn apply(&mut self, entry: &Entry) -> Result<(), ParseError> {
+        let key = entry.key.iter().map(String::as_str).collect::<Vec<_>>();
+
+        match key.as_slice() {
+            ["show_other_users"] => self.show_other_users = entry.bool()?,
+            ["privacy"] => self.privacy = entry.bool()?,
+            ["display"] => {
+                let overlay = entry.display()?;
+                self.sinks.retain(|sink| *sink != Sink::Overlay);
+                if overlay {
+                    self.sinks.insert(0, Sink::Overlay);
+                }
+            }
+            ["sinks"] => self.sinks = entry.sinks()?,
+            ["hook", "command"] => self.hook = Some(entry.string()?),
+            ["led", "name"] => self.led = Some(entry.string()?),
+            ["style"] => self.style = entry.style()?,
+            ["shapes"] => self.shapes = entry.bool()?,
+            ["tray"] => self.tray = entry.bool()?,
+            ["lock_screen"] => self.lock_screen = entry.bool()?,
+            ["keyboard"] => self.keyboard = entry.keyboard_interactivity()?,
+            ["inhibit_idle"] => self.inhibit_idle = entry.bool()?,
+            ["shortcuts"] => self.shortcuts = entry.bool()?,
+            ["pause_during_screencast"] => self.pause_during_screencast = entry.bool()?,
+            ["crash_reports"] => self.crash_reports = entry.bool()?,
+            ["audit"] => self.audit = entry.bool()?,
+            ["homed"] => self.homed = entry.bool()?,
+            ["watchdog"] => self.watchdog = entry.u32_in(0..=MAX_WATCHDOG)?,
+            ["device", name, "seat"] => {
+                self.device_seats.insert(name.to_string(), entry.string()?);
+            }
+            ["device", name, "retries"] => {
+                self.device_quirks
+                    .entry(name.to_string())
+                    .or_default()
+                    .retries = Some(entry.bool()?);
+            }
+            ["device", name, "timeout"] => {
+                self.device_quirks
+                    .entry(name.to_string())
+                    .or_default()
+                    .timeout = Some(entry.u32_in(0..=MAX_TIMEOUT)?);
+            }
+            ["geometry", "max_area"] => self.max_area = entry.fraction()?,
+            ["geometry", key] => self.position.apply(key, entry)?,
+            ["geometry", state, key] => self.state_sizes.apply(state, key, entry)?,
+            ["colors", "prompt"] => self.colors.prompt = entry.color()?,
+            ["colors", "enroll"] => self.colors.enroll = entry.color()?,
+            ["colors", "success"] => self.colors.success = entry.color()?,
+            ["colors", "failure"] => self.colors.failure = entry.color()?,
+            ["colors", "palette"] => (),
+            ["night", "start"] => self.night.start = entry.time_of_day()?,
+            ["night", "end"] => self.night.end = entry.time_of_day()?,
+            ["night", "warmth"] => self.night.warmth = entry.fraction()?,
+            ["night", "follow_gnome"] => self.night.follow_gnome = entry.bool()?,
+            ["signal", name, key] => {
+                let rule = self.signal_rules.entry(name.to_string()).or_default();
+                match *key {
+                    "match" => rule.rule = entry.string()?,
+                    "bus" => rule.bus = entry.bus()?,
+                    "action" => rule.action = entry.signal_action()?,
+                    "text" => rule.text = entry.string()?,
+                    "prompt" => rule.prompt = Some(entry.string()?),
+                    _ => return Err(entry.unknown_key()),
+                }
+            }
+            ["dock", "enabled"] => self.dock.enabled = entry.bool()?,
+            ["dock", "color"] => self.dock.color = entry.color()?,
+            ["theme", "schedule", key] => self.theme_schedule.apply(key, entry)?,
+            ["shadow", "style"] => self.shadow.style = entry.shadow_style()?,
+            ["shadow", "size"] => self.shadow.size = entry.u32_in(1..=MAX_SHADOW)?,
+            ["shadow", "color"] => self.shadow.color = entry.color()?,
+            ["quirks", "layer"] => self.quirks.layer = Some(entry.layer()?),
+            ["quirks", "namespace"] => self.quirks.namespace = Some(entry.string()?),
+            ["quirks", "scale"] => self.quirks.scale = Some(entry.scale_strategy()?),
+            ["attention", "flashes"] => self.attention.flashes = entry.u32_in(0..=MAX_FLASHES)?,
+            ["attention", "intensity"] => self.attention.intensity = entry.fraction()?,
+            ["attention", "chime_after"] => {
+                self.attention.chime_after = entry.u32_in(0..=MAX_ESCALATE_AFTER)?
+            }
+            ["attention", "escalate_after"] => {
+                self.attention.escalate_after = entry.u32_in(0..=MAX_ESCALATE_AFTER)?
+            }
+            ["attention", "timeout"] => self.attention.timeout = entry.u32_in(0..=MAX_TIMEOUT)?,
+            ["attention", "max_tries"] => self.attention.max_tries = entry.u32_in(0..=MAX_TRIES)?,
+            ["attention", "fallback_linger"] => {
+                self.attention.fallback_linger = entry.u32_in(1..=MAX_FALLBACK_LINGER)?
+            }
+            ["attention", "escalated_thickness"] => {
+                self.attention.escalated_thickness = entry.u32_in(1..=MAX_THICKNESS)?
+            }
+            _ => return Err(entry.unknown_key()),
+        }
+
+        Ok(())
+    }
+
+    fn apply_output(&mut self, entry: &Entry) -> Result<(), ParseError> {
+        let [_, name, key] = entry.key.as_slice() else {
+            return Err(entry.unknown_key());
+        };
+
+        let profile = self
+            .outputs
+            .entry(name.clone())
+            .or_insert_with(|| OutputProfile {
+                enabled: true,
+                position: self.position.clone(),
+                scale_override: None,
+            });
+
+        match key.as_str() {
+            "enabled" => profile.enabled = entry.bool()?,
+            "scale_override" => profile.scale_override = Some(entry.scale()?),
+            key => profile.position.apply(key, entry)?,
+        }
+
+        Ok(())
+    }
+
+    /// The config as used on a login screen: shown for whoever is logging in, in the lock screen
+    /// layout, and without anything that needs a user session.
+    pub fn for_greeter(mut self) -> Config {
+        self.show_other_users = true;
+        self.tray = false;
+        self.shortcuts = false;
+        self.pause_during_screencast = false;
+
+        self.position = self.position.lock_screen_layout();
+        for profile in self.outputs.values_mut() {
+            profile.position = profile.position.lock_screen_layout();
+        }
+
+        self
+    }
+
+    /// Where to put the prompt on an output, or None if it shouldn't be shown there.
+    pub fn position_for(&self, output_name: Option<&str>) -> Option<PositionInfo> {
+        match output_name.and_then(|name| self.outputs.get(name)) {
+            Some(profile) if !profile.enabled => None,
+            Some(profile) => Some(profile.position.clone()),
+            None => Some(self.position.clone()),
+        }
+    }
+
+    /// The scale to use on an output instead of the compositor's, if the config has one.
+    pub fn scale_override_for(&self, output_name: Option<&str>) -> Option<f32> {
+        self.outputs.get(output_name?)?.scale_override
+    }
+}
+
+impl PositionInfo {
+    /// Centered on the same edge and twice the size, so it can't be missed on a lock or login
+    /// screen.
+    pub fn lock_screen_layout(&self) -> PositionInfo {
+        PositionInfo {
+            edge: self.edge,
+            close_to: Anchor::empty(),
+            offset: 0,
+            length: (self.length * 2).min(MAX_LENGTH),
+            thickness: (self.thickness * 2).min(MAX_THICKNESS),
+            notch: self.notch,
+            segments: self.segments.clone(),
+            millimeters: Millimeters::default(),
+        }
+    }
+
+    /// The geometry and state sizes shrunk so the bar fits on an output `along` by `across` pixels
+    /// in every state, covering at most `max_area` of it. None when they already do.
+    pub fn limited(
+        &self,
+        sizes: &StateSizes,
+        (along, across): (u32, u32),
+        max_area: f32,
+    ) -> Option<(PositionInfo, StateSizes)> {
+        let length = sizes.fit(self).length.min(along).max(1);
+        let area = max_area as f64 * along as f64 * across as f64;
+        let most = BarSize {
+            length,
+            thickness: ((area / length as f64) as u32).clamp(1, across.max(1)),
+        };
+
+        let mut pos = self.clone();
+        pos.length = pos.length.min(most.length);
+        pos.thickness = pos.thickness.min(most.thickness);
+        // The offset gives way for the bar to fit.
+        if !pos.close_to.is_empty() {
+            pos.offset = pos.offset.min(along - length);
+        }
+        let sizes_limited = sizes.limited(most);
+        (pos != *self || sizes_limited != *sizes).then_some((pos, sizes_limited))
+    }
+
+    /// The same geometry in a window of exactly `width` by `height` pixels, like a whole output or
+    /// whatever size the compositor configured. The bar stays where it would be on its own, and is
+    /// clipped by a smaller window.
+    pub fn fitted(&self, width: u32, height: u32) -> PositionInfo {
+        let (along, across) = match self.edge {
+            Anchor::TOP | Anchor::BOTTOM => (width, height),
+            _ => (height, width),
+        };
+        // Too narrow for the offset, so the bar can't be seen at all.
+        let offset = self.offset.min(along);
+
+        PositionInfo {
+            length: along - if self.close_to.is_empty() { 0 } else { offset },
+            thickness: across,
+            offset,
+            ..self.clone()
+        }
+    }
+
+    /// The geometry with the millimeters turned into pixels, at `pixels_per_mm` along the edge.
+    /// The pixels are kept when that isn't known.
+    pub fn in_pixels(&self, pixels_per_mm: Option<f32>) -> PositionInfo {
+        let mut pos = self.clone();
+        let Some(pixels_per_mm) = pixels_per_mm else {
+            return pos;
+        };
+
+        let pixels = |mm: f32| ((mm * pixels_per_mm).round() as u32).min(MAX_LENGTH);
+        if let Some(offset) = self.millimeters.offset {
+            pos.offset = pixels(offset);
+        }
+        if let Some(length) = self.millimeters.length {
+            pos.length = pixels(length).max(1);
+        }
+        pos.millimeters = Millimeters::default();
+        pos
+    }
+
+    fn apply(&mut self, key: &str, entry: &Entry) -> Result<(), ParseError> {
+        match key {
+            "edge" => self.edge = entry.anchor()?,
+            "close_to" => self.close_to = entry.close_to()?,
+            "offset" => self.offset = entry.u32_in(0..=MAX_LENGTH)?,
+            "length" => self.length = entry.u32_in(1..=MAX_LENGTH)?,
+            "thickness" => self.thickness = entry.u32_in(1..=MAX_THICKNESS)?,
+            "notch" => self.notch = entry.u32_in(0..=MAX_LENGTH)?,
+            "segments" => self.segments = entry.segments()?,
+            "offset_mm" => self.millimeters.offset = Some(entry.millimeters(0.)?),
+            "length_mm" => self.millimeters.length = Some(entry.millimeters(1.)?),
+            _ => return Err(entry.unknown_key()),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self, section: &str) -> Result<(), String> {
+        let along_edge = match self.edge {
+            Anchor::TOP | Anchor::BOTTOM => Anchor::LEFT | Anchor::RIGHT,
+            _ => Anchor::TOP | Anchor::BOTTOM,
+        };
+
+        if !along_edge.contains(self.close_to) {
+            return Err(format!(
+                "{section}.close_to must be \"center\" or at one end of the {} edge",
+                anchor_name(self.edge)
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn anchor_name(anchor: Anchor) -> &'static str {
+    match anchor {
+        Anchor::TOP => "top",
+        Anchor::BOTTOM => "bottom",
+        Anchor::LEFT => "left",
+        Anchor::RIGHT => "right",
+        _ if anchor.is_empty() => "center",
+        _ => "?",
+    }
+}
+
+/// Notices when the config file gets saved, by polling its modification time.
+pub struct Watcher {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Watcher {
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    pub fn new() -> Self {
+        let path = Config::path();
+        let modified = path.as_deref().and_then(modified_time);
+
+        Watcher {
+            path,
+            modified,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns the reloaded config, with the same selection, if the file changed since the last
+    /// time we looked.
+    pub fn poll(&mut self, selection: Selection) -> Option<Config> {
+        if self.last_check.elapsed() < Self::INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified_time(self.path.as_deref()?);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        Some(Config::load_selected(selection))
+    }
+}
+
+fn modified_time(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Accepts `HH:MM`, returning minutes after midnight.
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u32 = hours.parse().ok().filter(|hours| *hours < 24)?;
+    let minutes: u32 = minutes.parse().ok().filter(|minutes| *minutes < 60)?;
+    Some(hours * 60 + minutes)
+}
+
+/// Accepts `#rgb`, `#rrggbb` and `#rrggbbaa`, returning ARGB.
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let rgba = match hex.len() {
+        3 => {
+            let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+            u32::from_str_radix(&expanded, 16).ok()? << 8 | 0xFF
+        }
+        6 => u32::from_str_radix(hex, 16).ok()? << 8 | 0xFF,
+        8 => u32::from_str_radix(hex, 16).ok()?,
+        _ => return None,
+    };
+
+    Some(rgba.rotate_right(8))
+}
+
+/// The inverse of [`parse_color`], leaving out the alpha if it's opaque.
+pub fn format_color(argb: u32) -> String {
+    let rgba = argb.rotate_left(8);
+    if rgba & 0xFF == 0xFF {
+        format!("#{:06x}", rgba >> 8)
+    } else {
+        format!("#{rgba:08x}")
+    }
+}
+
+/// `fprint-prompt --check-config`: reports every problem in the config file.
+pub fn check() -> i32 {
+    let Some(path) = Config::path() else {
+        eprintln!("could not determine the config directory");
+        return exit::FAILURE;
+    };
+
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("could not read {}: {e}", path.display());
+            return exit::FAILURE;
+        }
+    };
+
+    let (_, errors) = Config::parse(&text);
+
+    for error in &errors {
+        println!("{}:{error}", path.display());
+
+        if let Some(line) = error.line.checked_sub(1).and_then(|i| text.lines().nth(i)) {
+            println!("    {line}");
+            println!("    {}^", " ".repeat(error.column.saturating_sub(1)));
+        }
+    }
+
+    if errors.is_empty() {
+        println!("{} is valid", path.display());
+        exit::SUCCESS
+    } else {
+        exit::FAILURE
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    /// 1-based, or 0 if the error isn't about a particular line.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Problems spanning several keys don't have a location.
+        if self.line == 0 {
+            return write!(f, " {}", self.message);
+        }
+
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Boolean(_) => "a boolean",
+        }
+    }
+}
+
+/// A single `key = value` pair, with the key prefixed by the section it appeared in.
+pub struct Entry {
+    pub key: Vec<String>,
+    pub value: Value,
+    pub line: usize,
+    pub key_column: usize,
+    /// Column of the value, for pointing at it in errors.
+    pub column: usize,
+}
+
+impl Entry {
+    /// A `[profile.name.when]` key, with the profile's name already cut off.
+    fn profile_rule(&self, profile: String) -> Result<ProfileRule, ParseError> {
+        match self.key.as_slice() {
+            [_, key] if key == "outputs_include" => Ok(ProfileRule {
+                profile,
+                outputs_include: self.string()?,
+            }),
+            _ => Err(self.unknown_key()),
+        }
+    }
+
+    pub fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    pub fn key_error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.key_column,
+            message: message.into(),
+        }
+    }
+
+    pub fn unknown_key(&self) -> ParseError {
+        self.key_error(format!("unknown key {}", self.key.join(".")))
+    }
+
+    fn expected(&self, what: &str) -> ParseError {
+        self.error(format!(
+            "{} should be {what}, not {}",
+            self.key.join("."),
+            self.value.type_name()
+        ))
+    }
+
+    pub fn string(&self) -> Result<String, ParseError> {
+        match &self.value {
+            Value::String(value) => Ok(value.clone()),
+            _ => Err(self.expected("a string")),
+        }
+    }
+
+    pub fn bool(&self) -> Result<bool, ParseError> {
+        match self.value {
+            Value::Boolean(value) => Ok(value),
+            _ => Err(self.expected("a boolean")),
+        }
+    }
+
+    pub fn u32_in(&self, range: RangeInclusive<u32>) -> Result<u32, ParseError> {
+        let Value::Integer(value) = self.value else {
+            return Err(self.expected("an integer"));
+        };
+
+        match u32::try_from(value) {
+            Ok(value) if range.contains(&value) => Ok(value),
+            _ => Err(self.error(format!(
+                "{} should be between {} and {}, not {value}",
+                self.key.join("."),
+                range.start(),
+                range.end()
+            ))),
+        }
+    }
+
+    /// A number from 0 to 1. Integers are accepted so `1` doesn't have to be written as `1.0`.
+    /// An output scale, from a quarter up to `MAX_SCALE`.
+    pub fn scale(&self) -> Result<f32, ParseError> {
+        let value = match self.value {
+            Value::Float(value) => value,
+            Value::Integer(value) => value as f64,
+            _ => return Err(self.expected("a number")),
+        };
+
+        if !(0.25..=MAX_SCALE).contains(&value) {
+            return Err(self.error(format!(
+                "{} should be between 0.25 and {MAX_SCALE}, not {value}",
+                self.key.join(".")
+            )));
+        }
+
+        Ok(value as f32)
+    }
+
+    pub fn fraction(&self) -> Result<f32, ParseError> {
+        let value = match self.value {
+            Value::Float(value) => value,
+            Value::Integer(value) => value as f64,
+            _ => return Err(self.expected("a number")),
+        };
+
+        if !(0. ..=1.).contains(&value) {
+            return Err(self.error(format!(
+                "{} should be between 0 and 1, not {value}",
+                self.key.join(".")
+            )));
+        }
+
+        Ok(value as f32)
+    }
+
+    /// A distance on the screen, at least `min` and no more than a meter.
+    pub fn millimeters(&self, min: f32) -> Result<f32, ParseError> {
+        let value = match self.value {
+            Value::Float(value) => value as f32,
+            Value::Integer(value) => value as f32,
+            _ => return Err(self.expected("a number")),
+        };
+
+        if !(min..=MAX_MILLIMETERS).contains(&value) {
+            return Err(self.error(format!(
+                "{} should be between {min} and {MAX_MILLIMETERS}, not {value}",
+                self.key.join(".")
+            )));
+        }
+
+        Ok(value)
+    }
+
+    /// Pieces of the bar, like `"0:60, 200:40"` for 60 pixels at its start and 40 more a bit
+    /// further along, sorted by where they start.
+    pub fn segments(&self) -> Result<Vec<(u32, u32)>, ParseError> {
+        let mut segments = vec![];
+        for segment in self.string()?.split(',').map(str::trim) {
+            if segment.is_empty() {
+                continue;
+            }
+            let parsed = segment.split_once(':').and_then(|(offset, length)| {
+                let offset: u32 = offset.trim().parse().ok()?;
+                let length: u32 = length.trim().parse().ok()?;
+                (offset <= MAX_LENGTH && (1..=MAX_LENGTH).contains(&length))
+                    .then_some((offset, length))
+            });
+            let Some(segment) = parsed else {
+                return Err(self.error(format!(
+                    "{} should list offsets and lengths like \"0:60, 200:40\", not {segment:?}",
+                    self.key.join(".")
+                )));
+            };
+            segments.push(segment);
+        }
+
+        segments.sort_unstable();
+        Ok(segments)
+    }
+
+    pub fn color(&self) -> Result<u32, ParseError> {
+        let color = self.string()?;
+        parse_color(&color).ok_or_else(|| {
+            self.error(format!(
+                "invalid color {color:?}, expected \"#rrggbb\" or \"#rrggbbaa\""
+            ))
+        })
+    }
+
+    /// "HH:MM", as minutes after midnight.
+    pub fn time_of_day(&self) -> Result<u32, ParseError> {
+        let time = self.string()?;
+
+        parse_time(&time).ok_or_else(|| {
+            self.error(format!(
+                "{} should be a time like \"21:30\", not {time:?}",
+                self.key.join(".")
+            ))
+        })
+    }
+
+    /// A built-in palette or a theme from assets.
+    pub fn palette(&self) -> Result<Theme, ParseError> {
+        let name = self.string()?;
+        let themes = ThemeRegistry::discover();
+
+        match themes.resolve(&name) {
+            Some(Ok(theme)) => Ok(theme),
+            Some(Err(e)) => Err(self.error(format!("theme {name:?} is broken: {e}"))),
+            None => {
+                let names: Vec<_> = themes.names().map(|name| format!("{name:?}")).collect();
+                Err(self.error(format!(
+                    "{} should be one of {}, not {name:?}",
+                    self.key.join("."),
+                    names.join(", ")
+                )))
+            }
+        }
+    }
+
+    pub fn shadow_style(&self) -> Result<ShadowStyle, ParseError> {
+        match self.string()?.as_str() {
+            "none" => Ok(ShadowStyle::None),
+            "drop" => Ok(ShadowStyle::Drop),
+            "outline" => Ok(ShadowStyle::Outline),
+            other => Err(self.error(format!(
+                "{} should be \"none\", \"drop\" or \"outline\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    pub fn bus(&self) -> Result<Bus, ParseError> {
+        match self.string()?.as_str() {
+            "system" => Ok(Bus::System),
+            "session" => Ok(Bus::Session),
+            address if address.contains(':') => Ok(Bus::Address(address.to_string())),
+            other => Err(self.error(format!(
+                "{} should be \"system\", \"session\" or a D-Bus address, not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    pub fn signal_action(&self) -> Result<SignalAction, ParseError> {
+        let action = self.string()?;
+        let argument = action
+            .strip_prefix('{')
+            .and_then(|action| action.strip_suffix('}'))
+            .and_then(|index| index.parse().ok());
+        match (action.as_str(), argument) {
+            ("show", _) => Ok(SignalAction::Show),
+            ("hide", _) => Ok(SignalAction::Hide),
+            (_, Some(index)) => Ok(SignalAction::Argument(index)),
+            (other, None) => Err(self.error(format!(
+                "{} should be \"show\", \"hide\" or an argument like \"{{0}}\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    pub fn keyboard_interactivity(&self) -> Result<KeyboardInteractivity, ParseError> {
+        match self.string()?.as_str() {
+            "none" => Ok(KeyboardInteractivity::None),
+            "on_demand" => Ok(KeyboardInteractivity::OnDemand),
+            "exclusive" => Ok(KeyboardInteractivity::Exclusive),
+            other => Err(self.error(format!(
+                "{} should be \"none\", \"on_demand\" or \"exclusive\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    pub fn layer(&self) -> Result<Layer, ParseError> {
+        match self.string()?.as_str() {
+            "overlay" => Ok(Layer::Overlay),
+            "top" => Ok(Layer::Top),
+            "bottom" => Ok(Layer::Bottom),
+            "background" => Ok(Layer::Background),
+            other => Err(self.error(format!(
+                "{} should be one of \"overlay\", \"top\", \"bottom\" or \"background\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    pub fn scale_strategy(&self) -> Result<ScaleStrategy, ParseError> {
+        match self.string()?.as_str() {
+            "fractional" => Ok(ScaleStrategy::Fractional),
+            "integer" => Ok(ScaleStrategy::Integer),
+            "none" => Ok(ScaleStrategy::None),
+            other => Err(self.error(format!(
+                "{} should be one of \"fractional\", \"integer\" or \"none\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    pub fn style(&self) -> Result<Style, ParseError> {
+        let name = self.string()?;
+        Style::from_name(&name).ok_or_else(|| {
+            let names: Vec<_> = Style::ALL
+                .iter()
+                .map(|style| format!("{:?}", style.name()))
+                .collect();
+            self.error(format!(
+                "{} should be one of {}, not {name:?}",
+                self.key.join("."),
+                names.join(", ")
+            ))
+        })
+    }
+
+    /// "overlay" or "none", as whether to show the overlay.
+    pub fn display(&self) -> Result<bool, ParseError> {
+        match self.string()?.as_str() {
+            "overlay" => Ok(true),
+            "none" => Ok(false),
+            other => Err(self.error(format!(
+                "{} should be \"overlay\" or \"none\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+
+    /// A comma-separated list of sinks, like "overlay,sound".
+    pub fn sinks(&self) -> Result<Vec<Sink>, ParseError> {
+        let mut sinks = vec![];
+        for name in self.string()?.split(',').map(str::trim) {
+            if name.is_empty() {
+                continue;
+            }
+            let sink = Sink::from_name(name).ok_or_else(|| {
+                let names: Vec<_> = Sink::ALL
+                    .iter()
+                    .map(|sink| format!("{:?}", sink.name()))
+                    .collect();
+                self.error(format!(
+                    "{} should list {}, not {name:?}",
+                    self.key.join("."),
+                    names.join(", ")
+                ))
+            })?;
+            if !sinks.contains(&sink) {
+                sinks.push(sink);
+            }
+        }
+        Ok(sinks)
+    }
+
+    /// Like [`Entry::anchor`], but also accepts "center".
+    pub fn close_to(&self) -> Result<Anchor, ParseError> {
+        if matches!(&self.value, Value::String(value) if value == "center") {
+            return Ok(Anchor::empty());
+        }
+
+        self.anchor()
+    }
+
+    pub fn anchor(&self) -> Result<Anchor, ParseError> {
+        match self.string()?.as_str() {
+            "top" => Ok(Anchor::TOP),
+            "bottom" => Ok(Anchor::BOTTOM),
+            "left" => Ok(Anchor::LEFT),
+            "right" => Ok(Anchor::RIGHT),
+            other => Err(self.error(format!(
+                "{} should be one of \"top\", \"bottom\", \"left\" or \"right\", not {other:?}",
+                self.key.join(".")
+            ))),
+        }
+    }
+}
+
+/// Splits the config into entries. Lines with syntax errors are skipped and reported.
+pub fn parse_entries(text: &str) -> (Vec<Entry>, Vec<ParseError>) {
+    let mut entries = vec![];
+    let mut errors = vec![];
+    let mut section = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let mut cursor = Cursor {
+            chars: line.chars().collect(),
+            pos: 0,
+            line: index + 1,
+        };
+
+        match parse_line(&mut cursor, &mut section) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => (),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (entries, errors)
+}
+
+fn parse_line(cursor: &mut Cursor, section: &mut Vec<String>) -> Result<Option<Entry>, ParseError> {
+    cursor.skip_whitespace();
+    if cursor.at_end_of_line() {
+        return Ok(None);
+    }
+
+    let entry = if cursor.eat('[') {
+        *section = cursor.parse_key()?;
+        cursor.expect(']')?;
+        None
+    } else {
+        let key_column = cursor.pos + 1;
+        let mut key = section.clone();
+        key.extend(cursor.parse_key()?);
+        cursor.expect('=')?;
+        cursor.skip_whitespace();
+        let column = cursor.pos + 1;
+        let value = cursor.parse_value()?;
+
+        Some(Entry {
+            key,
+            value,
+            line: cursor.line,
+            key_column,
+            column,
+        })
+    };
+
+    cursor.skip_whitespace();
+    if !cursor.at_end_of_line() {
+        return Err(cursor.error("expected end of line"));
+    }
+
+    Ok(entry)
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.pos + 1,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end_of_line(&self) -> bool {
+        matches!(self.peek(), None | Some('#'))
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{expected}'")))
+        }
+    }
+
+    /// Parses a dotted key like `output."eDP-1".offset`.
+    fn parse_key(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut key = vec![];
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some('"') {
+                key.push(self.parse_string()?);
+            } else {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.pos += 1;
+                }
+                if start == self.pos {
+                    return Err(self.error("expected a key"));
+                }
+                key.push(self.chars[start..self.pos].iter().collect());
+            }
+
+            self.skip_whitespace();
+            if !self.eat('.') {
+                return Ok(key);
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    string.push(escaped);
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    string.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        if self.peek() == Some('"') {
+            return self.parse_string().map(Value::String);
+        }
+
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && c != '#') {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+
+        let value = match word.as_str() {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ => {
+                let number = word.replace('_', "");
+                if let Ok(integer) = number.parse() {
+                    Value::Integer(integer)
+                } else if let Ok(float) = number.parse() {
+                    Value::Float(float)
+                } else {
+                    self.pos = start;
+                    return Err(self.error(format!("invalid value {word:?}")));
+                }
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(edge: Anchor, close_to: Anchor) -> PositionInfo {
+        PositionInfo {
+            thickness: 8,
+            length: 138,
+            edge,
+            close_to,
+            offset: 100,
+            notch: 0,
+            segments: vec![],
+            millimeters: Millimeters::default(),
+        }
+    }
+
+    const LAYOUTS: [(Anchor, Anchor); 6] = [
+        (Anchor::TOP, Anchor::LEFT),
+        (Anchor::BOTTOM, Anchor::RIGHT),
+        (Anchor::LEFT, Anchor::TOP),
+        (Anchor::RIGHT, Anchor::BOTTOM),
+        (Anchor::TOP, Anchor::empty()),
+        (Anchor::RIGHT, Anchor::empty()),
+    ];
+
+    #[test]
+    fn fitted_to_its_own_size_is_unchanged() {
+        for (edge, close_to) in LAYOUTS {
+            let pos = geometry(edge, close_to);
+            let fitted = pos.fitted(pos.win_width(), pos.win_height());
+            assert_eq!(fitted.size(), pos.size());
+            assert_eq!(fitted.offset(), pos.offset());
+        }
+    }
+
+    #[test]
+    fn fitted_window_is_exactly_the_given_size() {
+        for (edge, close_to) in LAYOUTS {
+            let pos = geometry(edge, close_to);
+            for (width, height) in [(1, 1), (50, 4), (120, 300), (238, 8), (1920, 1080), (0, 0)] {
+                let fitted = pos.fitted(width, height);
+                assert_eq!(
+                    (fitted.win_width(), fitted.win_height()),
+                    (width, height),
+                    "{edge:?} {close_to:?} in {width}x{height}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smaller_window_clips_the_bar() {
+        let pos = geometry(Anchor::TOP, Anchor::LEFT);
+        let fitted = pos.fitted(150, 4);
+        assert_eq!(fitted.length, 50);
+        assert_eq!(fitted.thickness, 4);
+        assert_eq!(fitted.offset, 100);
+
+        // Narrower than the offset: nothing of the bar is left.
+        let fitted = pos.fitted(60, 8);
+        assert_eq!(fitted.length, 0);
+        assert_eq!(fitted.offset, 60);
+    }
+
+    #[test]
+    fn bar_stays_inside_a_fitted_window() {
+        for (edge, close_to) in LAYOUTS {
+            let pos = geometry(edge, close_to);
+            for (width, height) in [(50, 4), (4, 50), (300, 300), (238, 8), (8, 238)] {
+                let fitted = pos.fitted(width, height);
+                // What the renderer does: never more than the window has room for.
+                let bar = BarSize {
+                    length: pos.length.min(fitted.length),
+                    thickness: pos.thickness.min(fitted.thickness),
+                };
+                let (x, y, bar_width, bar_height) = fitted.bar_rect(bar);
+                assert!(
+                    x + bar_width <= width && y + bar_height <= height,
+                    "{edge:?} {close_to:?} in {width}x{height}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn geometry_too_big_for_the_output_is_shrunk() {
+        let sizes = StateSizes::default();
+        let pos = geometry(Anchor::TOP, Anchor::LEFT);
+        assert!(pos.limited(&sizes, (1920, 1080), 0.25).is_none());
+
+        // A typo'd offset: the bar is moved back onto the output.
+        let far = PositionInfo {
+            offset: 4000,
+            ..geometry(Anchor::TOP, Anchor::LEFT)
+        };
+        let (limited, _) = far.limited(&sizes, (1920, 1080), 0.25).unwrap();
+        assert_eq!(limited.offset + limited.length, 1920);
+
+        // Too thick, in a state: it can't cover more than max_area.
+        let mut sizes = StateSizes::default();
+        sizes.overrides[BarState::Failure as usize] = (Some(1920), Some(256));
+        let (limited, sizes) = pos.limited(&sizes, (1920, 400), 0.25).unwrap();
+        let fitted = sizes.fit(&limited);
+        assert!(fitted.length * fitted.thickness <= 1920 * 400 / 4);
+    }
+
+    #[test]
+    fn signal_rules_are_parsed() {
+        let (config, errors) = Config::parse(
+            "[signal.waiting]\n\
+             match = \"type='signal',member='Waiting'\"\n\
+             action = \"{0}\"\n\
+             [signal.done]\n\
+             match = \"type='signal',member='Done'\"\n\
+             bus = \"session\"\n\
+             action = \"hide\"\n\
+             prompt = \"waiting\"\n\
+             [signal.shown]\n\
+             match = \"type='signal',member='Shown'\"\n\
+             action = \"show\"\n\
+             text = \"Waiting on {1}\"\n",
+        );
+        assert!(errors.is_empty());
+
+        let waiting = &config.signal_rules["waiting"];
+        assert_eq!(waiting.rule, "type='signal',member='Waiting'");
+        assert_eq!(waiting.bus, Bus::System);
+        assert_eq!(waiting.action, SignalAction::Argument(0));
+        assert_eq!(waiting.prompt, None);
+
+        let done = &config.signal_rules["done"];
+        assert_eq!(done.bus, Bus::Session);
+        assert_eq!(done.action, SignalAction::Hide);
+        assert_eq!(done.prompt.as_deref(), Some("waiting"));
+
+        let shown = &config.signal_rules["shown"];
+        assert_eq!(shown.action, SignalAction::Show);
+        assert_eq!(shown.text, "Waiting on {1}");
+    }
+
+    #[test]
+    fn bad_signal_rules_are_reported() {
+        for action in ["\"{}\"", "\"{x}\"", "\"{0\"", "\"toggle\"", "1"] {
+            let (config, errors) = Config::parse(&format!(
+                "[signal.waiting]\nmatch = \"type='signal'\"\naction = {action}\n"
+            ));
+            assert_eq!(errors.len(), 1, "{action}");
+            assert_eq!(errors[0].line, 3, "{action}");
+            // The rest of the rule is still used.
+            assert_eq!(config.signal_rules["waiting"].action, SignalAction::Show);
+        }
+
+        let (config, errors) = Config::parse("[signal.waiting]\naction = \"show\"\n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .message
+            .contains("signal.waiting needs a match rule"));
+        assert!(config.signal_rules.is_empty());
+
+        let (_, errors) = Config::parse("[signal.waiting]\nmatch = \"type='signal'\"\nwhen = 1\n");
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -0,0 +1,160 @@
+// Cleaning up after a panic.
+//
+// Unwinding drops the layer surfaces, and [`FlushOnPanic`] makes sure the compositor hears about
+// it before the process goes, so no bar is left stuck on screen. With `crash_reports = true`, the
+// panic and the last things logged are also written to `$XDG_STATE_HOME/fprint-prompt/`, as they
+// are when the watchdog finds the main loop stuck.
+
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use wayland_client::Connection;
+
+use crate::instance;
+
+/// How many recent events go into a crash report.
+const RECENT_EVENTS: usize = 64;
+
+static REPORTS: AtomicBool = AtomicBool::new(false);
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Sets up logging, keeping the recent lines for crash reports, and the panic hook.
+pub fn install() {
+    let logger = Logger {
+        inner: env_logger::Builder::from_default_env().build(),
+    };
+    log::set_max_level(logger.inner.filter());
+    if let Err(e) = log::set_boxed_logger(Box::new(logger)) {
+        eprintln!("could not set up logging: {e}");
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        if REPORTS.load(Ordering::Relaxed) {
+            write_report(
+                "crash",
+                &format!(
+                    "fprint-prompt {} crashed\n\n{info}\n\n{}",
+                    env!("CARGO_PKG_VERSION"),
+                    std::backtrace::Backtrace::force_capture()
+                ),
+            );
+        }
+    }));
+}
+
+pub fn set_reports(enabled: bool) {
+    REPORTS.store(enabled, Ordering::Relaxed);
+}
+
+/// Remembers something that happened, for the next crash report.
+pub fn record(event: String) {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0., |since| since.as_secs_f64());
+
+    // Never wait on a panicking thread that held the lock.
+    let Ok(mut recent) = RECENT.try_lock() else {
+        return;
+    };
+    if recent.len() == RECENT_EVENTS {
+        recent.pop_front();
+    }
+    recent.push_back(format!("{seconds:.3} {event}"));
+}
+
+/// Flushes the Wayland connection when dropped during a panic, sending the requests that tear
+/// down the surfaces dropped before it. Has to be created before them, to be dropped after.
+pub struct FlushOnPanic(pub Connection);
+
+impl Drop for FlushOnPanic {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            let _ = self.0.flush();
+        }
+    }
+}
+
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.inner.matches(record) {
+            self::record(format!(
+                "{} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Writes a report about the main loop hanging, if reports are enabled.
+pub fn report_stall(description: &str) {
+    if REPORTS.load(Ordering::Relaxed) {
+        write_report(
+            "stall",
+            &format!(
+                "fprint-prompt {} stalled\n\n{description}",
+                env!("CARGO_PKG_VERSION")
+            ),
+        );
+    }
+}
+
+/// Writes `what` happened with the recent events to `{kind}-{time}.txt`, or `{kind}-NAME-{time}.txt`
+/// for `--instance NAME`.
+fn write_report(kind: &str, what: &str) {
+    let Some(dir) = state_dir() else {
+        return;
+    };
+
+    let mut report = format!("{what}\n\nRecent events:\n");
+    if let Ok(recent) = RECENT.try_lock() {
+        for event in recent.iter() {
+            let _ = writeln!(report, "{event}");
+        }
+    }
+
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let path = dir.join(format!("{}-{seconds}.txt", instance::file_name(kind)));
+
+    match std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(&path, report)) {
+        Ok(()) => eprintln!("wrote a {kind} report to {}", path.display()),
+        Err(e) => eprintln!("could not write a {kind} report to {}: {e}", path.display()),
+    }
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+
+    Some(state_home.join("fprint-prompt"))
+}
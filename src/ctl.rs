@@ -0,0 +1,61 @@
+// `fprint-prompt ctl`: controls the running overlay through its Control1 interface, e.g.
+// `fprint-prompt ctl profile docked` from a script that runs when docking. `ctl latency` prints
+// how long its frames take to be shown.
+
+use std::collections::BTreeMap;
+
+use dbus::ffidisp::{stdintf::org_freedesktop_dbus::Properties, Connection as DbusConnection};
+
+use crate::{
+    exit, instance,
+    ipc::{control, PATH},
+};
+
+const USAGE: &str = "usage: fprint-prompt ctl [pause|resume|profile NAME|profile --none|latency]";
+const TIMEOUT_MS: i32 = 1000;
+
+pub fn run(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let dbus = match DbusConnection::new_session() {
+        Ok(dbus) => dbus,
+        Err(e) => {
+            eprintln!("could not connect to the session bus: {e}");
+            return exit::FAILURE;
+        }
+    };
+    let proxy = dbus.with_path(instance::bus_name(), PATH, TIMEOUT_MS);
+
+    let called = match args.as_slice() {
+        ["pause"] => proxy.method_call(control::INTERFACE, "Pause", ()),
+        ["resume"] => proxy.method_call(control::INTERFACE, "Resume", ()),
+        ["profile", "--none"] => proxy.method_call(control::INTERFACE, "SetProfile", ("",)),
+        ["profile", name] => proxy.method_call(control::INTERFACE, "SetProfile", (*name,)),
+        ["latency"] => proxy
+            .get::<BTreeMap<String, u64>>(control::INTERFACE, "Latency")
+            .map(|latency| {
+                for (name, value) in latency {
+                    println!("{name} {value}");
+                }
+            }),
+        _ => {
+            eprintln!("{USAGE}");
+            return exit::USAGE;
+        }
+    };
+
+    match called {
+        Ok(()) => exit::SUCCESS,
+        Err(e) if e.name() == Some("org.freedesktop.DBus.Error.ServiceUnknown") => {
+            match instance::name() {
+                Some(name) => eprintln!("fprint-prompt instance {name} isn't running"),
+                None => eprintln!("fprint-prompt isn't running"),
+            }
+            exit::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}", e.message().unwrap_or("the overlay didn't answer"));
+            exit::FAILURE
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// `fprint-prompt monitor`: prints every D-Bus message to and from fprintd, for bug reports about
+// readers that behave oddly. Like dbus-monitor, but only fprintd's messages, with each argument
+// decoded along with its type, and every line timestamped.
+//
+// Method calls and their replies can only be seen by a monitor, which the system bus only lets
+// root become. Anyone else gets fprintd's signals, which is all the overlay sees anyway.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dbus::{blocking::Connection, Message, MessageType};
+use fprint_prompt::{bus, fprint::SERVICE};
+
+use crate::exit;
+
+const DBUS: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn run() -> i32 {
+    let bus = bus::selected();
+    let conn = match bus.channel() {
+        Ok(channel) => Connection::from(channel),
+        Err(e) => {
+            eprintln!("could not connect to {bus}: {e}");
+            return exit::FAILURE;
+        }
+    };
+
+    let owner = format!("type='signal',sender='{DBUS}',member='NameOwnerChanged',arg0='{SERVICE}'");
+    let rules = vec![
+        format!("sender='{SERVICE}'"),
+        format!("destination='{SERVICE}'"),
+        owner.clone(),
+    ];
+    let monitoring = conn
+        .with_proxy(DBUS, DBUS_PATH, TIMEOUT)
+        .method_call::<(), _, _, _>(
+            "org.freedesktop.DBus.Monitoring",
+            "BecomeMonitor",
+            (rules, 0u32),
+        );
+    match monitoring {
+        Ok(()) => eprintln!("Monitoring fprintd on {bus}"),
+        Err(e) => {
+            eprintln!("could not become a monitor, showing only fprintd's signals: {e}");
+            eprintln!("Run as root to see method calls and replies too.");
+            for rule in [format!("type='signal',sender='{SERVICE}'"), owner] {
+                if let Err(e) = conn.add_match_no_cb(&rule) {
+                    eprintln!("could not listen on {bus}: {e}");
+                    return exit::FAILURE;
+                }
+            }
+        }
+    }
+
+    let channel = conn.channel();
+    loop {
+        if let Err(e) = channel.read_write(None) {
+            eprintln!("lost {bus}: {e:?}");
+            return exit::FAILURE;
+        }
+        while let Some(mut msg) = channel.pop_message() {
+            // Said to us for listening, not by fprintd.
+            if msg.sender().as_deref() == Some(DBUS)
+                && msg.member().as_deref() != Some("NameOwnerChanged")
+            {
+                continue;
+            }
+            println!("{}", describe(&mut msg));
+        }
+    }
+}
+
+/// One line for a message: when, what kind, between whom, what for, and its arguments.
+fn describe(msg: &mut Message) -> String {
+    let mut line = format!(
+        "{} {:<6} {} -> {}",
+        timestamp(),
+        match msg.msg_type() {
+            MessageType::MethodCall => "call",
+            MessageType::MethodReturn => "return",
+            MessageType::Error => "error",
+            MessageType::Signal => "signal",
+        },
+        msg.sender().as_deref().unwrap_or("-"),
+        msg.destination().as_deref().unwrap_or("*"),
+    );
+
+    if let Some(path) = msg.path() {
+        line += &format!(" {path}");
+    }
+    match (msg.interface(), msg.member()) {
+        (Some(interface), Some(member)) => line += &format!(" {interface}.{member}"),
+        (None, Some(member)) => line += &format!(" {member}"),
+        _ => (),
+    }
+    if let Err(error) = msg.as_result() {
+        line += &format!(" {}", error.name().unwrap_or("?"));
+    }
+    if let Some(serial) = msg.get_reply_serial() {
+        line += &format!(" (reply to {serial})");
+    } else if msg.msg_type() == MessageType::MethodCall {
+        line += &format!(" (serial {})", msg.get_serial().unwrap_or(0));
+    }
+
+    let mut args = msg.iter_init();
+    let mut decoded = vec![];
+    while let Some(arg) = args.get_refarg() {
+        decoded.push(format!("{}: {arg:?}", args.signature()));
+        args.next();
+    }
+    if !decoded.is_empty() {
+        line += &format!(" [{}]", decoded.join(", "));
+    }
+    line
+}
+
+/// The local time of day, to the millisecond.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as libc::time_t;
+
+    // SAFETY: localtime_r only writes to the tm we give it.
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut tm);
+        tm
+    };
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+        now.subsec_millis()
+    )
+}
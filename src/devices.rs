@@ -0,0 +1,56 @@
+// `fprint-prompt devices`: lists the fingerprint readers fprintd knows about.
+
+use crate::{bus, exit, fprint, reader_quirks, sandbox};
+
+pub fn run() -> i32 {
+    let bus = bus::selected();
+    let dbus = match bus.connect() {
+        Ok(dbus) => dbus,
+        Err(e) => {
+            eprintln!("could not connect to {bus}: {e}");
+            return exit::FAILURE;
+        }
+    };
+
+    let devices = match fprint::get_devices(&dbus) {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("could not list devices: {e}");
+            if let Some(hint) = sandbox::permission_hint() {
+                eprintln!("{hint}");
+            }
+            return exit::FAILURE;
+        }
+    };
+
+    if devices.is_empty() {
+        println!("No fingerprint readers found.");
+        return exit::SUCCESS;
+    }
+
+    let username = fprint::current_username();
+
+    for device in devices {
+        println!("{}", device.path);
+        match device.name() {
+            Ok(name) => {
+                println!("  name:      {name}");
+                if reader_quirks::is_match_on_chip(&name) {
+                    println!("  matching:  on the reader, which doesn't report retries");
+                }
+            }
+            Err(e) => println!("  name:      unknown ({e})"),
+        }
+        match device.scan_type() {
+            Ok(scan_type) => println!("  scan type: {scan_type}"),
+            Err(e) => println!("  scan type: unknown ({e})"),
+        }
+        match device.list_enrolled_fingers(&username) {
+            Ok(fingers) if fingers.is_empty() => println!("  enrolled:  none for {username:?}"),
+            Ok(fingers) => println!("  enrolled:  {}", fingers.join(", ")),
+            Err(e) => println!("  enrolled:  unknown ({e})"),
+        }
+    }
+
+    exit::SUCCESS
+}
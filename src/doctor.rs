@@ -0,0 +1,230 @@
+// `fprint-prompt doctor`: checks everything the overlay depends on and prints a readiness report.
+
+use std::fmt::Display;
+
+use smithay_client_toolkit::reexports::{
+    protocols::wp::{
+        fractional_scale::v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        viewporter::client::wp_viewporter::WpViewporter,
+    },
+    protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::ZwlrLayerShellV1,
+};
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry, wl_shm::WlShm},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use crate::{
+    bus,
+    config::Config,
+    exit, fprint,
+    quirks::{Compositor, Quirks},
+    sandbox,
+};
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn pass(&mut self, msg: impl Display) {
+        println!("  [ ok ] {msg}");
+    }
+
+    fn warn(&mut self, msg: impl Display) {
+        println!("  [warn] {msg}");
+    }
+
+    fn fail(&mut self, msg: impl Display, hint: &str) {
+        self.failures += 1;
+        println!("  [FAIL] {msg}");
+        if !hint.is_empty() {
+            println!("         {hint}");
+        }
+    }
+}
+
+/// Runs all checks and returns the process exit code.
+pub fn run() -> i32 {
+    let mut report = Report::default();
+
+    println!("Wayland:");
+    check_wayland(&mut report);
+
+    println!("fprintd:");
+    check_fprintd(&mut report);
+
+    println!();
+    if report.failures == 0 {
+        println!("Everything looks ready.");
+        exit::SUCCESS
+    } else {
+        println!("{} check(s) failed.", report.failures);
+        exit::FAILURE
+    }
+}
+
+fn check_wayland(report: &mut Report) {
+    match std::env::var("XDG_SESSION_TYPE").as_deref() {
+        Ok("wayland") => report.pass("XDG_SESSION_TYPE is wayland"),
+        Ok(other) => report.warn(format!(
+            "XDG_SESSION_TYPE is {other:?}, expected \"wayland\""
+        )),
+        Err(_) => report.warn("XDG_SESSION_TYPE is not set"),
+    }
+
+    let Ok(display) = std::env::var("WAYLAND_DISPLAY") else {
+        report.fail(
+            "WAYLAND_DISPLAY is not set",
+            "fprint-prompt must run inside a Wayland session.",
+        );
+        return;
+    };
+
+    let conn = match Connection::connect_to_env() {
+        Ok(conn) => {
+            report.pass(format!("connected to {display}"));
+            conn
+        }
+        Err(e) => {
+            report.fail(
+                format!("could not connect to {display}: {e}"),
+                "Is the compositor running, and is XDG_RUNTIME_DIR set?",
+            );
+            return;
+        }
+    };
+
+    let (globals, _queue) = match registry_queue_init::<Probe>(&conn) {
+        Ok(init) => init,
+        Err(e) => {
+            report.fail(format!("could not list globals: {e}"), "");
+            return;
+        }
+    };
+
+    let has_global = |name: &str| {
+        globals
+            .contents()
+            .with_list(|list| list.iter().any(|global| global.interface == name))
+    };
+
+    let required = [
+        (WlShm::interface().name, "shared memory buffers"),
+        (WpViewporter::interface().name, "viewporter"),
+    ];
+
+    let compositor = Compositor::detect();
+    let quirks = Quirks::new(compositor, &Config::load().quirks);
+    report.pass(format!(
+        "running on {}: {:?} layer, namespace {:?}, {:?} scaling",
+        compositor.name(),
+        quirks.layer,
+        quirks.namespace,
+        quirks.scale
+    ));
+
+    let fractional_scale = WpFractionalScaleManagerV1::interface().name;
+    if has_global(fractional_scale) {
+        report.pass(format!("fractional scaling ({fractional_scale})"));
+    } else {
+        report.warn(format!(
+            "fractional scaling ({fractional_scale}) is not supported, so the bar follows whole \
+             output scales"
+        ));
+    }
+
+    let layer_shell = ZwlrLayerShellV1::interface().name;
+    if has_global(layer_shell) {
+        report.pass(format!("layer-shell ({layer_shell})"));
+    } else {
+        report.warn(format!(
+            "layer-shell ({layer_shell}) is not supported, so the prompt can only be drawn by a \
+             shell extension following {}",
+            crate::ipc::NAME
+        ));
+    }
+
+    for (interface, description) in required {
+        if has_global(interface) {
+            report.pass(format!("{description} ({interface})"));
+        } else {
+            report.fail(
+                format!("{description} ({interface}) is not supported by the compositor"),
+                "fprint-prompt needs a wlroots-style compositor (sway, Hyprland, KWin, niri, ...).",
+            );
+        }
+    }
+}
+
+fn check_fprintd(report: &mut Report) {
+    let bus = bus::selected();
+    let dbus = match bus.connect() {
+        Ok(dbus) => dbus,
+        Err(e) => {
+            report.fail(format!("could not connect to {bus}: {e}"), "");
+            return;
+        }
+    };
+
+    let devices = match fprint::get_devices(&dbus) {
+        Ok(devices) => {
+            report.pass(format!("{} is reachable", fprint::SERVICE));
+            devices
+        }
+        Err(e) => {
+            let hint = sandbox::permission_hint().unwrap_or_else(|| {
+                "Install fprintd and make sure it is D-Bus activatable.".to_string()
+            });
+            report.fail(format!("{} is not reachable: {e}", fprint::SERVICE), &hint);
+            return;
+        }
+    };
+
+    if devices.is_empty() {
+        report.fail(
+            "no fingerprint readers found",
+            "Check that your reader is supported by libfprint.",
+        );
+        return;
+    }
+
+    let username = fprint::current_username();
+
+    for device in devices {
+        let name = device.name().unwrap_or_else(|_| device.path.to_string());
+
+        match device.scan_type() {
+            Ok(scan_type) => report.pass(format!("{name}: scan type is {scan_type}")),
+            Err(e) => report.warn(format!("{name}: could not read scan type: {e}")),
+        }
+
+        match device.list_enrolled_fingers(&username) {
+            Ok(fingers) if fingers.is_empty() => report.fail(
+                format!("{name}: no fingerprints enrolled for {username:?}"),
+                "Enroll one with `fprintd-enroll`.",
+            ),
+            Ok(fingers) => report.pass(format!(
+                "{name}: enrolled for {username:?}: {}",
+                fingers.join(", ")
+            )),
+            Err(e) => report.fail(format!("{name}: could not list enrolled fingers: {e}"), ""),
+        }
+    }
+}
+
+struct Probe;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Probe {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
@@ -0,0 +1,33 @@
+// The exit statuses, for session managers and service files to tell apart. `fprint-prompt --help`
+// prints [`STATUSES`], which has to be kept in step.
+
+/// Exited as asked, or the command did what it should.
+pub const SUCCESS: i32 = 0;
+/// The command didn't work out, e.g. `doctor` found a problem or `ctl` couldn't reach the overlay.
+pub const FAILURE: i32 = 1;
+/// The command line was wrong.
+pub const USAGE: i32 = 2;
+/// Couldn't connect to the Wayland compositor, with `--require wayland`.
+pub const WAYLAND: i32 = 3;
+/// The compositor has no wl_compositor.
+pub const COMPOSITOR: i32 = 4;
+/// Couldn't share memory with the compositor.
+pub const SHM: i32 = 5;
+/// The compositor has no wp_viewporter.
+pub const VIEWPORTER: i32 = 6;
+/// Couldn't reach fprintd, or the bus it's on, with `--require fprintd`.
+pub const FPRINTD: i32 = 7;
+/// The watchdog found the main loop stuck. EX_TEMPFAIL: starting again is likely to work.
+pub const STALLED: i32 = 75;
+
+pub const STATUSES: &str = "\
+exit statuses:
+   0  exited as asked, or the command succeeded
+   1  the command failed
+   2  the command line was wrong
+   3  no Wayland connection (with --require wayland)
+   4  the compositor has no wl_compositor
+   5  could not share memory with the compositor
+   6  the compositor has no wp_viewporter
+   7  fprintd is not reachable (with --require fprintd)
+  75  the main loop got stuck; restarting is likely to help";
@@ -0,0 +1,202 @@
+// Small helpers for querying fprintd over the system bus.
+//
+// See https://fprint.freedesktop.org/fprintd-dev/ for the interface documentation.
+
+use std::collections::HashMap;
+
+use dbus::{
+    ffidisp::{stdintf::org_freedesktop_dbus::Properties, ConnPath, Connection as DbusConnection},
+    Path,
+};
+
+pub const SERVICE: &str = "net.reactivated.Fprint";
+pub const MANAGER_PATH: &str = "/net/reactivated/Fprint/Manager";
+pub const MANAGER_INTERFACE: &str = "net.reactivated.Fprint.Manager";
+pub const DEVICE_INTERFACE: &str = "net.reactivated.Fprint.Device";
+
+const NO_ENROLLED_PRINTS: &str = "net.reactivated.Fprint.Error.NoEnrolledPrints";
+const TIMEOUT_MS: i32 = 5000;
+
+/// The user fprintd should look up prints for. An empty name means "the caller".
+pub fn current_username() -> String {
+    std::env::var("USER").unwrap_or_default()
+}
+
+pub fn get_devices(dbus: &DbusConnection) -> Result<Vec<Device<'_>>, dbus::Error> {
+    let manager = dbus.with_path(SERVICE, MANAGER_PATH, TIMEOUT_MS);
+    let (paths,): (Vec<Path<'static>>,) =
+        manager.method_call(MANAGER_INTERFACE, "GetDevices", ())?;
+
+    Ok(paths
+        .into_iter()
+        .map(|path| Device::new(dbus, path))
+        .collect())
+}
+
+pub fn get_default_device(dbus: &DbusConnection) -> Result<Device<'_>, dbus::Error> {
+    let manager = dbus.with_path(SERVICE, MANAGER_PATH, TIMEOUT_MS);
+    let (path,): (Path<'static>,) =
+        manager.method_call(MANAGER_INTERFACE, "GetDefaultDevice", ())?;
+
+    Ok(Device::new(dbus, path))
+}
+
+pub struct Device<'a> {
+    dbus: &'a DbusConnection,
+    pub path: Path<'static>,
+}
+
+impl<'a> Device<'a> {
+    pub fn new(dbus: &'a DbusConnection, path: Path<'static>) -> Self {
+        Device { dbus, path }
+    }
+
+    fn proxy(&self) -> ConnPath<'_, &'a DbusConnection> {
+        self.dbus.with_path(SERVICE, self.path.clone(), TIMEOUT_MS)
+    }
+
+    pub fn name(&self) -> Result<String, dbus::Error> {
+        self.proxy().get(DEVICE_INTERFACE, "name")
+    }
+
+    /// Either "press" or "swipe".
+    pub fn scan_type(&self) -> Result<String, dbus::Error> {
+        self.proxy().get(DEVICE_INTERFACE, "scan-type")
+    }
+
+    /// Whether a verification or enrollment is waiting for a finger. Only fprintd 1.92 and newer
+    /// have this.
+    pub fn finger_needed(&self) -> Result<bool, dbus::Error> {
+        self.proxy().get(DEVICE_INTERFACE, "finger-needed")
+    }
+
+    /// How many scans it takes to enroll a finger, or None if the driver doesn't say.
+    pub fn enroll_stages(&self) -> Result<Option<u32>, dbus::Error> {
+        let stages: i32 = self.proxy().get(DEVICE_INTERFACE, "num-enroll-stages")?;
+        Ok(u32::try_from(stages).ok().filter(|&stages| stages > 0))
+    }
+
+    /// Doesn't require claiming the device. No enrolled prints is not treated as an error.
+    pub fn list_enrolled_fingers(&self, username: &str) -> Result<Vec<String>, dbus::Error> {
+        match self
+            .proxy()
+            .method_call(DEVICE_INTERFACE, "ListEnrolledFingers", (username,))
+        {
+            Ok((fingers,)) => Ok(fingers),
+            Err(e) if e.name() == Some(NO_ENROLLED_PRINTS) => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn claim(&self, username: &str) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(DEVICE_INTERFACE, "Claim", (username,))
+    }
+
+    pub fn release(&self) -> Result<(), dbus::Error> {
+        self.proxy().method_call(DEVICE_INTERFACE, "Release", ())
+    }
+
+    /// Requires the device to be claimed. Progress is reported through `VerifyStatus` signals.
+    pub fn verify_start(&self, finger_name: &str) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(DEVICE_INTERFACE, "VerifyStart", (finger_name,))
+    }
+
+    pub fn verify_stop(&self) -> Result<(), dbus::Error> {
+        self.proxy().method_call(DEVICE_INTERFACE, "VerifyStop", ())
+    }
+
+    /// Requires the device to be claimed. Progress is reported through `EnrollStatus` signals.
+    pub fn enroll_start(&self, finger_name: &str) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(DEVICE_INTERFACE, "EnrollStart", (finger_name,))
+    }
+
+    pub fn enroll_stop(&self) -> Result<(), dbus::Error> {
+        self.proxy().method_call(DEVICE_INTERFACE, "EnrollStop", ())
+    }
+
+    /// Requires the device to be claimed. Only fprintd 1.92 and newer have this.
+    pub fn delete_enrolled_finger(&self, finger_name: &str) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(DEVICE_INTERFACE, "DeleteEnrolledFinger", (finger_name,))
+    }
+}
+
+/// Every finger name fprintd accepts, in the order it lists them.
+pub const FINGER_NAMES: [&str; 10] = [
+    "left-thumb",
+    "left-index-finger",
+    "left-middle-finger",
+    "left-ring-finger",
+    "left-little-finger",
+    "right-thumb",
+    "right-index-finger",
+    "right-middle-finger",
+    "right-ring-finger",
+    "right-little-finger",
+];
+
+/// Remembers which fingers the current user has enrolled on each device, so prompts don't have to
+/// wait on fprintd every time.
+#[derive(Default)]
+pub struct EnrolledFingers {
+    cache: HashMap<Path<'static>, Vec<String>>,
+}
+
+impl EnrolledFingers {
+    pub fn get(&mut self, dbus: &DbusConnection, device: &Path<'static>) -> &[String] {
+        if !self.cache.contains_key(device) {
+            match Device::new(dbus, device.clone()).list_enrolled_fingers(&current_username()) {
+                Ok(fingers) => {
+                    self.cache.insert(device.clone(), fingers);
+                }
+                Err(e) => {
+                    log::warn!("could not list enrolled fingers on {device}: {e}");
+                    return &[];
+                }
+            }
+        }
+
+        &self.cache[device]
+    }
+
+    pub fn invalidate(&mut self, device: &Path<'static>) {
+        self.cache.remove(device);
+    }
+
+    /// Text telling the user which finger to put on the sensor. fprintd says "any" when every
+    /// enrolled finger is acceptable, in which case we list them.
+    pub fn hint(
+        &mut self,
+        dbus: &DbusConnection,
+        device: &Path<'static>,
+        finger_name: &str,
+    ) -> String {
+        if finger_name != "any" {
+            return format!("Use your {}", finger_label(finger_name));
+        }
+
+        match self.get(dbus, device) {
+            [] => "Touch the fingerprint sensor".to_string(),
+            [finger] => format!("Use your {}", finger_label(finger)),
+            [rest @ .., last] => format!(
+                "Use your {} or {}",
+                rest.iter()
+                    .map(|finger| finger_label(finger))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                finger_label(last)
+            ),
+        }
+    }
+}
+
+/// Shown instead of the hint when finger names are kept private.
+pub const PRIVATE_HINT: &str = "Fingerprint required";
+
+/// "right-index-finger" -> "right index"
+fn finger_label(finger_name: &str) -> String {
+    finger_name.trim_end_matches("-finger").replace('-', " ")
+}
@@ -0,0 +1,153 @@
+// Carrying on without the compositor, when Wayland isn't `--require`d and can't be had. The
+// sinks besides the overlay are fed as they would be by it, with notifications standing in for it
+// unless `--status-stream` or `--swaybar` was given.
+//
+// Only what fprintd says is followed here; everything drawn, and what's picked with the pointer
+// or the tray, is up to the overlay.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use dbus::Path;
+use fprint_prompt::MonitorEvent;
+
+use crate::{
+    config::Config,
+    fprint, hotplug,
+    prompts::Prompts,
+    reader_quirks::ReaderQuirks,
+    session,
+    sinks::{Sink, Sinks, View},
+    sleep::SleepMonitor,
+    startup,
+    status::{self, Status},
+    transition::{self, Transition},
+};
+
+/// How long to wait for fprintd before polling the sinks anyway, e.g. for a chime.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shows prompts until killed. Returns only when there's nothing to show them on.
+pub fn run(status_stream: Option<status::Format>) {
+    let mut config = Config::load();
+    config.sinks.retain(|sink| *sink != Sink::Overlay);
+    if status_stream.is_none() && !config.sinks.contains(&Sink::Notification) {
+        config.sinks.push(Sink::Notification);
+    }
+    let monitor = startup::listen_to_fprintd(true);
+    let dbus = monitor.connection();
+    let seat_id = session::seat_id(dbus);
+
+    let mut sinks = Sinks::new(&config, status_stream, None);
+    if sinks.is_empty() {
+        log::error!("Without Wayland, there's nothing left to show prompts on");
+        return;
+    }
+    log::info!("Running without Wayland");
+
+    let mut prompts = Prompts::default();
+    let mut enrolled_fingers = fprint::EnrolledFingers::default();
+    let mut reader_quirks = HashMap::new();
+    let mut paused = false;
+    // Made up for the sessions of readers that went away.
+    let mut cut_off = VecDeque::new();
+    let mut sleep_monitor = SleepMonitor::new();
+    sinks.event(
+        &view(&prompts, &reader_quirks, &config, paused),
+        &Status {
+            device: None,
+            finger: None,
+            result: None,
+        },
+    );
+    loop {
+        if sinks.toggle_requested() {
+            paused = !paused;
+        }
+        sinks.poll(&view(&prompts, &reader_quirks, &config, paused));
+        if sleep_monitor.poll(&mut prompts, dbus) {
+            sinks.event(
+                &view(&prompts, &reader_quirks, &config, paused),
+                &Status {
+                    device: None,
+                    finger: None,
+                    result: None,
+                },
+            );
+        }
+
+        let (device, event) = match cut_off.pop_front() {
+            Some(incoming) => incoming,
+            None => match monitor.next_timeout(POLL_INTERVAL) {
+                Some((device, MonitorEvent::Fprint(event))) => (device, event),
+                Some((device, event)) => {
+                    match event {
+                        MonitorEvent::DeviceRemoved => {
+                            reader_quirks.remove(&device);
+                        }
+                        MonitorEvent::FprintdStopped => reader_quirks.clear(),
+                        _ => (),
+                    }
+                    cut_off.extend(hotplug::follow(
+                        &prompts,
+                        &mut enrolled_fingers,
+                        &device,
+                        &event,
+                    ));
+                    continue;
+                }
+                None => continue,
+            },
+        };
+
+        let context = transition::Context {
+            dbus,
+            config: &config,
+            seat_id: &seat_id,
+            for_pam: true,
+        };
+        let finger = match transition::follow(
+            &mut prompts,
+            &mut enrolled_fingers,
+            &mut reader_quirks,
+            &context,
+            &device,
+            &event,
+        ) {
+            Transition::Ignored => continue,
+            Transition::Started { finger, .. } => finger,
+            Transition::Ended { .. } | Transition::Changed => None,
+        };
+
+        sinks.event(
+            &view(&prompts, &reader_quirks, &config, paused),
+            &Status {
+                device: Some(&device),
+                finger: finger.as_deref(),
+                result: event.result(),
+            },
+        );
+    }
+}
+
+fn view<'a>(
+    prompts: &'a Prompts,
+    reader_quirks: &HashMap<Path<'static>, ReaderQuirks>,
+    config: &'a Config,
+    paused: bool,
+) -> View<'a> {
+    View {
+        prompts,
+        config,
+        paused,
+        ignored_for: prompts.ignored_for(|device| {
+            reader_quirks
+                .get(device)
+                .copied()
+                .unwrap_or_default()
+                .retries
+        }),
+    }
+}
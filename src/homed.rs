@@ -0,0 +1,178 @@
+// Saying what a verification is for when systemd-homed is unlocking a home directory, like after
+// the system woke up with the home locked, or when logging in. homed says what it's doing only
+// through the State property of the home's object, so that is followed here. homed doesn't say
+// how its owner is asked, so a prompt is only shown while fprintd is verifying too.
+//
+// Only the user's own home is followed, except on the greeter, where it can be anyone's.
+
+use std::{collections::HashMap, os::fd::RawFd};
+
+use dbus::{
+    arg::{prop_cast, PropMap},
+    ffidisp::{stdintf::org_freedesktop_dbus::Properties, Connection as DbusConnection},
+    Message, MessageType, Path,
+};
+use fprint_prompt::bus;
+
+use crate::{
+    prompts::{Prompts, SessionKind},
+    signal_rules::SignalEvent,
+};
+
+const SERVICE: &str = "org.freedesktop.home1";
+const HOME_INTERFACE: &str = "org.freedesktop.home1.Home";
+const TIMEOUT_MS: i32 = 1000;
+/// Where fprintd's readers are, which the prompts of its verifications are for.
+const FPRINTD_DEVICES: &str = "/net/reactivated/Fprint/Device/";
+const MATCH: &str = "type='signal',sender='org.freedesktop.home1',\
+     interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',\
+     path_namespace='/org/freedesktop/home1/home',arg0='org.freedesktop.home1.Home'";
+
+/// The states homed is in while it waits on its owner to unlock the home.
+const UNLOCKING: &[&str] = &[
+    "activating",
+    "activating-for-acquire",
+    "unlocking",
+    "unlocking-for-acquire",
+    "authenticating",
+    "authenticating-while-active",
+    "authenticating-for-acquire",
+];
+
+pub struct HomedUnlocks {
+    dbus: DbusConnection,
+    /// Whose home to follow, or None for everyone's.
+    user: Option<String>,
+    /// The owners of the homes heard of, by object path.
+    owners: HashMap<Path<'static>, String>,
+    /// The homes being unlocked, with their owners.
+    unlocking: Vec<(Path<'static>, String)>,
+    /// The homes with a prompt up.
+    shown: Vec<Path<'static>>,
+    fds: Vec<RawFd>,
+}
+
+impl HomedUnlocks {
+    /// Follows the home of `user`, or every home for None. homed is always on the system bus,
+    /// wherever fprintd is.
+    pub fn new(user: Option<String>) -> Option<HomedUnlocks> {
+        let dbus = DbusConnection::new_system()
+            .and_then(|dbus| {
+                dbus.add_match(MATCH)?;
+                Ok(dbus)
+            })
+            .map_err(|e| log::warn!("could not listen for systemd-homed: {e}"))
+            .ok()?;
+        Some(HomedUnlocks {
+            fds: bus::readable_fds(&dbus),
+            dbus,
+            user,
+            owners: HashMap::new(),
+            unlocking: vec![],
+            shown: vec![],
+        })
+    }
+
+    /// The prompts shown or hidden since the last poll, by the homed object they're for. They're
+    /// only shown while one of `prompts` is for a verification of fprintd's.
+    pub fn poll(&mut self, prompts: &Prompts) -> Vec<(Path<'static>, SignalEvent)> {
+        let messages: Vec<_> = self.dbus.incoming(0).collect();
+        for msg in messages {
+            let Some((home, changed)) = parse(&msg) else {
+                continue;
+            };
+            let Some(owner) = self.owner(&home, &changed) else {
+                continue;
+            };
+            if self.user.as_ref().is_some_and(|user| *user != owner) {
+                continue;
+            }
+            let Some(state) = self.state(&home, &changed) else {
+                continue;
+            };
+
+            self.unlocking.retain(|(unlocking, _)| *unlocking != home);
+            if UNLOCKING.contains(&state.as_str()) {
+                log::info!("systemd-homed is unlocking the home of {owner}");
+                self.unlocking.push((home, owner));
+            }
+        }
+
+        let verifying = prompts.sessions().iter().any(|session| {
+            session.kind == SessionKind::Verify && session.device.starts_with(FPRINTD_DEVICES)
+        });
+        let mut events = vec![];
+        self.shown.retain(|home| {
+            let keep = verifying
+                && self
+                    .unlocking
+                    .iter()
+                    .any(|(unlocking, _)| unlocking == home);
+            if !keep {
+                events.push((home.clone(), SignalEvent::Hide));
+            }
+            keep
+        });
+        if !verifying {
+            return events;
+        }
+        for (home, owner) in &self.unlocking {
+            if self.shown.contains(home) {
+                continue;
+            }
+            let text = match self.user {
+                Some(_) => "Unlocking your home".to_string(),
+                None => format!("Unlocking the home of {owner}"),
+            };
+            self.shown.push(home.clone());
+            events.push((home.clone(), SignalEvent::Show(text)));
+        }
+        events
+    }
+
+    /// To wake up for, when homed says something.
+    pub fn watch_fds(&self) -> &[RawFd] {
+        &self.fds
+    }
+
+    fn owner(&mut self, home: &Path<'static>, changed: &PropMap) -> Option<String> {
+        if let Some(owner) = self.owners.get(home) {
+            return Some(owner.clone());
+        }
+        let owner = match prop_cast::<String>(changed, "UserName") {
+            Some(owner) => owner.clone(),
+            None => self
+                .dbus
+                .with_path(SERVICE, home, TIMEOUT_MS)
+                .get::<String>(HOME_INTERFACE, "UserName")
+                .map_err(|e| log::warn!("could not get the owner of {home}: {e}"))
+                .ok()?,
+        };
+        self.owners.insert(home.clone(), owner.clone());
+        Some(owner)
+    }
+
+    /// homed sends its properties changing along, or only says that they did.
+    fn state(&self, home: &Path<'static>, changed: &PropMap) -> Option<String> {
+        match prop_cast::<String>(changed, "State") {
+            Some(state) => Some(state.clone()),
+            None => self
+                .dbus
+                .with_path(SERVICE, home, TIMEOUT_MS)
+                .get::<String>(HOME_INTERFACE, "State")
+                .map_err(|e| log::warn!("could not get the state of {home}: {e}"))
+                .ok(),
+        }
+    }
+}
+
+fn parse(msg: &Message) -> Option<(Path<'static>, PropMap)> {
+    if msg.msg_type() != MessageType::Signal {
+        return None;
+    }
+    let (interface, changed) = msg.read2::<String, PropMap>().ok()?;
+    if interface != HOME_INTERFACE {
+        return None;
+    }
+    Some((msg.path()?.into_static(), changed))
+}
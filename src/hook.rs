@@ -0,0 +1,93 @@
+// The `hook` sink: runs `hook.command` through `sh -c` whenever the prompt changes, for
+// whatever the other sinks don't cover. It gets the prompt in the environment:
+//
+// - `FPRINT_PROMPT_STATE`: "verify", "enroll", "fallback", "busy", "disconnected", "paused" or
+//   "idle"
+// - `FPRINT_PROMPT_TEXT`: what the prompt says, empty when idle
+//
+// "fallback", "busy" and "disconnected" are for a moment after the password is asked for instead
+// of a finger, after too many tries, because another process had the reader, or because it went
+// away.
+//
+// What the command prints goes to stderr, as stdout is kept for the status stream.
+
+use std::process::{Child, Command, Stdio};
+
+use crate::{
+    config::Config,
+    prompts::SessionKind,
+    sinks::{OutputSink, View},
+};
+
+pub struct Hook {
+    command: Option<String>,
+    /// The state and text the hook last ran for.
+    last: Option<(&'static str, String)>,
+    /// Runs that haven't finished, to be reaped.
+    running: Vec<Child>,
+}
+
+impl Hook {
+    pub fn new(config: &Config) -> Hook {
+        if config.hook.is_none() {
+            log::warn!("the hook sink is on, but there's no hook.command to run");
+        }
+        Hook {
+            command: config.hook.clone(),
+            last: None,
+            running: vec![],
+        }
+    }
+
+    fn run(&mut self, state: &str, text: &str) {
+        let Some(command) = &self.command else {
+            return;
+        };
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("FPRINT_PROMPT_STATE", state)
+            .env("FPRINT_PROMPT_TEXT", text)
+            .stdin(Stdio::null())
+            .stdout(std::io::stderr())
+            .spawn();
+        match spawned {
+            Ok(child) => self.running.push(child),
+            Err(e) => log::warn!("could not run the hook: {e}"),
+        }
+    }
+}
+
+impl OutputSink for Hook {
+    fn poll(&mut self, view: &View) {
+        self.running
+            .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+
+        let primary = view.prompts.primary();
+        let notice = view.prompts.notice();
+        let state = match primary.map(|session| session.kind) {
+            _ if view.paused => "paused",
+            None => notice.map_or("idle", |(state, _)| state),
+            Some(SessionKind::Verify) => "verify",
+            Some(SessionKind::Enroll) => "enroll",
+        };
+        let text = match primary {
+            Some(session) => &session.text,
+            None => notice.map_or("", |(_, text)| text),
+        };
+        if self
+            .last
+            .as_ref()
+            .is_some_and(|(last_state, last_text)| *last_state == state && last_text == text)
+        {
+            return;
+        }
+
+        self.last = Some((state, text.to_string()));
+        self.run(state, text);
+    }
+
+    fn configure(&mut self, config: &Config) {
+        self.command.clone_from(&config.hook);
+    }
+}
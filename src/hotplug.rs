@@ -0,0 +1,65 @@
+// Readers that come and go. When one is unplugged, or fprintd goes away, whatever it was doing
+// ends without a last VerifyStatus or EnrollStatus, which would leave its prompt up for good. So
+// the one fprintd sends for a reader disconnected mid-scan is made up here, and the prompt ends
+// the way it would have, sending the user to the password.
+
+use dbus::Path;
+use fprint_prompt::{fprint::EnrolledFingers, signals::FprintEvent, MonitorEvent};
+
+use crate::prompts::{Prompts, SessionKind};
+
+/// Follows a reader coming or going: forgets what was learned about it, and returns the events
+/// that end its sessions.
+pub fn follow(
+    prompts: &Prompts,
+    enrolled_fingers: &mut EnrolledFingers,
+    device: &Path<'static>,
+    event: &MonitorEvent,
+) -> Vec<(Path<'static>, FprintEvent)> {
+    match event {
+        MonitorEvent::DeviceAdded => {
+            log::info!("Reader plugged in: {device}");
+            enrolled_fingers.invalidate(device);
+            return vec![];
+        }
+        MonitorEvent::DeviceRemoved => {
+            log::info!("Reader unplugged: {device}");
+            enrolled_fingers.invalidate(device);
+        }
+        MonitorEvent::FprintdStopped => {
+            log::info!("fprintd stopped");
+            *enrolled_fingers = EnrolledFingers::default();
+        }
+        MonitorEvent::Fprint(_)
+        | MonitorEvent::FingerNeeded(_)
+        | MonitorEvent::FingerPresent(_) => return vec![],
+    }
+
+    prompts
+        .sessions()
+        .iter()
+        .filter(|session| {
+            matches!(event, MonitorEvent::FprintdStopped) || session.device == *device
+        })
+        .map(|session| {
+            let (what, event) = match session.kind {
+                SessionKind::Verify => (
+                    "verification",
+                    FprintEvent::VerifyStatus {
+                        result: Some("verify-disconnected".to_string()),
+                        done: Some(true),
+                    },
+                ),
+                SessionKind::Enroll => (
+                    "enrollment",
+                    FprintEvent::EnrollStatus {
+                        result: Some("enroll-disconnected".to_string()),
+                        done: Some(true),
+                    },
+                ),
+            };
+            log::warn!("The {what} on {} was cut off", session.device);
+            (session.device.clone(), event)
+        })
+        .collect()
+}
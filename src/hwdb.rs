@@ -0,0 +1,89 @@
+// Best guesses for where the fingerprint reader sits on common laptops, matched against DMI data.
+//
+// These are only starting points for `--init-config`; screens and resolutions vary too much within
+// a model line for the offsets to be exact.
+
+use smithay_client_toolkit::shell::wlr_layer::Anchor;
+
+use crate::config::{Millimeters, PositionInfo};
+
+pub struct Preset {
+    pub description: &'static str,
+    pub position: PositionInfo,
+}
+
+struct Entry {
+    sys_vendor: &'static str,
+    /// Prefix of the DMI product name, or of the product family for vendors like Lenovo that put
+    /// the model number in the product name.
+    product_prefix: &'static str,
+    description: &'static str,
+    edge: Anchor,
+    close_to: Anchor,
+    offset: u32,
+    length: u32,
+}
+
+const ENTRIES: &[Entry] = &[
+    Entry {
+        sys_vendor: "Framework",
+        product_prefix: "Laptop",
+        description: "Framework Laptop (reader in the power button, top right of the keyboard)",
+        edge: Anchor::RIGHT,
+        close_to: Anchor::TOP,
+        offset: 100,
+        length: 138,
+    },
+    Entry {
+        sys_vendor: "Dell Inc.",
+        product_prefix: "XPS",
+        description: "Dell XPS (reader in the power button, top right of the keyboard)",
+        edge: Anchor::RIGHT,
+        close_to: Anchor::TOP,
+        offset: 60,
+        length: 120,
+    },
+    Entry {
+        sys_vendor: "LENOVO",
+        product_prefix: "ThinkPad",
+        description: "ThinkPad (reader on the palm rest, right of the touchpad)",
+        edge: Anchor::BOTTOM,
+        close_to: Anchor::RIGHT,
+        offset: 300,
+        length: 160,
+    },
+];
+
+/// A field of `/sys/class/dmi/id`, or empty if it can't be read.
+pub fn read_dmi(field: &str) -> String {
+    std::fs::read_to_string(format!("/sys/class/dmi/id/{field}"))
+        .map(|value| value.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Looks up the machine we're running on.
+pub fn detect() -> Option<Preset> {
+    let sys_vendor = read_dmi("sys_vendor");
+    let product_name = read_dmi("product_name");
+    let product_family = read_dmi("product_family");
+
+    let entry = ENTRIES.iter().find(|entry| {
+        sys_vendor == entry.sys_vendor
+            && (product_name.starts_with(entry.product_prefix)
+                || product_family.starts_with(entry.product_prefix))
+    })?;
+
+    Some(Preset {
+        description: entry.description,
+        position: PositionInfo {
+            thickness: 8,
+            length: entry.length,
+            edge: entry.edge,
+            close_to: entry.close_to,
+            offset: entry.offset,
+            notch: 0,
+            segments: vec![],
+            millimeters: Millimeters::default(),
+        },
+    })
+}
@@ -0,0 +1,422 @@
+// `fprint-prompt --init-config`: writes a commented default config for the user to start from.
+
+use std::fmt::Write;
+
+use crate::{
+    config::{anchor_name, format_color, Config},
+    exit, hwdb,
+    sinks::Sink,
+    theme::{Style, ThemeRegistry},
+};
+
+pub fn run() -> i32 {
+    let Some(path) = Config::path() else {
+        eprintln!("could not determine the config directory");
+        return exit::FAILURE;
+    };
+
+    if path.exists() {
+        eprintln!("{} already exists, not overwriting it", path.display());
+        return exit::FAILURE;
+    }
+
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("could not create {}: {e}", dir.display());
+            return exit::FAILURE;
+        }
+    }
+
+    let text = default_config(hwdb::detect(), &connected_outputs());
+
+    if let Err(e) = std::fs::write(&path, text) {
+        eprintln!("could not write {}: {e}", path.display());
+        return exit::FAILURE;
+    }
+
+    println!("Wrote {}", path.display());
+    exit::SUCCESS
+}
+
+/// Names of the connected DRM connectors, which are what compositors name their outputs after.
+fn connected_outputs() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else {
+        return vec![];
+    };
+
+    let mut outputs: Vec<String> = entries
+        .flatten()
+        .filter(|entry| {
+            std::fs::read_to_string(entry.path().join("status"))
+                .is_ok_and(|status| status.trim() == "connected")
+        })
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // "card1-eDP-1" -> "eDP-1"
+            Some(name.split_once('-')?.1.to_string())
+        })
+        .collect();
+
+    outputs.sort();
+    outputs
+}
+
+fn default_config(preset: Option<hwdb::Preset>, outputs: &[String]) -> String {
+    let defaults = Config::default();
+    let mut text = String::new();
+
+    // Geometry is only uncommented if we have a better guess than the defaults.
+    let (position, geometry_prefix) = match &preset {
+        Some(preset) => (&preset.position, ""),
+        None => (&defaults.position, "#"),
+    };
+
+    let _ = writeln!(
+        text,
+        "\
+# fprint-prompt configuration
+#
+# Generated by `fprint-prompt --init-config`. Settings that are commented out show their default
+# value. Check for mistakes with `fprint-prompt --check-config`.
+
+# Also show prompts when another user, e.g. a login screen on another VT, is asked for a fingerprint.
+#show_other_users = {show_other_users}
+
+# Only say a fingerprint is required, never which fingers are enrolled or asked for. Applies to
+# the overlay, the status stream, D-Bus and the log.
+#privacy = {privacy}
+
+# Where prompts are shown, in a comma-separated list of:
+#   {sink_names}
+# That's on screen, as a desktop notification, with chimes while a verification is ignored (see
+# [attention]), on stdout as with --status-stream, by running [hook]'s command, or by lighting
+# [led]. Without \"overlay\", no surface is ever created.
+#sinks = \"{sinks}\"
+
+# How the prompt looks: {styles}. Dots and rings sit in the middle of the bar's area, and the glow
+# fades out away from the screen edge.
+#style = \"{style}\"
+
+# Mark how a scan went with a shape as well as a color: a checkmark for a match, a cross for a
+# failure, and a dot for a scan to try again. On by default with the colorblind palettes.
+#shapes = {shapes}
+
+# Show a tray icon with a menu for pausing the overlay.
+#tray = {tray}
+
+# Ask the desktop for global shortcuts to hide or show the overlay, and to show the last result
+# again. The keys are picked in the desktop's own settings.
+#shortcuts = {shortcuts}
+
+# Hide prompts while the screen is recorded or streamed through the desktop portal, so they don't
+# end up in the recording.
+#pause_during_screencast = {pause_during_screencast}
+
+# While the screen is locked, center the bar and make it twice as big, above the lock screen where
+# the compositor allows it.
+#lock_screen = {lock_screen}
+
+# Whether the prompt takes keyboard focus while it's shown, so Escape closes fprint-prompt: \"none\",
+# \"on_demand\" for when it's clicked, or \"exclusive\" to take it from other windows right away.
+#keyboard = \"none\"
+
+# Keep the screen from dimming or locking while a verification waits for a finger.
+#inhibit_idle = {inhibit_idle}
+
+# When crashing, write what happened to ~/.local/state/fprint-prompt/ for a bug report.
+#crash_reports = {crash_reports}
+
+# Write every verification to the systemd journal: how it ended and which process asked for it.
+# `journalctl MESSAGE_ID=c6fbc44ce43a453baec258836f19d3f3` lists them.
+#audit = {audit}
+
+# Say so while a fingerprint is asked for to unlock your systemd-homed home directory, like
+# after waking up.
+#homed = {homed}
+
+# Seconds fprint-prompt may hang before it starts over, e.g. when the compositor stops answering.
+# Under systemd it exits with status 75 for `Restart=on-failure` to restart it. 0 never does.
+#watchdog = {watchdog}
+
+[geometry]",
+        show_other_users = defaults.show_other_users,
+        privacy = defaults.privacy,
+        sink_names = Sink::ALL
+            .iter()
+            .map(|sink| format!("\"{}\"", sink.name()))
+            .collect::<Vec<_>>()
+            .join(", "),
+        sinks = defaults
+            .sinks
+            .iter()
+            .map(|sink| sink.name())
+            .collect::<Vec<_>>()
+            .join(","),
+        style = defaults.style.name(),
+        shapes = defaults.shapes,
+        styles = Style::ALL
+            .iter()
+            .map(|style| format!("\"{}\"", style.name()))
+            .collect::<Vec<_>>()
+            .join(", "),
+        tray = defaults.tray,
+        shortcuts = defaults.shortcuts,
+        pause_during_screencast = defaults.pause_during_screencast,
+        lock_screen = defaults.lock_screen,
+        inhibit_idle = defaults.inhibit_idle,
+        crash_reports = defaults.crash_reports,
+        audit = defaults.audit,
+        homed = defaults.homed,
+        watchdog = defaults.watchdog,
+    );
+
+    match &preset {
+        Some(preset) => {
+            let _ = writeln!(
+                text,
+                "# Best guess for this machine: {}.",
+                preset.description
+            );
+        }
+        None => {
+            let _ = writeln!(
+                text,
+                "# This machine isn't known, so these are just the defaults."
+            );
+        }
+    }
+
+    if !outputs.is_empty() {
+        let _ = writeln!(
+            text,
+            "# Outputs found on this machine: {}.",
+            outputs.join(", ")
+        );
+    }
+
+    let _ = writeln!(
+        text,
+        "\
+#
+# The prompt is a bar drawn along one edge of the screen, which should point at the reader.
+# Which edge: \"top\", \"bottom\", \"left\" or \"right\".
+{geometry_prefix}edge = \"{edge}\"
+# Which end of that edge the offset is measured from, or \"center\" to center the bar.
+{geometry_prefix}close_to = \"{close_to}\"
+# Distance in pixels from that end of the edge to the start of the bar.
+{geometry_prefix}offset = {offset}
+# Size of the bar in pixels.
+{geometry_prefix}length = {length}
+{geometry_prefix}thickness = {thickness}
+# The most of an output the bar may cover in any state, as a fraction of its area. A geometry
+# covering more, or not fitting on the output, is shrunk with a warning.
+#max_area = {max_area}
+# Or the offset and length in millimeters, which follow the reader through resolution and scale
+# changes. Outputs that don't report their physical size use the pixels above.
+#offset_mm = 20
+#length_mm = 30
+# A gap in pixels cut out of the middle of the bar, so it frames a camera notch or hinge cut-out
+# instead of drawing through it. Set the bar's offset and length to center it on the cut-out.
+#notch = 0
+# Or only draw pieces of the bar, each as an offset and length in pixels from its start, to mark
+# the reader and the power button at once. Pieces past the bar's length are cut off.
+#segments = \"0:60, 200:40\"
+
+# The bar can take a different length or thickness while in a certain state, easing between them:
+# \"verify\", \"enroll\", \"success\" or \"failure\". For example a thin line while waiting, that
+# flashes across the whole edge when a finger doesn't match.
+#[geometry.verify]
+#thickness = 4
+#[geometry.failure]
+#length = 1920
+#thickness = 12
+
+[colors]
+# Colors to start from, which the colors below override. One of
+# {palettes}.
+# More themes can be installed as fprint-prompt/themes/<name>.theme in a data directory like
+# ~/.local/share. A theme sets the colors, and can set `style`, `shapes` and [attention]'s `flashes`
+# and `intensity`. With `inherit = \"high-contrast\"` it only has to say what's different.
+#palette = \"default\"
+# \"#rrggbb\" or \"#rrggbbaa\".
+# While waiting for a finger to verify.
+#prompt = \"{prompt}\"
+# While enrolling a new finger, filling up as scans are accepted. When both are going on, the bar
+# is split between them.
+#enroll = \"{enroll}\"
+# After a verification. A match holds the bar and fades it out, a failure blinks it.
+#success = \"{success}\"
+#failure = \"{failure}\"
+
+[attention]
+# Flash the bar when a verification starts, to draw the eye to it.
+#flashes = {flashes}
+# How far the bar fades out while flashing, from 0 to 1.
+#intensity = {intensity:?}
+# When a verification has gone this many seconds without the reader being touched, make the bar
+# this thick until it is. 0 seconds turns this off.
+#escalate_after = {escalate_after}
+#escalated_thickness = {escalated_thickness}
+# And after this many seconds, chime until it is, more often and louder each time. Useful when the
+# reader is somewhere the bar can't be seen from. Needs canberra-gtk-play, and plays the sound
+# theme's bell, or fprint-prompt/sounds/reminder.oga in a data directory. 0 turns this off.
+#chime_after = {chime_after}
+# How many seconds a verification waits for a finger before it times out, which the bar counts
+# down. pam_fprintd's default is 30. 0 turns the countdown off.
+#timeout = {timeout}
+# pam_fprintd's max-tries: after this many verifications in a row that don't match, the bar shows
+# for fallback_linger seconds that the password is asked for instead, holding the failure color
+# rather than blinking it. 0 stops counting, but a reader going away still shows it, and so does
+# one another process had claimed, which can only be seen when running as root.
+#max_tries = {max_tries}
+#fallback_linger = {fallback_linger}
+
+[night]
+# Warm up the colors between these times, from 0 (off) to 1 (very orange).
+#warmth = 0.5
+#start = \"{night_start}\"
+#end = \"{night_end}\"
+# Or warm them while GNOME's night light is on, instead of on a schedule.
+#follow_gnome = {follow_gnome}
+
+[dock]
+# Dock the prompt as a strip along the whole edge on the bottom layer, which tiling compositors
+# keep room for so windows never go under it. It stays up in `color` while nothing is waiting, and
+# the bar is drawn in it as usual. Set quirks.layer to put it on another layer.
+#enabled = {dock_enabled}
+#color = \"{dock_color}\"
+
+[theme.schedule]
+# Switch the palette by itself from these times on, until the next one. A palette set in [colors]
+# is replaced, and the colors set there still go on top.
+#\"07:00\" = \"default\"
+#\"21:30\" = \"high-contrast\"
+# Or follow the desktop's light and dark style, through the settings portal. These go before the
+# times, which are only used when it can't be told.
+#dark = \"high-contrast\"
+#light = \"default\"
+
+[shadow]
+# Draw around the bar so it stays visible on light windows: \"none\", \"drop\" for a shadow
+# that fades out, or \"outline\" for a solid edge.
+#style = \"drop\"
+#size = {shadow_size}
+#color = \"{shadow_color}\"
+
+# Run through `sh -c` whenever the prompt changes, with FPRINT_PROMPT_STATE set to \"verify\",
+# \"enroll\", \"paused\" or \"idle\" and FPRINT_PROMPT_TEXT to what it says. Needs \"hook\" in sinks.
+#[hook]
+#command = \"notify-send \\\"$FPRINT_PROMPT_TEXT\\\"\"
+
+# Lit while a verification waits, by its name under /sys/class/leds. Needs \"led\" in sinks, and
+# usually a udev rule letting you write to it.
+#[led]
+#name = \"platform::micmute\"
+
+# How the surface is set up, which differs between compositors. The defaults are picked for the
+# compositor fprint-prompt finds itself running on.
+#[quirks]
+# \"overlay\", \"top\", \"bottom\" or \"background\".
+#layer = \"overlay\"
+# What layer rules in the compositor's config can match on.
+#namespace = \"fprint-prompt\"
+# \"fractional\", \"integer\" to follow the output's whole-number scale, or \"none\".
+#scale = \"fractional\"
+
+# On multi-seat systems, assign fingerprint readers to seats by the name shown in
+# `fprint-prompt devices`. Readers that aren't listed are shown on every seat.
+#[device.\"Synaptics Sensors\"]
+#seat = \"seat1\"
+# Match-on-chip readers, like Goodix's and ELAN's, are recognised by their name and never make
+# the bar grow for being ignored, since they don't say when a finger was on them. `retries` says
+# whether a reader does, and `timeout` replaces [attention]'s countdown for its verifications.
+#retries = true
+#timeout = 30
+
+# Per-output overrides, by the output's name. Geometry keys not given here are taken from
+# [geometry] above, and `enabled = false` keeps the prompt off that output entirely.
+#[output.\"{example_output}\"]
+#enabled = true
+#edge = \"top\"
+#close_to = \"center\"
+# Used instead of the scale the compositor says the output has, for ones that get it wrong.
+#scale_override = 1.5
+
+# Show prompts for D-Bus signals besides fprintd's, like from a custom PAM module. Each section is
+# a match rule and what to do when a signal matches it: \"show\" a prompt with `text`, where {{0}},
+# {{1}} and so on are the signal's arguments, \"hide\" it, or \"{{0}}\" to show it while that argument
+# is true, non-zero or not empty and hide it otherwise. Rules with the same `prompt` show and hide
+# the same one, which is the section's name otherwise. `bus` is \"system\", \"session\" or an address.
+#[signal.yubikey]
+#match = \"type='signal',interface='com.example.Touch',member='Waiting'\"
+#bus = \"session\"
+#action = \"{{0}}\"
+#text = \"Touch your {{1}}\"
+
+# Profiles hold any of the settings above, which replace the rest of the file's while the profile
+# is in use. Switch between them with `fprint-prompt ctl profile docked`, or back to none with
+# `fprint-prompt ctl profile --none`. With a `when` rule, the first profile whose output is
+# connected is switched to whenever outputs are plugged in or out, or none if no rule matches.
+#[profile.docked.when]
+#outputs_include = \"{example_output}\"
+#[profile.docked.geometry]
+#edge = \"bottom\"
+#close_to = \"center\"
+#[profile.docked.colors]
+#palette = \"high-contrast\"
+
+# Apps hold any of the settings above too, for verifications started while one of their windows
+# has focus, on top of the profile in use. They're kept until the verification is over. Needs a
+# compositor that tells which window has focus, like sway or Hyprland.
+#[app.\"org.keepassxc.KeePassXC\".geometry]
+#thickness = 16
+#[app.\"foot\"]
+#sinks = \"overlay\"
+
+# Instances started with `fprint-prompt --instance NAME` hold any of the settings above for
+# themselves, under profiles and apps. Each has a bus name, control socket and saved state of its
+# own, so several can run at once, like one for a security key next to the one for fprintd, with
+# `fprint-prompt --instance fido ctl pause` to control it.
+#[instance.fido]
+#sinks = \"overlay\"
+#[instance.fido.geometry]
+#edge = \"right\"",
+        example_output = outputs.first().map_or("HDMI-A-1", String::as_str),
+        edge = anchor_name(position.edge),
+        close_to = anchor_name(position.close_to),
+        offset = position.offset,
+        length = position.length,
+        thickness = position.thickness,
+        max_area = defaults.max_area,
+        prompt = format_color(defaults.colors.prompt),
+        dock_enabled = defaults.dock.enabled,
+        dock_color = format_color(defaults.dock.color),
+        enroll = format_color(defaults.colors.enroll),
+        success = format_color(defaults.colors.success),
+        failure = format_color(defaults.colors.failure),
+        palettes = ThemeRegistry::discover()
+            .names()
+            .map(|name| format!("\"{name}\""))
+            .collect::<Vec<_>>()
+            .join(", "),
+        flashes = defaults.attention.flashes,
+        intensity = defaults.attention.intensity,
+        escalate_after = defaults.attention.escalate_after,
+        escalated_thickness = defaults.attention.escalated_thickness,
+        chime_after = defaults.attention.chime_after,
+        timeout = defaults.attention.timeout,
+        max_tries = defaults.attention.max_tries,
+        fallback_linger = defaults.attention.fallback_linger,
+        night_start = format_time(defaults.night.start),
+        night_end = format_time(defaults.night.end),
+        follow_gnome = defaults.night.follow_gnome,
+        shadow_size = defaults.shadow.size,
+        shadow_color = format_color(defaults.shadow.color),
+    );
+
+    text
+}
+
+fn format_time(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,44 @@
+// Which of several instances running on purpose this is, picked with `--instance NAME`: one per
+// output, say, or one for a FIDO key next to the one for fprintd. Each gets a bus name, control
+// socket, saved state, crash reports and `[instance.NAME]` config section of its own, so they
+// don't take each other for a second copy of themselves. `--record` always writes where it's told,
+// so it's up to whoever starts them to give each instance a file of its own.
+
+use std::sync::OnceLock;
+
+use crate::ipc;
+
+static SELECTED: OnceLock<String> = OnceLock::new();
+
+/// Whether `name` can name an instance. It ends up in a bus name, so it has to start with a letter,
+/// with only letters, digits and underscores after that.
+pub fn is_valid(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Makes [`name`] return `name`. Only the first call has an effect.
+pub fn select(name: String) {
+    let _ = SELECTED.set(name);
+}
+
+/// The instance picked with [`select`], or None for the unnamed one.
+pub fn name() -> Option<&'static str> {
+    SELECTED.get().map(String::as_str)
+}
+
+/// The bus name the instance serves on, like `io.github.darkwater.FprintPrompt.fido`.
+pub fn bus_name() -> String {
+    match name() {
+        Some(name) => format!("{}.{name}", ipc::NAME),
+        None => ipc::NAME.to_string(),
+    }
+}
+
+/// A file name of the instance's own, like `fprint-prompt-fido` for `fprint-prompt`.
+pub fn file_name(name_for_default: &str) -> String {
+    match name() {
+        Some(name) => format!("{name_for_default}-{name}"),
+        None => name_for_default.to_string(),
+    }
+}
@@ -0,0 +1,132 @@
+// Lets other programs pause the overlay, e.g. a script that runs when screen recording starts.
+//
+//   Interface: io.github.darkwater.FprintPrompt.Control1
+//
+//   Methods:
+//     Pause()   Stop showing prompts. fprintd is still followed, and how verifications went
+//               while paused is shown once resumed.
+//     Resume()
+//     SetProfile(s name)
+//               Switch to a `[profile.name]` section of the config, or back to none of them with
+//               an empty name.
+//
+//   Properties, with PropertiesChanged:
+//     Paused    b
+//     Profile   s   The profile in use, or empty.
+//     Profiles  as  Every profile in the config.
+//
+//   Properties, without:
+//     Latency   a{st}
+//               How long the latest frames took to be shown, by wp_presentation: the median and
+//               longest in microseconds from being committed (`commit_to_present_median_us`,
+//               `commit_to_present_max_us`) and from the signal they show (`signal_to_...`), and
+//               how many `frames`, `signals` and `discarded` frames there were in all. Without
+//               the medians and longest until a frame is shown, which it never is where the
+//               compositor doesn't tell.
+
+use std::collections::HashMap;
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    ffidisp::Connection as DbusConnection,
+    Message,
+};
+
+use super::{error, PROPERTIES_INTERFACE};
+use crate::{config::Config, presentation::Latency};
+
+pub const INTERFACE: &str = "io.github.darkwater.FprintPrompt.Control1";
+
+pub enum ControlAction {
+    Pause,
+    Resume,
+    /// None for the config without a profile.
+    SetProfile(Option<String>),
+}
+
+#[derive(Default)]
+pub struct Control {
+    paused: Option<bool>,
+    profile: Option<String>,
+    profiles: Vec<String>,
+    latency: Latency,
+}
+
+impl Control {
+    pub fn update(
+        &mut self,
+        dbus: &DbusConnection,
+        paused: bool,
+        config: &Config,
+        latency: &Latency,
+    ) {
+        // Changes with every frame, so it's only read.
+        self.latency.clone_from(latency);
+        if self.paused == Some(paused)
+            && self.profile == config.profile
+            && self.profiles == config.profiles
+        {
+            return;
+        }
+        self.paused = Some(paused);
+        self.profile = config.profile.clone();
+        self.profiles = config.profiles.clone();
+
+        super::signal(dbus, PROPERTIES_INTERFACE, "PropertiesChanged", |msg| {
+            msg.append3(INTERFACE, self.announced(), Vec::<String>::new())
+        });
+    }
+
+    pub fn properties(&self) -> PropMap {
+        let mut properties = self.announced();
+        let latency: Box<dyn RefArg> = Box::new(self.latency.summary());
+        properties.insert("Latency".to_string(), Variant(latency));
+        properties
+    }
+
+    /// The properties sent along with PropertiesChanged.
+    fn announced(&self) -> PropMap {
+        let paused: Box<dyn RefArg> = Box::new(self.paused.unwrap_or(false));
+        let profile: Box<dyn RefArg> = Box::new(self.profile.clone().unwrap_or_default());
+        let profiles: Box<dyn RefArg> = Box::new(self.profiles.clone());
+        HashMap::from([
+            ("Paused".to_string(), Variant(paused)),
+            ("Profile".to_string(), Variant(profile)),
+            ("Profiles".to_string(), Variant(profiles)),
+        ])
+    }
+
+    pub fn handle(&self, msg: &Message, member: &str) -> (Message, Option<ControlAction>) {
+        match member {
+            "Pause" => (msg.method_return(), Some(ControlAction::Pause)),
+            "Resume" => (msg.method_return(), Some(ControlAction::Resume)),
+            "SetProfile" => match msg.read1::<&str>() {
+                Ok("") => (msg.method_return(), Some(ControlAction::SetProfile(None))),
+                Ok(name) if self.profiles.iter().any(|profile| profile == name) => (
+                    msg.method_return(),
+                    Some(ControlAction::SetProfile(Some(name.to_string()))),
+                ),
+                Ok(name) => (
+                    error(
+                        msg,
+                        "org.freedesktop.DBus.Error.InvalidArgs",
+                        &format!("there is no profile {name:?}"),
+                    ),
+                    None,
+                ),
+                Err(e) => (
+                    error(
+                        msg,
+                        "org.freedesktop.DBus.Error.InvalidArgs",
+                        &e.to_string(),
+                    ),
+                    None,
+                ),
+            },
+            _ => (
+                error(msg, "org.freedesktop.DBus.Error.UnknownMethod", member),
+                None,
+            ),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+// Interfaces we serve on the session bus for other programs to follow and control the prompt.
+//
+//   Bus name:  io.github.darkwater.FprintPrompt, with `.NAME` after it for `--instance NAME`
+//   Path:      /io/github/darkwater/FprintPrompt
+//
+// Control1 is always there. Shell1 is only served when we can't draw the prompt ourselves.
+// The same controls are also on a Unix socket, in `socket`.
+
+use std::ffi::CString;
+
+use dbus::{arg::PropMap, ffidisp::Connection as DbusConnection, Message, MessageType};
+
+pub mod control;
+pub mod shell;
+pub mod socket;
+
+use crate::{config::Config, presentation::Latency, prompts::Prompts};
+
+pub const NAME: &str = "io.github.darkwater.FprintPrompt";
+pub const PATH: &str = "/io/github/darkwater/FprintPrompt";
+pub const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// An error reply to a method call.
+pub fn error(msg: &Message, name: &str, detail: &str) -> Message {
+    let message = CString::new(detail).unwrap_or_default();
+    msg.error(&name.into(), &message)
+}
+
+pub struct Server {
+    dbus: DbusConnection,
+    control: control::Control,
+    shell: Option<shell::ShellIpc>,
+}
+
+impl Server {
+    /// Takes the bus name, also serving Shell1 if `shell` is set.
+    pub fn new(shell: bool) -> Result<Server, dbus::Error> {
+        let dbus = DbusConnection::new_session()?;
+        dbus.register_name(&crate::instance::bus_name(), 0)?;
+        dbus.register_object_path(PATH)?;
+
+        Ok(Server {
+            dbus,
+            control: control::Control::default(),
+            shell: shell.then(shell::ShellIpc::default),
+        })
+    }
+
+    /// Publishes the current state, signalling whatever changed.
+    pub fn update(&mut self, prompts: &Prompts, config: &Config, paused: bool, latency: &Latency) {
+        self.control.update(&self.dbus, paused, config, latency);
+        if let Some(shell) = &mut self.shell {
+            shell.update(&self.dbus, prompts, config);
+        }
+    }
+
+    /// Answers method calls, returning the first control request.
+    pub fn poll(&mut self) -> Option<control::ControlAction> {
+        let mut action = None;
+
+        while let Some(msg) = self.dbus.incoming(0).next() {
+            if msg.msg_type() != MessageType::MethodCall {
+                continue;
+            }
+
+            let (reply, requested) = self.handle(&msg);
+            action = action.or(requested);
+
+            if !msg.get_no_reply() {
+                let _ = self.dbus.send(reply);
+            }
+        }
+
+        action
+    }
+
+    fn handle(&self, msg: &Message) -> (Message, Option<control::ControlAction>) {
+        let member = msg.member().map(|m| m.to_string()).unwrap_or_default();
+
+        let reply = match (msg.interface().as_deref(), member.as_str()) {
+            (Some(PROPERTIES_INTERFACE), "GetAll") => {
+                let interface: &str = msg.read1().unwrap_or("");
+                msg.method_return().append1(self.properties(interface))
+            }
+            (Some(PROPERTIES_INTERFACE), "Get") => {
+                let (interface, name) = msg.read2::<&str, &str>().unwrap_or(("", ""));
+                match self.properties(interface).get(name) {
+                    Some(value) => msg.method_return().append1(value),
+                    None => error(msg, "org.freedesktop.DBus.Error.UnknownProperty", name),
+                }
+            }
+            (Some(control::INTERFACE), _) => return self.control.handle(msg, &member),
+            _ => error(msg, "org.freedesktop.DBus.Error.UnknownMethod", &member),
+        };
+
+        (reply, None)
+    }
+
+    fn properties(&self, interface: &str) -> PropMap {
+        match interface {
+            control::INTERFACE => self.control.properties(),
+            shell::INTERFACE => self
+                .shell
+                .as_ref()
+                .map(shell::ShellIpc::properties)
+                .unwrap_or_default(),
+            _ => PropMap::new(),
+        }
+    }
+}
+
+/// Sends a signal from our object.
+fn signal(
+    dbus: &DbusConnection,
+    interface: &str,
+    member: &str,
+    append: impl FnOnce(Message) -> Message,
+) {
+    match Message::new_signal(PATH, interface, member) {
+        Ok(msg) => {
+            let _ = dbus.send(append(msg));
+        }
+        Err(e) => log::warn!("could not create {member} signal: {e}"),
+    }
+}
@@ -0,0 +1,134 @@
+// Hands the prompt off to a desktop shell that draws it itself, for compositors without
+// layer-shell like GNOME's Mutter, where a companion Shell extension can render the bar.
+//
+// Schema, version 1. Additions only; anything incompatible gets a new interface name.
+//
+//   Interface: io.github.darkwater.FprintPrompt.Shell1
+//
+//   Properties, all read-only, with org.freedesktop.DBus.Properties.PropertiesChanged emitted
+//   whenever any of them change:
+//     State     s  "idle", "verify", "enroll", or "fallback", "busy" or "disconnected" for a
+//                  moment after the password is asked for instead of a finger
+//     Text      s  What to tell the user, e.g. which finger to use. Empty when idle.
+//     Edge      s  "top", "bottom", "left" or "right"
+//     CloseTo   s  Which end of the edge Offset is measured from, or "center"
+//     Offset    u  Pixels from that end to the start of the bar
+//     Length    u  Pixels
+//     Thickness u  Pixels
+//     Color     u  ARGB of the bar in its current state
+//
+//   Signals:
+//     StateChanged(s state, s text)  Same as the State and Text properties changing.
+
+use std::collections::HashMap;
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    ffidisp::Connection as DbusConnection,
+};
+
+use super::PROPERTIES_INTERFACE;
+use crate::{
+    config::{anchor_name, Config},
+    prompts::{Prompts, SessionKind},
+};
+
+pub const INTERFACE: &str = "io.github.darkwater.FprintPrompt.Shell1";
+
+#[derive(Clone, PartialEq)]
+struct State {
+    state: &'static str,
+    text: String,
+    edge: &'static str,
+    close_to: &'static str,
+    offset: u32,
+    length: u32,
+    thickness: u32,
+    color: u32,
+}
+
+impl State {
+    fn new(prompts: &Prompts, config: &Config) -> State {
+        let primary = prompts.primary();
+        let notice = prompts.notice();
+        let pos = &config.position;
+
+        State {
+            state: match primary.map(|session| session.kind) {
+                None => notice.map_or("idle", |(state, _)| state),
+                Some(SessionKind::Verify) => "verify",
+                Some(SessionKind::Enroll) => "enroll",
+            },
+            text: match primary {
+                Some(session) => session.text.clone(),
+                None => notice.map_or(String::new(), |(_, text)| text.to_string()),
+            },
+            edge: anchor_name(pos.edge),
+            close_to: anchor_name(pos.close_to),
+            offset: pos.offset,
+            length: pos.length,
+            thickness: pos.thickness,
+            color: match primary.map(|session| session.kind) {
+                Some(SessionKind::Enroll) => config.colors.enroll,
+                None if notice.is_some() => config.colors.failure,
+                _ => config.colors.prompt,
+            },
+        }
+    }
+
+    fn properties(&self) -> PropMap {
+        let properties: [(&str, Box<dyn RefArg>); 8] = [
+            ("State", Box::new(self.state.to_string())),
+            ("Text", Box::new(self.text.clone())),
+            ("Edge", Box::new(self.edge.to_string())),
+            ("CloseTo", Box::new(self.close_to.to_string())),
+            ("Offset", Box::new(self.offset)),
+            ("Length", Box::new(self.length)),
+            ("Thickness", Box::new(self.thickness)),
+            ("Color", Box::new(self.color)),
+        ];
+
+        properties
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Variant(value)))
+            .collect::<HashMap<_, _>>()
+    }
+}
+
+#[derive(Default)]
+pub struct ShellIpc {
+    state: Option<State>,
+}
+
+impl ShellIpc {
+    /// Publishes the current state, if it changed.
+    pub fn update(&mut self, dbus: &DbusConnection, prompts: &Prompts, config: &Config) {
+        let state = State::new(prompts, config);
+        if self.state.as_ref() == Some(&state) {
+            return;
+        }
+
+        let state_changed = self
+            .state
+            .as_ref()
+            .is_none_or(|old| old.state != state.state || old.text != state.text);
+
+        super::signal(dbus, PROPERTIES_INTERFACE, "PropertiesChanged", |msg| {
+            msg.append3(INTERFACE, state.properties(), Vec::<String>::new())
+        });
+        if state_changed {
+            super::signal(dbus, INTERFACE, "StateChanged", |msg| {
+                msg.append2(state.state, &state.text)
+            });
+        }
+
+        self.state = Some(state);
+    }
+
+    pub fn properties(&self) -> PropMap {
+        self.state
+            .as_ref()
+            .map(State::properties)
+            .unwrap_or_default()
+    }
+}
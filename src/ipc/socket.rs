@@ -0,0 +1,223 @@
+// The control socket: JSON-RPC 2.0 on `$XDG_RUNTIME_DIR/fprint-prompt.sock`, or
+// `fprint-prompt-NAME.sock` for `--instance NAME`, for widgets that would rather not speak D-Bus.
+// The protocol is described in the library's `rpc` module.
+
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use fprint_prompt::rpc::{self, Method, State};
+
+use super::control::ControlAction;
+use crate::instance;
+
+/// The longest request a client can send, past which it's disconnected.
+const MAX_LINE: usize = 64 * 1024;
+/// How much can wait to be written to a client that isn't reading, before it's disconnected.
+const MAX_QUEUED: usize = 1024 * 1024;
+
+pub struct RpcSocket {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<Client>,
+    state: State,
+    /// What `state` was built from.
+    version: Option<Version>,
+}
+
+/// What the state is built from, so it's only built again once that changes.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Version {
+    pub visible: bool,
+    /// [`Prompts::generation`](crate::prompts::Prompts::generation).
+    pub prompts: u64,
+    /// Goes up when the profile, the outputs or where the bar was drawn change.
+    pub surfaces: u64,
+    /// How many surfaces there are, which go without drawing anything when they're gone.
+    pub surface_count: usize,
+}
+
+struct Client {
+    stream: UnixStream,
+    /// What has been read of a line that hasn't ended yet.
+    partial: Vec<u8>,
+    /// What couldn't be written yet without blocking.
+    queued: Vec<u8>,
+    subscribed: bool,
+}
+
+impl RpcSocket {
+    pub fn new() -> io::Result<RpcSocket> {
+        let dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "XDG_RUNTIME_DIR isn't set"))?;
+        RpcSocket::bind(
+            PathBuf::from(dir).join(format!("{}.sock", instance::file_name("fprint-prompt"))),
+        )
+    }
+
+    /// Listens on `path`.
+    pub fn bind(path: PathBuf) -> io::Result<RpcSocket> {
+        // Left behind by an instance that didn't get to clean up, unless it's still running.
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another fprint-prompt is listening",
+                ));
+            }
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(RpcSocket {
+            listener,
+            path,
+            clients: vec![],
+            state: State::default(),
+            version: None,
+        })
+    }
+
+    /// Publishes the current state, notifying subscribers if it changed. It's only built, with
+    /// `state`, when it was built from something else last time.
+    pub fn update(&mut self, version: Version, state: impl FnOnce() -> State) {
+        if self.version == Some(version) {
+            return;
+        }
+        self.version = Some(version);
+        let state = state();
+        if state == self.state {
+            return;
+        }
+        let line = state.notification().to_string();
+        self.state = state;
+
+        self.clients
+            .retain_mut(|client| !client.subscribed || client.send(&line));
+    }
+
+    /// Takes new clients and answers their requests, returning the first that controls the
+    /// overlay.
+    pub fn poll(&mut self) -> Option<ControlAction> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        log::warn!("could not set up a control socket client: {e}");
+                        continue;
+                    }
+                    self.clients.push(Client {
+                        stream,
+                        partial: vec![],
+                        queued: vec![],
+                        subscribed: false,
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("could not accept on the control socket: {e}");
+                    break;
+                }
+            }
+        }
+
+        let mut action = None;
+        let state = &self.state;
+        self.clients.retain_mut(|client| {
+            if !client.flush() {
+                return false;
+            }
+
+            let mut buffer = [0; 4096];
+            // Reading stops at a line too long, to take the lines before it first.
+            while client.partial.len() <= MAX_LINE {
+                match client.stream.read(&mut buffer) {
+                    // Hung up.
+                    Ok(0) => return false,
+                    Ok(read) => client.partial.extend_from_slice(&buffer[..read]),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(_) => return false,
+                }
+            }
+
+            while let Some(end) = client.partial.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.partial.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let (reply, requested) = client.handle(&line, state);
+                if action.is_none() {
+                    action = requested;
+                }
+                if reply.is_some_and(|reply| !client.send(&reply)) {
+                    return false;
+                }
+            }
+
+            if client.partial.len() > MAX_LINE {
+                log::warn!("disconnecting a control socket client sending a line too long");
+                return false;
+            }
+            true
+        });
+
+        action
+    }
+}
+
+impl Client {
+    /// Writes a line, or queues it until the client reads. False if the client is gone, or has
+    /// left too much unread.
+    fn send(&mut self, line: &str) -> bool {
+        self.queued.extend_from_slice(line.as_bytes());
+        self.queued.push(b'\n');
+        if self.queued.len() > MAX_QUEUED {
+            log::warn!("disconnecting a control socket client that isn't reading");
+            return false;
+        }
+        self.flush()
+    }
+
+    /// Writes what was queued, as far as it goes without blocking. False if the client is gone.
+    fn flush(&mut self) -> bool {
+        while !self.queued.is_empty() {
+            match self.stream.write(&self.queued) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.queued.drain(..written);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+
+    /// The reply to a line, if it needs one, and what it asks of the overlay.
+    fn handle(&mut self, line: &str, state: &State) -> (Option<String>, Option<ControlAction>) {
+        let (method, reply) = rpc::answer(line, state);
+        let action = match method {
+            Some(Method::Show) => Some(ControlAction::Resume),
+            Some(Method::Hide) => Some(ControlAction::Pause),
+            Some(Method::Subscribe) => {
+                self.subscribed = true;
+                None
+            }
+            Some(Method::GetState) | None => None,
+        };
+        (reply.map(|reply| reply.to_string()), action)
+    }
+}
+
+impl Drop for RpcSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
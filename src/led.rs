@@ -0,0 +1,84 @@
+// The `led` sink: lights an LED from /sys/class/leds while a verification waits, like a
+// keyboard backlight or the fingerprint reader's own, for when the screen is off or out of sight.
+// Writing to it usually takes a udev rule granting the user access.
+
+use std::path::PathBuf;
+
+use crate::{
+    config::Config,
+    prompts::SessionKind,
+    sinks::{OutputSink, View},
+};
+
+const LEDS: &str = "/sys/class/leds";
+
+pub struct Led {
+    name: Option<String>,
+    /// Whether we lit it.
+    lit: bool,
+    /// Set once writing failed, so it isn't retried every time.
+    failed: bool,
+}
+
+impl Led {
+    pub fn new(config: &Config) -> Led {
+        if config.led.is_none() {
+            log::warn!("the led sink is on, but there's no led.name to light");
+        }
+        Led {
+            name: config.led.clone(),
+            lit: false,
+            failed: false,
+        }
+    }
+
+    fn set(&mut self, lit: bool) {
+        self.lit = lit;
+        let Some(name) = &self.name else {
+            return;
+        };
+
+        let dir = PathBuf::from(LEDS).join(name);
+        let brightness = if lit {
+            std::fs::read_to_string(dir.join("max_brightness"))
+                .map_or_else(|_| "1".to_string(), |max| max.trim().to_string())
+        } else {
+            "0".to_string()
+        };
+        if let Err(e) = std::fs::write(dir.join("brightness"), brightness) {
+            log::warn!("could not light {}: {e}", dir.display());
+            self.failed = true;
+        }
+    }
+}
+
+impl OutputSink for Led {
+    fn poll(&mut self, view: &View) {
+        let waiting = !view.paused
+            && view
+                .prompts
+                .primary()
+                .is_some_and(|session| session.kind == SessionKind::Verify);
+        if waiting != self.lit && !self.failed {
+            self.set(waiting);
+        }
+    }
+
+    fn configure(&mut self, config: &Config) {
+        if config.led != self.name {
+            if self.lit {
+                self.set(false);
+            }
+            self.name.clone_from(&config.led);
+            self.failed = false;
+        }
+    }
+}
+
+impl Drop for Led {
+    fn drop(&mut self) {
+        if self.lit {
+            self.set(false);
+        }
+    }
+}
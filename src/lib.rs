@@ -0,0 +1,23 @@
+//! The monitoring half of fprint-prompt: following fprintd on the system bus to see when a
+//! fingerprint reader wants a finger and how the scan went, without drawing anything.
+//!
+//! ```no_run
+//! use fprint_prompt::{FprintMonitor, MonitorEvent};
+//!
+//! let monitor = FprintMonitor::new().unwrap();
+//! for (device, event) in monitor.iter() {
+//!     if let MonitorEvent::FingerNeeded(needed) = event {
+//!         println!("{device}: finger needed: {needed}");
+//!     }
+//! }
+//! ```
+
+pub mod bus;
+pub mod fprint;
+pub mod monitor;
+pub mod rpc;
+pub mod signals;
+pub mod testing;
+
+pub use monitor::{FprintMonitor, MonitorEvent, PromptSource};
+pub use signals::{FprintEvent, ScanResult};
@@ -0,0 +1,77 @@
+// Noticing when the screen is locked, so the prompt can switch to a layout that works on a lock
+// screen.
+//
+// ext-session-lock doesn't tell other clients when a lock is active, so we go by logind's
+// LockedHint, which some lockers set, and otherwise by whether a known locker is running as us.
+
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    time::{Duration, Instant},
+};
+
+use dbus::{ffidisp::Connection as DbusConnection, Path};
+
+use crate::{sandbox, session};
+
+/// Process names of lockers that use ext-session-lock, cut to the 15 characters the kernel keeps.
+const LOCKERS: &[&str] = &[
+    "swaylock",
+    "hyprlock",
+    "gtklock",
+    "waylock",
+    "kscreenlocker_g",
+];
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct LockMonitor {
+    checked: Option<Instant>,
+    locked: bool,
+    /// Our logind session, once found.
+    session: Option<Path<'static>>,
+}
+
+impl LockMonitor {
+    /// Whether the screen is locked, checking again at most once a second.
+    pub fn poll(&mut self, dbus: &DbusConnection) -> bool {
+        if self
+            .checked
+            .is_some_and(|checked| checked.elapsed() < CHECK_INTERVAL)
+        {
+            return self.locked;
+        }
+        self.checked = Some(Instant::now());
+
+        if self.session.is_none() {
+            self.session = session::own_session(dbus)
+                .map_err(|e| log::warn!("could not find our logind session: {e}"))
+                .ok();
+        }
+        let hinted = self
+            .session
+            .as_ref()
+            .is_some_and(|path| session::is_locked(dbus, path).unwrap_or(false));
+
+        self.locked = hinted || locker_running();
+        self.locked
+    }
+}
+
+/// Whether one of [`LOCKERS`] is running as the current user.
+fn locker_running() -> bool {
+    // Flatpak gives us our own PID namespace, so only LockedHint works there.
+    if sandbox::in_flatpak() {
+        return false;
+    }
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return false;
+    };
+    let uid = unsafe { libc::getuid() };
+
+    entries.flatten().any(|entry| {
+        entry.metadata().is_ok_and(|meta| meta.uid() == uid)
+            && fs::read_to_string(entry.path().join("comm"))
+                .is_ok_and(|comm| LOCKERS.contains(&comm.trim()))
+    })
+}
@@ -0,0 +1,48 @@
+// `fprint-prompt --lock`: locks the session with ext-session-lock and shows the prompt on the lock
+// surfaces, so it's always above the lock screen, unlocking once a finger matches.
+//
+// There's no password to fall back on. If the reader stops working, the lock stays until
+// fprint-prompt is killed from another VT and the compositor lets another locker take over.
+
+use crate::{bus, exit, fprint, sandbox};
+
+pub fn run() -> i32 {
+    // The claim is tied to this connection, so it has to outlive the lock.
+    let bus = bus::selected();
+    let dbus = match bus.connect() {
+        Ok(dbus) => dbus,
+        Err(e) => {
+            eprintln!("could not connect to {bus}, not locking: {e}");
+            return exit::FAILURE;
+        }
+    };
+
+    let device = match fprint::get_default_device(&dbus) {
+        Ok(device) => device,
+        Err(e) => {
+            eprintln!("no fingerprint reader available, not locking: {e}");
+            if let Some(hint) = sandbox::permission_hint() {
+                eprintln!("{hint}");
+            }
+            return exit::FAILURE;
+        }
+    };
+
+    if let Err(e) = device.claim(&fprint::current_username()) {
+        eprintln!("could not claim {}, not locking: {e}", device.path);
+        return exit::FAILURE;
+    }
+
+    let result = crate::run_overlay(crate::Mode::Lock(&device));
+
+    let _ = device.verify_stop();
+    let _ = device.release();
+
+    match result.as_deref() {
+        Some("verify-match") => exit::SUCCESS,
+        _ => {
+            eprintln!("The session was not unlocked.");
+            exit::FAILURE
+        }
+    }
+}
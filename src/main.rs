@@ -1,24 +1,45 @@
 // Based on:
 // https://github.com/Smithay/client-toolkit/blob/c583de8dd5651f8168c6513cd282137c42aae049/examples/simple_layer.rs
 
-use std::convert::TryInto;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    os::fd::{AsRawFd, RawFd},
+    time::{Duration, Instant},
+};
 
-use dbus::{ffidisp::Connection as DbusConnection, Message, MessageType};
+use dbus::{ffidisp::Connection as DbusConnection, Path};
+use fprint_prompt::{
+    bus::{self, Bus},
+    fprint, rpc, signals, MonitorEvent,
+};
 use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState},
+    compositor::{CompositorHandler, CompositorState, Region},
     delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm,
+    delegate_registry, delegate_seat, delegate_session_lock, delegate_shm,
     output::{OutputHandler, OutputState},
     reexports::protocols::wp::{
         fractional_scale::v1::client::{
             wp_fractional_scale_manager_v1::{self, WpFractionalScaleManagerV1},
             wp_fractional_scale_v1::{self, WpFractionalScaleV1},
         },
+        idle_inhibit::zv1::client::{
+            zwp_idle_inhibit_manager_v1::{self, ZwpIdleInhibitManagerV1},
+            zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
+        },
+        presentation_time::client::{
+            wp_presentation::{self, WpPresentation},
+            wp_presentation_feedback::{self, WpPresentationFeedback},
+        },
         viewporter::client::{
             wp_viewport::{self, WpViewport},
             wp_viewporter::{self, WpViewporter},
         },
     },
+    reexports::protocols_wlr::foreign_toplevel::v1::client::{
+        zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
@@ -26,6 +47,10 @@ use smithay_client_toolkit::{
         pointer::{PointerEvent, PointerEventKind, PointerHandler},
         Capability, SeatHandler, SeatState,
     },
+    session_lock::{
+        SessionLock, SessionLockHandler, SessionLockState, SessionLockSurface,
+        SessionLockSurfaceConfigure,
+    },
     shell::{
         wlr_layer::{
             Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
@@ -33,114 +58,357 @@ use smithay_client_toolkit::{
         },
         WaylandSurface,
     },
-    shm::{slot::SlotPool, Shm, ShmHandler},
+    shm::{
+        slot::{Buffer, CreateBufferError, SlotPool},
+        Shm, ShmHandler,
+    },
 };
 use wayland_client::{
+    event_created_child,
     globals::registry_queue_init,
     protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface},
-    Connection, Dispatch, QueueHandle,
+    Connection, Dispatch, Proxy, QueueHandle,
 };
 
-struct PositionInfo {
-    thickness: u32,
-    length: u32,
-    edge: Anchor,
-    close_to: Anchor,
-    offset: u32,
-}
+#[cfg(test)]
+mod allocations;
+mod animation;
+mod apps;
+mod assets;
+mod audit;
+mod busy;
+mod config;
+mod crash;
+mod ctl;
+mod dbus_monitor;
+mod devices;
+mod doctor;
+mod exit;
+mod headless;
+mod homed;
+mod hook;
+mod hotplug;
+mod hwdb;
+mod init_config;
+mod instance;
+mod ipc;
+mod led;
+mod lock;
+mod locker;
+mod night;
+mod notify;
+mod pacing;
+mod persist;
+mod portal;
+mod presentation;
+mod prompts;
+mod quirks;
+mod reader_quirks;
+mod record;
+mod report;
+mod requester;
+mod ruler;
+mod sandbox;
+mod schedule;
+mod session;
+mod shape;
+mod signal_rules;
+mod sinks;
+mod sleep;
+#[cfg(test)]
+mod snapshots;
+mod sound;
+mod startup;
+mod status;
+mod style;
+mod theme;
+mod trace;
+mod transition;
+mod tray;
+mod verify_test;
+mod watchdog;
+
+use config::{BarSize, BarState, Millimeters, PositionInfo, ShadowStyle};
+use ipc::control::ControlAction;
+use portal::shortcuts::ShortcutAction;
+use prompts::{Outcome, Prompts, SessionKind};
+use quirks::ScaleStrategy;
+use reader_quirks::ReaderQuirks;
+use shape::{Mark, Shape};
+use signals::ScanResult;
+use sinks::{Sink, Sinks};
+use startup::Failure;
+use trace::wayland_trace;
+use transition::Transition;
 
-impl PositionInfo {
-    fn win_width(&self) -> u32 {
-        match self.edge {
-            Anchor::TOP | Anchor::BOTTOM => self.length + self.offset,
-            Anchor::LEFT | Anchor::RIGHT => self.thickness,
-            _ => unreachable!(),
+fn main() {
+    std::env::set_var("RUST_LOG", "info");
+    crash::install();
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--wayland-trace") {
+        args.remove(index);
+        trace::enable();
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--record") {
+        let Some(path) = args.get(index + 1) else {
+            eprintln!("--record needs a file to record to");
+            std::process::exit(exit::USAGE);
+        };
+        record::enable(path.into());
+        args.drain(index..=index + 1);
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--bus-address") {
+        let Some(address) = args.get(index + 1) else {
+            eprintln!("--bus-address needs a D-Bus address, like unix:path=/run/dbus/test");
+            std::process::exit(exit::USAGE);
+        };
+        bus::select(Bus::Address(address.clone()));
+        args.drain(index..=index + 1);
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--session-bus") {
+        args.remove(index);
+        bus::select(Bus::Session);
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--instance") {
+        let Some(name) = args.get(index + 1) else {
+            eprintln!("--instance needs a name, like fido");
+            std::process::exit(exit::USAGE);
+        };
+        if !instance::is_valid(name) {
+            eprintln!(
+                "{name:?} can't name an instance: it has to start with a letter, with only \
+                 letters, digits and underscores after that"
+            );
+            std::process::exit(exit::USAGE);
         }
+        instance::select(name.clone());
+        args.drain(index..=index + 1);
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--require") {
+        let Some(list) = args.get(index + 1) else {
+            eprintln!("--require needs what can't be done without, like wayland,fprintd");
+            std::process::exit(exit::USAGE);
+        };
+        match startup::Capability::parse_list(list) {
+            Ok(required) => startup::select_required(required),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(exit::USAGE);
+            }
+        }
+        args.drain(index..=index + 1);
     }
 
-    fn win_height(&self) -> u32 {
-        match self.edge {
-            Anchor::TOP | Anchor::BOTTOM => self.thickness,
-            Anchor::LEFT | Anchor::RIGHT => self.length + self.offset,
-            _ => unreachable!(),
+    match args.first().map(String::as_str) {
+        Some("doctor") => std::process::exit(doctor::run()),
+        Some("devices") => std::process::exit(devices::run()),
+        Some("monitor") => std::process::exit(dbus_monitor::run()),
+        Some("ctl") => std::process::exit(ctl::run(&args[1..])),
+        Some("test") => std::process::exit(verify_test::run()),
+        Some("report") => std::process::exit(report::run(&args[1..])),
+        Some("--check-config") => std::process::exit(config::check()),
+        Some("--init-config") => std::process::exit(init_config::run()),
+        Some("--preview") => {
+            run_overlay(Mode::Preview);
+        }
+        Some("--show-ruler") => {
+            println!("Numbers are the distance in pixels from the end of the edge the bar is");
+            println!("closest to. Put the one next to the start of your reader in `offset`, and");
+            println!("the length it spans in `length`. Press Ctrl-C when done.");
+            run_overlay(Mode::Ruler);
+        }
+        Some("--lock") => std::process::exit(locker::run()),
+        Some("--replay") => {
+            let Some(path) = args.get(1) else {
+                eprintln!("--replay needs a file recorded with --record");
+                std::process::exit(exit::USAGE);
+            };
+            run_overlay(Mode::Replay(path.into()));
+        }
+        Some("--greeter") => {
+            run_overlay(Mode::Greeter);
+        }
+        Some("--status-stream") => {
+            run_overlay(Mode::Normal {
+                status_stream: Some(status::Format::Json),
+            });
+        }
+        Some("--swaybar") => {
+            run_overlay(Mode::Normal {
+                status_stream: Some(status::Format::Swaybar),
+            });
+        }
+        Some("--help") => {
+            println!("{USAGE}");
+            println!();
+            println!("{}", exit::STATUSES);
+        }
+        Some(other) => {
+            eprintln!("unknown command: {other}");
+            eprintln!("{USAGE}");
+            std::process::exit(exit::USAGE);
+        }
+        None => {
+            run_overlay(Mode::Normal {
+                status_stream: None,
+            });
         }
     }
 }
 
-fn main() {
-    std::env::set_var("RUST_LOG", "info");
-    env_logger::init();
-
-    let pos = PositionInfo {
-        thickness: 8,
-        length: 138,
-        edge: Anchor::RIGHT,
-        close_to: Anchor::TOP,
-        offset: 100,
+enum Mode<'a> {
+    /// Shows the prompt for fprintd verifications until the surface is closed. Also reports the
+    /// state on stdout in the `status_stream` format.
+    Normal {
+        status_stream: Option<status::Format>,
+    },
+    /// Runs on a greetd login screen: shows the prompt for whoever is logging in, and exits once
+    /// a verification matches and the session is about to start.
+    Greeter,
+    /// Starts a verification on the device once we're listening, and returns its result when it
+    /// finishes.
+    Test(&'a fprint::Device<'a>),
+    /// Locks the session and draws the prompt on the lock surfaces, verifying on the device until
+    /// a finger matches and the session is unlocked.
+    Lock(&'a fprint::Device<'a>),
+    /// Plays back the fprintd signals recorded in a file instead of listening to fprintd, and
+    /// exits once they've all been shown.
+    Replay(std::path::PathBuf),
+    /// Keeps showing the prompt without listening to fprintd, so the config can be tweaked.
+    Preview,
+    /// Shows a ruler along the whole edge instead of the prompt, without listening to fprintd.
+    Ruler,
+}
+
+impl Mode<'_> {
+    /// The config adjusted for the mode.
+    fn config(&self, config: config::Config) -> config::Config {
+        match self {
+            Mode::Greeter => config.for_greeter(),
+            // Nothing that could quit without unlocking, and the prompt is always on the lock
+            // surfaces.
+            Mode::Lock(_) => {
+                let mut sinks = config.sinks.clone();
+                if !sinks.contains(&Sink::Overlay) {
+                    sinks.push(Sink::Overlay);
+                }
+                config::Config {
+                    tray: false,
+                    shortcuts: false,
+                    sinks,
+                    ..config
+                }
+            }
+            _ => config,
+        }
+    }
+}
+
+const USAGE: &str = "usage: fprint-prompt [--wayland-trace] [--record FILE] [--bus-address ADDRESS|--session-bus] [--instance NAME] [--require wayland,fprintd] [doctor|devices|monitor|test|report [FILE]|ctl ...|--check-config|--init-config|--preview|--show-ruler|--greeter|--lock|--replay FILE|--status-stream|--swaybar|--help]";
+
+/// Runs the overlay until it exits. Returns the verification result in [`Mode::Test`].
+fn run_overlay(mode: Mode) -> Option<String> {
+    // Only the plain overlay has something to fall back on.
+    if let Mode::Normal { status_stream } = mode {
+        if !startup::is_required(startup::Capability::Wayland) {
+            if let Err(failure) = startup::check_wayland() {
+                failure.log();
+                headless::run(status_stream);
+                failure.exit();
+            }
+        }
+    }
+
+    // What was picked at runtime in the last run, for the plain overlay to pick up again.
+    let mut persisted = matches!(mode, Mode::Normal { .. }).then(persist::Persisted::load);
+    let saved_profile = persisted
+        .as_ref()
+        .and_then(|persisted| persisted.state.profile.as_deref());
+    let config = match saved_profile {
+        Some(profile) => config::Config::load_profile(Some(profile)),
+        None => config::Config::load(),
     };
+    let config = mode.config(config);
+    if let Some(persisted) = &mut persisted {
+        let state = &mut persisted.state;
+        // Gone from the config since.
+        if state.profile.is_some() && config.profile.is_none() {
+            log::info!("Saved profile {:?} no longer exists", state.profile);
+            state.profile = None;
+        }
+        if state.verifications > 0 {
+            log::info!(
+                "{} of {} verifications so far matched",
+                state.matches,
+                state.verifications
+            );
+        }
+    }
+    let mut config_watcher = config::Watcher::new();
+    crash::set_reports(config.crash_reports);
 
     // All Wayland apps start by connecting the compositor (server).
-    let conn = Connection::connect_to_env().unwrap();
+    let conn = startup::require(startup::connect_wayland(), Failure::Wayland);
+    let _flush_on_panic = crash::FlushOnPanic(conn.clone());
 
     // Enumerate the list of globals to get the protocols the server implements.
-    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let (globals, mut event_queue) = startup::require(registry_queue_init(&conn), Failure::Wayland);
     let qh = event_queue.handle();
 
     // The compositor (not to be confused with the server which is commonly called the compositor) allows
     // configuring surfaces to be presented.
-    let compositor = CompositorState::bind(&globals, &qh).expect("wl_compositor is not available");
+    let compositor =
+        CompositorState::bind(&globals, &qh).unwrap_or_else(|_| Failure::Compositor.exit());
     // This app uses the wlr layer shell, which may not be available with every compositor.
-    let layer_shell = LayerShell::bind(&globals, &qh).expect("layer shell is not available");
+    // Without it, e.g. on GNOME, the state is handed to the shell over D-Bus to draw instead.
+    let layer_shell = LayerShell::bind(&globals, &qh)
+        .map_err(|e| log::warn!("layer shell is not available, only publishing the state: {e}"))
+        .ok();
     // Since we are not using the GPU in this example, we use wl_shm to allow software rendering to a buffer
     // we share with the compositor process.
-    let shm = Shm::bind(&globals, &qh).expect("wl_shm is not available");
+    let shm = startup::require(Shm::bind(&globals, &qh), Failure::Shm);
+
+    let compositor_kind = quirks::Compositor::detect();
+    log::info!("Running on {}", compositor_kind.name());
 
+    // Without fractional scaling, the output's whole-number scale is followed instead.
     let fsm = globals
         .bind::<WpFractionalScaleManagerV1, _, _>(&qh, 1..=1, ())
-        .expect("wp_fractional_scale_manager_v1 is not available");
+        .map_err(|e| log::warn!("fractional scaling is not available: {e}"))
+        .ok();
 
     let viewporter = globals
         .bind::<WpViewporter, _, _>(&qh, 1..=1, ())
-        .expect("wp_fractional_scale_manager_v1 is not available");
+        .unwrap_or_else(|_| Failure::Viewporter.exit());
 
-    // A layer surface is created from a surface.
-    let surface = compositor.create_surface(&qh);
+    // Without it, the screen can still dim or lock in the middle of a prompt.
+    let idle_inhibit_manager = globals
+        .bind::<ZwpIdleInhibitManagerV1, _, _>(&qh, 1..=1, ())
+        .map_err(|e| log::warn!("idle inhibiting is not available: {e}"))
+        .ok();
 
-    let fs = fsm.get_fractional_scale(&surface, &qh, ());
-    let viewport = viewporter.get_viewport(&surface, &qh, ());
-
-    let output_state = OutputState::new(&globals, &qh);
-
-    // And then we create the layer shell.
-    let layer = layer_shell.create_layer_surface(
-        &qh,
-        surface,
-        Layer::Overlay,
-        Some("fprint-prompt"),
-        // HACK: just first output
-        output_state.outputs().next().as_ref(),
-    );
-
-    // Configure the layer surface, providing things like the anchor on screen, desired size and the keyboard
-    // interactivity
-    layer.set_anchor(pos.edge | pos.close_to);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::None);
-    layer.set_size(pos.win_width(), pos.win_height());
-
-    // In order for the layer surface to be mapped, we need to perform an initial commit with no attached
-    // buffer. For more info, see WaylandSurface::commit
-    //
-    // The compositor will respond with an initial configure that we can then use to present to the layer
-    // surface with the correct options.
-    layer.commit();
+    // Tells when frames are shown, to measure how long that takes.
+    let presentation = globals
+        .bind::<WpPresentation, _, _>(&qh, 1..=1, ())
+        .map_err(|e| log::info!("Can't tell when frames are shown: {e}"))
+        .ok();
 
     // We don't know how large the window will be yet, so lets assume the minimum size we suggested for the
     // initial memory allocation.
-    let pool = SlotPool::new(
-        pos.win_width() as usize * pos.win_height() as usize * 4,
-        &shm,
-    )
-    .expect("Failed to create pool");
+    let pool = startup::require(
+        SlotPool::new(
+            config.position.win_width() as usize * config.position.win_height() as usize * 4,
+            &shm,
+        ),
+        Failure::Shm,
+    );
+
+    if let Some(hint) = sandbox::permission_hint() {
+        log::warn!("{hint}");
+    }
+    let monitor = startup::listen_to_fprintd(matches!(mode, Mode::Normal { .. } | Mode::Greeter));
+    let dbus = monitor.connection();
 
     let mut simple_layer = SimpleLayer {
         // Seats and outputs may be hotplugged at runtime, therefore we need to setup a registry state to
@@ -148,169 +416,1273 @@ fn main() {
         registry_state: RegistryState::new(&globals),
         seat_state: SeatState::new(&globals, &qh),
         output_state: OutputState::new(&globals, &qh),
+        compositor,
+        layer_shell,
+        fractional_scale_manager: fsm,
+        presentation,
+        presentation_clock: None,
+        latency: presentation::Latency::default(),
+        rpc_changes: 0,
+        compositor_kind,
+        quirks: quirks::Quirks::new(compositor_kind, &config.quirks),
+        viewporter,
+        idle_inhibit_manager,
+        toplevels: apps::Toplevels::default(),
         shm,
 
         exit: false,
-        first_configure: true,
         pool,
-        width: pos.win_width(),
-        height: pos.win_height(),
-        shift: None,
-        layer,
-        keyboard: None,
-        keyboard_focus: false,
-        pointer: None,
-
-        pos,
-        scale: 1.,
-        viewport,
-        prompt: None,
+        surfaces: Vec::new(),
+        seats: Vec::new(),
+
+        config,
+        night_light: night::NightLight::default(),
+        paused: persisted
+            .as_ref()
+            .filter(|persisted| persisted.state.paused)
+            .map(|_| Pause::default()),
+        locked: false,
+        session_lock: None,
+        show_ruler: matches!(mode, Mode::Ruler),
+        prompts: Prompts::default(),
+        reader_quirks: HashMap::new(),
+        opacity: persisted
+            .as_ref()
+            .map_or(1., |persisted| persisted.state.opacity.max(MIN_OPACITY)),
+        signal_at: None,
+        frame: None,
+        seat_id: session::seat_id(dbus),
+        profile_switch: None,
+        app_switch: None,
     };
+    // Tells which app a verification is for, by the window with focus.
+    simple_layer
+        .toplevels
+        .follow(&simple_layer.config.apps, &globals, &qh);
+
+    let mut enrolled_fingers = fprint::EnrolledFingers::default();
+
+    // Previews only show the overlay, which they draw whatever the prompts do.
+    let overlay = sinks::Overlay::default();
+    let mut sinks = match mode {
+        Mode::Preview | Mode::Ruler => None,
+        Mode::Normal { status_stream } => Some(Sinks::new(
+            &simple_layer.config,
+            status_stream,
+            Some(overlay.clone()),
+        )),
+        _ => Some(Sinks::new(
+            &simple_layer.config,
+            None,
+            Some(overlay.clone()),
+        )),
+    };
+    let mut tray = simple_layer.config.tray.then(start_tray).flatten();
+    let mut shortcuts = simple_layer
+        .config
+        .shortcuts
+        .then(start_shortcuts)
+        .flatten();
+    let mut screencast = simple_layer
+        .config
+        .pause_during_screencast
+        .then(start_screencast_monitor)
+        .flatten();
+    // Whether we paused for a screen cast, and should resume when it's over.
+    let mut paused_for_screencast = false;
+    // Without layer-shell, the state is also published for a shell extension to draw.
+    let mut ipc = ipc::Server::new(simple_layer.layer_shell.is_none())
+        .map_err(|e| log::warn!("could not serve on the session bus: {e}"))
+        .ok();
+    let mut rpc_socket = ipc::socket::RpcSocket::new()
+        .map_err(|e| log::warn!("could not open the control socket: {e}"))
+        .ok();
+    let mut finger = None;
+    let mut lock_monitor = lock::LockMonitor::default();
+    let mut sleep_monitor = sleep::SleepMonitor::new();
+    // The verifications shown are our own in the other modes.
+    let mut requesters =
+        matches!(mode, Mode::Normal { .. } | Mode::Greeter).then(requester::Requesters::new);
+    let mut claim_errors = matches!(mode, Mode::Normal { .. } | Mode::Greeter)
+        .then(busy::ClaimErrors::new)
+        .flatten();
+    let mut journal = audit::Journal::default();
+    let mut signal_rules = signal_rules::SignalRules::default();
+    if matches!(mode, Mode::Normal { .. } | Mode::Greeter) {
+        signal_rules.configure(&simple_layer.config);
+    }
+    let homed_user = (!matches!(mode, Mode::Greeter)).then(fprint::current_username);
+    let mut homed = (matches!(mode, Mode::Normal { .. } | Mode::Greeter)
+        && simple_layer.config.homed)
+        .then(|| homed::HomedUnlocks::new(homed_user.clone()))
+        .flatten();
+    let mut recorder = record::Recorder::start();
+    let mut replay = match &mode {
+        Mode::Replay(path) => match record::Replay::load(path) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                log::error!("could not replay {}: {e}", path.display());
+                return None;
+            }
+        },
+        _ => None,
+    };
+    // In greeter mode, when a verification matched and the user's session is starting.
+    let mut logged_in = None;
+
+    if let Some(sinks) = &mut sinks {
+        sinks.event(
+            &simple_layer.view(),
+            &status::Status {
+                device: None,
+                finger: None,
+                result: None,
+            },
+        );
+    }
 
-    let dbus = DbusConnection::new_system().unwrap();
-
-    dbus.add_match("interface='net.reactivated.Fprint.Device',member='VerifyStatus'")
-        .unwrap();
+    match mode {
+        Mode::Normal { .. } | Mode::Greeter => {
+            pick_up_running(&mut simple_layer, dbus, &mut enrolled_fingers)
+        }
+        Mode::Ruler | Mode::Replay(_) => (),
+        Mode::Test(device) => device
+            .verify_start("any")
+            .expect("failed to start verification"),
+        Mode::Lock(device) => {
+            // Before locking, as nothing could unlock it otherwise.
+            if let Err(e) = device.verify_start("any") {
+                log::error!("could not start verifying, not locking: {e}");
+                return None;
+            }
+            match SessionLockState::new(&globals, &qh).lock(&qh) {
+                Ok(lock) => simple_layer.session_lock = Some(lock),
+                Err(e) => {
+                    log::error!("session locking is not available: {e}");
+                    return None;
+                }
+            }
+        }
+        Mode::Preview => simple_layer.prompts.start(
+            Path::new("/preview").unwrap(),
+            SessionKind::Verify,
+            "Preview".to_string(),
+        ),
+    }
 
-    dbus.add_match("interface='net.reactivated.Fprint.Device',member='VerifyFingerSelected'")
-        .unwrap();
+    let watchdog = watchdog::Watchdog::start(simple_layer.config.watchdog);
+    let mut theme_clock = schedule::ThemeClock::default();
+    // When to start verifying again on the lock screen, after fprintd refused to.
+    let mut verify_again: Option<Instant> = None;
+    // Kept from one turn to the next, so waiting doesn't allocate.
+    let mut pollfds = vec![];
 
     // We don't draw immediately, the configure will notify us when to first draw.
     loop {
-        event_queue.blocking_dispatch(&mut simple_layer).unwrap();
+        // Wake up for whichever comes first, the compositor or fprintd, so a signal never waits
+        // for a frame callback to be handled. Without surfaces there are no frame callbacks, and
+        // the timeout keeps everything else polled.
+        watchdog.stage("talking to the compositor");
+        event_queue.flush().unwrap();
+        if let Some(guard) = event_queue.prepare_read() {
+            let timeout_ms = if simple_layer.app_switch.is_some() {
+                0
+            } else if replay.is_some() {
+                10
+            } else {
+                250
+            };
+            let watched = std::iter::once(guard.connection_fd().as_raw_fd())
+                .chain(monitor.watch_fds().iter().copied())
+                .chain(requesters.iter().flat_map(requester::Requesters::watch_fds))
+                .chain(claim_errors.as_ref().map(busy::ClaimErrors::watch_fd))
+                .chain(signal_rules.watch_fds())
+                .chain(
+                    homed
+                        .iter()
+                        .flat_map(|homed| homed.watch_fds().iter().copied()),
+                );
+            wait_readable(&mut pollfds, watched, timeout_ms);
+            let _ = guard.read();
+        }
+        event_queue.dispatch_pending(&mut simple_layer).unwrap();
+
+        watchdog.stage("polling D-Bus");
+        let mut new_config = config_watcher.poll(simple_layer.config.selection());
+        if new_config.is_some() {
+            log::info!("Reloaded config");
+        }
+
+        if let Some(ipc) = &mut ipc {
+            match ipc.poll() {
+                Some(ControlAction::Pause) => simple_layer.set_paused(&qh, true),
+                Some(ControlAction::Resume) => simple_layer.set_paused(&qh, false),
+                Some(ControlAction::SetProfile(profile)) => {
+                    log::info!("Switching to profile {profile:?}");
+                    new_config = Some(config::Config::load_selected(config::Selection {
+                        profile: profile.as_deref(),
+                        ..simple_layer.config.selection()
+                    }));
+                }
+                None => (),
+            }
+            ipc.update(
+                &simple_layer.prompts,
+                &simple_layer.config,
+                simple_layer.paused.is_some(),
+                &simple_layer.latency,
+            );
+        }
+
+        if let Some(profile) = simple_layer.profile_switch.take() {
+            log::info!("Switching to profile {profile:?} for the connected outputs");
+            new_config = Some(config::Config::load_selected(config::Selection {
+                profile: profile.as_deref(),
+                ..simple_layer.config.selection()
+            }));
+        }
+
+        // An app keeps its settings until its verification is over, outcome and all.
+        let prompts_over =
+            simple_layer.prompts.is_empty() && simple_layer.prompts.outcome().is_none();
+        if new_config.is_none() {
+            let app = match simple_layer.app_switch.take() {
+                Some(app) => Some(app),
+                None if prompts_over && simple_layer.config.app.is_some() => Some(None),
+                None => None,
+            };
+            if let Some(app) = app {
+                match &app {
+                    Some(app) => log::info!("Using the settings for {app:?}"),
+                    None => log::info!("Back to the usual settings"),
+                }
+                new_config = Some(config::Config::load_selected(config::Selection {
+                    app: app.as_deref(),
+                    ..simple_layer.config.selection()
+                }));
+            }
+        }
+
+        // Once anything else is reloaded, the next turn takes it from there.
+        let theme = theme_clock.current(&simple_layer.config.theme_schedule);
+        if new_config.is_none() && theme != simple_layer.config.theme.as_deref() {
+            match theme {
+                Some(theme) => log::info!("Switching to the scheduled theme {theme:?}"),
+                None => log::info!("Switching back to the configured colors"),
+            }
+            new_config = Some(config::Config::load_selected(config::Selection {
+                theme,
+                ..simple_layer.config.selection()
+            }));
+        }
 
-        if let Some(msg) = dbus.incoming(10).next() {
-            match verify_status_msg(&msg) {
-                Some(FprintEvent::VerifyFingerSelected { finger_name }) => {
-                    println!("Finger selected: {:?}", finger_name);
-                    simple_layer.prompt = finger_name;
+        if let Some(new_config) = new_config {
+            simple_layer.apply_config(&qh, mode.config(new_config));
+            simple_layer
+                .toplevels
+                .follow(&simple_layer.config.apps, &globals, &qh);
+            watchdog.set_timeout(simple_layer.config.watchdog);
+            if let Some(sinks) = &mut sinks {
+                sinks.configure(&simple_layer.config);
+            }
+            if matches!(mode, Mode::Normal { .. } | Mode::Greeter) {
+                signal_rules.configure(&simple_layer.config);
+                if simple_layer.config.homed != homed.is_some() {
+                    homed = simple_layer
+                        .config
+                        .homed
+                        .then(|| homed::HomedUnlocks::new(homed_user.clone()))
+                        .flatten();
                 }
-                Some(FprintEvent::VerifyStatus { result, done }) => {
-                    println!("Result: {:?}, Done: {:?}", result, done);
+            }
+
+            if simple_layer.config.tray != tray.is_some() {
+                tray = simple_layer.config.tray.then(start_tray).flatten();
+            }
+            if simple_layer.config.shortcuts != shortcuts.is_some() {
+                shortcuts = simple_layer
+                    .config
+                    .shortcuts
+                    .then(start_shortcuts)
+                    .flatten();
+            }
+            if simple_layer.config.pause_during_screencast != screencast.is_some() {
+                screencast = simple_layer
+                    .config
+                    .pause_during_screencast
+                    .then(start_screencast_monitor)
+                    .flatten();
+            }
+        }
+
+        if let Some(rpc_socket) = &mut rpc_socket {
+            rpc_socket.update(simple_layer.rpc_version(), || simple_layer.rpc_state());
+            match rpc_socket.poll() {
+                Some(ControlAction::Pause) => simple_layer.set_paused(&qh, true),
+                Some(ControlAction::Resume) => simple_layer.set_paused(&qh, false),
+                Some(ControlAction::SetProfile(_)) | None => (),
+            }
+        }
 
-                    if done == Some(true) {
-                        simple_layer.prompt = None;
+        if let Some(tray) = &mut tray {
+            match tray.poll() {
+                Some(tray::TrayAction::SetPaused(paused)) => simple_layer.set_paused(&qh, paused),
+                Some(tray::TrayAction::Preview) => {
+                    let preview = std::env::current_exe()
+                        .and_then(|exe| std::process::Command::new(exe).arg("--preview").spawn());
+                    if let Err(e) = preview {
+                        log::warn!("could not start the preview: {e}");
                     }
                 }
+                Some(tray::TrayAction::Quit) => return None,
+                None => (),
+            }
+
+            tray.set_state(
+                !simple_layer.prompts.is_empty(),
+                simple_layer.paused.is_some(),
+            );
+        }
+
+        simple_layer.update_escalation();
+        simple_layer.update_idle_inhibit(&qh);
+        if let Some(sinks) = &mut sinks {
+            sinks.poll(&simple_layer.view());
+            if sinks.toggle_requested() {
+                simple_layer.toggle_paused(&qh);
+            }
+        }
+
+        let casting = screencast.as_mut().is_some_and(|monitor| monitor.poll());
+        if casting && !paused_for_screencast && simple_layer.paused.is_none() {
+            log::info!("Pausing while the screen is being cast");
+            simple_layer.set_paused(&qh, true);
+            paused_for_screencast = true;
+        } else if !casting && paused_for_screencast {
+            paused_for_screencast = false;
+            if simple_layer.paused.is_some() {
+                log::info!("Resuming now the screen cast is over");
+                simple_layer.set_paused(&qh, false);
+            }
+        }
+
+        if let Some(shortcuts) = &mut shortcuts {
+            match shortcuts.poll() {
+                Some(ShortcutAction::TogglePaused) => simple_layer.toggle_paused(&qh),
+                Some(ShortcutAction::ShowLastResult) => simple_layer.prompts.replay_outcome(),
                 None => (),
             }
         }
 
+        if logged_in.is_some_and(|at: Instant| at.elapsed() >= animation::OUTCOME_DURATION) {
+            log::info!("Logged in, exiting");
+            return None;
+        }
+
+        if let Some(persisted) = &mut persisted {
+            let state = &mut persisted.state;
+            state.profile.clone_from(&simple_layer.config.profile);
+            // A pause for a screen cast ends with it, so it isn't the user's to keep.
+            state.paused = simple_layer.paused.is_some() && !paused_for_screencast;
+            state.opacity = simple_layer.opacity;
+            persisted.save();
+        }
+
+        if matches!(mode, Mode::Preview | Mode::Ruler) {
+            if simple_layer.exit {
+                return None;
+            }
+            continue;
+        }
+
+        let locked = matches!(mode, Mode::Lock(_))
+            || simple_layer.config.lock_screen && lock_monitor.poll(dbus);
+        simple_layer.set_locked(&qh, locked);
+
+        if let (Mode::Lock(claimed), Some(at)) = (&mode, verify_again) {
+            if at <= Instant::now() {
+                verify_again = verify_lock(&mut simple_layer, claimed);
+                simple_layer.redraw(&qh);
+            }
+        }
+
+        if sleep_monitor.poll(&mut simple_layer.prompts, dbus) {
+            if let Some(sinks) = &mut sinks {
+                sinks.event(
+                    &simple_layer.view(),
+                    &status::Status {
+                        device: None,
+                        finger: None,
+                        result: None,
+                    },
+                );
+            }
+        }
+
+        let abandoned = requesters
+            .as_mut()
+            .map(|requesters| requesters.poll())
+            .unwrap_or_default();
+        let mut changed = false;
+        for (device, process) in abandoned {
+            if simple_layer.config.audit {
+                journal.write(&audit::Entry {
+                    device: Some(&device),
+                    ending: audit::Ending::Abandoned,
+                    result: None,
+                    finger: finger.as_deref(),
+                    duration: simple_layer.prompts.age(&device, SessionKind::Verify),
+                    requester: Some(&process),
+                });
+            }
+            changed |= simple_layer.prompts.finish(&device, SessionKind::Verify);
+        }
+        let homed_events = homed
+            .as_mut()
+            .map(|homed| homed.poll(&simple_layer.prompts))
+            .unwrap_or_default();
+        for (prompt, event) in signal_rules.poll().into_iter().chain(homed_events) {
+            match event {
+                signal_rules::SignalEvent::Show(text) => {
+                    log::info!("Prompt from a signal: {text:?}");
+                    simple_layer
+                        .prompts
+                        .start(prompt, SessionKind::Verify, text);
+                }
+                signal_rules::SignalEvent::Hide => {
+                    simple_layer.prompts.finish(&prompt, SessionKind::Verify);
+                }
+            }
+            changed = true;
+        }
+        if claim_errors
+            .as_mut()
+            .is_some_and(|claim_errors| claim_errors.poll())
+            && (simple_layer.config.show_other_users || session::is_own_verification(dbus))
+        {
+            log::info!("The reader was busy, so the password is asked for instead");
+            if simple_layer.config.audit {
+                journal.write(&audit::Entry {
+                    device: None,
+                    ending: audit::Ending::Ended(Outcome::Busy),
+                    result: None,
+                    finger: None,
+                    duration: None,
+                    requester: None,
+                });
+            }
+            simple_layer
+                .prompts
+                .linger(Outcome::Busy, &simple_layer.config.attention);
+            changed = true;
+        }
+        if changed {
+            if let Some(sinks) = &mut sinks {
+                sinks.event(
+                    &simple_layer.view(),
+                    &status::Status {
+                        device: None,
+                        finger: None,
+                        result: None,
+                    },
+                );
+            }
+        }
+
+        // Everything that has come in, so nothing waits for the next wakeup.
+        watchdog.stage("handling fprintd events");
+        // Made up for the sessions of readers that went away.
+        let mut cut_off = VecDeque::new();
+        loop {
+            let (device, event) = match (cut_off.pop_front(), &mut replay) {
+                (Some(incoming), _) => incoming,
+                (None, Some(replay)) => match replay.next(0) {
+                    Some(incoming) => incoming,
+                    None => break,
+                },
+                (None, None) => match monitor.next_timeout(Duration::ZERO) {
+                    Some((device, MonitorEvent::Fprint(event))) => (device, event),
+                    Some((device, event)) => {
+                        match event {
+                            MonitorEvent::DeviceRemoved => {
+                                simple_layer.reader_quirks.remove(&device);
+                            }
+                            MonitorEvent::FprintdStopped => simple_layer.reader_quirks.clear(),
+                            // Re-asserted since resuming.
+                            MonitorEvent::FingerNeeded(true) => simple_layer.prompts.keep(&device),
+                            _ => (),
+                        }
+                        cut_off.extend(hotplug::follow(
+                            &simple_layer.prompts,
+                            &mut enrolled_fingers,
+                            &device,
+                            &event,
+                        ));
+                        continue;
+                    }
+                    None => break,
+                },
+            };
+            if let Some(recorder) = &mut recorder {
+                recorder.record(&device, &event);
+            }
+            // Only the claimed reader can unlock.
+            if let Mode::Lock(claimed) = mode {
+                if device != claimed.path {
+                    log::info!("Ignoring {device}, {} is the one claimed", claimed.path);
+                    continue;
+                }
+            }
+
+            let result = event.result().map(str::to_string);
+            let context = transition::Context {
+                dbus,
+                config: &simple_layer.config,
+                seat_id: &simple_layer.seat_id,
+                // The lock screen keeps asking for a finger, where PAM would ask for the password.
+                for_pam: matches!(mode, Mode::Normal { .. } | Mode::Greeter),
+            };
+            let transition = transition::follow(
+                &mut simple_layer.prompts,
+                &mut enrolled_fingers,
+                &mut simple_layer.reader_quirks,
+                &context,
+                &device,
+                &event,
+            );
+            match transition {
+                Transition::Ignored => continue,
+                Transition::Started {
+                    finger: selected,
+                    first,
+                } => {
+                    finger = selected;
+                    if first {
+                        simple_layer.pick_app();
+                    }
+                    if let Some(requesters) = &mut requesters {
+                        requesters.track(&device);
+                    }
+                }
+                Transition::Ended { outcome, duration } => {
+                    if let Some(outcome) = outcome {
+                        if let Some(persisted) = &mut persisted {
+                            persisted.record(outcome);
+                        }
+                        if let Some(pause) = &mut simple_layer.paused {
+                            pause.outcomes.push(outcome);
+                        }
+                        if matches!(mode, Mode::Greeter) && outcome == Outcome::Success {
+                            logged_in = Some(Instant::now());
+                        }
+                    }
+                    let scan = ScanResult::parse(result.as_deref());
+                    if simple_layer.config.audit {
+                        let outcome = outcome.unwrap_or(match scan {
+                            ScanResult::Match => Outcome::Success,
+                            _ => Outcome::Failure,
+                        });
+                        journal.write(&audit::Entry {
+                            device: Some(&device),
+                            ending: audit::Ending::Ended(outcome),
+                            result: result.as_deref(),
+                            finger: finger.as_deref(),
+                            duration,
+                            requester: requesters
+                                .as_ref()
+                                .and_then(|requesters| requesters.requester(&device)),
+                        });
+                    }
+                    if let Some(requesters) = &mut requesters {
+                        requesters.forget(&device);
+                    }
+
+                    if matches!(mode, Mode::Test(_)) {
+                        return result;
+                    }
+                    if let Mode::Lock(claimed) = mode {
+                        if scan == ScanResult::Match {
+                            simple_layer.unlock();
+                            let _ = event_queue.flush();
+                            return result;
+                        }
+
+                        // Keep asking until a finger matches.
+                        let _ = claimed.verify_stop();
+                        verify_again = verify_lock(&mut simple_layer, claimed);
+                    }
+                }
+                Transition::Changed => (),
+            }
+
+            if let Some(sinks) = &mut sinks {
+                sinks.event(
+                    &simple_layer.view(),
+                    &status::Status {
+                        device: Some(&device),
+                        finger: finger.as_deref(),
+                        result: result.as_deref(),
+                    },
+                );
+            }
+            simple_layer.signal_at.get_or_insert_with(Instant::now);
+        }
+        watchdog.stage("drawing");
+        // Show it now rather than on the next frame callback. With the settings for the app it's for,
+        // once they're loaded.
+        if simple_layer.app_switch.is_none() {
+            if overlay.take_changed() {
+                simple_layer.redraw(&qh);
+            } else {
+                // Surfaces are only drawn on frame callbacks while something moves, so whatever
+                // else changed is drawn here.
+                simple_layer.draw_changed(&qh);
+            }
+        }
+
         if simple_layer.exit {
-            println!("exiting example");
-            break;
+            log::info!("exiting example");
+            return None;
+        }
+        if replay.as_ref().is_some_and(|replay| replay.is_done())
+            && simple_layer.prompts.is_empty()
+            && simple_layer.prompts.outcome().is_none()
+        {
+            log::info!("Replay finished");
+            return None;
+        }
+    }
+}
+
+/// Shows the prompt for verifications that were already waiting for a finger when we started, so
+/// their VerifyFingerSelected was missed. fprintd only says that a finger is needed, so any
+/// enrolled finger is asked for, and an enrollment is taken for a verification until its first
+/// EnrollStatus.
+fn pick_up_running(
+    simple_layer: &mut SimpleLayer,
+    dbus: &DbusConnection,
+    enrolled_fingers: &mut fprint::EnrolledFingers,
+) {
+    let devices = match fprint::get_devices(dbus) {
+        Ok(devices) => devices,
+        Err(e) => {
+            log::warn!("could not list devices: {e}");
+            return;
+        }
+    };
+
+    for device in devices {
+        match device.finger_needed() {
+            Ok(true) => (),
+            Ok(false) => continue,
+            Err(e) => {
+                log::info!("could not tell whether {} is in use: {e}", device.path);
+                continue;
+            }
+        }
+        if !simple_layer.config.show_other_users && !session::is_own_verification(dbus) {
+            continue;
         }
+        if !session::is_device_on_seat(
+            dbus,
+            &simple_layer.config,
+            &device.path,
+            &simple_layer.seat_id,
+        ) {
+            continue;
+        }
+
+        log::info!("{} was already waiting for a finger", device.path);
+        reader_quirks::learn(
+            &mut simple_layer.reader_quirks,
+            dbus,
+            &simple_layer.config,
+            &device.path,
+        );
+        let text = if simple_layer.config.privacy {
+            fprint::PRIVATE_HINT.to_string()
+        } else {
+            enrolled_fingers.hint(dbus, &device.path, "any")
+        };
+        simple_layer
+            .prompts
+            .start(device.path.clone(), SessionKind::Verify, text);
     }
 }
 
+/// Starts verifying on the lock screen again, or shows why it couldn't and returns when to retry.
+fn verify_lock(simple_layer: &mut SimpleLayer, claimed: &fprint::Device) -> Option<Instant> {
+    let e = claimed.verify_start("any").err()?;
+    log::warn!("could not verify again: {e}");
+    simple_layer.prompts.start(
+        claimed.path.clone(),
+        SessionKind::Verify,
+        "The reader isn't responding, trying again".to_string(),
+    );
+    Some(Instant::now() + LOCK_RETRY)
+}
+
+/// Waits until one of the `watched` file descriptors is readable, like the compositor's or
+/// fprintd's, or the timeout passes. `fds` is scratch space, kept between calls.
+fn wait_readable(
+    fds: &mut Vec<libc::pollfd>,
+    watched: impl Iterator<Item = RawFd>,
+    timeout_ms: i32,
+) {
+    fds.clear();
+    fds.extend(watched.map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    }));
+    unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+}
+
+fn start_tray() -> Option<tray::Tray> {
+    tray::Tray::new()
+        .map_err(|e| log::warn!("could not show the tray icon: {e}"))
+        .ok()
+}
+
+fn start_screencast_monitor() -> Option<portal::screencast::ScreencastMonitor> {
+    portal::screencast::ScreencastMonitor::new()
+        .map_err(|e| log::warn!("could not watch for screen casts: {e}"))
+        .ok()
+}
+
+fn start_shortcuts() -> Option<portal::shortcuts::Shortcuts> {
+    portal::shortcuts::Shortcuts::new()
+        .map_err(|e| log::warn!("could not set up global shortcuts: {e}"))
+        .ok()
+}
+
+/// Behind the prompt on lock surfaces.
+const LOCK_BACKGROUND: u32 = 0xFF000000;
+/// How long to wait before asking fprintd to verify on the lock screen again after it refused.
+const LOCK_RETRY: Duration = Duration::from_secs(2);
+/// How much one notch of the scroll wheel over the prompt fades it.
+const OPACITY_STEP: f32 = 0.1;
+/// How far a touchpad scrolls for one notch.
+const SCROLL_STEP_PIXELS: f32 = 15.;
+/// How far the pointer has to be dragged towards the screen edge to swipe the prompt away, in
+/// surface coordinates. Bars are thin, so this is little.
+const SWIPE_DISTANCE: f64 = 3.;
+/// How close to the edge the swipe has to end.
+const SWIPE_EDGE: f64 = 1.;
+/// Scrolling never fades the prompt out entirely, or it couldn't be found to scroll back.
+const MIN_OPACITY: f32 = 0.2;
+
 struct SimpleLayer {
     registry_state: RegistryState,
     seat_state: SeatState,
     output_state: OutputState,
+    compositor: CompositorState,
+    layer_shell: Option<LayerShell>,
+    fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    presentation: Option<WpPresentation>,
+    /// The clock the compositor tells presentation times on, once it's said which.
+    presentation_clock: Option<u32>,
+    /// How long frames took to be shown lately.
+    latency: presentation::Latency,
+    /// Goes up when what the control socket tells of the surfaces changes, see
+    /// [`SimpleLayer::rpc_version`].
+    rpc_changes: u64,
+    compositor_kind: quirks::Compositor,
+    /// How surfaces are set up on this compositor.
+    quirks: quirks::Quirks,
+    viewporter: WpViewporter,
+    idle_inhibit_manager: Option<ZwpIdleInhibitManagerV1>,
+    /// The windows, for which app has focus.
+    toplevels: apps::Toplevels,
     shm: Shm,
 
     exit: bool,
-    first_configure: bool,
     pool: SlotPool,
-    width: u32,
-    height: u32,
-    shift: Option<u32>,
-    layer: LayerSurface,
+    /// One for each output on our seat that the prompt is enabled on.
+    surfaces: Vec<PromptSurface>,
+    /// Input devices of the seats we take input from.
+    seats: Vec<SeatInput>,
+
+    config: config::Config,
+    night_light: night::NightLight,
+    /// Set while paused from the swaybar block, the tray, a global shortcut or D-Bus. fprintd is
+    /// still followed, but nothing is shown.
+    paused: Option<Pause>,
+    /// Whether the screen is locked, which switches to the lock screen layout.
+    locked: bool,
+    /// The session lock we asked for with `--lock`. Surfaces cover every output once it's held.
+    session_lock: Option<SessionLock>,
+    /// Draw a ruler along the edge instead of the prompt.
+    show_ruler: bool,
+    prompts: Prompts,
+    /// Quirks of the readers that have prompted, looked up when a verification starts.
+    reader_quirks: HashMap<Path<'static>, ReaderQuirks>,
+    /// Set by scrolling over the prompt, and kept until fprint-prompt exits.
+    opacity: f32,
+    /// When the first signal since the last commit was handled, to trace how long it takes to
+    /// show.
+    signal_at: Option<Instant>,
+    /// The next frame to draw, written over the one drawn before it so drawing doesn't allocate.
+    frame: Option<RenderState>,
+    /// The logind seat we're running on.
+    seat_id: String,
+    /// A profile picked by the outputs that came or went, for the main loop to load.
+    profile_switch: Option<Option<String>>,
+    /// The app a verification that just started is for, for the main loop to load its settings.
+    app_switch: Option<Option<String>>,
+}
+
+/// The keyboard and pointer of one seat, for as long as it has them.
+struct SeatInput {
+    seat: wl_seat::WlSeat,
     keyboard: Option<wl_keyboard::WlKeyboard>,
+    /// Whether the keyboard is focused on one of our surfaces.
     keyboard_focus: bool,
     pointer: Option<wl_pointer::WlPointer>,
+    /// Where the pointer was pressed on one of our surfaces, until it's released.
+    pressed_at: Option<(f64, f64)>,
+}
+
+impl SeatInput {
+    fn release_keyboard(&mut self) {
+        if let Some(keyboard) = self.keyboard.take() {
+            keyboard.release();
+        }
+        self.keyboard_focus = false;
+    }
+
+    fn release_pointer(&mut self) {
+        if let Some(pointer) = self.pointer.take() {
+            pointer.release();
+        }
+        self.pressed_at = None;
+    }
+}
+
+/// What happened while the overlay was paused, to catch up on once it's resumed.
+#[derive(Default)]
+struct Pause {
+    outcomes: Vec<Outcome>,
+}
+
+/// What the prompt's surface is to the compositor.
+enum Role {
+    Layer(LayerSurface),
+    /// Covering the whole output while we hold the session lock, with `--lock`.
+    Lock(SessionLockSurface),
+}
 
+impl Role {
+    fn wl_surface(&self) -> &wl_surface::WlSurface {
+        match self {
+            Role::Layer(layer) => layer.wl_surface(),
+            Role::Lock(lock) => lock.wl_surface(),
+        }
+    }
+
+    fn commit(&self) {
+        self.wl_surface().commit();
+    }
+}
+
+/// The surface showing the prompt on one output, along with its per-surface state.
+struct PromptSurface {
+    output: wl_output::WlOutput,
+    role: Role,
+    viewport: WpViewport,
+    _fractional_scale: Option<WpFractionalScaleV1>,
+
+    first_configure: bool,
+    width: u32,
+    height: u32,
+    /// From this output's profile in the config.
     pos: PositionInfo,
     scale: f32,
-    viewport: WpViewport,
-    prompt: Option<String>,
+    /// Thicker than configured because the prompt is being ignored.
+    escalated: Option<u32>,
+    /// From the config, as the window has to fit the bar in each of them.
+    state_sizes: config::StateSizes,
+    /// From the config, as the window needs room around the bar for it.
+    shadow: config::Shadow,
+    /// The whole edge, when showing the ruler.
+    ruler: Option<PositionInfo>,
+    /// The size of the output in surface coordinates, for lock surfaces, which cover all of it.
+    covering: Option<(u32, u32)>,
+    /// Stretched along the whole edge with an exclusive zone, from `[dock]`.
+    docked: bool,
+    /// From this output's profile, in place of the scale the compositor prefers.
+    scale_override: Option<f32>,
+    /// The bar's size while it's shown, easing between states.
+    resize: Option<animation::Resize>,
+    /// The indicators crossfading between states.
+    handoff: animation::Handoff,
+    /// The geometry for the size the compositor last configured, which is what gets drawn, so the
+    /// buffer always matches the acked size. Differs from [`PromptSurface::geometry`] while a
+    /// resize is waiting for its configure, or when the compositor picked another size.
+    shown: PositionInfo,
+    /// What was last sent with `set_opaque_region`, in surface coordinates.
+    opaque_region: Option<(i32, i32, i32, i32)>,
+    /// Whether the input region was last set for having no prompts.
+    idle: bool,
+    /// Held while a verification is waiting, so the screen doesn't dim or lock.
+    idle_inhibitor: Option<ZwpIdleInhibitorV1>,
+    /// From the config, for while prompts are shown.
+    keyboard: KeyboardInteractivity,
+    /// What was last sent with `set_keyboard_interactivity`.
+    sent_keyboard: KeyboardInteractivity,
+    /// When frame callbacks were asked for and came, to draw at the output's pace.
+    pacing: pacing::FramePacing,
+    buffers: Buffers,
+    /// The last frame drawn, to tell whether the next one looks any different.
+    drawn: Option<RenderState>,
 }
 
-enum FprintEvent {
-    VerifyFingerSelected {
-        finger_name: Option<String>,
-    },
-    VerifyStatus {
-        result: Option<String>,
-        done: Option<bool>,
-    },
+/// The buffers a surface was last drawn to, to draw to again once the compositor lets go of them
+/// instead of allocating one for every frame.
+#[derive(Default)]
+struct Buffers {
+    buffers: Vec<Buffer>,
+    /// The width, height and format they all have.
+    size: Option<(u32, u32, wl_shm::Format)>,
 }
 
-fn verify_status_msg(msg: &Message) -> Option<FprintEvent> {
-    if msg.msg_type() != MessageType::Signal {
-        return None;
-    };
-    if &*msg.interface().unwrap() != "net.reactivated.Fprint.Device" {
-        return None;
-    };
-    // if &*msg.member().unwrap() != "VerifyStatus" {
-    //     return None;
-    // };
-    // let (result, done) = msg.get2::<&str, bool>();
-    // Some((result, done))
+impl Buffers {
+    /// One on screen, one being drawn, and one the compositor is slow to let go of.
+    const KEPT: usize = 3;
 
-    match msg.member().as_deref() {
-        Some("VerifyFingerSelected") => {
-            let finger_name = msg.get1::<String>();
-            Some(FprintEvent::VerifyFingerSelected { finger_name })
+    /// Which buffer to draw the next frame to, allocating one if none of the size and format is
+    /// free.
+    fn free(
+        &mut self,
+        pool: &mut SlotPool,
+        width: u32,
+        height: u32,
+        format: wl_shm::Format,
+    ) -> Result<usize, CreateBufferError> {
+        if self.size != Some((width, height, format)) {
+            self.buffers.clear();
+            self.size = Some((width, height, format));
         }
-        Some("VerifyStatus") => {
-            let (result, done) = msg.get2::<String, bool>();
-            Some(FprintEvent::VerifyStatus { result, done })
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.canvas(pool).is_some())
+        {
+            return Ok(index);
         }
-        _ => None,
+
+        if self.buffers.len() == Self::KEPT {
+            self.buffers.remove(0);
+        }
+        let stride = width as i32 * 4;
+        let (buffer, _) = pool.create_buffer(width as i32, height as i32, stride, format)?;
+        self.buffers.push(buffer);
+        Ok(self.buffers.len() - 1)
     }
 }
 
-impl CompositorHandler for SimpleLayer {
-    fn scale_factor_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
-    ) {
-        // Not needed for this example.
+impl PromptSurface {
+    /// The geometry of the window, big enough for the bar in any state.
+    fn geometry(&self) -> PositionInfo {
+        if let Some(ruler) = &self.ruler {
+            return ruler.clone();
+        }
+        if let Some((width, height)) = self.covering {
+            return self.pos.fitted(
+                (width as f32 * self.scale).round() as u32,
+                (height as f32 * self.scale).round() as u32,
+            );
+        }
+
+        let mut pos = self.state_sizes.fit(&self.pos);
+        if let Some(thickness) = self.escalated {
+            pos.thickness = pos.thickness.max(thickness);
+        }
+
+        // The bar hugs the screen edge and the start of its length, so only the other sides
+        // need room for the shadow.
+        let margin = self.shadow.margin();
+        pos.thickness += margin;
+        pos.length += if pos.close_to.is_empty() {
+            margin * 2
+        } else {
+            margin
+        };
+        pos
     }
 
-    fn transform_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_transform: wl_output::Transform,
-    ) {
-        // Not needed for this example.
+    /// Eases the bar towards the size for a state, returning the size for this frame.
+    fn animate_bar(&mut self, state: Option<BarState>) -> BarSize {
+        let Some(state) = state else {
+            self.resize = None;
+            return self.shown.size();
+        };
+        let target = self.bar_size(state);
+        let resize = self
+            .resize
+            .get_or_insert_with(|| animation::Resize::new(target));
+        resize.retarget(target);
+
+        // The window may still be waiting for a resize to fit it.
+        let size = resize.current();
+        BarSize {
+            length: size.length.min(self.shown.length),
+            thickness: size.thickness.min(self.shown.thickness),
+        }
     }
 
-    fn frame(
-        &mut self,
-        _conn: &Connection,
-        qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _time: u32,
-    ) {
-        self.draw(qh);
+    fn bar_size(&self, state: BarState) -> BarSize {
+        let mut size = self.state_sizes.size(&self.pos, state);
+        if let Some(thickness) = self.escalated {
+            size.thickness = size.thickness.max(thickness);
+        }
+        size
     }
 
-    fn surface_enter(
+    /// Marks a rectangle of the buffer as opaque, or clears the opaque region. Only sent when it
+    /// changes, and takes effect on the next commit.
+    fn set_opaque(&mut self, compositor: &CompositorState, opaque: Option<(u32, u32, u32, u32)>) {
+        // In surface coordinates, rounded inwards so no translucent pixel is ever covered.
+        let region = opaque.map(|(x, y, width, height)| {
+            let scale_x = self.width as f32 / self.shown.win_width() as f32;
+            let scale_y = self.height as f32 / self.shown.win_height() as f32;
+
+            let left = (x as f32 * scale_x).ceil() as i32;
+            let top = (y as f32 * scale_y).ceil() as i32;
+            let right = ((x + width) as f32 * scale_x).floor() as i32;
+            let bottom = ((y + height) as f32 * scale_y).floor() as i32;
+            (left, top, right - left, bottom - top)
+        });
+
+        if region == self.opaque_region {
+            return;
+        }
+        self.opaque_region = region;
+
+        match region.and_then(|rect| Some((rect, Region::new(compositor).ok()?))) {
+            Some(((x, y, width, height), region)) => {
+                region.add(x, y, width, height);
+                self.role
+                    .wl_surface()
+                    .set_opaque_region(Some(region.wl_region()));
+            }
+            None => self.role.wl_surface().set_opaque_region(None),
+        }
+    }
+
+    /// Asks for the next frame callback, unless one is already coming.
+    fn request_frame(&mut self, qh: &QueueHandle<SimpleLayer>) {
+        if !self.pacing.is_pending() {
+            let surface = self.role.wl_surface();
+            surface.frame(qh, surface.clone());
+            self.pacing.requested();
+        }
+    }
+
+    /// An idle surface is invisible, so it shouldn't take clicks or keys meant for whatever is
+    /// under it either.
+    fn set_idle(&mut self, compositor: &CompositorState, idle: bool) {
+        let keyboard = if idle {
+            KeyboardInteractivity::None
+        } else {
+            self.keyboard
+        };
+        if let Role::Layer(layer) = &self.role {
+            if keyboard != self.sent_keyboard {
+                layer.set_keyboard_interactivity(keyboard);
+                self.sent_keyboard = keyboard;
+            }
+        }
+
+        if idle == self.idle {
+            return;
+        }
+        self.idle = idle;
+
+        if !idle {
+            self.role.wl_surface().set_input_region(None);
+        } else if let Ok(empty) = Region::new(compositor) {
+            self.role
+                .wl_surface()
+                .set_input_region(Some(empty.wl_region()));
+        }
+    }
+
+    /// The size to ask the compositor for, in surface coordinates.
+    fn requested_size(&self) -> (u32, u32) {
+        let pos = self.geometry();
+        let size = (
+            (pos.win_width() as f32 / self.scale).round() as u32,
+            (pos.win_height() as f32 / self.scale).round() as u32,
+        );
+        // Docked, the compositor stretches it along the edge.
+        match (self.docked, pos.edge) {
+            (false, _) => size,
+            (true, Anchor::TOP | Anchor::BOTTOM) => (0, size.1),
+            (true, _) => (size.0, 0),
+        }
+    }
+
+    /// Where the surface is anchored: the bar's corner of the output, or the whole edge when
+    /// docked.
+    fn anchor(&self) -> Anchor {
+        let pos = self.geometry();
+        match (self.docked, pos.edge) {
+            (false, _) => pos.edge | pos.close_to,
+            (true, Anchor::TOP | Anchor::BOTTOM) => pos.edge | Anchor::LEFT | Anchor::RIGHT,
+            (true, _) => pos.edge | Anchor::TOP | Anchor::BOTTOM,
+        }
+    }
+
+    /// The room kept for a docked surface, in surface coordinates: the bar's usual thickness,
+    /// so windows don't move when it escalates. Otherwise -1, so the bar is at the very edge of
+    /// the output rather than moved clear of panels, where notches and `get_state` expect it.
+    fn exclusive_zone(&self) -> i32 {
+        if self.docked {
+            (self.pos.thickness as f32 / self.scale).ceil() as i32
+        } else {
+            -1
+        }
+    }
+
+    /// Takes the size the compositor configured. Usually it's what was asked for, but it may be
+    /// another, e.g. when the output is smaller than the bar. The geometry is then fitted to that
+    /// size, clipping the bar or leaving room around it, so what's drawn matches what was acked.
+    fn set_configured_size(&mut self, width: u32, height: u32) {
+        let requested = self.requested_size();
+        // Zero leaves it to us.
+        let (width, height) = match (width, height) {
+            (0, 0) => requested,
+            (0, height) => (requested.0, height),
+            (width, 0) => (width, requested.1),
+            size => size,
+        };
+        self.width = width;
+        self.height = height;
+
+        self.shown = if (width, height) == requested {
+            self.geometry()
+        } else if self.docked {
+            // Whatever length the edge has.
+            self.geometry().fitted(
+                (width as f32 * self.scale).round() as u32,
+                (height as f32 * self.scale).round() as u32,
+            )
+        } else {
+            log::warn!(
+                "asked for a {}x{} surface but got {width}x{height}, fitting the bar to it",
+                requested.0,
+                requested.1
+            );
+            self.geometry().fitted(
+                (width as f32 * self.scale).round() as u32,
+                (height as f32 * self.scale).round() as u32,
+            )
+        };
+    }
+
+    fn update_size(&self) {
+        let Role::Layer(layer) = &self.role else {
+            // Lock surfaces are always the size of the output.
+            return;
+        };
+        let (width, height) = self.requested_size();
+
+        wayland_trace!(
+            "set_size",
+            surface = self.role.wl_surface().id(),
+            width = width,
+            height = height,
+            scale = self.scale,
+        );
+        layer.set_size(width, height);
+        layer.set_exclusive_zone(self.exclusive_zone());
+        layer.commit();
+    }
+}
+
+impl CompositorHandler for SimpleLayer {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        wayland_trace!("scale_factor", surface = surface.id(), factor = new_factor);
+
+        if self.scale_strategy() != ScaleStrategy::Integer {
+            return;
+        }
+        let Some(surface) = self
+            .surfaces
+            .iter_mut()
+            .find(|prompt| prompt.role.wl_surface() == surface && prompt.scale_override.is_none())
+        else {
+            return;
+        };
+
+        surface.scale = new_factor as f32;
+        surface.update_size();
+    }
+
+    fn transform_changed(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
+        surface: &wl_surface::WlSurface,
+        new_transform: wl_output::Transform,
     ) {
-        // Not needed for this example.
+        wayland_trace!(
+            "transform",
+            surface = surface.id(),
+            transform = format!("{new_transform:?}"),
+        );
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        time: u32,
+    ) {
+        if let Some(prompt) = self
+            .surfaces
+            .iter_mut()
+            .find(|prompt| prompt.role.wl_surface() == surface)
+        {
+            prompt.pacing.done(time);
+        }
+        self.draw(qh, surface);
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        wayland_trace!(
+            "enter",
+            surface = surface.id(),
+            output = self.output_name(output),
+        );
     }
 
     fn surface_leave(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
     ) {
-        // Not needed for this example.
+        wayland_trace!(
+            "leave",
+            surface = surface.id(),
+            output = self.output_name(output),
+        );
     }
 }
 
@@ -322,63 +1694,133 @@ impl OutputHandler for SimpleLayer {
     fn new_output(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         output: wl_output::WlOutput,
     ) {
-        if self
-            .output_state
-            .info(&output)
-            .and_then(|info| info.name)
-            .as_deref()
-            == Some("eDP-1")
-        {
-            // use this display?
-        }
+        self.trace_output("new_output", &output);
+        self.sync_surface(qh, &output);
+        self.pick_profile(None);
     }
 
     fn update_output(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
     ) {
+        self.trace_output("update_output", &output);
+        // Moved among the others, say, which the control socket tells where the bar is by.
+        self.rpc_changes += 1;
+        // A new mode changes how many pixels the millimeters in the geometry are.
+        self.sync_surface(qh, &output);
     }
 
     fn output_destroyed(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
+        wayland_trace!("output_destroyed", output = self.output_name(&output));
+        self.surfaces.retain(|surface| surface.output != output);
+        self.pick_profile(Some(&output));
     }
 }
 
 impl LayerShellHandler for SimpleLayer {
-    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
-        self.exit = true;
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, layer: &LayerSurface) {
+        wayland_trace!("closed", surface = layer.wl_surface().id());
+        // Usually because the output went away. Another surface is made if it comes back.
+        self.surfaces
+            .retain(|surface| surface.role.wl_surface() != layer.wl_surface());
     }
 
     fn configure(
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        _layer: &LayerSurface,
+        layer: &LayerSurface,
         configure: LayerSurfaceConfigure,
         _serial: u32,
     ) {
-        if configure.new_size.0 == 0 || configure.new_size.1 == 0 {
-            self.width = 256;
-            self.height = 256;
-        } else {
-            self.width = configure.new_size.0;
-            self.height = configure.new_size.1;
-        }
+        wayland_trace!(
+            "configure",
+            surface = layer.wl_surface().id(),
+            width = configure.new_size.0,
+            height = configure.new_size.1,
+        );
+
+        let Some(surface) = self
+            .surfaces
+            .iter_mut()
+            .find(|surface| surface.role.wl_surface() == layer.wl_surface())
+        else {
+            return;
+        };
+
+        // The configure has been acked, so the next commit must match it. The new geometry is
+        // drawn from here on, with the buffer and its destination size committed together.
+        surface.set_configured_size(configure.new_size.0, configure.new_size.1);
+        let first_configure = std::mem::take(&mut surface.first_configure);
+        self.reserve_pool();
 
         // Initiate the first draw.
-        if self.first_configure {
-            self.first_configure = false;
+        if first_configure {
+            self.draw(qh, &layer.wl_surface().clone());
+        }
+    }
+}
 
-            self.draw(qh);
+impl SessionLockHandler for SimpleLayer {
+    fn locked(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, _lock: SessionLock) {
+        log::info!("Locked the session");
+        self.sync_surfaces(qh);
+    }
+
+    fn finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, lock: SessionLock) {
+        if lock.is_locked() {
+            log::warn!("the compositor took back the session lock");
+        } else {
+            log::error!("the compositor refused to lock the session, is another locker running?");
+        }
+        self.surfaces.clear();
+        self.session_lock = None;
+        self.exit = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        lock_surface: SessionLockSurface,
+        configure: SessionLockSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let (width, height) = configure.new_size;
+        wayland_trace!(
+            "configure",
+            surface = lock_surface.wl_surface().id(),
+            width = width,
+            height = height,
+        );
+
+        let Some(surface) = self
+            .surfaces
+            .iter_mut()
+            .find(|surface| surface.role.wl_surface() == lock_surface.wl_surface())
+        else {
+            return;
+        };
+
+        surface.width = width;
+        surface.height = height;
+        surface.covering = Some((width, height));
+        surface.shown = surface.geometry();
+        let first_configure = std::mem::take(&mut surface.first_configure);
+        self.reserve_pool();
+
+        if first_configure {
+            self.draw(qh, &lock_surface.wl_surface().clone());
         }
     }
 }
@@ -397,22 +1839,39 @@ impl SeatHandler for SimpleLayer {
         seat: wl_seat::WlSeat,
         capability: Capability,
     ) {
-        if capability == Capability::Keyboard && self.keyboard.is_none() {
-            println!("Set keyboard capability");
-            let keyboard = self
-                .seat_state
-                .get_keyboard(qh, &seat, None)
-                .expect("Failed to create keyboard");
-            self.keyboard = Some(keyboard);
+        if !self.is_our_seat(&seat) {
+            return;
+        }
+
+        let index = match self.seats.iter().position(|input| input.seat == seat) {
+            Some(index) => index,
+            None => {
+                self.seats.push(SeatInput {
+                    seat: seat.clone(),
+                    keyboard: None,
+                    keyboard_focus: false,
+                    pointer: None,
+                    pressed_at: None,
+                });
+                self.seats.len() - 1
+            }
+        };
+        let input = &mut self.seats[index];
+
+        if capability == Capability::Keyboard && input.keyboard.is_none() {
+            log::info!("Set keyboard capability");
+            match self.seat_state.get_keyboard(qh, &seat, None) {
+                Ok(keyboard) => input.keyboard = Some(keyboard),
+                Err(e) => log::warn!("could not get the keyboard: {e}"),
+            }
         }
 
-        if capability == Capability::Pointer && self.pointer.is_none() {
-            println!("Set pointer capability");
-            let pointer = self
-                .seat_state
-                .get_pointer(qh, &seat)
-                .expect("Failed to create pointer");
-            self.pointer = Some(pointer);
+        if capability == Capability::Pointer && input.pointer.is_none() {
+            log::info!("Set pointer capability");
+            match self.seat_state.get_pointer(qh, &seat) {
+                Ok(pointer) => input.pointer = Some(pointer),
+                Err(e) => log::warn!("could not get the pointer: {e}"),
+            }
         }
     }
 
@@ -420,21 +1879,34 @@ impl SeatHandler for SimpleLayer {
         &mut self,
         _conn: &Connection,
         _: &QueueHandle<Self>,
-        _: wl_seat::WlSeat,
+        seat: wl_seat::WlSeat,
         capability: Capability,
     ) {
-        if capability == Capability::Keyboard && self.keyboard.is_some() {
-            println!("Unset keyboard capability");
-            self.keyboard.take().unwrap().release();
+        let Some(input) = self.seats.iter_mut().find(|input| input.seat == seat) else {
+            return;
+        };
+
+        if capability == Capability::Keyboard && input.keyboard.is_some() {
+            log::info!("Unset keyboard capability");
+            input.release_keyboard();
         }
 
-        if capability == Capability::Pointer && self.pointer.is_some() {
-            println!("Unset pointer capability");
-            self.pointer.take().unwrap().release();
+        if capability == Capability::Pointer && input.pointer.is_some() {
+            log::info!("Unset pointer capability");
+            input.release_pointer();
         }
     }
 
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        let Some(index) = self.seats.iter().position(|input| input.seat == seat) else {
+            return;
+        };
+
+        log::info!("Seat removed");
+        let mut input = self.seats.remove(index);
+        input.release_keyboard();
+        input.release_pointer();
+    }
 }
 
 impl KeyboardHandler for SimpleLayer {
@@ -442,15 +1914,15 @@ impl KeyboardHandler for SimpleLayer {
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
-        _: &wl_keyboard::WlKeyboard,
+        keyboard: &wl_keyboard::WlKeyboard,
         surface: &wl_surface::WlSurface,
         _: u32,
         _: &[u32],
         keysyms: &[Keysym],
     ) {
-        if self.layer.wl_surface() == surface {
-            println!("Keyboard focus on window with pressed syms: {keysyms:?}");
-            self.keyboard_focus = true;
+        if self.is_prompt_surface(surface) {
+            log::info!("Keyboard focus on window with pressed syms: {keysyms:?}");
+            self.set_keyboard_focus(keyboard, true);
         }
     }
 
@@ -458,13 +1930,13 @@ impl KeyboardHandler for SimpleLayer {
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
-        _: &wl_keyboard::WlKeyboard,
+        keyboard: &wl_keyboard::WlKeyboard,
         surface: &wl_surface::WlSurface,
         _: u32,
     ) {
-        if self.layer.wl_surface() == surface {
-            println!("Release keyboard focus on window");
-            self.keyboard_focus = false;
+        if self.is_prompt_surface(surface) {
+            log::info!("Release keyboard focus on window");
+            self.set_keyboard_focus(keyboard, false);
         }
     }
 
@@ -476,7 +1948,13 @@ impl KeyboardHandler for SimpleLayer {
         _: u32,
         event: KeyEvent,
     ) {
-        println!("Key press: {event:?}");
+        // Lock surfaces get the keyboard. Whatever is typed there stays private, and can't close
+        // the lock.
+        if self.session_lock.is_some() {
+            return;
+        }
+
+        log::info!("Key press: {event:?}");
         // press 'esc' to exit
         if event.keysym == Keysym::Escape {
             self.exit = true;
@@ -491,7 +1969,9 @@ impl KeyboardHandler for SimpleLayer {
         _: u32,
         event: KeyEvent,
     ) {
-        println!("Key release: {event:?}");
+        if self.session_lock.is_none() {
+            log::info!("Key release: {event:?}");
+        }
     }
 
     fn update_modifiers(
@@ -503,7 +1983,7 @@ impl KeyboardHandler for SimpleLayer {
         modifiers: Modifiers,
         _layout: u32,
     ) {
-        println!("Update modifiers: {modifiers:?}");
+        log::info!("Update modifiers: {modifiers:?}");
     }
 }
 
@@ -511,37 +1991,54 @@ impl PointerHandler for SimpleLayer {
     fn pointer_frame(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _pointer: &wl_pointer::WlPointer,
+        qh: &QueueHandle<Self>,
+        pointer: &wl_pointer::WlPointer,
         events: &[PointerEvent],
     ) {
         use PointerEventKind::*;
         for event in events {
             // Ignore events for other surfaces
-            if &event.surface != self.layer.wl_surface() {
+            if !self.is_prompt_surface(&event.surface) {
                 continue;
             }
             match event.kind {
                 Enter { .. } => {
-                    println!("Pointer entered @{:?}", event.position);
+                    log::info!("Pointer entered @{:?}", event.position);
                 }
                 Leave { .. } => {
-                    println!("Pointer left");
+                    log::info!("Pointer left");
                 }
                 Motion { .. } => {}
                 Press { button, .. } => {
-                    println!("Press {:x} @ {:?}", button, event.position);
-                    self.shift = self.shift.xor(Some(0));
+                    log::info!("Press {:x} @ {:?}", button, event.position);
+                    if let Some(input) = self.seat_input(pointer) {
+                        input.pressed_at = Some(event.position);
+                    }
                 }
                 Release { button, .. } => {
-                    println!("Release {:x} @ {:?}", button, event.position);
+                    log::info!("Release {:x} @ {:?}", button, event.position);
+                    let pressed_at = self
+                        .seat_input(pointer)
+                        .and_then(|input| input.pressed_at.take());
+                    if pressed_at.is_some_and(|from| {
+                        self.is_swipe_to_edge(&event.surface, from, event.position)
+                    }) {
+                        log::info!("Swiped into the edge, pausing");
+                        self.set_paused(qh, true);
+                    }
                 }
-                Axis {
-                    horizontal,
-                    vertical,
-                    ..
-                } => {
-                    println!("Scroll H:{horizontal:?}, V:{vertical:?}");
+                Axis { vertical, .. } => {
+                    // Scrolling down fades the prompt, up brings it back.
+                    let steps = if vertical.discrete != 0 {
+                        vertical.discrete as f32
+                    } else {
+                        vertical.absolute as f32 / SCROLL_STEP_PIXELS
+                    };
+                    let opacity = (self.opacity - steps * OPACITY_STEP).clamp(MIN_OPACITY, 1.);
+                    if opacity != self.opacity {
+                        self.opacity = opacity;
+                        log::info!("Opacity: {:.0}%", opacity * 100.);
+                    }
                 }
             }
         }
@@ -572,6 +2069,120 @@ impl Dispatch<WpViewport, ()> for SimpleLayer {
     }
 }
 
+impl Dispatch<ZwpIdleInhibitManagerV1, ()> for SimpleLayer {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpIdleInhibitManagerV1,
+        _event: zwp_idle_inhibit_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpIdleInhibitorV1, ()> for SimpleLayer {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpIdleInhibitorV1,
+        _event: zwp_idle_inhibitor_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for SimpleLayer {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrForeignToplevelManagerV1,
+        _event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+
+    event_created_child!(SimpleLayer, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for SimpleLayer {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        state.toplevels.handle(proxy, event);
+    }
+}
+
+impl Dispatch<WpPresentation, ()> for SimpleLayer {
+    fn event(
+        state: &mut Self,
+        _proxy: &WpPresentation,
+        event: wp_presentation::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wp_presentation::Event::ClockId { clk_id } = event {
+            state.presentation_clock = Some(clk_id);
+        }
+    }
+}
+
+impl Dispatch<WpPresentationFeedback, presentation::Committed> for SimpleLayer {
+    fn event(
+        state: &mut Self,
+        _proxy: &WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        committed: &presentation::Committed,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wp_presentation_feedback::Event::Presented {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+                refresh,
+                ..
+            } => {
+                let shown = Duration::new(((tv_sec_hi as u64) << 32) | tv_sec_lo as u64, tv_nsec);
+                let since_commit = shown.saturating_sub(committed.at);
+                let since_signal = committed
+                    .since_signal
+                    .map(|since_signal| since_signal + since_commit);
+                state.latency.presented(since_commit, since_signal);
+                // One for every frame would push everything else out of a crash report.
+                if trace::enabled() {
+                    wayland_trace!(
+                        "presented",
+                        surface = committed.surface,
+                        commit_to_present_us = since_commit.as_micros(),
+                        signal_to_present_us = since_signal
+                            .map_or("-".to_string(), |since| since.as_micros().to_string()),
+                        refresh_ns = refresh,
+                    );
+                }
+            }
+            wp_presentation_feedback::Event::Discarded => {
+                state.latency.discarded();
+                if trace::enabled() {
+                    wayland_trace!("discarded", surface = committed.surface);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
 impl Dispatch<WpFractionalScaleManagerV1, ()> for SimpleLayer {
     fn event(
         _state: &mut Self,
@@ -585,25 +2196,29 @@ impl Dispatch<WpFractionalScaleManagerV1, ()> for SimpleLayer {
     }
 }
 
-impl Dispatch<WpFractionalScaleV1, ()> for SimpleLayer {
+impl Dispatch<WpFractionalScaleV1, wl_surface::WlSurface> for SimpleLayer {
     fn event(
         state: &mut Self,
         _proxy: &WpFractionalScaleV1,
         event: wp_fractional_scale_v1::Event,
-        _data: &(),
+        surface: &wl_surface::WlSurface,
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
         if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event {
-            state.scale = scale as f32 / 120.;
-            state.layer.set_size(
-                state.pos.win_width() * 120 / scale,
-                state.pos.win_height() * 120 / scale,
-            );
-            state.viewport.set_destination(
-                (state.pos.win_width() * 120 / scale) as i32,
-                (state.pos.win_height() * 120 / scale) as i32,
+            wayland_trace!(
+                "preferred_scale",
+                surface = surface.id(),
+                scale = scale as f32 / 120.,
             );
+            let Some(surface) = state.surfaces.iter_mut().find(|prompt| {
+                prompt.role.wl_surface() == surface && prompt.scale_override.is_none()
+            }) else {
+                return;
+            };
+
+            surface.scale = scale as f32 / 120.;
+            surface.update_size();
         }
     }
 }
@@ -615,63 +2230,1117 @@ impl ShmHandler for SimpleLayer {
 }
 
 impl SimpleLayer {
-    pub fn draw(&mut self, qh: &QueueHandle<Self>) {
-        let width = self.pos.win_width();
-        let height = self.pos.win_height();
-        let stride = width as i32 * 4;
+    fn create_surface(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        output: &wl_output::WlOutput,
+        pos: PositionInfo,
+        state_sizes: config::StateSizes,
+    ) {
+        let lock = self.session_lock.as_ref().filter(|lock| lock.is_locked());
+        if lock.is_none() && self.layer_shell.is_none() {
+            return;
+        }
+        let scale_override = self.scale_override(output);
+        if let Some(scale) = scale_override {
+            log::info!(
+                "Using scale {scale} on {} instead of the compositor's",
+                self.output_name(output)
+            );
+        }
 
-        let (buffer, canvas) = self
-            .pool
-            .create_buffer(
-                width as i32,
-                height as i32,
-                stride,
-                wl_shm::Format::Argb8888,
-            )
-            .expect("create buffer");
-
-        // Draw to the window:
-        if self.prompt.is_some() {
-            let shift = self.shift.unwrap_or(0);
-            canvas
-                .chunks_exact_mut(4)
-                .enumerate()
-                .for_each(|(index, chunk)| {
-                    let _x = ((index + shift as usize) % width as usize) as u32;
-                    let y = (index / width as usize) as u32;
-
-                    let color: u32 = if y < self.pos.offset { 0x0 } else { 0xFFFFFFFF };
-
-                    let array: &mut [u8; 4] = chunk.try_into().unwrap();
-                    *array = color.to_le_bytes();
-                });
+        // A layer surface is created from a surface.
+        let surface = self.compositor.create_surface(qh);
+
+        let fractional_scale = self
+            .fractional_scale_manager
+            .as_ref()
+            .filter(|_| self.scale_strategy() == ScaleStrategy::Fractional)
+            .map(|fsm| fsm.get_fractional_scale(&surface, qh, surface.clone()));
+        let viewport = self.viewporter.get_viewport(&surface, qh, ());
+
+        // And then we create the layer shell, or the lock surface while we hold the lock.
+        let role = match (lock, &self.layer_shell) {
+            (Some(lock), _) => Role::Lock(lock.create_lock_surface(surface, output, qh)),
+            (None, Some(layer_shell)) => Role::Layer(layer_shell.create_layer_surface(
+                qh,
+                surface,
+                self.quirks.layer,
+                Some(self.quirks.namespace.clone()),
+                Some(output),
+            )),
+            (None, None) => unreachable!("checked above"),
+        };
+
+        let mut prompt_surface = PromptSurface {
+            output: output.clone(),
+            role,
+            viewport,
+            _fractional_scale: fractional_scale,
+
+            first_configure: true,
+            pacing: pacing::FramePacing::default(),
+            width: 0,
+            height: 0,
+            shown: pos.clone(),
+            ruler: self.ruler_for(output, &pos),
+            covering: None,
+            docked: self.config.dock.enabled && lock.is_none(),
+            pos,
+            scale: scale_override.unwrap_or(1.),
+            scale_override,
+            escalated: None,
+            state_sizes,
+            shadow: self.config.shadow,
+            resize: None,
+            handoff: animation::Handoff::default(),
+            opaque_region: None,
+            idle: false,
+            idle_inhibitor: None,
+            keyboard: self.config.keyboard,
+            sent_keyboard: KeyboardInteractivity::None,
+            buffers: Buffers::default(),
+            drawn: None,
+        };
+        let geometry = prompt_surface.geometry();
+        prompt_surface.width = geometry.win_width();
+        prompt_surface.height = geometry.win_height();
+        prompt_surface.shown = geometry;
+
+        // Lock surfaces are configured to the output's size without asking.
+        let Role::Layer(layer) = &prompt_surface.role else {
+            self.surfaces.push(prompt_surface);
+            return;
+        };
+
+        // Configure the layer surface, providing things like the anchor on screen, desired size and the keyboard
+        // interactivity
+        layer.set_anchor(prompt_surface.anchor());
+        layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+        let (width, height) = prompt_surface.requested_size();
+        layer.set_size(width, height);
+        layer.set_exclusive_zone(prompt_surface.exclusive_zone());
+
+        // In order for the layer surface to be mapped, we need to perform an initial commit with no attached
+        // buffer. For more info, see WaylandSurface::commit
+        //
+        // The compositor will respond with an initial configure that we can then use to present to the layer
+        // surface with the correct options.
+        layer.commit();
+
+        self.surfaces.push(prompt_surface);
+    }
 
-            if let Some(shift) = &mut self.shift {
-                *shift = (*shift + 1) % width;
+    /// The scale strategy from the quirks, unless the compositor can't do it.
+    fn scale_strategy(&self) -> ScaleStrategy {
+        match self.quirks.scale {
+            ScaleStrategy::Fractional if self.fractional_scale_manager.is_none() => {
+                ScaleStrategy::Integer
             }
+            scale => scale,
+        }
+    }
+
+    /// The scale the config sets for an output, if any.
+    fn scale_override(&self, output: &wl_output::WlOutput) -> Option<f32> {
+        let name = self.output_state.info(output).and_then(|info| info.name);
+        self.config.scale_override_for(name.as_deref())
+    }
+
+    fn output_name(&self, output: &wl_output::WlOutput) -> String {
+        self.output_state
+            .info(output)
+            .and_then(|info| info.name)
+            .unwrap_or_else(|| output.id().to_string())
+    }
+
+    fn trace_output(&self, event: &str, output: &wl_output::WlOutput) {
+        let Some(info) = self.output_state.info(output) else {
+            return;
+        };
+        let mode = info.modes.iter().find(|mode| mode.current);
+
+        wayland_trace!(
+            event,
+            output = self.output_name(output),
+            mode = mode.map_or("?".to_string(), |mode| format!(
+                "{}x{}",
+                mode.dimensions.0, mode.dimensions.1
+            )),
+            transform = format!("{:?}", info.transform),
+            scale = info.scale_factor,
+            logical_position = format!("{:?}", info.logical_position),
+            logical_size = format!("{:?}", info.logical_size),
+        );
+    }
+
+    /// The ruler's geometry on an output, with `--show-ruler`.
+    fn ruler_for(&self, output: &wl_output::WlOutput, pos: &PositionInfo) -> Option<PositionInfo> {
+        if !self.show_ruler {
+            return None;
+        }
+        ruler::geometry(pos, &self.output_state.info(output)?)
+    }
+
+    /// The geometry and state sizes for an output, shrunk with a warning when they'd cover more of
+    /// it than `max_area`, so a typo can't put a bar over the whole desktop.
+    fn limit_area(
+        &self,
+        output: &wl_output::WlOutput,
+        pos: PositionInfo,
+    ) -> (PositionInfo, config::StateSizes) {
+        let sizes = self.config.state_sizes;
+        let limited = self
+            .output_state
+            .info(output)
+            .and_then(|info| ruler::output_size(&pos, &info))
+            .and_then(|size| pos.limited(&sizes, size, self.config.max_area));
+        match limited {
+            Some((limited, limited_sizes)) => {
+                log::warn!(
+                    "The geometry doesn't fit on {} or covers more than max_area = {} of it, so \
+                     the bar is shrunk to {}x{} at offset {}",
+                    self.output_name(output),
+                    self.config.max_area,
+                    limited.length,
+                    limited.thickness,
+                    limited.offset,
+                );
+                (limited, limited_sizes)
+            }
+            None => (pos, sizes),
+        }
+    }
+
+    /// Creates, updates or removes the surface on an output to match the config.
+    fn sync_surface(&mut self, qh: &QueueHandle<Self>, output: &wl_output::WlOutput) {
+        let name = self.output_state.info(output).and_then(|info| info.name);
+        let pos = match &self.session_lock {
+            // Every output needs a lock surface once we hold the lock, prompt or not.
+            Some(lock) => lock.is_locked().then(|| {
+                self.config
+                    .position_for(name.as_deref())
+                    .unwrap_or_else(|| self.config.position.clone())
+            }),
+            None => self.config.position_for(name.as_deref()).filter(|_| {
+                (self.config.sinks.contains(&Sink::Overlay) || self.show_ruler)
+                    && self.paused.is_none()
+                    && self.is_our_output(output)
+            }),
+        }
+        .map(|pos| {
+            let pixels_per_mm = self
+                .output_state
+                .info(output)
+                .and_then(|info| ruler::pixels_per_mm(&pos, &info));
+            if pixels_per_mm.is_none() && pos.millimeters != Millimeters::default() {
+                log::warn!(
+                    "{} doesn't say how big it is, so the geometry is in pixels there",
+                    self.output_name(output)
+                );
+            }
+            pos.in_pixels(pixels_per_mm)
+        })
+        .map(|pos| self.limit_area(output, pos))
+        .map(|(pos, state_sizes)| {
+            if self.locked {
+                (pos.lock_screen_layout(), state_sizes)
+            } else {
+                (pos, state_sizes)
+            }
+        });
+
+        let existing = self
+            .surfaces
+            .iter()
+            .position(|surface| &surface.output == output);
+
+        match (existing, pos) {
+            (None, Some((pos, state_sizes))) => self.create_surface(qh, output, pos, state_sizes),
+            // The compositor's scale only comes again when it changes, so it's easiest to start
+            // over with it or without it.
+            (Some(index), Some((pos, state_sizes)))
+                if self.surfaces[index].scale_override != self.scale_override(output) =>
+            {
+                self.surfaces.remove(index);
+                self.create_surface(qh, output, pos, state_sizes);
+            }
+            (Some(index), Some((pos, state_sizes))) => {
+                let ruler = self.ruler_for(output, &pos);
+                let surface = &mut self.surfaces[index];
+                surface.pos = pos;
+                surface.ruler = ruler;
+                if let Role::Layer(layer) = &surface.role {
+                    layer.set_anchor(surface.anchor());
+                }
+                surface.state_sizes = state_sizes;
+                surface.shadow = self.config.shadow;
+                surface.keyboard = self.config.keyboard;
+                surface.update_size();
+            }
+            (Some(index), None) => {
+                self.surfaces.remove(index);
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// Picks up changes from a reloaded config.
+    fn apply_config(&mut self, qh: &QueueHandle<Self>, config: config::Config) {
+        // The profile might be another.
+        self.rpc_changes += 1;
+        // Docking changes the layer and anchors, so it's easiest to start over.
+        if config.dock.enabled != self.config.dock.enabled {
+            self.surfaces.clear();
+        }
+        self.config = config;
+        crash::set_reports(self.config.crash_reports);
+        self.reader_quirks.clear();
+
+        self.update_quirks();
+        self.night_light.invalidate();
+        self.sync_surfaces(qh);
+    }
+
+    /// Picks the quirks for the config and lock state. The layer and namespace can only be set
+    /// when creating a surface, so the surfaces are recreated if they change.
+    fn update_quirks(&mut self) {
+        let mut quirks = quirks::Quirks::new(self.compositor_kind, &self.config.quirks);
+        if self.locked {
+            // Anything lower is certainly under the lock screen.
+            quirks.layer = Layer::Overlay;
+        } else if self.config.dock.enabled && self.config.quirks.layer.is_none() {
+            // Like a panel, under fullscreen windows.
+            quirks.layer = Layer::Bottom;
+        }
+
+        if quirks != self.quirks {
+            self.quirks = quirks;
+            self.surfaces.clear();
+        }
+    }
+
+    /// Gives up the session lock taken with `--lock`.
+    fn unlock(&mut self) {
+        if let Some(lock) = self.session_lock.take() {
+            log::info!("Unlocking the session");
+            lock.unlock();
+        }
+        self.surfaces.clear();
+    }
+
+    /// Switches to or from the lock screen layout.
+    fn set_locked(&mut self, qh: &QueueHandle<Self>, locked: bool) {
+        if locked == self.locked {
+            return;
+        }
+        log::info!("Screen {}", if locked { "locked" } else { "unlocked" });
+
+        self.locked = locked;
+        self.update_quirks();
+        self.sync_surfaces(qh);
+    }
+
+    fn toggle_paused(&mut self, qh: &QueueHandle<Self>) {
+        self.set_paused(qh, self.paused.is_none());
+    }
+
+    /// Pauses or resumes the overlay. On resuming, the last verification that finished while
+    /// paused is shown again.
+    fn set_paused(&mut self, qh: &QueueHandle<Self>, paused: bool) {
+        match (paused, self.paused.take()) {
+            (true, pause) => self.paused = Some(pause.unwrap_or_default()),
+            (false, Some(pause)) => {
+                if !pause.outcomes.is_empty() {
+                    let matched = pause
+                        .outcomes
+                        .iter()
+                        .filter(|outcome| **outcome == Outcome::Success)
+                        .count();
+                    log::info!(
+                        "While paused, {matched} of {} verifications matched",
+                        pause.outcomes.len()
+                    );
+                    self.prompts.replay_outcome();
+                }
+            }
+            (false, None) => return,
+        }
+
+        self.sync_surfaces(qh);
+    }
+
+    /// Picks the first profile whose rule matches the connected outputs, besides `gone`, or none of
+    /// them. Only done when outputs change, so a profile switched to by hand stays until then.
+    fn pick_profile(&mut self, gone: Option<&wl_output::WlOutput>) {
+        if self.config.profile_rules.is_empty() {
+            return;
+        }
+
+        let names: Vec<String> = self
+            .output_state
+            .outputs()
+            .filter(|output| Some(output) != gone)
+            .filter_map(|output| self.output_state.info(&output)?.name)
+            .collect();
+        let wanted = self
+            .config
+            .profile_rules
+            .iter()
+            .find(|rule| names.contains(&rule.outputs_include))
+            .map(|rule| rule.profile.clone());
+
+        if wanted != self.config.profile {
+            self.profile_switch = Some(wanted);
+        }
+    }
+
+    fn sync_surfaces(&mut self, qh: &QueueHandle<Self>) {
+        for output in self.output_state.outputs().collect::<Vec<_>>() {
+            self.sync_surface(qh, &output);
+        }
+    }
+
+    /// Keeps the screen from dimming or locking while a verification waits for a finger. The
+    /// compositor only honours an inhibitor while its surface is visible, so each surface has one.
+    fn update_idle_inhibit(&mut self, qh: &QueueHandle<Self>) {
+        let Some(manager) = &self.idle_inhibit_manager else {
+            return;
+        };
+        let inhibit = self.config.inhibit_idle
+            && self.paused.is_none()
+            && self
+                .prompts
+                .indicators()
+                .any(|kind| kind == SessionKind::Verify);
+
+        for surface in &mut self.surfaces {
+            match (&surface.idle_inhibitor, inhibit) {
+                (None, true) => {
+                    surface.idle_inhibitor =
+                        Some(manager.create_inhibitor(surface.role.wl_surface(), qh, ()));
+                }
+                (Some(inhibitor), false) => {
+                    inhibitor.destroy();
+                    surface.idle_inhibitor = None;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Grows the bar while a verification is being ignored, and shrinks it back once it isn't.
+    fn update_escalation(&mut self) {
+        let attention = &self.config.attention;
+        let escalated = (attention.escalate_after > 0
+            && self.ignored_for().is_some_and(|ignored| {
+                ignored > Duration::from_secs(attention.escalate_after.into())
+            }))
+        .then_some(attention.escalated_thickness);
+
+        for surface in &mut self.surfaces {
+            if surface.escalated != escalated {
+                surface.escalated = escalated;
+                surface.update_size();
+            }
+        }
+    }
+
+    /// Picks the `[app]` settings for a verification that started with no other prompt up, by the
+    /// window with focus. Once picked, they're kept until every prompt is over.
+    fn pick_app(&mut self) {
+        if self.config.apps.is_empty() {
+            return;
+        }
+
+        let app = self
+            .toplevels
+            .focused()
+            .filter(|app| self.config.apps.iter().any(|name| name == app));
+        if app != self.config.app.as_deref() {
+            self.app_switch = Some(app.map(str::to_string));
+        }
+    }
+
+    /// How long a verification has gone without the reader being touched, on readers that say
+    /// when they are.
+    fn ignored_for(&self) -> Option<Duration> {
+        self.prompts
+            .ignored_for(|device| self.quirks_of(device).retries)
+    }
+
+    /// What the sinks show.
+    fn view(&self) -> sinks::View<'_> {
+        sinks::View {
+            prompts: &self.prompts,
+            config: &self.config,
+            paused: self.paused.is_some(),
+            ignored_for: self.ignored_for(),
+        }
+    }
+
+    /// What the state for the control socket is built from.
+    fn rpc_version(&self) -> ipc::socket::Version {
+        ipc::socket::Version {
+            visible: self.paused.is_none(),
+            prompts: self.prompts.generation(),
+            surfaces: self.rpc_changes,
+            surface_count: self.surfaces.len(),
+        }
+    }
+
+    /// The state for the control socket.
+    fn rpc_state(&self) -> rpc::State {
+        rpc::State {
+            visible: self.paused.is_none(),
+            profile: self.config.profile.clone(),
+            prompts: self
+                .prompts
+                .sessions()
+                .iter()
+                .map(|session| rpc::Prompt {
+                    kind: match session.kind {
+                        SessionKind::Verify => rpc::PromptKind::Verify,
+                        SessionKind::Enroll => rpc::PromptKind::Enroll,
+                    },
+                    text: session.text.clone(),
+                    device: session.device.to_string(),
+                })
+                .collect(),
+            surfaces: self
+                .surfaces
+                .iter()
+                .filter_map(|surface| self.rpc_surface(surface))
+                .collect(),
+        }
+    }
+
+    /// Where the bar was last drawn on a surface, in the compositor's logical coordinates, which
+    /// xdg-output tells for each output.
+    fn rpc_surface(&self, surface: &PromptSurface) -> Option<rpc::Surface> {
+        let frame = surface.drawn.as_ref()?;
+        let info = self.output_state.info(&surface.output)?;
+        let (output_x, output_y) = info.logical_position?;
+        let (output_width, output_height) = info.logical_size?;
+
+        // Where the surface is on the output, from its anchor. Lock surfaces cover all of it.
+        let anchor = match surface.role {
+            Role::Lock(_) => Anchor::all(),
+            Role::Layer(_) => surface.anchor(),
+        };
+        let place = |start: Anchor, end: Anchor, output: i32, size: u32| match (
+            anchor.contains(start),
+            anchor.contains(end),
+        ) {
+            (true, _) => 0,
+            (false, true) => output - size as i32,
+            (false, false) => (output - size as i32) / 2,
+        };
+        let surface_x = place(Anchor::LEFT, Anchor::RIGHT, output_width, surface.width);
+        let surface_y = place(Anchor::TOP, Anchor::BOTTOM, output_height, surface.height);
+
+        // The bar within the surface, from buffer pixels.
+        let (x, y, width, height) = frame.pos.bar_rect(frame.bar);
+        let scale_x = surface.width as f32 / frame.pos.win_width().max(1) as f32;
+        let scale_y = surface.height as f32 / frame.pos.win_height().max(1) as f32;
+        Some(rpc::Surface {
+            output: self.output_name(&surface.output),
+            x: output_x + surface_x + (x as f32 * scale_x).round() as i32,
+            y: output_y + surface_y + (y as f32 * scale_y).round() as i32,
+            width: (width as f32 * scale_x).round() as u32,
+            height: (height as f32 * scale_y).round() as u32,
+            drawn: !frame.colors.is_empty() || frame.idle_color.is_some(),
+        })
+    }
+
+    /// The quirks of a reader, or the defaults for one that hasn't prompted yet.
+    fn quirks_of(&self, device: &Path) -> ReaderQuirks {
+        self.reader_quirks.get(device).copied().unwrap_or_default()
+    }
+
+    /// Grows the pool up front to fit every surface at its current size, so a resize or scale
+    /// change doesn't have to grow it in the middle of drawing a frame.
+    fn reserve_pool(&mut self) {
+        // Each surface can have a buffer held by the compositor while the next one is drawn.
+        let needed: usize = self
+            .surfaces
+            .iter()
+            .map(|surface| {
+                surface.shown.win_width() as usize * surface.shown.win_height() as usize * 4 * 2
+            })
+            .sum();
+
+        if needed > self.pool.len() {
+            if let Err(e) = self.pool.resize(needed) {
+                log::warn!("could not grow the buffer pool to {needed} bytes: {e}");
+            }
+        }
+    }
+
+    /// Argb8888 if the compositor has it, otherwise Xrgb8888, which has the same layout but
+    /// ignores alpha: transparent parts are drawn black and flashes dim instead of fading.
+    fn pixel_format(&self) -> wl_shm::Format {
+        let formats = self.shm.formats();
+
+        if !formats.contains(&wl_shm::Format::Argb8888)
+            && formats.contains(&wl_shm::Format::Xrgb8888)
+        {
+            wl_shm::Format::Xrgb8888
         } else {
-            canvas.iter_mut().for_each(|pixel| *pixel = 0);
+            wl_shm::Format::Argb8888
+        }
+    }
+
+    fn set_keyboard_focus(&mut self, keyboard: &wl_keyboard::WlKeyboard, focus: bool) {
+        if let Some(input) = self
+            .seats
+            .iter_mut()
+            .find(|input| input.keyboard.as_ref() == Some(keyboard))
+        {
+            input.keyboard_focus = focus;
+        }
+    }
+
+    fn seat_input(&mut self, pointer: &wl_pointer::WlPointer) -> Option<&mut SeatInput> {
+        self.seats
+            .iter_mut()
+            .find(|input| input.pointer.as_ref() == Some(pointer))
+    }
+
+    /// Whether a drag from `from` to `to` pushed the bar into the screen edge it's on: towards the
+    /// edge, and let go right at it. Lock surfaces can't be swiped away.
+    fn is_swipe_to_edge(
+        &self,
+        surface: &wl_surface::WlSurface,
+        from: (f64, f64),
+        to: (f64, f64),
+    ) -> bool {
+        let Some(prompt) = self
+            .surfaces
+            .iter()
+            .find(|prompt| prompt.role.wl_surface() == surface && prompt.covering.is_none())
+        else {
+            return false;
+        };
+
+        let (width, height) = (prompt.width as f64, prompt.height as f64);
+        let from_edge = |(x, y): (f64, f64)| match prompt.shown.edge {
+            Anchor::TOP => y,
+            Anchor::BOTTOM => height - y,
+            Anchor::LEFT => x,
+            _ => width - x,
+        };
+
+        from_edge(from) - from_edge(to) >= SWIPE_DISTANCE && from_edge(to) <= SWIPE_EDGE
+    }
+
+    fn is_prompt_surface(&self, surface: &wl_surface::WlSurface) -> bool {
+        self.surfaces
+            .iter()
+            .any(|prompt| prompt.role.wl_surface() == surface)
+    }
+
+    /// Outputs on other seats' GPUs are left alone. Outputs we can't place (e.g. in a nested
+    /// compositor) are assumed to be ours.
+    fn is_our_output(&self, output: &wl_output::WlOutput) -> bool {
+        let Some(name) = self.output_state.info(output).and_then(|info| info.name) else {
+            return true;
+        };
+
+        session::output_seat(&name).is_none_or(|seat| seat == self.seat_id)
+    }
+
+    /// Compositors don't have to name their seats after logind's, so if none of them match ours we
+    /// accept whichever we're given.
+    fn is_our_seat(&self, seat: &wl_seat::WlSeat) -> bool {
+        let name_of =
+            |seat: &wl_seat::WlSeat| self.seat_state.info(seat).and_then(|info| info.name);
+
+        if !self
+            .seat_state
+            .seats()
+            .any(|seat| name_of(&seat).as_deref() == Some(self.seat_id.as_str()))
+        {
+            return true;
         }
 
+        name_of(seat).as_deref() == Some(self.seat_id.as_str())
+    }
+
+    /// Draws every surface that has been configured, without waiting for frame callbacks unless
+    /// they're due within a refresh or the surface is hidden.
+    fn redraw(&mut self, qh: &QueueHandle<Self>) {
+        for index in 0..self.surfaces.len() {
+            let prompt_surface = &self.surfaces[index];
+            if !prompt_surface.first_configure && !prompt_surface.pacing.wait_for_callback() {
+                self.prepare_frame(index);
+                self.present_frame(qh, index);
+            }
+        }
+    }
+
+    /// Draws the surfaces that look different than they were last drawn. Those waiting for a frame
+    /// callback are left to it. Catches whatever changed without a redraw, once nothing moves.
+    fn draw_changed(&mut self, qh: &QueueHandle<Self>) {
+        let nothing_to_show = self.prompts.is_empty() && self.prompts.outcome().is_none();
+        for index in 0..self.surfaces.len() {
+            let prompt_surface = &self.surfaces[index];
+            if prompt_surface.first_configure || prompt_surface.pacing.is_pending() {
+                continue;
+            }
+            // A blank surface with nothing to show stays blank until its size changes, so the
+            // frame isn't worked out again on every turn of the loop while idle.
+            let stays_blank = nothing_to_show
+                && !prompt_surface.docked
+                && prompt_surface.ruler.is_none()
+                && prompt_surface.covering.is_none()
+                && !self.is_moving(prompt_surface)
+                && prompt_surface
+                    .drawn
+                    .as_ref()
+                    .is_some_and(|drawn| drawn.is_blank() && drawn.pos == prompt_surface.shown);
+            if !stays_blank && self.prepare_frame(index) {
+                self.present_frame(qh, index);
+            }
+        }
+    }
+
+    pub fn draw(&mut self, qh: &QueueHandle<Self>, surface: &wl_surface::WlSurface) {
+        let Some(index) = self
+            .surfaces
+            .iter()
+            .position(|prompt| prompt.role.wl_surface() == surface)
+        else {
+            return;
+        };
+
+        self.prepare_frame(index);
+        self.present_frame(qh, index);
+    }
+
+    /// Works out a surface's next frame, into [`SimpleLayer::frame`]. Returns whether it looks any
+    /// different than the last one drawn.
+    fn prepare_frame(&mut self, index: usize) -> bool {
+        // Everything that moves on by itself does so here, before the frame is taken.
+        let state = self.bar_state();
+        let bar = self.surfaces[index].animate_bar(state);
+        let warmth = self
+            .night_light
+            .is_active(&self.config.night)
+            .then_some(self.config.night.warmth);
+        let last = self.frame.take();
+        let mut frame = self.render_state(last, &self.surfaces[index], bar, warmth);
+        self.surfaces[index]
+            .handoff
+            .apply(state, &mut frame.colors, &mut frame.shapes);
+
+        let changed = self.surfaces[index]
+            .drawn
+            .as_ref()
+            .is_none_or(|drawn| !drawn.looks_like(&frame));
+        self.frame = Some(frame);
+        changed
+    }
+
+    /// Draws the frame from [`SimpleLayer::prepare_frame`] and commits it, asking for the next
+    /// frame callback while anything on the bar moves.
+    fn present_frame(&mut self, qh: &QueueHandle<Self>, index: usize) {
+        let moving = self.is_moving(&self.surfaces[index]);
+        let Some(frame) = &mut self.frame else {
+            return;
+        };
+
+        let prompt_surface = &mut self.surfaces[index];
+        let width = frame.pos.win_width();
+        let height = frame.pos.win_height();
+        let buffer = match prompt_surface
+            .buffers
+            .free(&mut self.pool, width, height, frame.format)
+        {
+            Ok(index) => index,
+            Err(e) => {
+                // Keep the frame callbacks coming, and try again on the next one.
+                log::warn!("could not allocate a {width}x{height} buffer: {e}");
+                prompt_surface.request_frame(qh);
+                prompt_surface.role.commit();
+                return;
+            }
+        };
+        let canvas = prompt_surface.buffers.buffers[buffer]
+            .canvas(&mut self.pool)
+            .expect("a free buffer");
+
+        let opaque = render(canvas, frame);
+        prompt_surface.set_opaque(&self.compositor, opaque);
+        prompt_surface.set_idle(
+            &self.compositor,
+            frame.colors.is_empty() && frame.ruler.is_none(),
+        );
+
         // Damage the entire window
-        self.layer
+        prompt_surface
+            .role
             .wl_surface()
             .damage_buffer(0, 0, width as i32, height as i32);
 
-        // Request our next frame
-        self.layer
-            .wl_surface()
-            .frame(qh, self.layer.wl_surface().clone());
-
-        // Attach and commit to present.
-        buffer
-            .attach_to(self.layer.wl_surface())
+        // Attach and commit to present. The destination goes in the same commit as the buffer, so a
+        // resize never shows a buffer stretched to the wrong size.
+        prompt_surface
+            .viewport
+            .set_destination(prompt_surface.width as i32, prompt_surface.height as i32);
+        prompt_surface.buffers.buffers[buffer]
+            .attach_to(prompt_surface.role.wl_surface())
             .expect("buffer attach");
-        self.layer.commit();
+        if moving {
+            prompt_surface.request_frame(qh);
+        }
+        let since_signal = self.signal_at.take().map(|at| at.elapsed());
+        if let (Some(presentation), Some(at)) = (
+            &self.presentation,
+            self.presentation_clock.and_then(presentation::now),
+        ) {
+            let surface = prompt_surface.role.wl_surface();
+            let committed = presentation::Committed {
+                surface: surface.id(),
+                at,
+                since_signal,
+            };
+            presentation.feedback(surface, qh, committed);
+        }
+        prompt_surface.role.commit();
+        prompt_surface.pacing.drawn();
+
+        // Kept to compare the next frame with, and the one drawn before becomes the next scratch.
+        std::mem::swap(&mut self.frame, &mut prompt_surface.drawn);
+        let moved = match (&self.frame, &prompt_surface.drawn) {
+            (Some(last), Some(drawn)) => last.pos != drawn.pos || last.bar != drawn.bar,
+            _ => true,
+        };
+        if moved {
+            self.rpc_changes += 1;
+        }
+
+        if let Some(since_signal) = since_signal {
+            wayland_trace!(
+                "latency",
+                surface = prompt_surface.role.wl_surface().id(),
+                signal_to_commit_us = since_signal.as_micros(),
+            );
+        }
+    }
+
+    /// Whether a surface could look different on the next frame with nothing but time passing, so
+    /// it needs the next frame callback.
+    fn is_moving(&self, prompt_surface: &PromptSurface) -> bool {
+        if prompt_surface
+            .resize
+            .as_ref()
+            .is_some_and(|resize| !resize.is_done())
+        {
+            return true;
+        }
+        if !prompt_surface.handoff.is_done() {
+            return true;
+        }
+        // An outcome fades or blinks, and goes once it's been shown for long enough.
+        if self.prompts.outcome().is_some() {
+            return true;
+        }
+
+        self.prompts.indicators().any(|kind| {
+            let latest = self.prompts.latest(kind);
+            let retrying = latest
+                .and_then(|session| session.retried)
+                .is_some_and(|retried| retried.elapsed() < shape::RETRY_DURATION);
+            let verifying = kind == SessionKind::Verify
+                && latest.is_some_and(|session| {
+                    let timeout = self
+                        .quirks_of(&session.device)
+                        .timeout
+                        .unwrap_or(self.config.attention.timeout);
+                    timeout > 0
+                        || animation::is_flashing(&self.config.attention, session.started.elapsed())
+                });
+            self.config.style.moves() || self.config.shapes && retrying || verifying
+        })
+    }
+
+    /// Which state's size the bar should have.
+    fn bar_state(&self) -> Option<BarState> {
+        match self.prompts.outcome() {
+            Some((Outcome::Success, _)) if self.prompts.is_empty() => Some(BarState::Success),
+            Some((
+                Outcome::Failure | Outcome::Fallback | Outcome::Busy | Outcome::Disconnected,
+                _,
+            )) if self.prompts.is_empty() => Some(BarState::Failure),
+            _ => match self.prompts.primary().map(|session| session.kind) {
+                Some(SessionKind::Verify) => Some(BarState::Verify),
+                Some(SessionKind::Enroll) => Some(BarState::Enroll),
+                None => None,
+            },
+        }
+    }
+
+    /// What a surface should show right now, written over the last frame's state so its vectors
+    /// keep their room. Only reads, so the frame is drawn from one moment even if the prompts
+    /// change in the meantime.
+    fn render_state(
+        &self,
+        last: Option<RenderState>,
+        prompt_surface: &PromptSurface,
+        bar: BarSize,
+        warmth: Option<f32>,
+    ) -> RenderState {
+        let mut frame = last.unwrap_or_else(|| RenderState {
+            pos: prompt_surface.shown.clone(),
+            bar,
+            colors: vec![],
+            progress: vec![],
+            shapes: vec![],
+            spans: vec![],
+            elapsed: Duration::ZERO,
+            style: self.config.style,
+            shadow: prompt_surface.shadow,
+            covering: false,
+            idle_color: None,
+            ruler: None,
+            format: self.pixel_format(),
+        });
+        frame.colors.clear();
+        frame.progress.clear();
+        frame.shapes.clear();
+
+        match self.prompts.outcome() {
+            // Once every session is done, how the last verification went is shown for a moment.
+            Some((outcome, elapsed)) if self.prompts.is_empty() => {
+                let color = match outcome {
+                    Outcome::Success => self.config.colors.success,
+                    Outcome::Failure
+                    | Outcome::Fallback
+                    | Outcome::Busy
+                    | Outcome::Disconnected => self.config.colors.failure,
+                };
+                frame.colors.push(animation::fade(
+                    theme::premultiply(color),
+                    animation::outcome_opacity(outcome, elapsed),
+                ));
+                frame.progress.push(None);
+                if self.config.shapes {
+                    frame.shapes.push(Some(Shape::for_outcome(outcome).into()));
+                }
+            }
+            // One segment of the bar per kind of session, most important first.
+            _ => {
+                for kind in self.prompts.indicators() {
+                    let latest = self.prompts.latest(kind);
+                    frame.colors.push(match kind {
+                        SessionKind::Verify => {
+                            let opacity = self.prompts.last_started(kind).map_or(1., |started| {
+                                animation::flash_opacity(&self.config.attention, started.elapsed())
+                            });
+                            animation::fade(theme::premultiply(self.config.colors.prompt), opacity)
+                        }
+                        SessionKind::Enroll => theme::premultiply(self.config.colors.enroll),
+                    });
+                    // How far the segment has filled up, for enrollments that say how many scans
+                    // they need, or how much time a verification has left.
+                    frame.progress.push(match kind {
+                        SessionKind::Verify => latest.and_then(|session| {
+                            animation::countdown(
+                                self.quirks_of(&session.device)
+                                    .timeout
+                                    .unwrap_or(self.config.attention.timeout),
+                                session.started.elapsed(),
+                            )
+                        }),
+                        SessionKind::Enroll => self.prompts.progress(kind),
+                    });
+                    // Marks for how the scans went, for those that want more than color to tell
+                    // by.
+                    if self.config.shapes {
+                        frame.shapes.push(
+                            latest
+                                .and_then(|session| session.retried)
+                                .filter(|retried| retried.elapsed() < shape::RETRY_DURATION)
+                                .map(|_| Shape::Dot.into()),
+                        );
+                    }
+                }
+            }
+        }
+        for color in &mut frame.colors {
+            if let Some(warmth) = warmth {
+                *color = theme::warm(*color, warmth);
+            }
+            *color = animation::fade(*color, self.opacity);
+        }
+
+        frame.pos.clone_from(&prompt_surface.shown);
+        frame.bar = bar;
+        frame.elapsed = self
+            .prompts
+            .primary()
+            .map_or(Duration::ZERO, |session| session.started.elapsed());
+        frame.style = self.config.style;
+        frame.shadow = prompt_surface.shadow;
+        frame.covering = prompt_surface.covering.is_some();
+        frame.idle_color = prompt_surface
+            .docked
+            .then(|| animation::fade(theme::premultiply(self.config.dock.color), self.opacity));
+        let ruler_color = theme::premultiply(self.config.colors.prompt);
+        match (&mut frame.ruler, prompt_surface.ruler.is_some()) {
+            (Some((pos, color)), true) => {
+                pos.clone_from(&prompt_surface.pos);
+                *color = ruler_color;
+            }
+            (ruler, shown) => *ruler = shown.then(|| (prompt_surface.pos.clone(), ruler_color)),
+        }
+        frame.format = self.pixel_format();
+        frame
+    }
+}
+
+/// Everything one frame of a surface is drawn from.
+struct RenderState {
+    /// The geometry the compositor last configured, in physical pixels.
+    pos: PositionInfo,
+    /// The bar's size within it, partway through easing to a state's size.
+    bar: BarSize,
+    /// One per segment, premultiplied. Empty while idle.
+    colors: Vec<u32>,
+    progress: Vec<Option<f32>>,
+    /// One per segment while `shapes` is on.
+    shapes: Vec<Option<Mark>>,
+    /// How long the primary session has been going, for styles that move.
+    elapsed: Duration,
+    style: theme::Style,
+    shadow: config::Shadow,
+    /// Lock surfaces hide the whole screen, so they're drawn over solid black.
+    covering: bool,
+    /// Premultiplied, drawn in place of nothing while idle, when docked.
+    idle_color: Option<u32>,
+    /// The bar's geometry and color, when showing the ruler instead.
+    ruler: Option<(PositionInfo, u32)>,
+    format: wl_shm::Format,
+    /// Filled in by [`render`] from `pos` and `bar`.
+    spans: Vec<(f32, f32)>,
+}
+
+impl RenderState {
+    /// Whether drawing `other` would come out the same. How long the session has been going is
+    /// only seen in styles that move, which are redrawn on every frame anyway.
+    fn looks_like(&self, other: &RenderState) -> bool {
+        self.pos == other.pos
+            && self.bar == other.bar
+            && self.colors == other.colors
+            && self.progress == other.progress
+            && self.shapes == other.shapes
+            && self.style == other.style
+            && self.shadow == other.shadow
+            && self.covering == other.covering
+            && self.idle_color == other.idle_color
+            && self.ruler == other.ruler
+            && self.format == other.format
+    }
+
+    /// Whether nothing comes out but a clear buffer.
+    fn is_blank(&self) -> bool {
+        self.colors.is_empty()
+            && self.idle_color.is_none()
+            && self.ruler.is_none()
+            && !self.covering
+    }
+}
+
+/// Draws a frame to the canvas, returning the rectangle that came out opaque, if any.
+fn render(canvas: &mut [u8], state: &mut RenderState) -> Option<(u32, u32, u32, u32)> {
+    style::spans(&state.pos, state.bar, &mut state.spans);
+    let state = &*state;
+    let pos = &state.pos;
+    let width = pos.win_width();
+    let height = pos.win_height();
+
+    // Docked, the bar stays up in the dock's color while nothing is waiting.
+    let idle = state.idle_color.map(|color| ([color], [None]));
+    let (colors, progress): (&[u32], &[Option<f32>]) = match &idle {
+        Some((color, progress)) if state.colors.is_empty() => (color, progress),
+        _ => (&state.colors, &state.progress),
+    };
+
+    let renderer = state.style.renderer();
+    let frame = style::Frame {
+        pos,
+        bar: state.bar,
+        colors,
+        progress,
+        spans: &state.spans,
+        shapes: &state.shapes,
+        elapsed: state.elapsed,
+    };
+
+    // The shadow is drawn first and the prompt over it, fading along with it.
+    let shadow = state.shadow;
+    let shadow_color = colors
+        .iter()
+        .map(|color| color >> 24)
+        .max()
+        .map_or(0, |alpha| {
+            animation::fade(theme::premultiply(shadow.color), alpha as f32 / 255.)
+        });
+    let shadow_at = |x: u32, y: u32| {
+        let distance = renderer.distance(&frame, x, y).hypot(frame.gap_depth(x, y));
+        animation::fade(shadow_color, theme::shadow_opacity(&shadow, distance))
+    };
+
+    // Draw to the window:
+    if !colors.is_empty() {
+        canvas
+            .chunks_exact_mut(4)
+            .enumerate()
+            .for_each(|(index, chunk)| {
+                let x = (index % width as usize) as u32;
+                let y = (index / width as usize) as u32;
+
+                let color = if frame.gap_depth(x, y) > 0. {
+                    0
+                } else {
+                    shape::draw(&frame, x, y, renderer.color(&frame, x, y))
+                };
+                let color = if shadow.style == ShadowStyle::None {
+                    color
+                } else {
+                    theme::over(color, shadow_at(x, y))
+                };
+                let color = if state.covering {
+                    theme::over(color, LOCK_BACKGROUND)
+                } else {
+                    color
+                };
+
+                let array: &mut [u8; 4] = chunk.try_into().unwrap();
+                *array = color.to_le_bytes();
+            });
+    } else if state.covering {
+        for chunk in canvas.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&LOCK_BACKGROUND.to_le_bytes());
+        }
+    } else {
+        // Premultiplied transparent black: nothing at all is shown while idle.
+        canvas.fill(0);
+    }
+
+    if let Some((bar, color)) = &state.ruler {
+        ruler::draw(canvas, pos, bar, *color);
+    }
 
-        // TODO save and reuse buffer when the window size is unchanged.  This is especially
-        // useful if you do damage tracking, since you don't need to redraw the undamaged parts
-        // of the canvas.
+    // Let the compositor skip blending what's behind the bar when it's solid. Without alpha,
+    // everything is.
+    if state.format != wl_shm::Format::Argb8888 || state.covering {
+        Some((0, 0, width, height))
+    } else if !colors.is_empty() {
+        renderer.opaque_rect(&frame)
+    } else {
+        None
     }
 }
 
@@ -684,6 +3353,7 @@ delegate_keyboard!(SimpleLayer);
 delegate_pointer!(SimpleLayer);
 
 delegate_layer!(SimpleLayer);
+delegate_session_lock!(SimpleLayer);
 
 delegate_registry!(SimpleLayer);
 
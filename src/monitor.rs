@@ -0,0 +1,233 @@
+// Following fprintd without any UI, for tools like status bars and lockers that only want to know
+// when a reader is in use.
+//
+// fprintd doesn't announce when a device is claimed or released, only what happens while it is:
+// the signals of a verification or enrollment, and the "finger-needed" and "finger-present"
+// properties on versions that have them. Readers being plugged in and out are told through the
+// ObjectManager that newer versions export their devices with, and fprintd itself going away
+// through the bus.
+//
+// Anyone on the bus can send signals that look like fprintd's, so only those from the unique name
+// that owns fprintd's at the time are believed.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    os::fd::RawFd,
+    time::Duration,
+};
+
+use dbus::{
+    arg::{prop_cast, PropMap},
+    ffidisp::Connection as DbusConnection,
+    Message, MessageType, Path,
+};
+
+use crate::{
+    bus,
+    fprint::{DEVICE_INTERFACE, MANAGER_PATH, SERVICE},
+    signals::{self, FprintEvent},
+};
+
+const DBUS: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const TIMEOUT_MS: i32 = 1000;
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const OBJECT_MANAGER_INTERFACE: &str = "org.freedesktop.DBus.ObjectManager";
+const MATCHES: &[&str] = &[
+    "type='signal',sender='net.reactivated.Fprint',interface='net.reactivated.Fprint.Device',\
+     member='VerifyStatus'",
+    "type='signal',sender='net.reactivated.Fprint',interface='net.reactivated.Fprint.Device',\
+     member='VerifyFingerSelected'",
+    "type='signal',sender='net.reactivated.Fprint',interface='net.reactivated.Fprint.Device',\
+     member='EnrollStatus'",
+    "type='signal',sender='net.reactivated.Fprint',interface='org.freedesktop.DBus.Properties',\
+     member='PropertiesChanged',arg0='net.reactivated.Fprint.Device'",
+    "type='signal',sender='net.reactivated.Fprint',interface='org.freedesktop.DBus.ObjectManager',\
+     path_namespace='/net/reactivated/Fprint'",
+    "type='signal',sender='org.freedesktop.DBus',member='NameOwnerChanged',\
+     arg0='net.reactivated.Fprint'",
+];
+
+#[derive(Clone, Debug)]
+pub enum MonitorEvent {
+    /// A signal sent during a verification or enrollment.
+    Fprint(FprintEvent),
+    /// Whether the device is waiting for a finger, roughly from claiming it until the scan.
+    FingerNeeded(bool),
+    /// Whether a finger is on the reader.
+    FingerPresent(bool),
+    /// The reader was plugged in.
+    DeviceAdded,
+    /// The reader was unplugged, ending whatever it was doing without a last signal.
+    DeviceRemoved,
+    /// fprintd went away, crashed or idle, ending whatever every reader was doing. Comes from the
+    /// manager's path.
+    FprintdStopped,
+}
+
+/// Where events about readers come from: fprintd through [`FprintMonitor`], or a
+/// [`FakeSource`](crate::testing::FakeSource) in tests.
+pub trait PromptSource {
+    /// The next event, with the device it came from, if one arrives within `timeout`.
+    fn next_timeout(&self, timeout: Duration) -> Option<(Path<'static>, MonitorEvent)>;
+}
+
+pub struct FprintMonitor {
+    dbus: DbusConnection,
+    /// fprintd's unique name, while it's running.
+    owner: RefCell<Option<String>>,
+    /// Properties that changed in the same signal as the last event.
+    pending: RefCell<VecDeque<(Path<'static>, MonitorEvent)>>,
+    fds: Vec<RawFd>,
+}
+
+impl FprintMonitor {
+    /// Starts listening on the system bus.
+    pub fn new() -> Result<FprintMonitor, dbus::Error> {
+        FprintMonitor::with_connection(DbusConnection::new_system()?)
+    }
+
+    /// Starts listening on a connection of the caller's, e.g. to a private test bus.
+    pub fn with_connection(dbus: DbusConnection) -> Result<FprintMonitor, dbus::Error> {
+        for rule in MATCHES {
+            dbus.add_match(rule)?;
+        }
+        // After matching, so a start in between is still heard of. Not running is an error.
+        let owner = dbus
+            .with_path(DBUS, DBUS_PATH, TIMEOUT_MS)
+            .method_call(DBUS, "GetNameOwner", (SERVICE,))
+            .ok()
+            .map(|(owner,): (String,)| owner);
+        Ok(FprintMonitor {
+            fds: bus::readable_fds(&dbus),
+            dbus,
+            owner: RefCell::new(owner),
+            pending: RefCell::default(),
+        })
+    }
+
+    /// The connection the events arrive on, which can also be used to query fprintd.
+    pub fn connection(&self) -> &DbusConnection {
+        &self.dbus
+    }
+
+    /// The next event, with the device it came from, if one arrives within `timeout`. Properties
+    /// that change together come out one at a time.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<(Path<'static>, MonitorEvent)> {
+        if let Some(event) = self.pending.borrow_mut().pop_front() {
+            return Some(event);
+        }
+
+        // Anything else on the bus is skipped, without waiting again if it's already queued.
+        let timeout_ms = timeout.as_millis().try_into().unwrap_or(u32::MAX);
+        self.dbus.incoming(timeout_ms).find_map(|msg| {
+            if let Some(owner) = parse_owner(&msg) {
+                let stopped = owner.is_none();
+                self.owner.replace(owner);
+                return stopped.then(|| (Path::from(MANAGER_PATH), MonitorEvent::FprintdStopped));
+            }
+            let owner = self.owner.borrow();
+            if owner.is_none() || msg.sender().as_deref() != owner.as_deref() {
+                return None;
+            }
+            drop(owner);
+
+            if let Some(event) = parse_lifecycle(&msg) {
+                return Some(event);
+            }
+
+            let device = msg.path()?.into_static();
+            if let Some(event) = signals::parse(&msg) {
+                return Some((device, MonitorEvent::Fprint(event)));
+            }
+
+            let mut pending = self.pending.borrow_mut();
+            pending.extend(
+                parse_properties(&msg)
+                    .into_iter()
+                    .map(|event| (device.clone(), event)),
+            );
+            pending.pop_front()
+        })
+    }
+
+    /// The file descriptors to wait on for reading, to use the monitor from another event loop.
+    /// Once one is readable, [`FprintMonitor::next_timeout`] with a zero timeout drains what came in.
+    pub fn watch_fds(&self) -> &[RawFd] {
+        &self.fds
+    }
+
+    /// Blocks for every event from now on.
+    pub fn iter(&self) -> impl Iterator<Item = (Path<'static>, MonitorEvent)> + '_ {
+        std::iter::from_fn(|| loop {
+            if let Some(event) = self.next_timeout(Duration::from_secs(60)) {
+                return Some(event);
+            }
+        })
+    }
+}
+
+impl PromptSource for FprintMonitor {
+    fn next_timeout(&self, timeout: Duration) -> Option<(Path<'static>, MonitorEvent)> {
+        FprintMonitor::next_timeout(self, timeout)
+    }
+}
+
+/// Readers coming and going.
+fn parse_lifecycle(msg: &Message) -> Option<(Path<'static>, MonitorEvent)> {
+    if msg.msg_type() != MessageType::Signal {
+        return None;
+    }
+
+    match (msg.interface().as_deref(), msg.member().as_deref()) {
+        (Some(OBJECT_MANAGER_INTERFACE), Some("InterfacesAdded")) => {
+            let (device, interfaces) = msg.read2::<Path, HashMap<String, PropMap>>().ok()?;
+            interfaces
+                .contains_key(DEVICE_INTERFACE)
+                .then(|| (device.into_static(), MonitorEvent::DeviceAdded))
+        }
+        (Some(OBJECT_MANAGER_INTERFACE), Some("InterfacesRemoved")) => {
+            let (device, interfaces) = msg.read2::<Path, Vec<String>>().ok()?;
+            interfaces
+                .iter()
+                .any(|interface| interface == DEVICE_INTERFACE)
+                .then(|| (device.into_static(), MonitorEvent::DeviceRemoved))
+        }
+        _ => None,
+    }
+}
+
+/// fprintd's new unique name when it starts, or None inside when it stops.
+fn parse_owner(msg: &Message) -> Option<Option<String>> {
+    if msg.msg_type() != MessageType::Signal
+        || msg.sender().as_deref() != Some(DBUS)
+        || msg.member().as_deref() != Some("NameOwnerChanged")
+    {
+        return None;
+    }
+    let (name, _, new_owner) = msg.read3::<&str, &str, &str>().ok()?;
+    (name == SERVICE).then(|| (!new_owner.is_empty()).then(|| new_owner.to_string()))
+}
+
+/// The properties we know about in a PropertiesChanged signal from a device.
+fn parse_properties(msg: &Message) -> Vec<MonitorEvent> {
+    if msg.msg_type() != MessageType::Signal
+        || msg.interface().as_deref() != Some(PROPERTIES_INTERFACE)
+        || msg.member().as_deref() != Some("PropertiesChanged")
+    {
+        return vec![];
+    }
+    let Ok((interface, changed)) = msg.read2::<String, PropMap>() else {
+        return vec![];
+    };
+    if interface != DEVICE_INTERFACE {
+        return vec![];
+    }
+
+    let needed =
+        prop_cast::<bool>(&changed, "finger-needed").map(|&b| MonitorEvent::FingerNeeded(b));
+    let present =
+        prop_cast::<bool>(&changed, "finger-present").map(|&b| MonitorEvent::FingerPresent(b));
+    needed.into_iter().chain(present).collect()
+}
@@ -0,0 +1,90 @@
+// Whether it's night, for warming up the colors. Either by a schedule in the config, or by
+// following GNOME's night light.
+
+use std::time::{Duration, Instant};
+
+use dbus::ffidisp::{stdintf::org_freedesktop_dbus::Properties, Connection as DbusConnection};
+
+use crate::config::Night;
+
+const GNOME_COLOR: &str = "org.gnome.SettingsDaemon.Color";
+const GNOME_COLOR_PATH: &str = "/org/gnome/SettingsDaemon/Color";
+const TIMEOUT_MS: i32 = 500;
+
+#[derive(Default)]
+pub struct NightLight {
+    active: bool,
+    last_check: Option<Instant>,
+    session: Option<DbusConnection>,
+}
+
+impl NightLight {
+    const INTERVAL: Duration = Duration::from_secs(30);
+
+    /// Whether colors should be warmed right now. Only looks again every so often.
+    pub fn is_active(&mut self, config: &Night) -> bool {
+        if config.warmth == 0. {
+            return false;
+        }
+
+        if self
+            .last_check
+            .is_none_or(|last_check| last_check.elapsed() >= Self::INTERVAL)
+        {
+            self.last_check = Some(Instant::now());
+            self.active = if config.follow_gnome {
+                self.gnome_night_light()
+            } else {
+                in_schedule(config, local_minutes())
+            };
+        }
+
+        self.active
+    }
+
+    /// Makes the next call look again, e.g. after the config changed.
+    pub fn invalidate(&mut self) {
+        self.last_check = None;
+    }
+
+    fn gnome_night_light(&mut self) -> bool {
+        if self.session.is_none() {
+            self.session = DbusConnection::new_session()
+                .map_err(|e| log::warn!("could not connect to the session bus: {e}"))
+                .ok();
+        }
+        let Some(session) = &self.session else {
+            return false;
+        };
+
+        session
+            .with_path(GNOME_COLOR, GNOME_COLOR_PATH, TIMEOUT_MS)
+            .get(GNOME_COLOR, "NightLightActive")
+            .unwrap_or_else(|e| {
+                log::warn!("could not read GNOME's night light state: {e}");
+                false
+            })
+    }
+}
+
+/// Whether a time, in minutes after midnight, falls in the schedule. It may wrap past midnight.
+fn in_schedule(config: &Night, minutes: u32) -> bool {
+    if config.start <= config.end {
+        (config.start..config.end).contains(&minutes)
+    } else {
+        minutes >= config.start || minutes < config.end
+    }
+}
+
+/// Minutes since local midnight.
+pub fn local_minutes() -> u32 {
+    // SAFETY: localtime_r only writes to the tm we give it.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return 0;
+        }
+        (tm.tm_hour * 60 + tm.tm_min) as u32
+    }
+}
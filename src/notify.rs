@@ -0,0 +1,89 @@
+// The `notification` sink: the prompt as a desktop notification, through
+// org.freedesktop.Notifications, taken back once the verification is over.
+
+use std::collections::HashMap;
+
+use dbus::{arg::Variant, ffidisp::Connection as DbusConnection};
+
+use crate::sinks::{OutputSink, View};
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const TIMEOUT_MS: i32 = 1000;
+const ICON: &str = "auth-fingerprint-symbolic";
+
+pub struct Notifier {
+    dbus: DbusConnection,
+    /// The notification showing, and its text.
+    shown: Option<(u32, String)>,
+}
+
+impl Notifier {
+    pub fn new() -> Option<Notifier> {
+        DbusConnection::new_session()
+            .map(|dbus| Notifier { dbus, shown: None })
+            .map_err(|e| log::warn!("could not connect to the session bus for notifications: {e}"))
+            .ok()
+    }
+
+    /// Shows `text`, replacing what was shown, or takes the notification back for None.
+    fn show(&mut self, text: Option<&str>) {
+        if self.shown.as_ref().map(|(_, shown)| shown.as_str()) == text {
+            return;
+        }
+        let proxy = self
+            .dbus
+            .with_path(NOTIFICATIONS, NOTIFICATIONS_PATH, TIMEOUT_MS);
+
+        let Some(text) = text else {
+            if let Some((id, _)) = self.shown.take() {
+                let closed: Result<(), _> =
+                    proxy.method_call(NOTIFICATIONS, "CloseNotification", (id,));
+                if let Err(e) = closed {
+                    log::warn!("could not take back the notification: {e}");
+                }
+            }
+            return;
+        };
+
+        // Critical, so it stays until it's taken back.
+        let hints = HashMap::from([("urgency", Variant(2u8))]);
+        let replaces = self.shown.as_ref().map_or(0, |(id, _)| *id);
+        let notified: Result<(u32,), _> = proxy.method_call(
+            NOTIFICATIONS,
+            "Notify",
+            (
+                "fprint-prompt",
+                replaces,
+                ICON,
+                "Fingerprint",
+                text,
+                Vec::<&str>::new(),
+                hints,
+                0i32,
+            ),
+        );
+        match notified {
+            Ok((id,)) => self.shown = Some((id, text.to_string())),
+            Err(e) => log::warn!("could not show a notification: {e}"),
+        }
+    }
+}
+
+impl OutputSink for Notifier {
+    fn poll(&mut self, view: &View) {
+        let text = view
+            .prompts
+            .primary()
+            .map(|session| session.text.as_str())
+            .or(view.prompts.notice().map(|(_, text)| text))
+            .filter(|_| !view.paused);
+        self.show(text);
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.show(None);
+    }
+}
@@ -0,0 +1,166 @@
+// Drawing a surface at the pace its output refreshes. Frame callbacks are only asked for while
+// something on the bar moves, and their timestamps tell how often the output refreshes, so a
+// redraw out of turn can leave it to the callback when that's due within a refresh anyway.
+//
+// Compositors hold callbacks back while a surface can't be seen, like when it's covered or the
+// output is off. A callback that's long overdue is taken to mean that, and the surface isn't drawn
+// out of turn again until it comes.
+
+use std::time::{Duration, Instant};
+
+pub struct FramePacing {
+    /// When the frame callback that hasn't come yet was asked for.
+    requested: Option<Instant>,
+    /// The timestamp of the last callback, in the compositor's milliseconds.
+    last_time: Option<u32>,
+    /// How long the output takes to refresh, going by the timestamps.
+    interval: Duration,
+    /// When the surface was last drawn.
+    drawn: Option<Instant>,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        FramePacing {
+            requested: None,
+            last_time: None,
+            interval: Self::DEFAULT_INTERVAL,
+            drawn: None,
+        }
+    }
+}
+
+impl FramePacing {
+    /// 60 Hz, until the timestamps tell otherwise.
+    const DEFAULT_INTERVAL: Duration = Duration::from_micros(16_667);
+    /// From 240 Hz down to 10 Hz. Longer gaps are from not asking for callbacks.
+    const SHORTEST: Duration = Duration::from_millis(4);
+    const LONGEST: Duration = Duration::from_millis(100);
+    /// How many refreshes a callback can be late by before the surface is taken to be hidden.
+    const HIDDEN_AFTER: u32 = 8;
+
+    /// Notes that a callback was asked for.
+    pub fn requested(&mut self) {
+        self.requested = Some(Instant::now());
+    }
+
+    /// Whether a callback was asked for and hasn't come yet.
+    pub fn is_pending(&self) -> bool {
+        self.requested.is_some()
+    }
+
+    /// Notes that a callback came, with its timestamp.
+    pub fn done(&mut self, time: u32) {
+        self.requested = None;
+        if let Some(last) = self.last_time.replace(time) {
+            // Callbacks can skip refreshes, so the gap between them is a whole number of refreshes.
+            // The shortest is taken right away, and a longer one slowly, for outputs switched to a
+            // lower refresh rate.
+            let gap = Duration::from_millis(time.wrapping_sub(last).into());
+            if (Self::SHORTEST..=Self::LONGEST).contains(&gap) {
+                if gap < self.interval {
+                    self.interval = gap;
+                } else if gap < self.interval * 3 {
+                    self.interval += (gap - self.interval) / 16;
+                }
+            }
+        }
+    }
+
+    /// Notes that the surface was drawn.
+    pub fn drawn(&mut self) {
+        self.drawn = Some(Instant::now());
+    }
+
+    /// Whether a redraw out of turn should be left to the coming callback: it's due within a
+    /// refresh, or the surface is hidden and drawing it would be wasted.
+    pub fn wait_for_callback(&self) -> bool {
+        let Some(requested) = self.requested else {
+            return false;
+        };
+        requested.elapsed() > self.interval * Self::HIDDEN_AFTER
+            || self
+                .drawn
+                .is_some_and(|drawn| drawn.elapsed() < self.interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Callbacks at each of `times`, asked for one after the other.
+    fn paced(times: &[u32]) -> FramePacing {
+        let mut pacing = FramePacing::default();
+        for &time in times {
+            pacing.requested();
+            pacing.done(time);
+        }
+        pacing
+    }
+
+    #[test]
+    fn learns_the_refresh_interval() {
+        // 120 Hz is taken right away.
+        let pacing = paced(&[1000, 1008, 1016]);
+        assert_eq!(pacing.interval, Duration::from_millis(8));
+
+        // Callbacks that skipped a few refreshes don't make it any longer.
+        let pacing = paced(&[1000, 1008, 1032, 1064]);
+        assert_eq!(pacing.interval, Duration::from_millis(8));
+
+        // Switched down to 30 Hz: it's crept up to.
+        let mut times = vec![];
+        let mut time = 1000;
+        for _ in 0..64 {
+            times.push(time);
+            time += 33;
+        }
+        let pacing = paced(&times);
+        assert!(pacing.interval > Duration::from_millis(30));
+        assert!(pacing.interval <= Duration::from_millis(33));
+    }
+
+    #[test]
+    fn ignores_gaps_from_not_asking() {
+        let pacing = paced(&[1000, 1250, 1251]);
+        assert_eq!(pacing.interval, FramePacing::DEFAULT_INTERVAL);
+    }
+
+    #[test]
+    fn follows_the_timestamps_around() {
+        let pacing = paced(&[u32::MAX - 3, 4]);
+        assert_eq!(pacing.interval, Duration::from_millis(8));
+    }
+
+    #[test]
+    fn waits_for_a_callback_that_is_due() {
+        let mut pacing = FramePacing::default();
+        assert!(!pacing.wait_for_callback());
+
+        pacing.requested();
+        pacing.drawn();
+        assert!(pacing.wait_for_callback());
+
+        // Drawn longer than a refresh ago, and the callback isn't overdue yet.
+        pacing.drawn = Instant::now().checked_sub(Duration::from_millis(20));
+        assert!(!pacing.wait_for_callback());
+    }
+
+    #[test]
+    fn takes_the_surface_as_hidden_after_eight_refreshes() {
+        let mut pacing = FramePacing {
+            drawn: Instant::now().checked_sub(Duration::from_secs(1)),
+            ..FramePacing::default()
+        };
+
+        pacing.requested = Instant::now().checked_sub(FramePacing::DEFAULT_INTERVAL * 7);
+        assert!(!pacing.wait_for_callback());
+
+        pacing.requested = Instant::now().checked_sub(FramePacing::DEFAULT_INTERVAL * 9);
+        assert!(pacing.wait_for_callback());
+
+        pacing.done(1000);
+        assert!(!pacing.wait_for_callback());
+    }
+}
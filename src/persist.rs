@@ -0,0 +1,139 @@
+// Runtime settings that outlive the process: the profile and pause picked over IPC or the tray,
+// the opacity scrolled to, and how many verifications there have been. They're kept in
+// `$XDG_STATE_HOME/fprint-prompt/state`, or `state-NAME` for `--instance NAME`, in the config's
+// syntax, so a restart, or systemd bringing us back after a crash, carries on where the last run
+// left off.
+//
+// Only the plain overlay uses this. The greeter, `--lock` and the previews always start fresh.
+
+use std::{fmt::Write, path::PathBuf};
+
+use crate::{
+    config::{self, Entry, ParseError, Value},
+    crash, instance,
+    prompts::Outcome,
+};
+
+#[derive(Clone, PartialEq)]
+pub struct SavedState {
+    pub profile: Option<String>,
+    pub paused: bool,
+    pub opacity: f32,
+    /// Every verification that finished, and how many of those matched.
+    pub verifications: u64,
+    pub matches: u64,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        SavedState {
+            profile: None,
+            paused: false,
+            opacity: 1.,
+            verifications: 0,
+            matches: 0,
+        }
+    }
+}
+
+pub struct Persisted {
+    pub state: SavedState,
+    /// What's in the file, so it's only written when something changed.
+    written: SavedState,
+}
+
+fn path() -> Option<PathBuf> {
+    Some(crash::state_dir()?.join(instance::file_name("state")))
+}
+
+impl Persisted {
+    /// The state from the last run, or the defaults. Whatever can't be read is left out.
+    pub fn load() -> Persisted {
+        let state = path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|text| parse(&text))
+            .unwrap_or_default();
+
+        Persisted {
+            written: state.clone(),
+            state,
+        }
+    }
+
+    pub fn record(&mut self, outcome: Outcome) {
+        self.state.verifications += 1;
+        if outcome == Outcome::Success {
+            self.state.matches += 1;
+        }
+    }
+
+    /// Writes the state out if it changed since the last time.
+    pub fn save(&mut self) {
+        if self.state == self.written {
+            return;
+        }
+        self.written = self.state.clone();
+
+        let Some(path) = path() else {
+            return;
+        };
+        let state = &self.state;
+        let mut text = String::new();
+        if let Some(profile) = &state.profile {
+            let _ = writeln!(
+                text,
+                "profile = {}",
+                crate::status::json_string(Some(profile))
+            );
+        }
+        let _ = writeln!(text, "paused = {}", state.paused);
+        let _ = writeln!(text, "opacity = {:?}", state.opacity);
+        let _ = writeln!(text, "verifications = {}", state.verifications);
+        let _ = writeln!(text, "matches = {}", state.matches);
+
+        // Written next to it and moved over it, so a crash never leaves half a file.
+        let temporary = path.with_extension("new");
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&temporary, text))
+            .and_then(|()| std::fs::rename(&temporary, &path));
+        if let Err(e) = written {
+            log::warn!("could not save the state to {}: {e}", path.display());
+        }
+    }
+}
+
+fn parse(text: &str) -> SavedState {
+    let mut state = SavedState::default();
+    let (entries, errors) = config::parse_entries(text);
+    for error in errors {
+        log::warn!("state:{error}");
+    }
+
+    for entry in entries {
+        if let Err(e) = apply(&mut state, &entry) {
+            log::warn!("state:{e}");
+        }
+    }
+    state
+}
+
+fn apply(state: &mut SavedState, entry: &Entry) -> Result<(), ParseError> {
+    match entry.key.join(".").as_str() {
+        "profile" => state.profile = Some(entry.string()?),
+        "paused" => state.paused = entry.bool()?,
+        "opacity" => state.opacity = entry.fraction()?,
+        "verifications" => state.verifications = count(entry)?,
+        "matches" => state.matches = count(entry)?,
+        _ => return Err(entry.unknown_key()),
+    }
+    Ok(())
+}
+
+fn count(entry: &Entry) -> Result<u64, ParseError> {
+    match entry.value {
+        Value::Integer(value) if value >= 0 => Ok(value as u64),
+        _ => Err(entry.error(format!("{} should be a count", entry.key.join(".")))),
+    }
+}
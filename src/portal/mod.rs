@@ -0,0 +1,43 @@
+// Clients for xdg-desktop-portal interfaces, on the session bus.
+//
+// Portal calls return a Request object right away, and the actual result comes later in its
+// Response signal. See https://flatpak.github.io/xdg-desktop-portal/docs/requests.html.
+
+use dbus::{arg::PropMap, Message};
+
+pub mod screencast;
+pub mod settings;
+pub mod shortcuts;
+
+pub const DESTINATION: &str = "org.freedesktop.portal.Desktop";
+pub const PATH: &str = "/org/freedesktop/portal/desktop";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+pub const TIMEOUT_MS: i32 = 1000;
+
+/// Match rule for the Response signals of our requests, which are sent only to us.
+pub const RESPONSE_MATCH: &str =
+    "type='signal',interface='org.freedesktop.portal.Request',member='Response'";
+
+/// Reads a Response signal: the request it's for, and its results if the request succeeded.
+pub fn response(msg: &Message) -> Option<(String, Option<PropMap>)> {
+    if msg.interface().as_deref() != Some(REQUEST_INTERFACE)
+        || msg.member().as_deref() != Some("Response")
+    {
+        return None;
+    }
+
+    let request = msg.path()?.to_string();
+    let (code, results): (u32, PropMap) = msg.read2().ok()?;
+    // 1 is cancelled by the user, 2 is any other failure.
+    Some((request, (code == 0).then_some(results)))
+}
+
+/// Options for a portal call, with the token that names its Request object.
+pub fn options(handle_token: &str) -> PropMap {
+    let mut options = PropMap::new();
+    options.insert(
+        "handle_token".to_string(),
+        dbus::arg::Variant(Box::new(handle_token.to_string())),
+    );
+    options
+}
@@ -0,0 +1,100 @@
+// Notices screen casts going through the ScreenCast portal, so prompts can be kept out of
+// recordings and streams.
+//
+// The portal doesn't tell anyone else about its sessions, so the session bus is watched as a
+// monitor instead: ScreenCast.Start begins a cast, which lasts until its session is closed or the
+// client that started it goes away.
+
+use std::{collections::HashSet, time::Duration};
+
+use dbus::{blocking::Connection, channel::Channel, Message, MessageType, Path};
+
+const SCREENCAST_INTERFACE: &str = "org.freedesktop.portal.ScreenCast";
+const SESSION_INTERFACE: &str = "org.freedesktop.portal.Session";
+const SESSION_PATH: &str = "/org/freedesktop/portal/desktop/session";
+
+pub struct ScreencastMonitor {
+    /// Monitors can't send anything, so messages are read straight off the channel instead of
+    /// being dispatched, which could answer method calls.
+    dbus: Connection,
+    /// Object paths of the sessions that have been started.
+    sessions: HashSet<String>,
+}
+
+impl ScreencastMonitor {
+    pub fn new() -> Result<ScreencastMonitor, dbus::Error> {
+        let dbus = Connection::new_session()?;
+
+        let rules = vec![
+            format!("type='method_call',interface='{SCREENCAST_INTERFACE}',member='Start'"),
+            format!("type='method_call',interface='{SESSION_INTERFACE}',member='Close'"),
+            format!("type='signal',interface='{SESSION_INTERFACE}',member='Closed'"),
+            "type='signal',sender='org.freedesktop.DBus',member='NameOwnerChanged'".to_string(),
+        ];
+        dbus.with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            Duration::from_millis(super::TIMEOUT_MS as u64),
+        )
+        .method_call::<(), _, _, _>(
+            "org.freedesktop.DBus.Monitoring",
+            "BecomeMonitor",
+            (rules, 0u32),
+        )?;
+
+        Ok(ScreencastMonitor {
+            dbus,
+            sessions: HashSet::new(),
+        })
+    }
+
+    /// Whether the screen is being cast, catching up on the bus without blocking.
+    pub fn poll(&mut self) -> bool {
+        let channel: &Channel = self.dbus.channel();
+        if let Err(e) = channel.read_write(Some(Duration::ZERO)) {
+            log::warn!("lost the session bus while watching for screen casts: {e:?}");
+        }
+
+        let messages: Vec<Message> = std::iter::from_fn(|| channel.pop_message()).collect();
+        for msg in messages {
+            self.handle(&msg);
+        }
+
+        !self.sessions.is_empty()
+    }
+
+    fn handle(&mut self, msg: &Message) {
+        let interface = msg.interface();
+        let member = msg.member();
+
+        match (msg.msg_type(), interface.as_deref(), member.as_deref()) {
+            (MessageType::MethodCall, Some(SCREENCAST_INTERFACE), Some("Start")) => {
+                if let Ok(session) = msg.read1::<Path>() {
+                    log::info!("Screen cast started: {session}");
+                    self.sessions.insert(session.to_string());
+                }
+            }
+            (MessageType::MethodCall, Some(SESSION_INTERFACE), Some("Close"))
+            | (MessageType::Signal, Some(SESSION_INTERFACE), Some("Closed")) => {
+                if let Some(session) = msg.path() {
+                    if self.sessions.remove(&*session) {
+                        log::info!("Screen cast stopped: {session}");
+                    }
+                }
+            }
+            (MessageType::Signal, _, Some("NameOwnerChanged")) => {
+                // Sessions are named after the unique name of the client, which the portal
+                // closes them for when it disconnects.
+                let Ok((name, _, new_owner)) = msg.read3::<&str, &str, &str>() else {
+                    return;
+                };
+                if let Some(unique) = name.strip_prefix(':').filter(|_| new_owner.is_empty()) {
+                    let prefix = format!("{SESSION_PATH}/{}/", unique.replace('.', "_"));
+                    self.sessions
+                        .retain(|session| !session.starts_with(&prefix));
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// The desktop's preferred color scheme through the org.freedesktop.portal.Settings portal, which
+// says when it changes, so a theme can follow the desktop between light and dark.
+
+use dbus::{arg::Variant, ffidisp::Connection as DbusConnection, MessageType};
+
+use super::{DESTINATION, PATH, TIMEOUT_MS};
+
+const INTERFACE: &str = "org.freedesktop.portal.Settings";
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+pub struct ColorScheme {
+    dbus: DbusConnection,
+    dark: bool,
+}
+
+impl ColorScheme {
+    /// Reads the color scheme and starts following it. Fails if there's no session bus or portal.
+    pub fn new() -> Result<ColorScheme, dbus::Error> {
+        let dbus = DbusConnection::new_session()?;
+        dbus.add_match(&format!(
+            "type='signal',interface='{INTERFACE}',member='SettingChanged'"
+        ))?;
+
+        let proxy = dbus.with_path(DESTINATION, PATH, TIMEOUT_MS);
+        // ReadOne is only in newer portals. Read wraps the value in another variant.
+        let scheme = match proxy.method_call(INTERFACE, "ReadOne", (NAMESPACE, KEY)) {
+            Ok((Variant(scheme),)) => scheme,
+            Err(_) => {
+                let (Variant(Variant(scheme)),): (Variant<Variant<u32>>,) =
+                    proxy.method_call(INTERFACE, "Read", (NAMESPACE, KEY))?;
+                scheme
+            }
+        };
+
+        Ok(ColorScheme {
+            dbus,
+            dark: is_dark(scheme),
+        })
+    }
+
+    /// Whether the desktop prefers a dark style, catching up on changes without blocking.
+    pub fn is_dark(&mut self) -> bool {
+        while let Some(msg) = self.dbus.incoming(0).next() {
+            if msg.msg_type() != MessageType::Signal
+                || msg.member().as_deref() != Some("SettingChanged")
+            {
+                continue;
+            }
+
+            if let Ok((NAMESPACE, KEY, Variant(scheme))) = msg.read3::<&str, &str, Variant<u32>>() {
+                self.dark = is_dark(scheme);
+                log::info!(
+                    "The desktop switched to a {} style",
+                    if self.dark { "dark" } else { "light" }
+                );
+            }
+        }
+
+        self.dark
+    }
+}
+
+/// 1 prefers dark and 2 prefers light. 0 has no preference, which apps take as light.
+fn is_dark(scheme: u32) -> bool {
+    scheme == 1
+}
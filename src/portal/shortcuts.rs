@@ -0,0 +1,172 @@
+// Global shortcuts through the org.freedesktop.portal.GlobalShortcuts portal, so the overlay can
+// be toggled from the keyboard on compositors that don't let us grab keys ourselves.
+//
+// A session is created and the shortcuts are bound to it. The first time, the desktop asks the
+// user which keys to use. After that, Activated signals come in whenever they're pressed.
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    ffidisp::Connection as DbusConnection,
+    Message, MessageType, Path,
+};
+
+use super::{DESTINATION, PATH, RESPONSE_MATCH, TIMEOUT_MS};
+
+const INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+const TOGGLE: &str = "toggle-overlay";
+const SHOW_LAST_RESULT: &str = "show-last-result";
+
+/// A shortcut that was pressed.
+pub enum ShortcutAction {
+    TogglePaused,
+    ShowLastResult,
+}
+
+enum State {
+    /// Waiting for the Response to CreateSession at this request path.
+    Creating(String),
+    /// Waiting for the Response to BindShortcuts.
+    Binding(String),
+    Bound,
+    Failed,
+}
+
+pub struct Shortcuts {
+    dbus: DbusConnection,
+    state: State,
+    session: Option<Path<'static>>,
+}
+
+impl Shortcuts {
+    /// Starts creating the session. Fails if there's no session bus or portal.
+    pub fn new() -> Result<Shortcuts, dbus::Error> {
+        let dbus = DbusConnection::new_session()?;
+        dbus.add_match(RESPONSE_MATCH)?;
+        dbus.add_match(&format!(
+            "type='signal',interface='{INTERFACE}',member='Activated'"
+        ))?;
+
+        let mut options = super::options("fprint_prompt_create");
+        options.insert(
+            "session_handle_token".to_string(),
+            Variant(Box::new("fprint_prompt".to_string())),
+        );
+
+        let (request,): (Path,) = dbus.with_path(DESTINATION, PATH, TIMEOUT_MS).method_call(
+            INTERFACE,
+            "CreateSession",
+            (options,),
+        )?;
+
+        Ok(Shortcuts {
+            dbus,
+            state: State::Creating(request.to_string()),
+            session: None,
+        })
+    }
+
+    /// Moves the setup along, returning the first shortcut pressed since the last poll.
+    pub fn poll(&mut self) -> Option<ShortcutAction> {
+        let mut action = None;
+
+        while let Some(msg) = self.dbus.incoming(0).next() {
+            if msg.msg_type() != MessageType::Signal {
+                continue;
+            }
+
+            if let Some((request, results)) = super::response(&msg) {
+                self.handle_response(&request, results);
+            } else if msg.member().as_deref() == Some("Activated") {
+                action = action.or_else(|| self.activated(&msg));
+            }
+        }
+
+        action
+    }
+
+    fn handle_response(&mut self, request: &str, results: Option<PropMap>) {
+        match &self.state {
+            State::Creating(pending) if pending == request => {
+                let session = results
+                    .as_ref()
+                    .and_then(|results| results.get("session_handle"))
+                    .and_then(|handle| handle.0.as_str())
+                    .and_then(|handle| Path::new(handle.to_string()).ok());
+
+                match session {
+                    Some(session) => self.bind(session),
+                    None => {
+                        log::warn!("could not create a global shortcuts session");
+                        self.state = State::Failed;
+                    }
+                }
+            }
+            State::Binding(pending) if pending == request => {
+                if results.is_some() {
+                    log::info!("Global shortcuts are bound");
+                    self.state = State::Bound;
+                } else {
+                    log::warn!("global shortcuts were not bound");
+                    self.state = State::Failed;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn bind(&mut self, session: Path<'static>) {
+        let shortcuts: Vec<(&str, PropMap)> = vec![
+            (TOGGLE, description("Hide or show the fingerprint prompt")),
+            (
+                SHOW_LAST_RESULT,
+                description("Show how the last fingerprint scan went"),
+            ),
+        ];
+
+        let bound: Result<(Path,), dbus::Error> = self
+            .dbus
+            .with_path(DESTINATION, PATH, TIMEOUT_MS)
+            .method_call(
+                INTERFACE,
+                "BindShortcuts",
+                (
+                    &session,
+                    shortcuts,
+                    "",
+                    super::options("fprint_prompt_bind"),
+                ),
+            );
+
+        self.state = match bound {
+            Ok((request,)) => State::Binding(request.to_string()),
+            Err(e) => {
+                log::warn!("could not bind global shortcuts: {e}");
+                State::Failed
+            }
+        };
+        self.session = Some(session);
+    }
+
+    fn activated(&self, msg: &Message) -> Option<ShortcutAction> {
+        let (session, id): (Path, &str) = msg.read2().ok()?;
+        if Some(&session) != self.session.as_ref() {
+            return None;
+        }
+
+        match id {
+            TOGGLE => Some(ShortcutAction::TogglePaused),
+            SHOW_LAST_RESULT => Some(ShortcutAction::ShowLastResult),
+            _ => None,
+        }
+    }
+}
+
+fn description(text: &str) -> PropMap {
+    let mut properties = PropMap::new();
+    properties.insert(
+        "description".to_string(),
+        Variant(Box::new(text.to_string()) as Box<dyn RefArg>),
+    );
+    properties
+}
@@ -0,0 +1,105 @@
+// How long after being committed frames are actually shown, measured with wp_presentation, so how
+// quickly the prompt appears after a signal from fprintd can be compared between compositors.
+// Every frame asks for feedback, which says when the compositor put it on screen, on a clock it
+// picks. Each frame is logged with `--wayland-trace`, and the latest ones are summed up in the
+// Control1 interface's Latency property, which `fprint-prompt ctl latency` prints. They aren't drawn
+// on the bar itself, which has no text to show them with.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use wayland_client::backend::ObjectId;
+
+/// What a frame's feedback is measured from.
+pub struct Committed {
+    pub surface: ObjectId,
+    /// When it was committed, on the compositor's presentation clock.
+    pub at: Duration,
+    /// How long after a signal it was committed, for the first frame showing it.
+    pub since_signal: Option<Duration>,
+}
+
+/// The time on one of the clocks `clock_gettime` knows, like the one wp_presentation picks.
+pub fn now(clock: u32) -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let read = unsafe { libc::clock_gettime(clock as libc::clockid_t, &mut time) } == 0;
+    read.then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+/// How long the latest frames took to be shown.
+#[derive(Clone, Default)]
+pub struct Latency {
+    /// From being committed.
+    commit: Recent,
+    /// From the signal they show, for the first frame after one.
+    signal: Recent,
+    /// Frames that were never shown, like when a newer one replaced them first.
+    discarded: u64,
+}
+
+impl Latency {
+    pub fn presented(&mut self, since_commit: Duration, since_signal: Option<Duration>) {
+        self.commit.push(since_commit);
+        if let Some(since_signal) = since_signal {
+            self.signal.push(since_signal);
+        }
+    }
+
+    pub fn discarded(&mut self) {
+        self.discarded += 1;
+    }
+
+    /// In microseconds, by name, for the Latency property.
+    pub fn summary(&self) -> BTreeMap<String, u64> {
+        let mut summary = BTreeMap::from([
+            ("frames".to_string(), self.commit.count),
+            ("discarded".to_string(), self.discarded),
+            ("signals".to_string(), self.signal.count),
+        ]);
+        for (name, recent) in [("commit", &self.commit), ("signal", &self.signal)] {
+            if let Some((median, max)) = recent.median_and_max() {
+                summary.insert(
+                    format!("{name}_to_present_median_us"),
+                    median.as_micros() as u64,
+                );
+                summary.insert(format!("{name}_to_present_max_us"), max.as_micros() as u64);
+            }
+        }
+        summary
+    }
+}
+
+/// The latest latencies, written over in a ring so recording one never allocates.
+#[derive(Clone)]
+struct Recent {
+    latencies: [Duration; Recent::KEPT],
+    /// Every latency recorded, of which the last [`Recent::KEPT`] are kept.
+    count: u64,
+}
+
+impl Default for Recent {
+    fn default() -> Self {
+        Recent {
+            latencies: [Duration::ZERO; Recent::KEPT],
+            count: 0,
+        }
+    }
+}
+
+impl Recent {
+    const KEPT: usize = 64;
+
+    fn push(&mut self, latency: Duration) {
+        self.latencies[(self.count % Self::KEPT as u64) as usize] = latency;
+        self.count += 1;
+    }
+
+    fn median_and_max(&self) -> Option<(Duration, Duration)> {
+        let mut kept = self.latencies;
+        let kept = &mut kept[..(self.count as usize).min(Self::KEPT)];
+        kept.sort_unstable();
+        Some((kept[kept.len().checked_sub(1)? / 2], *kept.last()?))
+    }
+}
@@ -0,0 +1,351 @@
+// Tracks every fprintd operation that wants the user's finger, so interleaved verify and enroll
+// sessions, or several readers prompting at once, don't clobber each other.
+
+use std::time::{Duration, Instant};
+
+use dbus::Path;
+use fprint_prompt::signals::ScanResult;
+
+use crate::{animation::OUTCOME_DURATION, config::Attention};
+
+/// Ordered by priority: when space is short, later kinds win.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SessionKind {
+    Enroll,
+    Verify,
+}
+
+/// How long after a verification that didn't match the next one counts as trying again, as
+/// pam_fprintd does right away.
+const NEXT_TRY: Duration = Duration::from_secs(5);
+
+/// How a verification ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// The last try didn't match, or the reader failed, so PAM asks for the password instead.
+    Fallback,
+    /// Another process had claimed the reader, so PAM asks for the password without trying it.
+    Busy,
+    /// The reader was unplugged or fprintd went away mid-verification, so PAM asks for the
+    /// password instead.
+    Disconnected,
+}
+
+impl Outcome {
+    /// For outcomes that send the user to the password, the state sinks report while it's shown
+    /// and what they say.
+    pub fn notice(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Outcome::Success | Outcome::Failure => None,
+            Outcome::Fallback => {
+                Some(("fallback", "Too many attempts, type your password instead"))
+            }
+            Outcome::Busy => Some((
+                "busy",
+                "Fingerprint reader busy, type your password instead",
+            )),
+            Outcome::Disconnected => Some((
+                "disconnected",
+                "Fingerprint reader disconnected, type your password instead",
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ActiveSession {
+    pub device: Path<'static>,
+    pub kind: SessionKind,
+    /// What to tell the user, e.g. which finger to use.
+    pub text: String,
+    pub started: Instant,
+    /// Last time the user did something with the reader, or when the session started.
+    pub touched: Instant,
+    /// The last scan that had to be tried again.
+    pub retried: Option<Instant>,
+    /// For enrollments, how many scans have passed out of how many are needed, once known.
+    pub stages: Option<(u32, u32)>,
+    /// Started before the system slept, and not heard of since.
+    pub stale: bool,
+}
+
+#[derive(Default)]
+pub struct Prompts {
+    sessions: Vec<ActiveSession>,
+    /// The last verification's outcome, when it came in and how long it's shown.
+    outcome: Option<(Outcome, Instant, Duration)>,
+    /// How many verifications in a row didn't match, and when the last one ended.
+    failures: Option<(u32, Instant)>,
+    /// Goes up whenever a session starts, ends or has its text changed.
+    generation: u64,
+}
+
+impl Prompts {
+    /// Starts a session, or updates the text of one already running on the device.
+    pub fn start(&mut self, device: Path<'static>, kind: SessionKind, text: String) {
+        self.outcome = None;
+        if self
+            .failures
+            .is_some_and(|(_, at)| at.elapsed() >= NEXT_TRY)
+        {
+            self.failures = None;
+        }
+        self.generation += 1;
+
+        match self
+            .sessions
+            .iter_mut()
+            .find(|session| session.device == device && session.kind == kind)
+        {
+            Some(session) => {
+                session.text = text;
+                session.stale = false;
+            }
+            None => self.sessions.push(ActiveSession {
+                device,
+                kind,
+                text,
+                started: Instant::now(),
+                touched: Instant::now(),
+                retried: None,
+                stages: None,
+                stale: false,
+            }),
+        }
+    }
+
+    /// Returns whether there was such a session.
+    pub fn finish(&mut self, device: &Path, kind: SessionKind) -> bool {
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|session| !(session.device == *device && session.kind == kind));
+        self.changed(before)
+    }
+
+    pub fn set_outcome(&mut self, outcome: Outcome) {
+        if outcome == Outcome::Success {
+            self.failures = None;
+        }
+        self.outcome = Some((outcome, Instant::now(), OUTCOME_DURATION));
+    }
+
+    /// Sets the outcome of a verification PAM asked for from its last scan. After `max_tries` in
+    /// a row that didn't match, or when the reader failed or went away, PAM asks for the password
+    /// instead.
+    pub fn end_for_pam(&mut self, scan: ScanResult, attention: &Attention) -> Outcome {
+        let fall_back = match scan {
+            ScanResult::Match => false,
+            ScanResult::Disconnected => {
+                log::info!("The reader went away, so the password is asked for instead");
+                self.failures = None;
+                self.linger(Outcome::Disconnected, attention);
+                return Outcome::Disconnected;
+            }
+            ScanResult::Failed => true,
+            _ => {
+                let failures = self.failures.map_or(0, |(failures, _)| failures) + 1;
+                self.failures = Some((failures, Instant::now()));
+                attention.max_tries > 0 && failures >= attention.max_tries
+            }
+        };
+        if !fall_back {
+            let outcome = match scan {
+                ScanResult::Match => Outcome::Success,
+                _ => Outcome::Failure,
+            };
+            self.set_outcome(outcome);
+            return outcome;
+        }
+
+        log::info!("Falling back to the password");
+        self.failures = None;
+        self.linger(Outcome::Fallback, attention);
+        Outcome::Fallback
+    }
+
+    /// Shows an outcome that sends the user to the password for `attention.fallback_linger`.
+    pub fn linger(&mut self, outcome: Outcome, attention: &Attention) {
+        let linger = Duration::from_secs(attention.fallback_linger.into());
+        self.outcome = Some((outcome, Instant::now(), linger));
+    }
+
+    /// Shows the last outcome again, if there's been a verification since the last one started.
+    pub fn replay_outcome(&mut self) {
+        if let Some((_, at, _)) = &mut self.outcome {
+            *at = Instant::now();
+        }
+    }
+
+    /// How the last verification ended and how long ago, while that's still worth showing.
+    pub fn outcome(&self) -> Option<(Outcome, Duration)> {
+        let (outcome, at, shown_for) = self.outcome?;
+        (at.elapsed() < shown_for).then(|| (outcome, at.elapsed()))
+    }
+
+    /// The state and text of [`Outcome::notice`], while nothing is waiting and the password is
+    /// asked for instead.
+    pub fn notice(&self) -> Option<(&'static str, &'static str)> {
+        let (outcome, _) = self.outcome().filter(|_| self.sessions.is_empty())?;
+        outcome.notice()
+    }
+
+    /// Notes that the reader was used, e.g. for a scan that didn't match.
+    pub fn touch(&mut self, device: &Path, kind: SessionKind) {
+        for session in &mut self.sessions {
+            if session.device == *device && session.kind == kind {
+                session.touched = Instant::now();
+                session.stale = false;
+            }
+        }
+    }
+
+    /// Notes a scan that has to be tried again, which counts as using the reader too.
+    pub fn retry(&mut self, device: &Path, kind: SessionKind) {
+        self.touch(device, kind);
+        for session in &mut self.sessions {
+            if session.device == *device && session.kind == kind {
+                session.retried = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Marks every session as likely dead, as when the system goes to sleep. Each is kept up until
+    /// fprintd is heard of again for it, or it's dropped by [`Prompts::drop_stale`].
+    pub fn mark_stale(&mut self) {
+        for session in &mut self.sessions {
+            session.stale = true;
+        }
+    }
+
+    /// Keeps the sessions on a reader that fprintd says is still waiting.
+    pub fn keep(&mut self, device: &Path) {
+        for session in &mut self.sessions {
+            if session.device == *device {
+                session.stale = false;
+            }
+        }
+    }
+
+    /// Drops the sessions still marked stale, returning whether there were any.
+    pub fn drop_stale(&mut self) -> bool {
+        let before = self.sessions.len();
+        self.sessions.retain(|session| !session.stale);
+        self.changed(before)
+    }
+
+    /// Whether there are no longer `before` sessions, moving on to the next generation if so.
+    fn changed(&mut self, before: usize) -> bool {
+        let changed = self.sessions.len() != before;
+        if changed {
+            self.generation += 1;
+        }
+        changed
+    }
+
+    /// Whether the number of scans an enrollment on the device needs is known yet.
+    pub fn has_stages(&self, device: &Path) -> bool {
+        self.sessions.iter().any(|session| {
+            session.device == *device
+                && session.kind == SessionKind::Enroll
+                && session.stages.is_some()
+        })
+    }
+
+    pub fn set_stages(&mut self, device: &Path, total: u32) {
+        for session in &mut self.sessions {
+            if session.device == *device && session.kind == SessionKind::Enroll {
+                session.stages = Some((0, total));
+            }
+        }
+    }
+
+    /// Notes a scan that counted towards an enrollment.
+    pub fn stage_passed(&mut self, device: &Path) {
+        for session in &mut self.sessions {
+            if session.device == *device && session.kind == SessionKind::Enroll {
+                if let Some((passed, total)) = &mut session.stages {
+                    *passed = (*passed + 1).min(*total);
+                }
+            }
+        }
+    }
+
+    /// How far along the most recent session of a kind is, from 0 to 1, if that's known.
+    pub fn progress(&self, kind: SessionKind) -> Option<f32> {
+        self.sessions
+            .iter()
+            .filter(|session| session.kind == kind)
+            .max_by_key(|session| session.started)?
+            .stages
+            .map(|(passed, total)| passed as f32 / total as f32)
+    }
+
+    /// The longest any verification has gone without the reader being touched. Only readers that
+    /// `reports_touches` are counted, since the others could be in use anyway.
+    pub fn ignored_for(&self, reports_touches: impl Fn(&Path) -> bool) -> Option<Duration> {
+        self.sessions
+            .iter()
+            .filter(|session| {
+                session.kind == SessionKind::Verify && reports_touches(&session.device)
+            })
+            .map(|session| session.touched.elapsed())
+            .max()
+    }
+
+    /// Every session, oldest first.
+    /// Changes whenever [`Prompts::sessions`] might have, for those that show them elsewhere.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn sessions(&self) -> &[ActiveSession] {
+        &self.sessions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// The session to describe to the user: the highest priority one, and the oldest among those.
+    pub fn primary(&self) -> Option<&ActiveSession> {
+        self.sessions
+            .iter()
+            .rev()
+            .max_by_key(|session| session.kind)
+    }
+
+    /// When the most recent session of a kind started.
+    pub fn last_started(&self, kind: SessionKind) -> Option<Instant> {
+        self.sessions
+            .iter()
+            .filter(|session| session.kind == kind)
+            .map(|session| session.started)
+            .max()
+    }
+
+    /// How long ago a device's session of a kind started.
+    pub fn age(&self, device: &Path, kind: SessionKind) -> Option<Duration> {
+        self.sessions
+            .iter()
+            .find(|session| session.device == *device && session.kind == kind)
+            .map(|session| session.started.elapsed())
+    }
+
+    /// The most recently started session of a kind.
+    pub fn latest(&self, kind: SessionKind) -> Option<&ActiveSession> {
+        self.sessions
+            .iter()
+            .filter(|session| session.kind == kind)
+            .max_by_key(|session| session.started)
+    }
+
+    /// One indicator per kind of session, highest priority first. Sessions of the same kind on
+    /// different readers are merged, since the user can only act on them the same way.
+    pub fn indicators(&self) -> impl Iterator<Item = SessionKind> + '_ {
+        [SessionKind::Verify, SessionKind::Enroll]
+            .into_iter()
+            .filter(|&kind| self.sessions.iter().any(|session| session.kind == kind))
+    }
+}
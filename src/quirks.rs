@@ -0,0 +1,118 @@
+// Differences between compositors.
+//
+// The compositor is guessed from the environment, and picks the defaults for how the surface is
+// set up. Anything we get wrong, or any compositor we don't know, can be fixed up in the
+// `[quirks]` section of the config.
+
+use smithay_client_toolkit::shell::wlr_layer::Layer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compositor {
+    Sway,
+    Hyprland,
+    Kwin,
+    Niri,
+    River,
+    Other,
+}
+
+impl Compositor {
+    pub fn detect() -> Compositor {
+        // Sockets are only set by the compositor itself, while XDG_CURRENT_DESKTOP may be set to
+        // something else for portal or theming reasons.
+        let sockets = [
+            ("HYPRLAND_INSTANCE_SIGNATURE", Compositor::Hyprland),
+            ("NIRI_SOCKET", Compositor::Niri),
+            ("SWAYSOCK", Compositor::Sway),
+        ];
+        if let Some((_, compositor)) = sockets
+            .into_iter()
+            .find(|(var, _)| std::env::var_os(var).is_some())
+        {
+            return compositor;
+        }
+
+        let desktops = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+        desktops
+            .split(':')
+            .find_map(|desktop| match desktop.to_ascii_lowercase().as_str() {
+                "sway" => Some(Compositor::Sway),
+                "hyprland" => Some(Compositor::Hyprland),
+                "kde" => Some(Compositor::Kwin),
+                "niri" => Some(Compositor::Niri),
+                "river" => Some(Compositor::River),
+                _ => None,
+            })
+            .unwrap_or(Compositor::Other)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compositor::Sway => "sway",
+            Compositor::Hyprland => "Hyprland",
+            Compositor::Kwin => "KWin",
+            Compositor::Niri => "niri",
+            Compositor::River => "river",
+            Compositor::Other => "an unknown compositor",
+        }
+    }
+}
+
+/// How the buffer is matched to the output's scale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleStrategy {
+    /// Follow wp_fractional_scale_v1.
+    Fractional,
+    /// Follow the whole-number scale of the output the surface is on.
+    Integer,
+    /// Always draw at scale 1, letting the compositor scale the buffer up.
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    pub layer: Layer,
+    /// Compositors key layer rules on this.
+    pub namespace: String,
+    pub scale: ScaleStrategy,
+}
+
+/// Quirks set in the config, taking precedence over the compositor's defaults.
+#[derive(Clone, Default)]
+pub struct QuirkOverrides {
+    pub layer: Option<Layer>,
+    pub namespace: Option<String>,
+    pub scale: Option<ScaleStrategy>,
+}
+
+impl Quirks {
+    pub fn new(compositor: Compositor, overrides: &QuirkOverrides) -> Quirks {
+        let mut quirks = Quirks {
+            layer: Layer::Overlay,
+            namespace: "fprint-prompt".to_string(),
+            scale: ScaleStrategy::Fractional,
+        };
+
+        match compositor {
+            // river only has whole-number output scales, and no fractional scale protocol.
+            Compositor::River => quirks.scale = ScaleStrategy::Integer,
+            Compositor::Sway
+            | Compositor::Hyprland
+            | Compositor::Kwin
+            | Compositor::Niri
+            | Compositor::Other => (),
+        }
+
+        if let Some(layer) = overrides.layer {
+            quirks.layer = layer;
+        }
+        if let Some(namespace) = &overrides.namespace {
+            quirks.namespace = namespace.clone();
+        }
+        if let Some(scale) = overrides.scale {
+            quirks.scale = scale;
+        }
+
+        quirks
+    }
+}
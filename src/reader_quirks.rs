@@ -0,0 +1,95 @@
+// Differences between fingerprint readers.
+//
+// Match-on-chip sensors, like Goodix's and ELAN's through libfprint-tod, do the matching on the
+// reader and tell fprintd little about it: most never report a retry, only the final result. The
+// reader is recognised by the name its driver gives it, which picks the defaults, and anything we
+// get wrong can be fixed up in its `[device."Name"]` section of the config.
+
+use std::collections::HashMap;
+
+use dbus::{ffidisp::Connection as DbusConnection, Path};
+
+use crate::{config::Config, fprint::Device};
+
+/// Words in a driver's device name that mean the matching happens on the chip.
+const MATCH_ON_CHIP: &[&str] = &["MOC", "Match-on-Chip", "Match on Chip"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReaderQuirks {
+    /// Whether the reader reports a retry when a finger doesn't scan well. Without them there's
+    /// no telling whether the user is trying, so the bar never grows to get noticed.
+    pub retries: bool,
+    /// Seconds a verification waits for a finger, if it's not `[attention]`'s timeout.
+    pub timeout: Option<u32>,
+}
+
+/// Quirks set in the config for one reader, taking precedence over its defaults.
+#[derive(Clone, Default)]
+pub struct ReaderQuirkOverrides {
+    pub retries: Option<bool>,
+    pub timeout: Option<u32>,
+}
+
+impl Default for ReaderQuirks {
+    fn default() -> Self {
+        ReaderQuirks {
+            retries: true,
+            timeout: None,
+        }
+    }
+}
+
+impl ReaderQuirks {
+    /// The quirks for a reader with the name its driver gave it.
+    pub fn new(name: &str, overrides: Option<&ReaderQuirkOverrides>) -> ReaderQuirks {
+        let mut quirks = ReaderQuirks::default();
+
+        if is_match_on_chip(name) {
+            quirks.retries = false;
+        }
+
+        if let Some(overrides) = overrides {
+            if let Some(retries) = overrides.retries {
+                quirks.retries = retries;
+            }
+            if let Some(timeout) = overrides.timeout {
+                quirks.timeout = Some(timeout);
+            }
+        }
+
+        quirks
+    }
+
+    /// Looks up the reader's name on fprintd. Readers whose name can't be read get the defaults.
+    pub fn for_device(
+        dbus: &DbusConnection,
+        config: &Config,
+        device: &Path<'static>,
+    ) -> ReaderQuirks {
+        match Device::new(dbus, device.clone()).name() {
+            Ok(name) => ReaderQuirks::new(&name, config.device_quirks.get(&name)),
+            Err(e) => {
+                log::warn!("could not get the name of {device}: {e}");
+                ReaderQuirks::default()
+            }
+        }
+    }
+}
+
+/// Looks up the quirks of a reader the first time it prompts.
+pub fn learn(
+    known: &mut HashMap<Path<'static>, ReaderQuirks>,
+    dbus: &DbusConnection,
+    config: &Config,
+    device: &Path<'static>,
+) {
+    if !known.contains_key(device) {
+        let quirks = ReaderQuirks::for_device(dbus, config, device);
+        log::info!("Quirks of {device}: {quirks:?}");
+        known.insert(device.clone(), quirks);
+    }
+}
+
+pub fn is_match_on_chip(name: &str) -> bool {
+    MATCH_ON_CHIP.iter().any(|words| name.contains(words))
+}
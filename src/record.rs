@@ -0,0 +1,230 @@
+// `--record <file>` writes every fprintd signal the overlay acts on to a file, with when it came
+// in. `--replay <file>` feeds them back in at the same pace instead of listening to fprintd, so a
+// visual bug someone reported can be watched again on any machine.
+//
+// One JSON object per line, like
+// `{"at":1520,"device":"/net/reactivated/Fprint/Device/0","signal":"VerifyStatus","result":"verify-no-match","done":true}`.
+// Outputs aren't recorded: the replay is drawn on whatever outputs are connected.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path as FsPath, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use dbus::Path;
+use fprint_prompt::rpc::Json;
+
+use crate::signals::FprintEvent;
+
+static RECORD_TO: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn enable(path: PathBuf) {
+    let _ = RECORD_TO.set(path);
+}
+
+pub struct Recorder {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Starts recording if `--record` was given.
+    pub fn start() -> Option<Recorder> {
+        let path = RECORD_TO.get()?;
+        match Recorder::create(path) {
+            Ok(recorder) => {
+                log::info!("Recording fprintd signals to {}", path.display());
+                Some(recorder)
+            }
+            Err(e) => {
+                log::warn!("could not record to {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    fn create(path: &FsPath) -> io::Result<Recorder> {
+        Ok(Recorder {
+            file: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, device: &Path, event: &FprintEvent) {
+        let mut fields = vec![
+            (
+                "at",
+                Json::Number(self.started.elapsed().as_millis() as f64),
+            ),
+            ("device", Json::String(device.to_string())),
+        ];
+        match event {
+            FprintEvent::VerifyFingerSelected { finger_name } => fields.extend([
+                ("signal", Json::String("VerifyFingerSelected".to_string())),
+                ("finger", Json::string_or_null(finger_name.as_deref())),
+            ]),
+            FprintEvent::VerifyStatus { result, done } => fields.extend([
+                ("signal", Json::String("VerifyStatus".to_string())),
+                ("result", Json::string_or_null(result.as_deref())),
+                ("done", done.map_or(Json::Null, Json::Bool)),
+            ]),
+            FprintEvent::EnrollStatus { result, done } => fields.extend([
+                ("signal", Json::String("EnrollStatus".to_string())),
+                ("result", Json::string_or_null(result.as_deref())),
+                ("done", done.map_or(Json::Null, Json::Bool)),
+            ]),
+        }
+
+        // Flushed every time, so nothing is lost when the overlay is killed.
+        let written =
+            writeln!(self.file, "{}", Json::object(fields)).and_then(|()| self.file.flush());
+        if let Err(e) = written {
+            log::warn!("could not record a signal: {e}");
+        }
+    }
+}
+
+pub struct Replay {
+    events: Vec<(Duration, Path<'static>, FprintEvent)>,
+    next: usize,
+    started: Instant,
+}
+
+impl Replay {
+    pub fn load(path: &FsPath) -> Result<Replay, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+        let mut events = vec![];
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = parse_line(line).ok_or_else(|| format!("line {} is broken", index + 1))?;
+            events.push(event);
+        }
+
+        Ok(Replay {
+            events,
+            next: 0,
+            started: Instant::now(),
+        })
+    }
+
+    /// The next signal if it's due, otherwise waits up to `timeout_ms` for it.
+    pub fn next(&mut self, timeout_ms: u32) -> Option<(Path<'static>, FprintEvent)> {
+        let (at, _, _) = self.events.get(self.next)?;
+
+        let wait = at.saturating_sub(self.started.elapsed());
+        if !wait.is_zero() {
+            std::thread::sleep(wait.min(Duration::from_millis(timeout_ms.into())));
+            return None;
+        }
+
+        let (_, device, event) = self.events[self.next].clone();
+        self.next += 1;
+        Some((device, event))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(Duration, Path<'static>, FprintEvent)> {
+    let json = Json::parse(line)?;
+    let string = |key: &str| match json.get(key)? {
+        Json::String(value) => Some(Some(value.clone())),
+        Json::Null => Some(None),
+        _ => None,
+    };
+    let done = match json.get("done") {
+        Some(Json::Bool(done)) => Some(*done),
+        _ => None,
+    };
+
+    let at = json.get("at")?.as_f64().filter(|at| *at >= 0.)?;
+    let at = Duration::from_millis(at as u64);
+    let device = Path::new(string("device")??).ok()?;
+    let event = match string("signal")??.as_str() {
+        "VerifyFingerSelected" => FprintEvent::VerifyFingerSelected {
+            finger_name: string("finger")?,
+        },
+        "VerifyStatus" => FprintEvent::VerifyStatus {
+            result: string("result")?,
+            done,
+        },
+        "EnrollStatus" => FprintEvent::EnrollStatus {
+            result: string("result")?,
+            done,
+        },
+        _ => return None,
+    };
+
+    Some((at, device, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let path =
+            std::env::temp_dir().join(format!("fprint-prompt-record-{}", std::process::id()));
+        let device = Path::new("/net/reactivated/Fprint/Device/0").unwrap();
+        let events = [
+            FprintEvent::VerifyFingerSelected {
+                finger_name: Some("right-index-finger".to_string()),
+            },
+            FprintEvent::VerifyStatus {
+                result: Some("verify-retry-scan".to_string()),
+                done: None,
+            },
+            FprintEvent::EnrollStatus {
+                result: Some("a \"quoted\"\nresult".to_string()),
+                done: Some(false),
+            },
+            FprintEvent::VerifyStatus {
+                result: None,
+                done: Some(true),
+            },
+        ];
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        for event in &events {
+            recorder.record(&device, event);
+        }
+        drop(recorder);
+        let replay = Replay::load(&path);
+        let _ = std::fs::remove_file(&path);
+
+        let replayed: Vec<_> = replay
+            .unwrap()
+            .events
+            .into_iter()
+            .map(|(_, device, event)| (device, event))
+            .collect();
+        let recorded: Vec<_> = events
+            .into_iter()
+            .map(|event| (device.clone(), event))
+            .collect();
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    fn rejects_broken_lines() {
+        for line in [
+            "",
+            "{}",
+            r#"{"at":-1,"device":"/a","signal":"VerifyStatus","result":null,"done":true}"#,
+            r#"{"at":0,"device":"not a path","signal":"VerifyStatus","result":null}"#,
+            r#"{"at":0,"device":"/a","signal":"Unknown"}"#,
+            r#"{"at":0,"device":"/a","signal":"VerifyStatus","result":1}"#,
+        ] {
+            assert!(parse_line(line).is_none(), "{line:?}");
+        }
+    }
+}
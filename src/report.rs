@@ -0,0 +1,276 @@
+// `fprint-prompt report [FILE]`: walks through enrolling a spare finger and verifying it, and
+// writes what the reader is and every signal it sent along the way to a JSON file, to attach to an
+// issue about a reader that needs a quirk.
+//
+// The username is left out, and so are the names of the fingers already enrolled. The spare
+// finger is deleted again at the end where fprintd can.
+
+use std::time::{Duration, Instant};
+
+use fprint_prompt::{
+    fprint::{self, Device},
+    rpc::Json,
+    FprintEvent, FprintMonitor, MonitorEvent,
+};
+
+use crate::{bus, exit, hwdb, reader_quirks, sandbox};
+
+const DEFAULT_FILE: &str = "fprint-prompt-report.json";
+
+/// How long a step can go without hearing from the reader before it's given up on.
+const STEP_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn run(args: &[String]) -> i32 {
+    let file = args.first().map_or(DEFAULT_FILE, String::as_str);
+
+    let bus = bus::selected();
+    let monitor = match bus.connect().and_then(FprintMonitor::with_connection) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("could not connect to {bus}: {e}");
+            return exit::FAILURE;
+        }
+    };
+    let dbus = monitor.connection();
+
+    let device = match fprint::get_default_device(dbus) {
+        Ok(device) => device,
+        Err(e) => {
+            eprintln!("no fingerprint reader available: {e}");
+            if let Some(hint) = sandbox::permission_hint() {
+                eprintln!("{hint}");
+            }
+            return exit::FAILURE;
+        }
+    };
+
+    let username = fprint::current_username();
+    let mut report = Report {
+        started: Instant::now(),
+        username: username.clone(),
+        signals: vec![],
+    };
+
+    let enrolled = match device.list_enrolled_fingers(&username) {
+        Ok(enrolled) => enrolled,
+        Err(e) => {
+            eprintln!(
+                "could not list the enrolled fingers on {}: {e}",
+                device.path
+            );
+            return exit::FAILURE;
+        }
+    };
+    let Some(finger) = fprint::FINGER_NAMES
+        .into_iter()
+        .find(|finger| !enrolled.iter().any(|enrolled| enrolled == finger))
+    else {
+        eprintln!("every finger is enrolled already; delete one with fprintd-delete first");
+        return exit::FAILURE;
+    };
+
+    if let Err(e) = device.claim(&username) {
+        eprintln!("could not claim {}: {e}", device.path);
+        return exit::FAILURE;
+    }
+
+    let label = finger.replace('-', " ");
+    println!("This enrolls your {label} and deletes it again at the end.");
+    println!("Keep touching the sensor with it until it's enrolled...");
+    let enroll = report.step(&monitor, &device, finger, |device, finger| {
+        device.enroll_start(finger)
+    });
+    let _ = device.enroll_stop();
+
+    let verify = if enroll.get("result").and_then(Json::as_str) == Some("enroll-completed") {
+        println!("Now touch the sensor with it once more to verify...");
+        let verify = report.step(&monitor, &device, finger, |device, finger| {
+            device.verify_start(finger)
+        });
+        let _ = device.verify_stop();
+        verify
+    } else {
+        println!("Enrolling didn't complete, so verifying is skipped.");
+        Json::Null
+    };
+
+    let cleanup = match device.delete_enrolled_finger(finger) {
+        Ok(()) => Json::String("deleted".to_string()),
+        Err(e) => {
+            eprintln!("could not delete the {label} again, `fprintd-delete` can: {e}");
+            report.error(&e)
+        }
+    };
+    let _ = device.release();
+
+    let bundle = Json::object([
+        (
+            "fprint_prompt",
+            Json::String(env!("CARGO_PKG_VERSION").to_string()),
+        ),
+        ("machine", machine()),
+        ("reader", report.reader(&device)),
+        ("enrolled_fingers", Json::Number(enrolled.len() as f64)),
+        ("enroll", enroll),
+        ("verify", verify),
+        ("cleanup", cleanup),
+        ("signals", Json::Array(report.signals)),
+    ]);
+
+    if let Err(e) = std::fs::write(file, format!("{bundle}\n")) {
+        eprintln!("could not write {file}: {e}");
+        return exit::FAILURE;
+    }
+    println!("Wrote the report to {file}. Have a look at it before attaching it to an issue.");
+    exit::SUCCESS
+}
+
+struct Report {
+    started: Instant,
+    /// Taken out of error messages.
+    username: String,
+    signals: Vec<Json>,
+}
+
+impl Report {
+    fn at(&self) -> Json {
+        Json::Number(self.started.elapsed().as_millis() as f64)
+    }
+
+    fn redact(&self, text: &str) -> String {
+        if self.username.is_empty() {
+            return text.to_string();
+        }
+        text.replace(&self.username, "<user>")
+    }
+
+    fn error(&self, error: &dbus::Error) -> Json {
+        Json::object([("error", Json::String(self.redact(&error.to_string())))])
+    }
+
+    /// Each property, or why it couldn't be read.
+    fn reader(&self, device: &Device) -> Json {
+        let name = device.name();
+        let match_on_chip = match &name {
+            Ok(name) => Json::Bool(reader_quirks::is_match_on_chip(name)),
+            Err(_) => Json::Null,
+        };
+        let property = |value: Result<Json, dbus::Error>| value.unwrap_or_else(|e| self.error(&e));
+
+        Json::object([
+            ("path", Json::String(device.path.to_string())),
+            ("name", property(name.map(Json::String))),
+            ("scan_type", property(device.scan_type().map(Json::String))),
+            (
+                "enroll_stages",
+                property(
+                    device.enroll_stages().map(|stages| {
+                        stages.map_or(Json::Null, |stages| Json::Number(stages.into()))
+                    }),
+                ),
+            ),
+            (
+                "finger_needed",
+                property(device.finger_needed().map(Json::Bool)),
+            ),
+            ("match_on_chip", match_on_chip),
+        ])
+    }
+
+    /// Starts a verification or enrollment and records its signals until it's done.
+    fn step(
+        &mut self,
+        monitor: &FprintMonitor,
+        device: &Device,
+        finger: &str,
+        start: impl FnOnce(&Device, &str) -> Result<(), dbus::Error>,
+    ) -> Json {
+        let started_at = self.at();
+        if let Err(e) = start(device, finger) {
+            eprintln!("could not start: {e}");
+            return self.error(&e);
+        }
+
+        let mut last = None;
+        let ended = loop {
+            let Some((path, event)) = monitor.next_timeout(STEP_TIMEOUT) else {
+                println!("Nothing happened for {} seconds.", STEP_TIMEOUT.as_secs());
+                break "timeout";
+            };
+            if path != device.path && !matches!(event, MonitorEvent::FprintdStopped) {
+                continue;
+            }
+            self.signals.push(self.signal(&event));
+
+            match event {
+                MonitorEvent::Fprint(
+                    FprintEvent::VerifyStatus { result, done }
+                    | FprintEvent::EnrollStatus { result, done },
+                ) => {
+                    if let Some(result) = &result {
+                        println!("  {result}");
+                    }
+                    last = result;
+                    if done == Some(true) {
+                        break "done";
+                    }
+                }
+                MonitorEvent::DeviceRemoved => break "unplugged",
+                MonitorEvent::FprintdStopped => break "fprintd-stopped",
+                _ => (),
+            }
+        };
+
+        Json::object([
+            ("started_at", started_at),
+            ("ended", Json::String(ended.to_string())),
+            ("result", Json::string_or_null(last.as_deref())),
+        ])
+    }
+
+    fn signal(&self, event: &MonitorEvent) -> Json {
+        let mut fields = vec![("at", self.at())];
+        match event {
+            MonitorEvent::Fprint(FprintEvent::VerifyFingerSelected { finger_name }) => {
+                fields.push(("signal", Json::String("VerifyFingerSelected".to_string())));
+                fields.push(("finger", Json::string_or_null(finger_name.as_deref())));
+            }
+            MonitorEvent::Fprint(FprintEvent::VerifyStatus { result, done }) => {
+                fields.push(("signal", Json::String("VerifyStatus".to_string())));
+                fields.push(("result", Json::string_or_null(result.as_deref())));
+                fields.push(("done", done.map_or(Json::Null, Json::Bool)));
+            }
+            MonitorEvent::Fprint(FprintEvent::EnrollStatus { result, done }) => {
+                fields.push(("signal", Json::String("EnrollStatus".to_string())));
+                fields.push(("result", Json::string_or_null(result.as_deref())));
+                fields.push(("done", done.map_or(Json::Null, Json::Bool)));
+            }
+            MonitorEvent::FingerNeeded(needed) => {
+                fields.push(("property", Json::String("finger-needed".to_string())));
+                fields.push(("value", Json::Bool(*needed)));
+            }
+            MonitorEvent::FingerPresent(present) => {
+                fields.push(("property", Json::String("finger-present".to_string())));
+                fields.push(("value", Json::Bool(*present)));
+            }
+            MonitorEvent::DeviceAdded => {
+                fields.push(("lifecycle", Json::String("added".to_string())));
+            }
+            MonitorEvent::DeviceRemoved => {
+                fields.push(("lifecycle", Json::String("removed".to_string())));
+            }
+            MonitorEvent::FprintdStopped => {
+                fields.push(("lifecycle", Json::String("fprintd-stopped".to_string())));
+            }
+        }
+        Json::object(fields)
+    }
+}
+
+/// What the quirks are matched on, besides the reader's name.
+fn machine() -> Json {
+    Json::object(
+        ["sys_vendor", "product_name", "product_family"]
+            .map(|field| (field, Json::String(hwdb::read_dmi(field)))),
+    )
+}
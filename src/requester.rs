@@ -0,0 +1,257 @@
+// The process that asked for a fingerprint, like sudo, so the prompt can go when it does. When
+// sudo is Ctrl-C'd, pam_fprintd never gets to stop the verification: fprintd drops the claim once
+// the connection goes, but sends no last VerifyStatus, which leaves the bar up.
+//
+// fprintd doesn't say who claimed a reader, so the requester is only a guess: the one process of
+// ours that connected to the bus lately, pam_fprintd connecting just before it claims. sudo and
+// polkit's helper are setuid, but their real uid is still the user's. With several such
+// processes there's no guessing, and a verification for anyone else, like on a greeter, has no
+// requester we can tell.
+//
+// A guess isn't enough to end a prompt on, so once the requester exits, fprintd is asked whether
+// the reader still wants a finger. Only if it doesn't, was the verification the requester's.
+//
+// Each requester is watched with a pidfd, or checked on every turn on kernels without them.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    time::{Duration, Instant},
+};
+
+use dbus::{ffidisp::Connection as DbusConnection, MessageType, Path};
+use fprint_prompt::{bus, fprint};
+
+const DBUS: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const TIMEOUT_MS: i32 = 1000;
+
+/// How long before a verification starts its requester can have connected.
+const WINDOW: Duration = Duration::from_secs(5);
+/// How long after its requester exits fprintd has to notice and stop wanting a finger.
+const GRACE: Duration = Duration::from_secs(2);
+
+/// Who asked for a verification, as far as can be told.
+pub struct Process {
+    pub pid: u32,
+    pub uid: libc::uid_t,
+    /// The name the kernel knows it by, like `sudo`, read when the verification started.
+    pub command: Option<String>,
+}
+
+struct Requester {
+    process: Process,
+    /// None where pidfds aren't supported.
+    pidfd: Option<OwnedFd>,
+    /// When it was first seen to have exited.
+    exited_at: Option<Instant>,
+}
+
+impl Requester {
+    fn exited(&self) -> bool {
+        match &self.pidfd {
+            Some(pidfd) => {
+                let mut fd = libc::pollfd {
+                    fd: pidfd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                unsafe { libc::poll(&mut fd, 1, 0) > 0 }
+            }
+            None => {
+                let alive = unsafe { libc::kill(self.process.pid as libc::pid_t, 0) } == 0;
+                !alive && std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+            }
+        }
+    }
+}
+
+pub struct Requesters {
+    /// Follows who connects to the bus fprintd is on.
+    dbus: Option<DbusConnection>,
+    /// Unique names that connected lately, oldest first.
+    connected: VecDeque<(String, Instant)>,
+    requesters: HashMap<Path<'static>, Requester>,
+}
+
+impl Requesters {
+    pub fn new() -> Requesters {
+        let dbus = bus::selected()
+            .connect()
+            .and_then(|dbus| {
+                dbus.add_match(&format!(
+                    "type='signal',sender='{DBUS}',member='NameOwnerChanged',arg1=''"
+                ))?;
+                Ok(dbus)
+            })
+            .map_err(|e| log::warn!("could not follow who asks for fingerprints: {e}"))
+            .ok();
+
+        Requesters {
+            dbus,
+            connected: VecDeque::new(),
+            requesters: HashMap::new(),
+        }
+    }
+
+    /// Catches up on the bus without blocking.
+    fn catch_up(&mut self) {
+        let Some(dbus) = &self.dbus else {
+            return;
+        };
+        while let Some(msg) = dbus.incoming(0).next() {
+            if msg.msg_type() != MessageType::Signal
+                || msg.member().as_deref() != Some("NameOwnerChanged")
+            {
+                continue;
+            }
+            if let (Some(name), Some("")) = msg.get2::<String, &str>() {
+                if name.starts_with(':') {
+                    self.connected.push_back((name, Instant::now()));
+                }
+            }
+        }
+
+        while self
+            .connected
+            .front()
+            .is_some_and(|(_, at)| at.elapsed() > WINDOW)
+        {
+            self.connected.pop_front();
+        }
+    }
+
+    /// Looks for the process behind a verification that just started on `device`, to watch.
+    pub fn track(&mut self, device: &Path<'static>) {
+        self.catch_up();
+        if self
+            .requesters
+            .get(device)
+            .is_some_and(|requester| !requester.exited())
+        {
+            return;
+        }
+        self.requesters.remove(device);
+
+        let Some(dbus) = &self.dbus else {
+            return;
+        };
+        let uid = unsafe { libc::getuid() };
+        let mut candidates: Vec<u32> = self
+            .connected
+            .iter()
+            .filter_map(|(name, _)| {
+                let (pid,): (u32,) = dbus
+                    .with_path(DBUS, DBUS_PATH, TIMEOUT_MS)
+                    .method_call(DBUS, "GetConnectionUnixProcessID", (name.as_str(),))
+                    .ok()?;
+                (pid != std::process::id() && real_uid(pid)? == uid).then_some(pid)
+            })
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        let [pid] = candidates[..] else {
+            log::info!(
+                "Could not tell which process is verifying on {device}: {} could be",
+                candidates.len()
+            );
+            return;
+        };
+
+        let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        let pidfd = (pidfd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(pidfd as RawFd) });
+        log::info!("Verifying on {device} for process {pid}");
+        let command = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+            .ok()
+            .map(|comm| comm.trim_end().to_string());
+        let process = Process { pid, uid, command };
+        self.requesters.insert(
+            device.clone(),
+            Requester {
+                process,
+                pidfd,
+                exited_at: None,
+            },
+        );
+    }
+
+    /// Who asked for the verification on `device`, if it's known.
+    pub fn requester(&self, device: &Path<'static>) -> Option<&Process> {
+        self.requesters
+            .get(device)
+            .map(|requester| &requester.process)
+    }
+
+    /// Stops watching the requester of a verification that ended.
+    pub fn forget(&mut self, device: &Path<'static>) {
+        self.requesters.remove(device);
+    }
+
+    /// The devices whose requester exited since the last poll while they no longer want a
+    /// finger, with who that was. Requesters that exited while their reader still wants one
+    /// weren't verifying there after all.
+    pub fn poll(&mut self) -> Vec<(Path<'static>, Process)> {
+        self.catch_up();
+        let Some(dbus) = &self.dbus else {
+            return vec![];
+        };
+
+        let mut abandoned = vec![];
+        self.requesters.retain(|device, requester| {
+            if requester.exited_at.is_none() && requester.exited() {
+                requester.exited_at = Some(Instant::now());
+            }
+            let Some(exited_at) = requester.exited_at else {
+                return true;
+            };
+
+            let pid = requester.process.pid;
+            match fprint::Device::new(dbus, device.clone()).finger_needed() {
+                Ok(false) => {
+                    log::info!("Process {pid} exited, ending the verification on {device}");
+                    abandoned.push(device.clone());
+                    true
+                }
+                // fprintd might not have noticed yet.
+                Ok(true) if exited_at.elapsed() < GRACE => true,
+                Ok(true) => {
+                    log::info!("Process {pid} exited, but {device} still wants a finger");
+                    false
+                }
+                Err(e) => {
+                    log::info!(
+                        "Process {pid} exited, but {device} can't say if it wants a finger: {e}"
+                    );
+                    false
+                }
+            }
+        });
+        abandoned
+            .into_iter()
+            .filter_map(|device| {
+                let requester = self.requesters.remove(&device)?;
+                Some((device, requester.process))
+            })
+            .collect()
+    }
+
+    /// To wake up for, when a requester exits.
+    pub fn watch_fds(&self) -> impl Iterator<Item = RawFd> + '_ {
+        self.requesters
+            .values()
+            .filter(|requester| requester.exited_at.is_none())
+            .filter_map(|requester| requester.pidfd.as_ref().map(AsRawFd::as_raw_fd))
+    }
+}
+
+/// The real uid a process runs as, if it can be read.
+fn real_uid(pid: u32) -> Option<libc::uid_t> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
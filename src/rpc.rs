@@ -0,0 +1,673 @@
+//! The JSON-RPC 2.0 protocol spoken on fprint-prompt's control socket, for widgets and scripts
+//! that would rather not use D-Bus.
+//!
+//! The socket is `$XDG_RUNTIME_DIR/fprint-prompt.sock`, or `fprint-prompt-NAME.sock` for an
+//! instance started with `--instance NAME`. Requests and responses are one JSON object per line,
+//! and batches aren't supported.
+//!
+//! | Method      | Result    | What it does                                                    |
+//! |-------------|-----------|-----------------------------------------------------------------|
+//! | `show`      | `true`    | Shows prompts again after `hide`.                               |
+//! | `hide`      | `true`    | Stops showing prompts. fprintd is still followed.               |
+//! | `get_state` | a [State] | The current state.                                              |
+//! | `subscribe` | a [State] | The current state, and a `state` notification whenever it changes. |
+//!
+//! A [State] looks like
+//! `{"visible":true,"profile":null,"prompts":[{"kind":"verify","text":"Use your right index","device":"/net/reactivated/Fprint/Device/0"}],"surfaces":[{"output":"eDP-1","x":1200,"y":0,"width":60,"height":8,"drawn":true}]}`.
+//! Each of the `surfaces` is where the bar is on an output, in the compositor's logical
+//! coordinates across all outputs, as tools like grim and slurp take them:
+//! `grim -g "1200,0 60x8"` screenshots it.
+//!
+//! Notifications look like `{"jsonrpc":"2.0","method":"state","params":{...}}`. Errors use the
+//! codes from the spec, like -32601 for an unknown method.
+//!
+//! ```no_run
+//! use std::io::{BufRead, BufReader, Write};
+//! use std::os::unix::net::UnixStream;
+//!
+//! use fprint_prompt::rpc::{Method, Request, State};
+//!
+//! let path = std::env::var("XDG_RUNTIME_DIR").unwrap() + "/fprint-prompt.sock";
+//! let mut socket = UnixStream::connect(path).unwrap();
+//! writeln!(socket, "{}", Request::new(1, Method::Subscribe).to_json()).unwrap();
+//! for line in BufReader::new(socket).lines() {
+//!     if let Some(state) = State::from_message(&line.unwrap()) {
+//!         println!("{} prompts", state.prompts.len());
+//!     }
+//! }
+//! ```
+
+use std::{
+    fmt::{self, Write},
+    iter::Peekable,
+    str::Chars,
+};
+
+pub const VERSION: &str = "2.0";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+/// How deep arrays and objects can nest, so a line of brackets can't exhaust the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Any JSON value. Objects keep their keys in order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// None unless `text` is exactly one JSON value, give or take whitespace.
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        chars.next().is_none().then_some(value)
+    }
+
+    /// A member of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// An object with the members in the given order.
+    pub fn object(members: impl IntoIterator<Item = (&'static str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string_or_null(value: Option<&str>) -> Json {
+        value.map_or(Json::Null, |value| Json::String(value.to_string()))
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            // Ids are usually whole numbers, which shouldn't come back as 1.0.
+            Json::Number(value) if value.fract() == 0. && value.abs() < 1e15 => {
+                write!(f, "{}", *value as i64)
+            }
+            Json::Number(value) if value.is_finite() => write!(f, "{value}"),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(value) => f.write_str(&json_string(Some(value))),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}:{value}", json_string(Some(name)))?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// A JSON string literal, or null.
+pub fn json_string(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "null".to_string();
+    };
+
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Option<Json> {
+    skip_whitespace(chars);
+    let nested = matches!(chars.peek()?, '{' | '[');
+    if nested && depth >= MAX_DEPTH {
+        return None;
+    }
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut members = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Some(Json::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                if chars.next()? != '"' {
+                    return None;
+                }
+                let name = parse_string(chars)?;
+                skip_whitespace(chars);
+                if chars.next()? != ':' {
+                    return None;
+                }
+                members.push((name, parse_value(chars, depth + 1)?));
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    '}' => return Some(Json::Object(members)),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut values = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars, depth + 1)?);
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::String)
+        }
+        '-' | '0'..='9' => {
+            let mut number = String::new();
+            while let Some(c) =
+                chars.next_if(|c| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+            {
+                number.push(c);
+            }
+            number.parse().ok().map(Json::Number)
+        }
+        _ => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                "null" => Some(Json::Null),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The rest of a string whose opening quote has been read.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => match chars.next()? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'u' => {
+                    let mut code = parse_hex(chars)?;
+                    // Outside the BMP, as a surrogate pair.
+                    if (0xD800..0xDC00).contains(&code) {
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = parse_hex(chars)?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return None;
+                        }
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    }
+                    string.push(char::from_u32(code)?);
+                }
+                c @ ('"' | '\\' | '/') => string.push(c),
+                _ => return None,
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let hex: String = chars.by_ref().take(4).collect();
+    if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Show,
+    Hide,
+    GetState,
+    Subscribe,
+}
+
+impl Method {
+    pub const ALL: [Method; 4] = [
+        Method::Show,
+        Method::Hide,
+        Method::GetState,
+        Method::Subscribe,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Show => "show",
+            Method::Hide => "hide",
+            Method::GetState => "get_state",
+            Method::Subscribe => "subscribe",
+        }
+    }
+}
+
+/// A call to one of the [Method]s. Without an id it's a notification, which isn't answered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub id: Option<Json>,
+    pub method: Method,
+}
+
+/// Why a request couldn't be handled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// The error response to a request with `id`, which is null when it couldn't be read.
+    pub fn response(&self, id: Option<&Json>) -> Json {
+        Json::object([
+            ("jsonrpc", Json::String(VERSION.to_string())),
+            (
+                "error",
+                Json::object([
+                    ("code", Json::Number(self.code as f64)),
+                    ("message", Json::String(self.message.clone())),
+                ]),
+            ),
+            ("id", id.cloned().unwrap_or(Json::Null)),
+        ])
+    }
+}
+
+impl Request {
+    pub fn new(id: i64, method: Method) -> Request {
+        Request {
+            id: Some(Json::Number(id as f64)),
+            method,
+        }
+    }
+
+    /// Reads a request, or the error to answer it with and its id if it had a readable one.
+    pub fn parse(line: &str) -> Result<Request, (Option<Json>, RpcError)> {
+        let Some(json) = Json::parse(line) else {
+            return Err((None, RpcError::new(PARSE_ERROR, "Parse error")));
+        };
+        let id = json.get("id").cloned();
+        let invalid = |id| (id, RpcError::new(INVALID_REQUEST, "Invalid Request"));
+
+        if json.get("jsonrpc").and_then(Json::as_str) != Some(VERSION) {
+            return Err(invalid(id));
+        }
+        let Some(name) = json.get("method").and_then(Json::as_str) else {
+            return Err(invalid(id));
+        };
+        let Some(method) = Method::ALL.into_iter().find(|method| method.name() == name) else {
+            let error = RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {name}"));
+            return Err((id, error));
+        };
+
+        Ok(Request { id, method })
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut members = vec![
+            ("jsonrpc", Json::String(VERSION.to_string())),
+            ("method", Json::String(self.method.name().to_string())),
+        ];
+        if let Some(id) = &self.id {
+            members.push(("id", id.clone()));
+        }
+        Json::object(members)
+    }
+
+    /// The successful response to this request.
+    pub fn response(&self, result: Json) -> Json {
+        Json::object([
+            ("jsonrpc", Json::String(VERSION.to_string())),
+            ("result", result),
+            ("id", self.id.clone().unwrap_or(Json::Null)),
+        ])
+    }
+}
+
+/// The method a line from a client calls, if it's a valid request, and the reply it needs, if any.
+/// `get_state` and `subscribe` return `state`. What the method does is up to the caller.
+pub fn answer(line: &str, state: &State) -> (Option<Method>, Option<Json>) {
+    let request = match Request::parse(line) {
+        Ok(request) => request,
+        // Notifications aren't answered, even for a method we don't have.
+        Err((None, error)) if error.code == METHOD_NOT_FOUND => return (None, None),
+        Err((id, error)) => return (None, Some(error.response(id.as_ref()))),
+    };
+
+    let result = match request.method {
+        Method::Show | Method::Hide => Json::Bool(true),
+        Method::GetState | Method::Subscribe => state.to_json(),
+    };
+    let reply = request.id.is_some().then(|| request.response(result));
+    (Some(request.method), reply)
+}
+
+/// What `get_state` and `subscribe` return, and `state` notifications carry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct State {
+    /// False while prompts are hidden, by `hide` or anything else that pauses the overlay.
+    pub visible: bool,
+    /// The config profile in use.
+    pub profile: Option<String>,
+    /// Everything waiting for a finger, oldest first.
+    pub prompts: Vec<Prompt>,
+    /// Where the bar is on each output it's on.
+    pub surfaces: Vec<Surface>,
+}
+
+/// The bar's rectangle on an output, in the compositor's logical coordinates across all outputs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Surface {
+    /// The output's name, like "eDP-1".
+    pub output: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Whether the bar is drawn there, which it isn't while nothing waits for a finger.
+    pub drawn: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    /// What the overlay tells the user, like "Use your right index".
+    pub text: String,
+    /// The fprintd device's object path.
+    pub device: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromptKind {
+    Verify,
+    Enroll,
+}
+
+impl PromptKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PromptKind::Verify => "verify",
+            PromptKind::Enroll => "enroll",
+        }
+    }
+}
+
+impl State {
+    pub fn to_json(&self) -> Json {
+        let prompts = self
+            .prompts
+            .iter()
+            .map(|prompt| {
+                Json::object([
+                    ("kind", Json::String(prompt.kind.name().to_string())),
+                    ("text", Json::String(prompt.text.clone())),
+                    ("device", Json::String(prompt.device.clone())),
+                ])
+            })
+            .collect();
+
+        let surfaces = self
+            .surfaces
+            .iter()
+            .map(|surface| {
+                Json::object([
+                    ("output", Json::String(surface.output.clone())),
+                    ("x", Json::Number(surface.x.into())),
+                    ("y", Json::Number(surface.y.into())),
+                    ("width", Json::Number(surface.width.into())),
+                    ("height", Json::Number(surface.height.into())),
+                    ("drawn", Json::Bool(surface.drawn)),
+                ])
+            })
+            .collect();
+
+        Json::object([
+            ("visible", Json::Bool(self.visible)),
+            ("profile", Json::string_or_null(self.profile.as_deref())),
+            ("prompts", Json::Array(prompts)),
+            ("surfaces", Json::Array(surfaces)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Option<State> {
+        let Json::Array(prompts) = json.get("prompts")? else {
+            return None;
+        };
+        let prompts = prompts
+            .iter()
+            .map(|prompt| {
+                let kind = match prompt.get("kind")?.as_str()? {
+                    "verify" => PromptKind::Verify,
+                    "enroll" => PromptKind::Enroll,
+                    _ => return None,
+                };
+                Some(Prompt {
+                    kind,
+                    text: prompt.get("text")?.as_str()?.to_string(),
+                    device: prompt.get("device")?.as_str()?.to_string(),
+                })
+            })
+            .collect::<Option<_>>()?;
+        // Older versions don't say.
+        let surfaces = match json.get("surfaces") {
+            Some(Json::Array(surfaces)) => surfaces
+                .iter()
+                .map(|surface| {
+                    let number = |key| surface.get(key)?.as_f64();
+                    Some(Surface {
+                        output: surface.get("output")?.as_str()?.to_string(),
+                        x: number("x")? as i32,
+                        y: number("y")? as i32,
+                        width: number("width")? as u32,
+                        height: number("height")? as u32,
+                        drawn: *surface.get("drawn")? == Json::Bool(true),
+                    })
+                })
+                .collect::<Option<_>>()?,
+            _ => vec![],
+        };
+
+        Some(State {
+            visible: *json.get("visible")? == Json::Bool(true),
+            profile: json.get("profile")?.as_str().map(str::to_string),
+            prompts,
+            surfaces,
+        })
+    }
+
+    /// The `state` notification sent to subscribers.
+    pub fn notification(&self) -> Json {
+        Json::object([
+            ("jsonrpc", Json::String(VERSION.to_string())),
+            ("method", Json::String("state".to_string())),
+            ("params", self.to_json()),
+        ])
+    }
+
+    /// The state in a line from the socket: a `state` notification, or the result of
+    /// `get_state` or `subscribe`.
+    pub fn from_message(line: &str) -> Option<State> {
+        let json = Json::parse(line)?;
+        State::from_json(json.get("params").or_else(|| json.get("result"))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let json = Json::parse(r#" {"a": [1, -2.5, 3e2, true, null], "b": {"c": "d"}, "e": []} "#);
+        assert_eq!(
+            json,
+            Some(Json::object([
+                (
+                    "a",
+                    Json::Array(vec![
+                        Json::Number(1.),
+                        Json::Number(-2.5),
+                        Json::Number(300.),
+                        Json::Bool(true),
+                        Json::Null,
+                    ])
+                ),
+                ("b", Json::object([("c", Json::String("d".to_string()))])),
+                ("e", Json::Array(vec![])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        let json = Json::parse(r#""\"\\\/\n\t\r\b\f\u00e9\ud83d\ude00""#);
+        assert_eq!(
+            json,
+            Some(Json::String("\"\\/\n\t\r\u{8}\u{c}é😀".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for text in [
+            "",
+            "{",
+            "[1,",
+            "[1 2]",
+            r#"{"a" 1}"#,
+            r#"{a: 1}"#,
+            r#""unterminated"#,
+            r#""\u12""#,
+            r#""\ud83d""#,
+            r#""\ud83d\ue000""#,
+            r#""\ud83d\u0041""#,
+            r#""\u+12a""#,
+            r#""\x41""#,
+            r#""\a""#,
+            "1.2.3",
+            "--1",
+            "nul",
+            "truth",
+            "1 2",
+            "{} x",
+        ] {
+            assert_eq!(Json::parse(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_some());
+        assert_eq!(Json::parse(&nested(MAX_DEPTH + 1)), None);
+        // Without the limit, this much would overflow the stack.
+        assert_eq!(Json::parse(&"[".repeat(1_000_000)), None);
+    }
+
+    #[test]
+    fn serializes_values() {
+        let json = Json::object([
+            ("id", Json::Number(1.)),
+            ("half", Json::Number(0.5)),
+            ("nan", Json::Number(f64::NAN)),
+            ("text", Json::String("a \"quote\"\n\u{1}".to_string())),
+            ("list", Json::Array(vec![Json::Null, Json::Bool(false)])),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"id":1,"half":0.5,"nan":null,"text":"a \"quote\"\n\u0001","list":[null,false]}"#
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = r#"{"a":[1,0.25,"\\ \"",{"b":null}],"c":true}"#;
+        assert_eq!(Json::parse(text).unwrap().to_string(), text);
+    }
+}
@@ -0,0 +1,226 @@
+// `fprint-prompt --show-ruler`: tick marks along the whole edge, numbered with their distance from
+// the `close_to` end, for reading off where the reader is.
+
+use smithay_client_toolkit::{
+    output::OutputInfo, reexports::client::protocol::wl_output, shell::wlr_layer::Anchor,
+};
+
+use crate::config::{Millimeters, PositionInfo};
+
+/// Room across the edge for the longest ticks and the numbers next to them.
+const THICKNESS: u32 = 56;
+const BACKGROUND: u32 = 0xC0000000;
+const TICKS: u32 = 0xFFFFFFFF;
+/// Where the numbers start, away from the screen edge.
+const LABEL_AT: u32 = 24;
+
+/// 3x5 digits, one row per byte with the leftmost pixel in bit 2.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b011, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const DIGIT_SCALE: u32 = 2;
+const DIGIT_ADVANCE: u32 = 4 * DIGIT_SCALE;
+const DIGIT_HEIGHT: u32 = 5 * DIGIT_SCALE;
+
+/// The geometry of the ruler: the configured edge, from end to end, in physical pixels. Centered
+/// bars are measured from the top or left end.
+pub fn geometry(pos: &PositionInfo, info: &OutputInfo) -> Option<PositionInfo> {
+    let (width, height) = mode_size(info)?;
+
+    let (length, start) = if is_horizontal(pos) {
+        (width, Anchor::LEFT)
+    } else {
+        (height, Anchor::TOP)
+    };
+
+    Some(PositionInfo {
+        edge: pos.edge,
+        close_to: if pos.close_to.is_empty() {
+            start
+        } else {
+            pos.close_to
+        },
+        offset: 0,
+        length: length.try_into().ok()?,
+        thickness: THICKNESS.max(pos.thickness),
+        notch: 0,
+        segments: vec![],
+        millimeters: Millimeters::default(),
+    })
+}
+
+/// How many physical pixels make up a millimeter along the edge, or None if the output doesn't
+/// say how big it is, like projectors and nested compositors.
+pub fn pixels_per_mm(pos: &PositionInfo, info: &OutputInfo) -> Option<f32> {
+    let (width, height) = mode_size(info)?;
+    let (mut width_mm, mut height_mm) = info.physical_size;
+    if is_rotated(info.transform) {
+        std::mem::swap(&mut width_mm, &mut height_mm);
+    }
+
+    let (pixels, mm) = if is_horizontal(pos) {
+        (width, width_mm)
+    } else {
+        (height, height_mm)
+    };
+    (mm > 0 && pixels > 0).then(|| pixels as f32 / mm as f32)
+}
+
+/// The output's size along the configured edge and across it, in physical pixels.
+pub fn output_size(pos: &PositionInfo, info: &OutputInfo) -> Option<(u32, u32)> {
+    let (width, height) = mode_size(info)?;
+    let (along, across) = if is_horizontal(pos) {
+        (width, height)
+    } else {
+        (height, width)
+    };
+    Some((along.try_into().ok()?, across.try_into().ok()?))
+}
+
+/// The size of the current mode, turned the way the output is.
+fn mode_size(info: &OutputInfo) -> Option<(i32, i32)> {
+    let mode = info.modes.iter().find(|mode| mode.current)?;
+    let (width, height) = mode.dimensions;
+    Some(if is_rotated(info.transform) {
+        (height, width)
+    } else {
+        (width, height)
+    })
+}
+
+fn is_rotated(transform: wl_output::Transform) -> bool {
+    matches!(
+        transform,
+        wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270
+    )
+}
+
+/// Draws the ruler, with the configured bar where it would be shown.
+pub fn draw(canvas: &mut [u8], ruler: &PositionInfo, bar: &PositionInfo, bar_color: u32) {
+    let width = ruler.win_width();
+    let height = ruler.win_height();
+
+    let bar_start = if bar.close_to.is_empty() {
+        ruler.length.saturating_sub(bar.length) / 2
+    } else {
+        bar.offset
+    };
+
+    for (index, chunk) in canvas.chunks_exact_mut(4).enumerate() {
+        let x = index as u32 % width;
+        let y = index as u32 / width;
+        let (along, across) = along_across(ruler, x, y);
+
+        let tick = match along {
+            _ if along % 100 == 0 => 20,
+            _ if along % 50 == 0 => 12,
+            _ if along % 10 == 0 => 6,
+            _ => 0,
+        };
+
+        let color = if across < tick {
+            TICKS
+        } else if across < bar.thickness && (bar_start..bar_start + bar.length).contains(&along) {
+            bar_color
+        } else {
+            BACKGROUND
+        };
+        chunk.copy_from_slice(&color.to_le_bytes());
+    }
+
+    // Number every long tick, on the side of it that counts up.
+    let mirrored = !(ruler.close_to == Anchor::TOP || ruler.close_to == Anchor::LEFT);
+    for along in (100..ruler.length).step_by(100) {
+        let mut digits = [0; 10];
+        let label = label(along, &mut digits);
+        let label_width = label.len() as u32 * DIGIT_ADVANCE;
+
+        let beside = |tick: u32, total: u32, size: u32| {
+            if mirrored {
+                (total - 1 - tick).checked_sub(2 + size)
+            } else {
+                Some(tick + 3)
+            }
+        };
+        let origin = if is_horizontal(ruler) {
+            let y = match ruler.edge {
+                Anchor::TOP => LABEL_AT,
+                _ => height - LABEL_AT - DIGIT_HEIGHT,
+            };
+            beside(along, width, label_width).map(|x| (x, y))
+        } else {
+            let x = match ruler.edge {
+                Anchor::LEFT => LABEL_AT,
+                _ => width - LABEL_AT - label_width,
+            };
+            beside(along, height, DIGIT_HEIGHT).map(|y| (x, y))
+        };
+
+        if let Some((x, y)) = origin {
+            draw_label(canvas, width, height, label, x, y);
+        }
+    }
+}
+
+/// The digits of `number`, written to the end of `digits`, so no string is made for every frame.
+fn label(mut number: u32, digits: &mut [u8; 10]) -> &[u8] {
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (number % 10) as u8;
+        number /= 10;
+        if number == 0 {
+            return &digits[start..];
+        }
+    }
+}
+
+fn draw_label(canvas: &mut [u8], width: u32, height: u32, label: &[u8], x: u32, y: u32) {
+    for (i, &digit) in label.iter().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let left = x + i as u32 * DIGIT_ADVANCE;
+
+        for py in 0..DIGIT_HEIGHT {
+            for px in 0..3 * DIGIT_SCALE {
+                let (cx, cy) = (left + px, y + py);
+                let lit = glyph[(py / DIGIT_SCALE) as usize] & (0b100 >> (px / DIGIT_SCALE)) != 0;
+                if lit && cx < width && cy < height {
+                    let index = (cy * width + cx) as usize * 4;
+                    canvas[index..index + 4].copy_from_slice(&TICKS.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// How far along the edge a pixel is from the `close_to` end, and how far from the screen edge.
+fn along_across(pos: &PositionInfo, x: u32, y: u32) -> (u32, u32) {
+    let (along, across, total) = match pos.edge {
+        Anchor::TOP => (x, y, pos.win_width()),
+        Anchor::BOTTOM => (x, pos.win_height() - 1 - y, pos.win_width()),
+        Anchor::LEFT => (y, x, pos.win_height()),
+        _ => (y, pos.win_width() - 1 - x, pos.win_height()),
+    };
+
+    if pos.close_to == Anchor::TOP || pos.close_to == Anchor::LEFT {
+        (along, across)
+    } else {
+        (total - 1 - along, across)
+    }
+}
+
+fn is_horizontal(pos: &PositionInfo) -> bool {
+    pos.edge == Anchor::TOP || pos.edge == Anchor::BOTTOM
+}
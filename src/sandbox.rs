@@ -0,0 +1,116 @@
+// Running inside Flatpak. The sandbox only lets us talk to the system bus names the app was
+// given, and flatpak grants none by default, so we check for them up front and say how to allow
+// them instead of failing on the first call with "name not provided by any .service files".
+
+use std::fs;
+
+use crate::{
+    bus::{self, Bus},
+    fprint,
+};
+
+const INFO_PATH: &str = "/.flatpak-info";
+/// fprintd, and logind to tell whose verification it is and whether the screen is locked.
+const SYSTEM_NAMES: &[&str] = &[fprint::SERVICE, "org.freedesktop.login1"];
+
+pub struct Flatpak {
+    app_id: String,
+    /// The whole system bus is reachable, with `--socket=system-bus`.
+    system_bus: bool,
+    /// Names from the "System Bus Policy" section that we may call.
+    talk_names: Vec<String>,
+}
+
+pub fn in_flatpak() -> bool {
+    std::path::Path::new(INFO_PATH).exists()
+}
+
+/// The sandbox we're in, if any.
+pub fn detect() -> Option<Flatpak> {
+    let info = fs::read_to_string(INFO_PATH).ok()?;
+
+    let mut flatpak = Flatpak {
+        app_id: String::new(),
+        system_bus: false,
+        talk_names: vec![],
+    };
+    let mut section = "";
+    for line in info.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match section {
+            "Application" if key == "name" => flatpak.app_id = value.to_string(),
+            "Context" if key == "sockets" => {
+                flatpak.system_bus = value.split(';').any(|socket| socket == "system-bus");
+            }
+            "System Bus Policy" if value == "talk" || value == "own" => {
+                flatpak.talk_names.push(key.to_string());
+            }
+            _ => (),
+        }
+    }
+
+    Some(flatpak)
+}
+
+impl Flatpak {
+    fn can_talk_to(&self, name: &str) -> bool {
+        self.system_bus
+            || self
+                .talk_names
+                .iter()
+                .any(|allowed| match allowed.strip_suffix(".*") {
+                    Some(prefix) => {
+                        name.starts_with(prefix) && name[prefix.len()..].starts_with('.')
+                    }
+                    None => allowed == name,
+                })
+    }
+
+    /// Names from [`SYSTEM_NAMES`] the sandbox keeps us from.
+    pub fn missing_names(&self) -> Vec<&'static str> {
+        SYSTEM_NAMES
+            .iter()
+            .copied()
+            .filter(|name| !self.can_talk_to(name))
+            .collect()
+    }
+
+    /// The command that gives the app access to the missing names.
+    pub fn override_command(&self) -> String {
+        let flags: Vec<String> = self
+            .missing_names()
+            .iter()
+            .map(|name| format!("--system-talk-name={name}"))
+            .collect();
+        format!(
+            "flatpak override --user {} {}",
+            flags.join(" "),
+            self.app_id
+        )
+    }
+}
+
+/// What to tell the user when the sandbox is why fprintd can't be reached. Nothing when talking to
+/// a bus other than the system bus, which flatpak doesn't filter.
+pub fn permission_hint() -> Option<String> {
+    if *bus::selected() != Bus::System {
+        return None;
+    }
+    let flatpak = detect()?;
+    let missing = flatpak.missing_names();
+    if missing.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "The Flatpak sandbox doesn't allow talking to {}. Allow it with `{}`.",
+        missing.join(" or "),
+        flatpak.override_command()
+    ))
+}
@@ -0,0 +1,54 @@
+// Switching themes by themselves, following `[theme.schedule]`: at times of day like the night
+// light, or along with the desktop's dark style, which the settings portal tells us about as
+// soon as it changes.
+
+use std::time::{Duration, Instant};
+
+use crate::{config::ThemeSchedule, night, portal::settings::ColorScheme};
+
+#[derive(Default)]
+pub struct ThemeClock {
+    minutes: u32,
+    last_check: Option<Instant>,
+    color_scheme: Option<ColorScheme>,
+    /// The portal couldn't be reached, so only the times are followed.
+    no_portal: bool,
+}
+
+impl ThemeClock {
+    const INTERVAL: Duration = Duration::from_secs(30);
+
+    /// The theme the schedule picks right now, if any. Only looks at the time every so often.
+    pub fn current<'a>(&mut self, schedule: &'a ThemeSchedule) -> Option<&'a str> {
+        if schedule.is_empty() {
+            return None;
+        }
+
+        if self
+            .last_check
+            .is_none_or(|last_check| last_check.elapsed() >= Self::INTERVAL)
+        {
+            self.last_check = Some(Instant::now());
+            self.minutes = night::local_minutes();
+        }
+
+        let dark = schedule
+            .follows_dark_style()
+            .then(|| self.is_dark())
+            .flatten();
+        schedule.pick(self.minutes, dark)
+    }
+
+    fn is_dark(&mut self) -> Option<bool> {
+        if self.color_scheme.is_none() && !self.no_portal {
+            self.color_scheme = ColorScheme::new()
+                .map_err(|e| {
+                    log::warn!("could not follow the desktop's dark style: {e}");
+                    self.no_portal = true;
+                })
+                .ok();
+        }
+
+        self.color_scheme.as_mut().map(ColorScheme::is_dark)
+    }
+}
@@ -0,0 +1,143 @@
+// Figuring out whose verification we're looking at.
+//
+// fprintd broadcasts its signals for every client, and doesn't tell us who claimed the device.
+// What logind can tell us is which session is in the foreground on our seat: a greeter or another
+// user's session on a different VT will be the active one while it's asking for a fingerprint.
+//
+// On multi-seat systems we also need to know which seat we're on, so we can stick to its outputs
+// and input devices.
+
+use std::fs;
+
+use dbus::{
+    ffidisp::{stdintf::org_freedesktop_dbus::Properties, Connection as DbusConnection},
+    Path,
+};
+
+use crate::{config::Config, fprint::Device};
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SEAT_INTERFACE: &str = "org.freedesktop.login1.Seat";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const TIMEOUT_MS: i32 = 1000;
+
+/// Our logind seat, from `$XDG_SEAT` or otherwise our session.
+pub fn seat_id(dbus: &DbusConnection) -> String {
+    if let Ok(seat) = std::env::var("XDG_SEAT") {
+        return seat;
+    }
+
+    let manager = dbus.with_path(LOGIN1, LOGIN1_PATH, TIMEOUT_MS);
+    let seat = manager
+        .method_call(MANAGER_INTERFACE, "GetSessionByPID", (std::process::id(),))
+        .and_then(|(session,): (Path<'static>,)| {
+            dbus.with_path(LOGIN1, session, TIMEOUT_MS)
+                .get::<(String, Path<'static>)>(SESSION_INTERFACE, "Seat")
+        });
+
+    match seat {
+        Ok((seat, _)) if !seat.is_empty() => seat,
+        Ok(_) => "seat0".to_string(),
+        Err(e) => {
+            log::warn!("could not determine our seat, assuming seat0: {e}");
+            "seat0".to_string()
+        }
+    }
+}
+
+/// The logind session fprint-prompt runs in.
+pub fn own_session(dbus: &DbusConnection) -> Result<Path<'static>, dbus::Error> {
+    let manager = dbus.with_path(LOGIN1, LOGIN1_PATH, TIMEOUT_MS);
+    let (session,): (Path<'static>,) =
+        manager.method_call(MANAGER_INTERFACE, "GetSessionByPID", (std::process::id(),))?;
+    Ok(session)
+}
+
+/// Whether logind was told the session is locked. Not every locker says so.
+pub fn is_locked(dbus: &DbusConnection, session: &Path<'static>) -> Result<bool, dbus::Error> {
+    dbus.with_path(LOGIN1, session.clone(), TIMEOUT_MS)
+        .get(SESSION_INTERFACE, "LockedHint")
+}
+
+/// Name of the user owning the foreground session on our seat.
+pub fn active_user(dbus: &DbusConnection) -> Result<String, dbus::Error> {
+    let manager = dbus.with_path(LOGIN1, LOGIN1_PATH, TIMEOUT_MS);
+    let (seat,): (Path<'static>,) =
+        manager.method_call(MANAGER_INTERFACE, "GetSeat", (seat_id(dbus),))?;
+
+    let (_id, session): (String, Path<'static>) = dbus
+        .with_path(LOGIN1, seat, TIMEOUT_MS)
+        .get(SEAT_INTERFACE, "ActiveSession")?;
+
+    dbus.with_path(LOGIN1, session, TIMEOUT_MS)
+        .get(SESSION_INTERFACE, "Name")
+}
+
+/// Whether a verification happening right now is meant for the user running fprint-prompt. Errs on
+/// the side of showing the prompt if logind can't be asked.
+pub fn is_own_verification(dbus: &DbusConnection) -> bool {
+    match active_user(dbus) {
+        Ok(user) => user == crate::fprint::current_username(),
+        Err(e) => {
+            log::warn!("could not determine the active session's user: {e}");
+            true
+        }
+    }
+}
+
+/// fprintd doesn't know about seats, so readers can be assigned to one in the config. Readers that
+/// aren't assigned are shown on every seat.
+pub fn is_device_on_seat(
+    dbus: &DbusConnection,
+    config: &Config,
+    device: &Path<'static>,
+    seat_id: &str,
+) -> bool {
+    if config.device_seats.is_empty() {
+        return true;
+    }
+
+    let name = match Device::new(dbus, device.clone()).name() {
+        Ok(name) => name,
+        Err(e) => {
+            log::warn!("could not get the name of {device}: {e}");
+            return true;
+        }
+    };
+
+    config
+        .device_seats
+        .get(&name)
+        .is_none_or(|seat| seat == seat_id)
+}
+
+/// The seat a DRM connector like "eDP-1" is attached to, according to udev. None if there's no
+/// such connector, e.g. in a nested compositor.
+pub fn output_seat(connector: &str) -> Option<String> {
+    for entry in fs::read_dir("/sys/class/drm").ok()?.flatten() {
+        let name = entry.file_name();
+
+        // Connectors are named like "card1-eDP-1".
+        let Some((card, name)) = name.to_str().and_then(|name| name.split_once('-')) else {
+            continue;
+        };
+        if name != connector {
+            continue;
+        }
+
+        let dev = fs::read_to_string(format!("/sys/class/drm/{card}/dev")).ok()?;
+        let udev_data =
+            fs::read_to_string(format!("/run/udev/data/c{}", dev.trim())).unwrap_or_default();
+
+        let seat = udev_data
+            .lines()
+            .find_map(|line| line.strip_prefix("E:ID_SEAT="))
+            .unwrap_or("seat0");
+
+        return Some(seat.to_string());
+    }
+
+    None
+}
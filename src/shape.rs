@@ -0,0 +1,96 @@
+// Marks drawn over the indicators with `shapes = true`, so how a scan went can be told by shape
+// as well as by color, whatever the palette or night light does to it: a checkmark for a match, a
+// cross for a failure, a dot for a scan to try again and three dots, like a password field, for
+// typing the password instead.
+//
+// They're a layer of their own over whichever style draws the indicators, sitting in the same
+// circle the dot and ring styles use.
+
+use std::time::Duration;
+
+use crate::{animation, prompts::Outcome, style::Frame, theme};
+
+/// How long the dot stays up after a scan that has to be tried again.
+pub const RETRY_DURATION: Duration = animation::OUTCOME_DURATION;
+
+/// Black, for marks on top of an indicator.
+const INK: u32 = 0xFF000000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Check,
+    Cross,
+    Dot,
+    Password,
+}
+
+/// A shape on an indicator, faded in or out while the bar hands off between states.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mark {
+    pub shape: Shape,
+    pub opacity: f32,
+}
+
+impl From<Shape> for Mark {
+    fn from(shape: Shape) -> Mark {
+        Mark { shape, opacity: 1. }
+    }
+}
+
+impl Shape {
+    pub fn for_outcome(outcome: Outcome) -> Shape {
+        match outcome {
+            Outcome::Success => Shape::Check,
+            Outcome::Failure => Shape::Cross,
+            Outcome::Fallback | Outcome::Busy | Outcome::Disconnected => Shape::Password,
+        }
+    }
+
+    /// How far a point is from the middle of the shape's strokes, in a circle of radius 1 around
+    /// (0, 0), with y pointing down the screen.
+    fn distance(self, x: f32, y: f32) -> f32 {
+        let stroke = |from, to| segment_distance((x, y), from, to);
+        match self {
+            Shape::Check => {
+                stroke((-0.5, 0.), (-0.15, 0.35)).min(stroke((-0.15, 0.35), (0.5, -0.35)))
+            }
+            Shape::Cross => stroke((-0.4, -0.4), (0.4, 0.4)).min(stroke((-0.4, 0.4), (0.4, -0.4))),
+            Shape::Dot => ((x * x + y * y).sqrt() - 0.2).max(0.),
+            Shape::Password => [-0.5, 0., 0.5]
+                .map(|dot| (((x - dot) * (x - dot) + y * y).sqrt() - 0.12).max(0.))
+                .into_iter()
+                .fold(f32::MAX, f32::min),
+        }
+    }
+}
+
+/// Draws the shape of the indicator a pixel belongs to, if it has one, over the pixel's `color`.
+/// On the indicator it's dark, and off it, as inside a ring, it's the indicator's color.
+pub fn draw(frame: &Frame, x: u32, y: u32, color: u32) -> u32 {
+    let (segment, dx, dy, radius) = frame.mark(x, y);
+    let Some(Some(mark)) = frame.shapes.get(segment) else {
+        return color;
+    };
+
+    let half_width = (radius * 0.12).max(0.75);
+    let distance = mark.shape.distance(dx / radius, dy / radius) * radius;
+    let coverage = (half_width - distance + 0.5).clamp(0., 1.) * mark.opacity;
+    if coverage == 0. {
+        return color;
+    }
+
+    let indicator = frame.colors[segment];
+    let ink = if color >> 24 >= 0x80 {
+        animation::fade(INK, (indicator >> 24) as f32 / 255.)
+    } else {
+        indicator
+    };
+    theme::over(animation::fade(ink, coverage), color)
+}
+
+fn segment_distance((x, y): (f32, f32), (x1, y1): (f32, f32), (x2, y2): (f32, f32)) -> f32 {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let t = (((x - x1) * dx + (y - y1) * dy) / (dx * dx + dy * dy)).clamp(0., 1.);
+    let (px, py) = (x - x1 - t * dx, y - y1 - t * dy);
+    (px * px + py * py).sqrt()
+}
@@ -0,0 +1,230 @@
+// Prompts from signals other than fprintd's, set up with `[signal.name]` sections, so setups like a
+// custom PAM module or a remote auth daemon can use the overlay without code of their own. Each
+// rule is a D-Bus match rule with what to do when it matches: show a prompt with some text, hide
+// it, or either depending on one of the signal's arguments.
+//
+// Every rule gets a connection of its own, so whatever arrives on it is known to be what the rule
+// matched, arguments and all, without matching it again here.
+
+use std::os::fd::RawFd;
+
+use dbus::{
+    arg::{ArgType, RefArg},
+    ffidisp::Connection as DbusConnection,
+    Message, MessageType, Path,
+};
+use fprint_prompt::bus;
+
+use crate::config::{Config, SignalAction, SignalRule};
+
+/// What a matched signal does to its prompt.
+pub enum SignalEvent {
+    Show(String),
+    Hide,
+}
+
+struct Listener {
+    /// The prompt's name, which its session's path is made from.
+    prompt: String,
+    rule: SignalRule,
+    dbus: DbusConnection,
+    fds: Vec<RawFd>,
+}
+
+#[derive(Default)]
+pub struct SignalRules {
+    listeners: Vec<Listener>,
+    /// The rules the listeners were started for.
+    rules: Vec<(String, SignalRule)>,
+}
+
+impl SignalRules {
+    /// Listens for the signals the config has rules for, and stops listening for those taken out
+    /// of it.
+    pub fn configure(&mut self, config: &Config) {
+        let rules: Vec<_> = config
+            .signal_rules
+            .iter()
+            .map(|(name, rule)| (name.clone(), rule.clone()))
+            .collect();
+        if rules == self.rules {
+            return;
+        }
+
+        self.listeners = rules
+            .iter()
+            .filter_map(|(name, rule)| {
+                let dbus = rule
+                    .bus
+                    .connect()
+                    .and_then(|dbus| {
+                        dbus.add_match(&rule.rule)?;
+                        Ok(dbus)
+                    })
+                    .map_err(|e| {
+                        log::warn!("could not listen for signal.{name} on {}: {e}", rule.bus)
+                    })
+                    .ok()?;
+                Some(Listener {
+                    prompt: rule.prompt.clone().unwrap_or_else(|| name.clone()),
+                    rule: rule.clone(),
+                    fds: bus::readable_fds(&dbus),
+                    dbus,
+                })
+            })
+            .collect();
+        self.rules = rules;
+    }
+
+    /// The prompts shown or hidden by signals that came in since the last poll, by the path their
+    /// sessions go by.
+    pub fn poll(&mut self) -> Vec<(Path<'static>, SignalEvent)> {
+        let mut events = vec![];
+        for listener in &self.listeners {
+            for msg in listener.dbus.incoming(0) {
+                // The bus says hello to every connection, whatever it listens for.
+                if msg.msg_type() != MessageType::Signal
+                    || msg.sender().as_deref() == Some("org.freedesktop.DBus")
+                        && matches!(msg.member().as_deref(), Some("NameAcquired" | "NameLost"))
+                {
+                    continue;
+                }
+
+                let args = arguments(&msg);
+                let show = match listener.rule.action {
+                    SignalAction::Show => true,
+                    SignalAction::Hide => false,
+                    SignalAction::Argument(index) => {
+                        args.get(index).is_some_and(|(_, truthy)| *truthy)
+                    }
+                };
+                let event = if show {
+                    SignalEvent::Show(fill_in(&listener.rule.text, &args))
+                } else {
+                    SignalEvent::Hide
+                };
+                events.push((path(&listener.prompt), event));
+            }
+        }
+        events
+    }
+
+    /// To wake up for, when a signal comes in.
+    pub fn watch_fds(&self) -> impl Iterator<Item = RawFd> + '_ {
+        self.listeners
+            .iter()
+            .flat_map(|listener| listener.fds.iter().copied())
+    }
+}
+
+/// The path a prompt's session goes by, from its name. Only letters, digits and underscores can be
+/// in a path element, so anything else becomes an underscore.
+fn path(prompt: &str) -> Path<'static> {
+    let element: String = prompt
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Path::new(format!("/signal/{element}")).unwrap_or_else(|_| Path::new("/signal").unwrap())
+}
+
+/// Each of a signal's arguments as text, and whether it counts as true.
+fn arguments(msg: &Message) -> Vec<(String, bool)> {
+    let mut args = vec![];
+    let mut iter = msg.iter_init();
+    while let Some(arg) = iter.get_refarg() {
+        args.push(describe(&*arg));
+        if !iter.next() {
+            break;
+        }
+    }
+    args
+}
+
+fn describe(arg: &dyn RefArg) -> (String, bool) {
+    if arg.arg_type() == ArgType::Boolean {
+        let value = arg.as_u64() == Some(1);
+        return (value.to_string(), value);
+    }
+    if let Some(text) = arg.as_str() {
+        return (text.to_string(), !text.is_empty());
+    }
+    if let Some(number) = arg.as_i64() {
+        return (number.to_string(), number != 0);
+    }
+    if let Some(number) = arg.as_u64() {
+        return (number.to_string(), number != 0);
+    }
+    if let Some(number) = arg.as_f64() {
+        return (number.to_string(), number != 0.);
+    }
+    // Containers, which a prompt has no use for.
+    (String::new(), false)
+}
+
+/// `text` with `{0}`, `{1}` and so on replaced by the arguments, or nothing for those the signal
+/// doesn't have.
+fn fill_in(text: &str, args: &[(String, bool)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut digits = String::new();
+        if c == '{' {
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            if !digits.is_empty() && chars.next_if_eq(&'}').is_some() {
+                let arg = digits.parse().ok().and_then(|index: usize| args.get(index));
+                filled.push_str(arg.map_or("", |(arg, _)| arg));
+                continue;
+            }
+        }
+        filled.push(c);
+        filled.push_str(&digits);
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<(String, bool)> {
+        values
+            .iter()
+            .map(|value| (value.to_string(), !value.is_empty()))
+            .collect()
+    }
+
+    #[test]
+    fn fills_in_arguments() {
+        let args = args(&["alice", "sudo"]);
+        assert_eq!(fill_in("{1} for {0}", &args), "sudo for alice");
+        assert_eq!(fill_in("{0}{0}", &args), "alicealice");
+        assert_eq!(fill_in("no arguments", &args), "no arguments");
+    }
+
+    #[test]
+    fn reads_every_digit_of_an_index() {
+        let args = args(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l"]);
+        assert_eq!(fill_in("{11}", &args), "l");
+        assert_eq!(fill_in("{01}", &args), "b");
+    }
+
+    #[test]
+    fn leaves_out_missing_arguments() {
+        let args = args(&["alice"]);
+        assert_eq!(fill_in("[{1}]", &args), "[]");
+        assert_eq!(fill_in("[{99999999999999999999}]", &args), "[]");
+    }
+
+    #[test]
+    fn keeps_what_isnt_an_index() {
+        let args = args(&["alice"]);
+        assert_eq!(fill_in("{0", &args), "{0");
+        assert_eq!(fill_in("{", &args), "{");
+        assert_eq!(fill_in("{}", &args), "{}");
+        assert_eq!(fill_in("{x}", &args), "{x}");
+        assert_eq!(fill_in("{0 }", &args), "{0 }");
+        assert_eq!(fill_in("{{0}}", &args), "{alice}");
+    }
+}
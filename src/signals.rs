@@ -0,0 +1,256 @@
+// Turning the fprintd signals we listen to into events.
+//
+// The signals come from the system bus, where anything can be sent, so nothing here assumes they
+// look like fprintd says they should: parsing never panics, and results we don't know about are
+// never mistaken for a match.
+
+use dbus::{Message, MessageType};
+
+use crate::fprint::DEVICE_INTERFACE;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FprintEvent {
+    VerifyFingerSelected {
+        finger_name: Option<String>,
+    },
+    VerifyStatus {
+        result: Option<String>,
+        done: Option<bool>,
+    },
+    EnrollStatus {
+        result: Option<String>,
+        done: Option<bool>,
+    },
+}
+
+impl FprintEvent {
+    /// What VerifyStatus or EnrollStatus said, like "verify-match".
+    pub fn result(&self) -> Option<&str> {
+        match self {
+            FprintEvent::VerifyFingerSelected { .. } => None,
+            FprintEvent::VerifyStatus { result, .. } | FprintEvent::EnrollStatus { result, .. } => {
+                result.as_deref()
+            }
+        }
+    }
+}
+
+/// What a `result` string from VerifyStatus or EnrollStatus means for the prompt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanResult {
+    /// "verify-match".
+    Match,
+    /// "verify-no-match".
+    NoMatch,
+    /// "enroll-stage-passed": one more scan towards the enrollment.
+    StagePassed,
+    /// "enroll-completed".
+    Completed,
+    /// The scan didn't work and the user should try again, like "verify-retry-scan".
+    Retry,
+    /// The reader went away mid-operation: "verify-disconnected" or "enroll-disconnected".
+    Disconnected,
+    /// The operation failed for another reason, like "verify-unknown-error".
+    Failed,
+    /// Nothing we know about. Treated like neither a match nor progress.
+    Unknown,
+}
+
+impl ScanResult {
+    pub fn parse(result: Option<&str>) -> ScanResult {
+        match result {
+            Some("verify-match") => ScanResult::Match,
+            Some("verify-no-match") => ScanResult::NoMatch,
+            Some("enroll-stage-passed") => ScanResult::StagePassed,
+            Some("enroll-completed") => ScanResult::Completed,
+            Some(
+                "verify-retry-scan"
+                | "verify-swipe-too-short"
+                | "verify-finger-not-centered"
+                | "verify-remove-and-retry"
+                | "enroll-retry-scan"
+                | "enroll-swipe-too-short"
+                | "enroll-finger-not-centered"
+                | "enroll-remove-and-retry",
+            ) => ScanResult::Retry,
+            Some("verify-disconnected" | "enroll-disconnected") => ScanResult::Disconnected,
+            Some(
+                "verify-unknown-error"
+                | "enroll-failed"
+                | "enroll-data-full"
+                | "enroll-duplicate"
+                | "enroll-unknown-error",
+            ) => ScanResult::Failed,
+            _ => ScanResult::Unknown,
+        }
+    }
+}
+
+/// The event for a signal from an fprintd device, or None for anything else.
+pub fn parse(msg: &Message) -> Option<FprintEvent> {
+    if msg.msg_type() != MessageType::Signal {
+        return None;
+    }
+    if msg.interface().as_deref() != Some(DEVICE_INTERFACE) {
+        return None;
+    }
+
+    match msg.member().as_deref() {
+        Some("VerifyFingerSelected") => {
+            let finger_name = msg.get1::<String>();
+            Some(FprintEvent::VerifyFingerSelected { finger_name })
+        }
+        Some("VerifyStatus") => {
+            let (result, done) = msg.get2::<String, bool>();
+            Some(FprintEvent::VerifyStatus { result, done })
+        }
+        Some("EnrollStatus") => {
+            let (result, done) = msg.get2::<String, bool>();
+            Some(FprintEvent::EnrollStatus { result, done })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbus::{arg::Variant, Message};
+
+    use super::*;
+
+    const MEMBERS: &[&str] = &[
+        "VerifyFingerSelected",
+        "VerifyStatus",
+        "EnrollStatus",
+        "",
+        "verifystatus",
+    ];
+    const STRINGS: &[&str] = &[
+        "",
+        "verify-match",
+        "verify-no-match",
+        "enroll-stage-passed",
+        "verify-match ",
+        "VERIFY-MATCH",
+        "verify-match\0",
+        "any",
+        "\u{fffd}\u{1f44d}",
+    ];
+
+    /// xorshift, to get the same cases every run without pulling in a crate.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// A signal on one of the members we look at, with up to three arguments of any type, so
+    /// some are right, some are truncated and most are nonsense.
+    fn random_signal(rng: &mut Rng) -> Message {
+        let member = MEMBERS[rng.below(MEMBERS.len())];
+        let interface = if rng.below(8) == 0 {
+            "net.reactivated.Fprint.Manager"
+        } else {
+            DEVICE_INTERFACE
+        };
+        let mut msg =
+            match Message::new_signal("/net/reactivated/Fprint/Device/0", interface, member) {
+                Ok(msg) => msg,
+                // An empty member isn't allowed on the bus either.
+                Err(_) => Message::new_signal("/", interface, "Other").unwrap(),
+            };
+
+        for _ in 0..rng.below(4) {
+            msg = match rng.below(6) {
+                0 | 1 => msg.append1(STRINGS[rng.below(STRINGS.len())]),
+                2 => msg.append1(rng.below(2) == 0),
+                3 => msg.append1(rng.below(1000) as i32),
+                4 => msg.append1(Variant(STRINGS[rng.below(STRINGS.len())])),
+                _ => msg.append1(vec![STRINGS[rng.below(STRINGS.len())]]),
+            };
+        }
+        msg
+    }
+
+    #[test]
+    fn malformed_signals_never_panic() {
+        let mut rng = Rng(0x5eed);
+        for _ in 0..10_000 {
+            let msg = random_signal(&mut rng);
+            let Some(event) = parse(&msg) else {
+                continue;
+            };
+
+            // Whatever came in, only the exact string counts as a match.
+            if let FprintEvent::VerifyStatus { result, .. }
+            | FprintEvent::EnrollStatus { result, .. } = event
+            {
+                if ScanResult::parse(result.as_deref()) == ScanResult::Match {
+                    assert_eq!(result.as_deref(), Some("verify-match"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        let call = Message::new_method_call(
+            "net.reactivated.Fprint",
+            "/net/reactivated/Fprint/Device/0",
+            DEVICE_INTERFACE,
+            "VerifyStatus",
+        )
+        .unwrap()
+        .append2("verify-match", true);
+        assert!(parse(&call).is_none());
+
+        let signal = Message::new_signal("/", "org.example.Other", "VerifyStatus")
+            .unwrap()
+            .append2("verify-match", true);
+        assert!(parse(&signal).is_none());
+    }
+
+    #[test]
+    fn truncated_signals_have_no_result() {
+        let msg = Message::new_signal("/", DEVICE_INTERFACE, "VerifyStatus").unwrap();
+        assert!(matches!(
+            parse(&msg),
+            Some(FprintEvent::VerifyStatus {
+                result: None,
+                done: None
+            })
+        ));
+    }
+
+    #[test]
+    fn unknown_results_are_unknown() {
+        for result in [
+            "",
+            "verify-match ",
+            "VERIFY-MATCH",
+            "verify-matched",
+            "enroll-",
+        ] {
+            assert_eq!(ScanResult::parse(Some(result)), ScanResult::Unknown);
+        }
+        assert_eq!(ScanResult::parse(None), ScanResult::Unknown);
+        assert_eq!(ScanResult::parse(Some("verify-match")), ScanResult::Match);
+        assert_eq!(
+            ScanResult::parse(Some("enroll-swipe-too-short")),
+            ScanResult::Retry
+        );
+        assert_eq!(
+            ScanResult::parse(Some("verify-disconnected")),
+            ScanResult::Disconnected
+        );
+        assert_eq!(
+            ScanResult::parse(Some("verify-unknown-error")),
+            ScanResult::Failed
+        );
+    }
+}
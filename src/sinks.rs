@@ -0,0 +1,186 @@
+// Where prompts are shown, picked with `sinks` in the config. All of them are fed the same state,
+// by the overlay's loop or by the one that runs without Wayland, so they combine freely.
+//
+// The overlay is one of them too. Its surfaces are the Wayland state the loop runs on, so they're
+// drawn by the loop, and the [`Overlay`] sink only tells it when the prompts changed.
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use crate::{
+    config::Config,
+    hook::Hook,
+    led::Led,
+    notify::Notifier,
+    prompts::Prompts,
+    sound::Reminder,
+    status::{self, Status, StatusStream},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sink {
+    Overlay,
+    Notification,
+    /// Chimes while a verification is ignored, see `attention.chime_after`.
+    Sound,
+    StatusStream,
+    /// Runs `hook.command` whenever the prompt changes.
+    Hook,
+    /// Lights `led.name` while a verification waits.
+    Led,
+}
+
+impl Sink {
+    pub const ALL: [Sink; 6] = [
+        Sink::Overlay,
+        Sink::Notification,
+        Sink::Sound,
+        Sink::StatusStream,
+        Sink::Hook,
+        Sink::Led,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sink::Overlay => "overlay",
+            Sink::Notification => "notification",
+            Sink::Sound => "sound",
+            Sink::StatusStream => "status-stream",
+            Sink::Hook => "hook",
+            Sink::Led => "led",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Sink> {
+        Self::ALL.into_iter().find(|sink| sink.name() == name)
+    }
+
+    /// Starts showing prompts here. None for sinks that aren't available, like the overlay without
+    /// Wayland. The status stream is written as `format`.
+    fn start(
+        self,
+        config: &Config,
+        format: status::Format,
+        overlay: Option<&Overlay>,
+    ) -> Option<Box<dyn OutputSink>> {
+        match self {
+            Sink::Overlay => Some(Box::new(overlay?.clone())),
+            Sink::Notification => Some(Box::new(Notifier::new()?)),
+            Sink::Sound => Some(Box::new(Reminder::default())),
+            Sink::StatusStream => Some(Box::new(StatusStream::new(format))),
+            Sink::Hook => Some(Box::new(Hook::new(config))),
+            Sink::Led => Some(Box::new(Led::new(config))),
+        }
+    }
+}
+
+/// What the sinks show.
+pub struct View<'a> {
+    pub prompts: &'a Prompts,
+    pub config: &'a Config,
+    /// Hidden from the tray, a shortcut, D-Bus or a screen cast.
+    pub paused: bool,
+    /// How long a verification has waited without the reader being touched, if one is waiting.
+    pub ignored_for: Option<Duration>,
+}
+
+pub trait OutputSink {
+    /// After each fprintd event, with what it said.
+    fn event(&mut self, _view: &View, _status: &Status) {}
+
+    /// On every turn of the loop.
+    fn poll(&mut self, _view: &View) {}
+
+    /// Whether hiding or showing the prompts was asked for since the last call, e.g. by clicking
+    /// the swaybar block.
+    fn toggle_requested(&mut self) -> bool {
+        false
+    }
+
+    /// Follows a reloaded config.
+    fn configure(&mut self, _config: &Config) {}
+}
+
+/// The overlay, as far as the sinks go: it notes when the prompts changed, for the loop to draw
+/// its surfaces right away rather than on the next frame callback.
+#[derive(Clone, Default)]
+pub struct Overlay(Rc<Cell<bool>>);
+
+impl Overlay {
+    /// Whether the prompts changed since the last call.
+    pub fn take_changed(&self) -> bool {
+        self.0.take()
+    }
+}
+
+impl OutputSink for Overlay {
+    fn event(&mut self, _view: &View, _status: &Status) {
+        self.0.set(true);
+    }
+}
+
+pub struct Sinks {
+    sinks: Vec<(Sink, Box<dyn OutputSink>)>,
+    /// Where the overlay is drawn from, when there is one.
+    overlay: Option<Overlay>,
+    /// What the status stream is written as, from the command line.
+    format: status::Format,
+    /// `--status-stream` or `--swaybar` was given, which writes it whatever the config says.
+    stream_requested: bool,
+}
+
+impl Sinks {
+    pub fn new(config: &Config, stream: Option<status::Format>, overlay: Option<Overlay>) -> Sinks {
+        let mut sinks = Sinks {
+            sinks: vec![],
+            overlay,
+            format: stream.unwrap_or(status::Format::Json),
+            stream_requested: stream.is_some(),
+        };
+        sinks.configure(config);
+        sinks
+    }
+
+    /// Starts the sinks that were added to the config and stops the ones taken out of it.
+    pub fn configure(&mut self, config: &Config) {
+        let wanted = |kind: Sink| {
+            config.sinks.contains(&kind) || kind == Sink::StatusStream && self.stream_requested
+        };
+
+        self.sinks.retain(|(kind, _)| wanted(*kind));
+        for (_, sink) in &mut self.sinks {
+            sink.configure(config);
+        }
+
+        for kind in Sink::ALL {
+            if !wanted(kind) || self.sinks.iter().any(|(running, _)| *running == kind) {
+                continue;
+            }
+            if let Some(sink) = kind.start(config, self.format, self.overlay.as_ref()) {
+                self.sinks.push((kind, sink));
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn event(&mut self, view: &View, status: &Status) {
+        for (_, sink) in &mut self.sinks {
+            sink.event(view, status);
+        }
+    }
+
+    pub fn poll(&mut self, view: &View) {
+        for (_, sink) in &mut self.sinks {
+            sink.poll(view);
+        }
+    }
+
+    pub fn toggle_requested(&mut self) -> bool {
+        // Every sink is asked, so none keeps a request for later.
+        self.sinks.iter_mut().fold(false, |requested, (_, sink)| {
+            sink.toggle_requested() || requested
+        })
+    }
+}
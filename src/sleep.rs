@@ -0,0 +1,116 @@
+// Prompts left over from before the system slept. A verification that was waiting when the lid
+// closed has almost always been given up on by the time it opens, PAM timing out or fprintd
+// dropping the claim, without a last signal saying so, which leaves the bar stuck.
+//
+// So logind's PrepareForSleep marks every prompt as stale. On resume, fprintd is asked whether
+// each reader still wants a finger, and the ones it doesn't are dropped. Versions too old to say
+// get a moment to re-assert them with a signal instead.
+
+use std::time::{Duration, Instant};
+
+use dbus::{ffidisp::Connection as DbusConnection, MessageType};
+use fprint_prompt::fprint;
+
+use crate::prompts::{Prompts, SessionKind};
+
+/// How long fprintd has to say something about a stale prompt when it can't be asked.
+const GRACE: Duration = Duration::from_secs(5);
+
+pub struct SleepMonitor {
+    /// logind is always on the system bus, whichever bus fprintd is followed on.
+    system: Option<DbusConnection>,
+    /// When we resumed, while stale prompts wait for fprintd.
+    resumed: Option<Instant>,
+}
+
+impl SleepMonitor {
+    pub fn new() -> SleepMonitor {
+        let system = DbusConnection::new_system()
+            .and_then(|system| {
+                system.add_match(
+                    "type='signal',interface='org.freedesktop.login1.Manager',\
+                     member='PrepareForSleep'",
+                )?;
+                Ok(system)
+            })
+            .map_err(|e| log::warn!("could not follow suspend and resume: {e}"))
+            .ok();
+
+        SleepMonitor {
+            system,
+            resumed: None,
+        }
+    }
+
+    /// Catches up on suspend and resume without blocking, returning whether prompts were
+    /// dropped. `fprintd` is the connection fprintd is followed on.
+    pub fn poll(&mut self, prompts: &mut Prompts, fprintd: &DbusConnection) -> bool {
+        let mut dropped = false;
+
+        while let Some(msg) = self
+            .system
+            .as_ref()
+            .and_then(|system| system.incoming(0).next())
+        {
+            if msg.msg_type() != MessageType::Signal
+                || msg.member().as_deref() != Some("PrepareForSleep")
+            {
+                continue;
+            }
+
+            match msg.read1::<bool>() {
+                Ok(true) => {
+                    log::info!("Going to sleep");
+                    prompts.mark_stale();
+                    self.resumed = None;
+                }
+                Ok(false) => {
+                    log::info!("Resumed");
+                    dropped |= self.resume(prompts, fprintd);
+                }
+                Err(e) => log::warn!("could not read PrepareForSleep: {e}"),
+            }
+        }
+
+        if self
+            .resumed
+            .is_some_and(|resumed| resumed.elapsed() >= GRACE)
+        {
+            self.resumed = None;
+            if prompts.drop_stale() {
+                log::info!("Dropped prompts that weren't re-asserted since resuming");
+                dropped = true;
+            }
+        }
+
+        dropped
+    }
+
+    fn resume(&mut self, prompts: &mut Prompts, fprintd: &DbusConnection) -> bool {
+        let stale: Vec<_> = prompts
+            .sessions()
+            .iter()
+            .filter(|session| session.stale)
+            .map(|session| session.device.clone())
+            .collect();
+
+        let mut dropped = false;
+        for device in stale {
+            match fprint::Device::new(fprintd, device.clone()).finger_needed() {
+                Ok(true) => prompts.keep(&device),
+                Ok(false) => {
+                    log::info!("{device} stopped waiting while asleep");
+                    for kind in [SessionKind::Verify, SessionKind::Enroll] {
+                        dropped |= prompts.finish(&device, kind);
+                    }
+                }
+                // Left stale, for fprintd to re-assert in time.
+                Err(e) => {
+                    log::info!("could not tell whether {device} is still in use: {e}");
+                    self.resumed = Some(Instant::now());
+                }
+            }
+        }
+        dropped
+    }
+}
@@ -0,0 +1,378 @@
+// Snapshot tests for the renderer: each style in each state, at scale 1 and 2, drawn with
+// [`render`] and compared with the golden images in tests/snapshots. After changing how something
+// looks on purpose, write them again with `UPDATE_SNAPSHOTS=1 cargo test`.
+//
+// The images are PNGs without compression, written and read by hand here. Channels may be off by
+// a little, for the float math that differs between platforms.
+
+use std::{path::PathBuf, time::Duration};
+
+use smithay_client_toolkit::shell::wlr_layer::Anchor;
+use wayland_client::protocol::wl_shm;
+
+use crate::{
+    config::{BarSize, Millimeters, PositionInfo, Shadow, ShadowStyle},
+    render,
+    shape::{Mark, Shape},
+    theme::{self, Palette, Style},
+    RenderState,
+};
+
+/// How far a channel can be from the golden image.
+const TOLERANCE: u8 = 2;
+
+#[derive(Default)]
+pub(crate) struct Case {
+    pub(crate) name: &'static str,
+    /// As written in the config. Empty while idle.
+    colors: Vec<u32>,
+    progress: Vec<Option<f32>>,
+    shapes: Vec<Option<Shape>>,
+    /// In pixels at scale 1, like the bar.
+    notch: u32,
+    segments: Vec<(u32, u32)>,
+    /// The dock's color, as written in the config, when docked.
+    dock: Option<u32>,
+}
+
+/// The dock's color in the docked cases.
+const DOCK: u32 = 0xFF_203040;
+
+pub(crate) fn cases() -> Vec<Case> {
+    let colors = Palette::Default.colors();
+    vec![
+        Case {
+            name: "verify",
+            colors: vec![colors.prompt],
+            progress: vec![Some(0.75)],
+            shapes: vec![],
+            ..Case::default()
+        },
+        Case {
+            name: "enroll",
+            colors: vec![colors.enroll],
+            progress: vec![Some(0.4)],
+            shapes: vec![],
+            ..Case::default()
+        },
+        Case {
+            name: "both",
+            colors: vec![colors.prompt, colors.enroll],
+            progress: vec![None, Some(0.4)],
+            shapes: vec![None, Some(Shape::Dot)],
+            ..Case::default()
+        },
+        Case {
+            name: "success",
+            colors: vec![colors.success],
+            progress: vec![None],
+            shapes: vec![Some(Shape::Check)],
+            ..Case::default()
+        },
+        Case {
+            name: "failure",
+            colors: vec![colors.failure],
+            progress: vec![None],
+            shapes: vec![Some(Shape::Cross)],
+            ..Case::default()
+        },
+        Case {
+            name: "fallback",
+            colors: vec![colors.failure],
+            progress: vec![None],
+            shapes: vec![Some(Shape::Password)],
+            ..Case::default()
+        },
+        // Straight alpha in the config, which has to come out premultiplied.
+        Case {
+            name: "translucent",
+            colors: vec![0x80_33AAFF],
+            progress: vec![Some(0.5)],
+            shapes: vec![],
+            ..Case::default()
+        },
+        Case {
+            name: "idle",
+            ..Case::default()
+        },
+        Case {
+            name: "docked-idle",
+            dock: Some(DOCK),
+            ..Case::default()
+        },
+        Case {
+            name: "notch",
+            colors: vec![colors.prompt],
+            progress: vec![None],
+            notch: 16,
+            ..Case::default()
+        },
+        Case {
+            name: "segments",
+            colors: vec![colors.prompt, colors.enroll],
+            progress: vec![None, None],
+            segments: vec![(0, 16), (28, 20)],
+            ..Case::default()
+        },
+    ]
+}
+
+pub(crate) fn state(case: &Case, style: Style, scale: u32) -> RenderState {
+    let shadow = Shadow {
+        style: ShadowStyle::Drop,
+        size: 3 * scale,
+        color: 0xC0000000,
+    };
+    let bar = BarSize {
+        length: 48 * scale,
+        thickness: 12 * scale,
+    };
+
+    RenderState {
+        pos: PositionInfo {
+            thickness: bar.thickness + shadow.margin(),
+            length: bar.length + shadow.margin(),
+            edge: Anchor::TOP,
+            close_to: Anchor::LEFT,
+            offset: 0,
+            notch: case.notch * scale,
+            segments: case
+                .segments
+                .iter()
+                .map(|&(offset, length)| (offset * scale, length * scale))
+                .collect(),
+            millimeters: Millimeters::default(),
+        },
+        bar,
+        colors: case
+            .colors
+            .iter()
+            .copied()
+            .map(theme::premultiply)
+            .collect(),
+        progress: case.progress.clone(),
+        shapes: case
+            .shapes
+            .iter()
+            .map(|shape| shape.map(Mark::from))
+            .collect(),
+        elapsed: Duration::from_millis(300),
+        style,
+        shadow,
+        covering: false,
+        idle_color: case.dock.map(theme::premultiply),
+        ruler: None,
+        format: wl_shm::Format::Argb8888,
+        spans: vec![],
+    }
+}
+
+#[test]
+fn idle_is_transparent_unless_docked() {
+    let cases = cases();
+    let case = |name| cases.iter().find(|case| case.name == name).unwrap();
+    for style in Style::ALL {
+        for scale in [1, 2] {
+            let mut idle = state(case("idle"), style, scale);
+            let mut canvas =
+                vec![0xAB; idle.pos.win_width() as usize * idle.pos.win_height() as usize * 4];
+            render(&mut canvas, &mut idle);
+            assert!(
+                canvas.chunks_exact(4).all(|pixel| pixel[3] == 0),
+                "{} at {scale}x isn't transparent while idle",
+                style.name()
+            );
+
+            // Docked, the bar is drawn in the dock's color instead. The glow only fades into it.
+            let mut docked = state(case("docked-idle"), style, scale);
+            let size = docked.pos.win_width() as usize * docked.pos.win_height() as usize;
+            let mut canvas = vec![0; size * 4];
+            render(&mut canvas, &mut docked);
+            let dock = theme::premultiply(DOCK).to_le_bytes();
+            assert!(
+                canvas.chunks_exact(4).any(|pixel| match style {
+                    Style::Glow => pixel[3] > 0,
+                    _ => pixel == dock,
+                }),
+                "{} at {scale}x isn't in the dock's color while docked",
+                style.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn segments_inside_the_notch_leave_the_rest_of_the_bar() {
+    let case = Case {
+        name: "swallowed",
+        colors: vec![Palette::Default.colors().prompt],
+        progress: vec![None],
+        ..Case::default()
+    };
+    for style in Style::ALL {
+        // `segments = "140:10"` and `notch = 40` on a 300 pixel bar.
+        let mut frame = state(&case, style, 1);
+        frame.pos.length += 300 - frame.bar.length;
+        frame.bar.length = 300;
+        frame.pos.notch = 40;
+        frame.pos.segments = vec![(140, 10)];
+        let size = frame.pos.win_width() as usize * frame.pos.win_height() as usize;
+        let mut canvas = vec![0; size * 4];
+        render(&mut canvas, &mut frame);
+
+        assert_eq!(frame.spans, [(0., 130.), (170., 300.)], "{}", style.name());
+        assert!(canvas.chunks_exact(4).any(|pixel| pixel[3] > 0));
+    }
+}
+
+#[test]
+fn renderer_matches_snapshots() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut failed = vec![];
+
+    for style in Style::ALL {
+        for case in cases() {
+            for scale in [1, 2] {
+                let mut state = state(&case, style, scale);
+                let (width, height) = (state.pos.win_width(), state.pos.win_height());
+                let mut canvas = vec![0; width as usize * height as usize * 4];
+                render(&mut canvas, &mut state);
+                let image = encode(width, height, &canvas);
+
+                let name = format!("{}-{}-{scale}x.png", style.name(), case.name);
+                let path = dir.join(&name);
+                if update {
+                    std::fs::create_dir_all(&dir).unwrap();
+                    std::fs::write(&path, &image).unwrap();
+                    continue;
+                }
+
+                let matches = std::fs::read(&path)
+                    .ok()
+                    .and_then(|golden| decode(&golden))
+                    .zip(decode(&image))
+                    .is_some_and(|(golden, actual)| {
+                        golden.len() == actual.len()
+                            && golden
+                                .iter()
+                                .zip(&actual)
+                                .all(|(golden, actual)| golden.abs_diff(*actual) <= TOLERANCE)
+                    });
+                if !matches {
+                    // Next to the golden image, to compare them.
+                    let _ = std::fs::write(path.with_extension("new.png"), &image);
+                    failed.push(name);
+                }
+            }
+        }
+    }
+
+    assert!(
+        failed.is_empty(),
+        "drawn differently than in tests/snapshots: {}",
+        failed.join(", ")
+    );
+}
+
+/// A PNG of a premultiplied ARGB canvas, as straight RGBA without compression.
+fn encode(width: u32, height: u32, canvas: &[u8]) -> Vec<u8> {
+    let mut raw = vec![];
+    for row in canvas.chunks_exact(width as usize * 4) {
+        // No filter.
+        raw.push(0);
+        for pixel in row.chunks_exact(4) {
+            let &[blue, green, red, alpha] = pixel else {
+                unreachable!()
+            };
+            let straight = |channel: u8| match alpha {
+                0 => 0,
+                alpha => ((channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8,
+            };
+            raw.extend([straight(red), straight(green), straight(blue), alpha]);
+        }
+    }
+
+    // zlib, with the data in stored blocks.
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        let length = block.len() as u16;
+        zlib.extend(length.to_le_bytes());
+        zlib.extend((!length).to_le_bytes());
+        zlib.extend(block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+
+    let mut header = width.to_be_bytes().to_vec();
+    header.extend(height.to_be_bytes());
+    // 8 bits per channel, RGBA, no interlacing.
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", &header), (b"IDAT", &zlib), (b"IEND", &vec![])] {
+        png.extend((data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend(kind);
+        png.extend(data);
+        let crc = crc32(&png[start..]);
+        png.extend(crc.to_be_bytes());
+    }
+    png
+}
+
+/// The filtered rows of a PNG written by [`encode`], or None for any other PNG.
+fn decode(png: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = png.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    let mut zlib = vec![];
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = rest.get(4..8)?;
+        let data = rest.get(8..8 + length)?;
+        if kind == b"IDAT" {
+            zlib.extend(data);
+        }
+        rest = &rest[(12 + length).min(rest.len())..];
+    }
+
+    let mut blocks = zlib.get(2..)?;
+    let mut raw = vec![];
+    loop {
+        let (&last, rest) = blocks.split_first()?;
+        // Only stored blocks.
+        if last & !1 != 0 {
+            return None;
+        }
+        let length = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+        raw.extend(rest.get(4..4 + length)?);
+        blocks = &rest[4 + length..];
+        if last == 1 {
+            return Some(raw);
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
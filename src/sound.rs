@@ -0,0 +1,108 @@
+// Chimes reminding the user that a verification is waiting, for when the reader is somewhere the
+// bar can't be seen from, like behind the lid.
+//
+// They're played with canberra-gtk-play, so they go through the desktop's mixer and follow its
+// event sound settings. An installed `fprint-prompt/sounds/reminder.oga` is played instead of the
+// sound theme's bell.
+
+use std::{
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use crate::{
+    assets::{self, Kind},
+    sinks::{OutputSink, View},
+};
+
+const PLAYER: &str = "canberra-gtk-play";
+const SOUND_ID: &str = "bell";
+const SOUND_FILE: &str = "reminder.oga";
+/// Each chime comes half as long after the last as that one did after the one before, down to
+/// this.
+const MIN_INTERVAL: Duration = Duration::from_secs(2);
+/// The first chime is this many decibels quieter than full volume, and each one after is
+/// [`VOLUME_STEP`] louder.
+const FIRST_VOLUME: f32 = -12.;
+const VOLUME_STEP: f32 = 3.;
+
+#[derive(Default)]
+pub struct Reminder {
+    /// Chimes since the reader was last touched.
+    chimed: u32,
+    /// The last chime, until it's done playing.
+    playing: Option<Child>,
+    /// Set once the player turned out to be missing, so it isn't looked for every time.
+    unavailable: bool,
+}
+
+impl Reminder {
+    /// Chimes if one is due, `after` seconds without the reader being touched and more often
+    /// after that. `ignored_for` is how long that's been, or None while nothing is waiting.
+    pub fn remind(&mut self, after: u32, ignored_for: Option<Duration>) {
+        if let Some(child) = &mut self.playing {
+            if !matches!(child.try_wait(), Ok(None)) {
+                self.playing = None;
+            }
+        }
+
+        let after = Duration::from_secs(after.into());
+        let Some(ignored_for) = ignored_for.filter(|ignored| !after.is_zero() && *ignored >= after)
+        else {
+            self.chimed = 0;
+            return;
+        };
+        if ignored_for < self.due(after) || self.playing.is_some() || self.unavailable {
+            return;
+        }
+
+        self.play();
+        self.chimed += 1;
+    }
+
+    /// How long without a touch the next chime is due at.
+    fn due(&self, after: Duration) -> Duration {
+        let mut due = after;
+        let mut interval = after;
+        for _ in 0..self.chimed {
+            interval = (interval / 2).max(MIN_INTERVAL);
+            due += interval;
+        }
+        due
+    }
+
+    fn play(&mut self) {
+        let volume = (FIRST_VOLUME + VOLUME_STEP * self.chimed as f32).min(0.);
+
+        let mut command = Command::new(PLAYER);
+        match assets::find(Kind::Sounds, SOUND_FILE) {
+            Some(path) => command.arg("--file").arg(path),
+            None => command.arg("--id").arg(SOUND_ID),
+        };
+        command
+            .arg("--description")
+            .arg("Fingerprint reminder")
+            .arg("--volume")
+            .arg(volume.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        match command.spawn() {
+            Ok(child) => self.playing = Some(child),
+            Err(e) => {
+                log::warn!("could not play a reminder with {PLAYER}: {e}");
+                self.unavailable = true;
+            }
+        }
+    }
+}
+
+impl OutputSink for Reminder {
+    fn poll(&mut self, view: &View) {
+        self.remind(
+            view.config.attention.chime_after,
+            view.ignored_for.filter(|_| !view.paused),
+        );
+    }
+}
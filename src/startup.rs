@@ -0,0 +1,311 @@
+// What the overlay can't start without, checked in order as it starts. A missing piece ends it
+// right there, with a message saying what to do about it and an exit status telling which piece
+// it was, instead of a panic from somewhere inside. Everything else, like layer-shell or a
+// reader, only makes it do with less.
+//
+// Wayland and fprintd can be let off with `--require`, for session managers that would rather
+// have the overlay carry on without them: without Wayland as notifications or only the status
+// stream, and without fprintd waiting for it to show up.
+//
+// Started by systemd, as a user service that's enabled, the overlay can come up before the
+// compositor has made its socket, or before it has put WAYLAND_DISPLAY in systemd's environment
+// for services to find. The compositor is waited for then, picking WAYLAND_DISPLAY up from systemd
+// once it's there, for longer while graphical-session.target is still starting.
+
+use std::{
+    fmt::Display,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use dbus::ffidisp::{stdintf::org_freedesktop_dbus::Properties, Connection as DbusConnection};
+use fprint_prompt::FprintMonitor;
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::wl_registry,
+    ConnectError, Connection, Dispatch, QueueHandle,
+};
+
+use crate::{bus, exit, fprint, sandbox};
+
+static REQUIRED: OnceLock<Vec<Capability>> = OnceLock::new();
+
+/// How long the compositor is waited for under systemd, and how long at most while the graphical
+/// session is still starting.
+const COMPOSITOR_WAIT: Duration = Duration::from_secs(30);
+const SESSION_STARTING_WAIT: Duration = Duration::from_secs(120);
+const RETRY_EVERY: Duration = Duration::from_millis(250);
+
+const SYSTEMD: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const TIMEOUT_MS: i32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    Wayland,
+    Fprintd,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 2] = [Capability::Wayland, Capability::Fprintd];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Wayland => "wayland",
+            Capability::Fprintd => "fprintd",
+        }
+    }
+
+    /// Parses a `--require` list, like `wayland,fprintd`. An empty one requires nothing.
+    pub fn parse_list(list: &str) -> Result<Vec<Capability>, String> {
+        list.split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Capability::ALL
+                    .into_iter()
+                    .find(|capability| capability.name() == name)
+                    .ok_or_else(|| format!("--require doesn't know {name:?}"))
+            })
+            .collect()
+    }
+}
+
+/// Makes only these fatal when missing. Only the first call has an effect.
+pub fn select_required(required: Vec<Capability>) {
+    let _ = REQUIRED.set(required);
+}
+
+/// Whether missing `capability` ends the overlay. Everything is, unless `--require` said otherwise.
+pub fn is_required(capability: Capability) -> bool {
+    REQUIRED
+        .get()
+        .is_none_or(|required| required.contains(&capability))
+}
+
+pub enum Failure {
+    /// Couldn't connect to the compositor, or it hung up while we asked for its globals.
+    Wayland(String),
+    Compositor,
+    Shm(String),
+    Viewporter,
+    Fprintd(String),
+}
+
+impl Failure {
+    pub fn code(&self) -> i32 {
+        match self {
+            Failure::Wayland(_) => exit::WAYLAND,
+            Failure::Compositor => exit::COMPOSITOR,
+            Failure::Shm(_) => exit::SHM,
+            Failure::Viewporter => exit::VIEWPORTER,
+            Failure::Fprintd(_) => exit::FPRINTD,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::Wayland(e) => format!("could not connect to the Wayland compositor: {e}"),
+            Failure::Compositor => "the compositor has no wl_compositor".to_string(),
+            Failure::Shm(e) => format!("could not share memory with the compositor: {e}"),
+            Failure::Viewporter => "the compositor doesn't support wp_viewporter".to_string(),
+            Failure::Fprintd(e) => format!("could not listen to fprintd: {e}"),
+        }
+    }
+
+    fn hint(&self) -> String {
+        match self {
+            Failure::Wayland(_) if std::env::var_os("WAYLAND_DISPLAY").is_none() => {
+                "WAYLAND_DISPLAY isn't set: start fprint-prompt from inside the Wayland session, \
+                 or import it into the systemd user environment."
+                    .to_string()
+            }
+            Failure::Wayland(_) => "Check that the compositor is still running.".to_string(),
+            Failure::Compositor | Failure::Viewporter => {
+                "fprint-prompt needs a compositor that supports these, like sway, Hyprland, \
+                 KWin or GNOME."
+                    .to_string()
+            }
+            Failure::Shm(_) => "Check that /dev/shm or memfd is available.".to_string(),
+            Failure::Fprintd(_) => sandbox::permission_hint().unwrap_or_else(|| {
+                "Install fprintd and make sure it is D-Bus activatable.".to_string()
+            }),
+        }
+    }
+
+    /// Says what went wrong and what to do about it.
+    pub fn log(&self) {
+        log::error!("{}", self.message());
+        log::error!("{}", self.hint());
+    }
+
+    /// Says what went wrong and what to do about it, and exits with the failure's status.
+    pub fn exit(self) -> ! {
+        self.log();
+        log::error!("`fprint-prompt doctor` checks everything fprint-prompt needs");
+        std::process::exit(self.code());
+    }
+}
+
+/// The value, or exits with the failure made from the error.
+pub fn require<T, E: Display>(result: Result<T, E>, failure: impl FnOnce(String) -> Failure) -> T {
+    result.unwrap_or_else(|e| failure(e.to_string()).exit())
+}
+
+/// Connects to the compositor, waiting for it a while when started by systemd.
+pub fn connect_wayland() -> Result<Connection, ConnectError> {
+    // systemd sets this for everything it starts.
+    if std::env::var_os("INVOCATION_ID").is_none() {
+        return Connection::connect_to_env();
+    }
+
+    let started = Instant::now();
+    let systemd = DbusConnection::new_session()
+        .map_err(|e| log::warn!("could not connect to the systemd user manager: {e}"))
+        .ok();
+    let mut waiting = false;
+    loop {
+        if let Some(systemd) = &systemd {
+            pick_up_wayland_display(systemd);
+        }
+        let e = match Connection::connect_to_env() {
+            Ok(conn) => {
+                if waiting {
+                    log::info!("The compositor is up after {:?}", started.elapsed());
+                }
+                return Ok(conn);
+            }
+            Err(e) => e,
+        };
+
+        let session = systemd
+            .as_ref()
+            .and_then(graphical_session_state)
+            .unwrap_or_default();
+        let wait = match session.as_str() {
+            "activating" => SESSION_STARTING_WAIT,
+            _ => COMPOSITOR_WAIT,
+        };
+        if started.elapsed() >= wait {
+            return Err(e);
+        }
+        if !waiting {
+            log::info!(
+                "Waiting for the compositor, graphical-session.target is {}: {e}",
+                if session.is_empty() {
+                    "unknown"
+                } else {
+                    &session
+                }
+            );
+            waiting = true;
+        }
+        std::thread::sleep(RETRY_EVERY);
+    }
+}
+
+/// Sets WAYLAND_DISPLAY from the systemd user manager's environment, where compositors put it for
+/// services started after them.
+fn pick_up_wayland_display(systemd: &DbusConnection) {
+    let Ok(environment) = systemd
+        .with_path(SYSTEMD, SYSTEMD_PATH, TIMEOUT_MS)
+        .get::<Vec<String>>("org.freedesktop.systemd1.Manager", "Environment")
+    else {
+        return;
+    };
+    let display = environment
+        .iter()
+        .find_map(|variable| variable.strip_prefix("WAYLAND_DISPLAY="));
+    if let Some(display) = display {
+        if std::env::var("WAYLAND_DISPLAY").as_deref() != Ok(display) {
+            log::info!("Picked up WAYLAND_DISPLAY={display} from systemd");
+            std::env::set_var("WAYLAND_DISPLAY", display);
+        }
+    }
+}
+
+/// Like `active` or `activating`, or None when systemd can't say.
+fn graphical_session_state(systemd: &DbusConnection) -> Option<String> {
+    let manager = systemd.with_path(SYSTEMD, SYSTEMD_PATH, TIMEOUT_MS);
+    let loaded: Result<(dbus::Path,), _> = manager.method_call(
+        "org.freedesktop.systemd1.Manager",
+        "GetUnit",
+        ("graphical-session.target",),
+    );
+    let unit = match loaded {
+        Ok((unit,)) => unit,
+        // Units that aren't loaded are inactive.
+        Err(e) if e.name() == Some("org.freedesktop.systemd1.NoSuchUnit") => {
+            return Some("inactive".to_string())
+        }
+        Err(_) => return None,
+    };
+    systemd
+        .with_path(SYSTEMD, unit, TIMEOUT_MS)
+        .get::<String>("org.freedesktop.systemd1.Unit", "ActiveState")
+        .ok()
+}
+
+/// Whether the compositor can be connected to and has the globals the overlay can't do without,
+/// for finding out before committing to it.
+pub fn check_wayland() -> Result<(), Failure> {
+    let conn = connect_wayland().map_err(|e| Failure::Wayland(e.to_string()))?;
+    let (globals, _) =
+        registry_queue_init::<Probe>(&conn).map_err(|e| Failure::Wayland(e.to_string()))?;
+
+    let has = |interface: &str| {
+        globals
+            .contents()
+            .with_list(|list| list.iter().any(|global| global.interface == interface))
+    };
+    if !has("wl_compositor") {
+        return Err(Failure::Compositor);
+    }
+    if !has("wl_shm") {
+        return Err(Failure::Shm("the compositor has no wl_shm".to_string()));
+    }
+    if !has("wp_viewporter") {
+        return Err(Failure::Viewporter);
+    }
+    Ok(())
+}
+
+/// Starts listening to fprintd on the selected bus. With `readers`, also asks for the readers,
+/// which starts fprintd if it isn't yet, and says if there are none.
+pub fn listen_to_fprintd(readers: bool) -> FprintMonitor {
+    let monitor = require(
+        bus::selected()
+            .connect()
+            .and_then(FprintMonitor::with_connection),
+        |e| Failure::Fprintd(format!("{e} on {}", bus::selected())),
+    );
+    if !readers {
+        return monitor;
+    }
+
+    match fprint::get_devices(monitor.connection()) {
+        Ok(devices) if devices.is_empty() => {
+            log::warn!("No fingerprint readers yet, prompting once one is plugged in")
+        }
+        Ok(_) => (),
+        Err(e) if is_required(Capability::Fprintd) => Failure::Fprintd(e.to_string()).exit(),
+        Err(e) => {
+            Failure::Fprintd(e.to_string()).log();
+            log::warn!("Carrying on without fprintd, prompting once it's there");
+        }
+    }
+    monitor
+}
+
+struct Probe;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Probe {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
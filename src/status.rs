@@ -0,0 +1,149 @@
+// `--status-stream`: the prompt's state as newline-delimited JSON on stdout, for bar modules like
+// Waybar's custom module (with `return-type = "json"`) or eww's `deflisten`.
+//
+// Every line is a complete object. `text` and `class` are what Waybar looks at, the rest is there
+// for scripts.
+//
+// `--swaybar`: the same state as a block in the i3bar/swaybar protocol, for use as a
+// `status_command`. Clicking the block hides or shows the overlay.
+
+use std::{
+    fmt::Write,
+    io::BufRead,
+    sync::mpsc::{self, Receiver},
+};
+
+pub use fprint_prompt::rpc::json_string;
+
+use crate::{
+    prompts::{Prompts, SessionKind},
+    sinks::{OutputSink, View},
+};
+
+/// What the stream reports after each fprintd event.
+pub struct Status<'a> {
+    pub device: Option<&'a str>,
+    pub finger: Option<&'a str>,
+    /// The result carried by the event being reported, like "verify-match".
+    pub result: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Swaybar,
+}
+
+pub struct StatusStream {
+    format: Format,
+    last: Option<String>,
+    /// The [`Prompts::notice`] of the last line, which ends without an event.
+    notice: Option<(&'static str, &'static str)>,
+    /// Click events from swaybar, read from stdin on another thread.
+    clicks: Option<Receiver<()>>,
+}
+
+impl StatusStream {
+    pub fn new(format: Format) -> Self {
+        let clicks = (format == Format::Swaybar).then(|| {
+            println!("{{\"version\":1,\"click_events\":true}}");
+            // The body is an infinite array of status lines.
+            println!("[");
+
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                // Also an infinite array, with one click event object per line.
+                for line in std::io::stdin().lock().lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if line.contains('{') && sender.send(()).is_err() {
+                        break;
+                    }
+                }
+            });
+            receiver
+        });
+
+        StatusStream {
+            format,
+            last: None,
+            notice: None,
+            clicks,
+        }
+    }
+
+    /// Whether our block was clicked since the last call.
+    pub fn clicked(&self) -> bool {
+        self.clicks
+            .as_ref()
+            .is_some_and(|clicks| clicks.try_iter().count() > 0)
+    }
+
+    /// Prints the current state, unless it's the same as the last line.
+    pub fn send(&mut self, prompts: &Prompts, status: &Status) {
+        self.notice = prompts.notice();
+        let state = match prompts.primary().map(|session| session.kind) {
+            None => self.notice.map_or("idle", |(state, _)| state),
+            Some(SessionKind::Verify) => "verify",
+            Some(SessionKind::Enroll) => "enroll",
+        };
+        let text = match prompts.primary() {
+            Some(session) => &session.text,
+            None => self.notice.map_or("", |(_, text)| text),
+        };
+
+        let mut line = String::new();
+        match self.format {
+            Format::Json => {
+                let _ = write!(
+                    line,
+                    "{{\"state\":{},\"text\":{},\"class\":{},\"device\":{},\"finger\":{},\"result\":{}}}",
+                    json_string(Some(state)),
+                    json_string(Some(text)),
+                    json_string(Some(state)),
+                    json_string(status.device),
+                    json_string(status.finger),
+                    json_string(status.result),
+                );
+            }
+            Format::Swaybar => {
+                let _ = write!(
+                    line,
+                    "[{{\"name\":\"fprint-prompt\",\"instance\":{},\"full_text\":{},\"urgent\":{}}}],",
+                    json_string(Some(state)),
+                    json_string(Some(text)),
+                    state != "idle",
+                );
+            }
+        }
+
+        if self.last.as_ref() != Some(&line) {
+            println!("{line}");
+            self.last = Some(line);
+        }
+    }
+}
+
+impl OutputSink for StatusStream {
+    fn event(&mut self, view: &View, status: &Status) {
+        self.send(view.prompts, status);
+    }
+
+    fn poll(&mut self, view: &View) {
+        if view.prompts.notice() != self.notice {
+            self.send(
+                view.prompts,
+                &Status {
+                    device: None,
+                    finger: None,
+                    result: None,
+                },
+            );
+        }
+    }
+
+    fn toggle_requested(&mut self) -> bool {
+        self.clicked()
+    }
+}
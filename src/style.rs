@@ -0,0 +1,341 @@
+// How the prompt is drawn inside its window. Every style gets the same bar-shaped area to fill,
+// and picks a [`Style`](crate::theme::Style) from the config.
+
+use std::{f32::consts::TAU, time::Duration};
+
+use smithay_client_toolkit::shell::wlr_layer::Anchor;
+
+use crate::{
+    animation,
+    config::{BarSize, PositionInfo},
+    shape::Mark,
+};
+
+/// How much of an indicator's color is left where it hasn't filled up yet.
+const UNFILLED: f32 = 0.3;
+
+/// Everything a style needs to draw one frame.
+pub struct Frame<'a> {
+    /// The window's geometry.
+    pub pos: &'a PositionInfo,
+    /// The bar within the window, which the style draws in.
+    pub bar: BarSize,
+    /// Premultiplied, one for each indicator, most important first. Never empty.
+    pub colors: &'a [u32],
+    /// How far each indicator has filled up, from 0 to 1, for those that make progress.
+    pub progress: &'a [Option<f32>],
+    /// The pieces of the bar that are drawn, from [`spans`].
+    pub spans: &'a [(f32, f32)],
+    /// The [`Mark`] on each indicator, if any. Empty when `shapes` is off.
+    pub shapes: &'a [Option<Mark>],
+    /// Since the prompt started, for styles that move.
+    pub elapsed: Duration,
+}
+
+impl Frame<'_> {
+    /// The middle of a pixel in bar coordinates: how far along the bar from its `close_to` end,
+    /// and how far from the screen edge. Outside the bar for pixels around it. The gaps between
+    /// the spans are left out, so the spans meet as if they were one bar.
+    fn local(&self, x: u32, y: u32) -> (f32, f32) {
+        let (along, across) = self.window_local(x, y);
+
+        let mut before = 0.;
+        let mut previous_end = self.spans.first().map_or(0., |&(start, _)| start);
+        for &(start, end) in self.spans {
+            if along < start {
+                return (before + (along - previous_end).min(0.), across);
+            }
+            if along < end {
+                return (before + along - start, across);
+            }
+            before += end - start;
+            previous_end = end;
+        }
+        (before + along - previous_end, across)
+    }
+
+    /// Like [`Frame::local`], with the gaps still in the bar.
+    fn window_local(&self, x: u32, y: u32) -> (f32, f32) {
+        let (bar_x, bar_y, _, _) = self.pos.bar_rect(self.bar);
+        let (x, y) = (x as f32 + 0.5 - bar_x as f32, y as f32 + 0.5 - bar_y as f32);
+
+        let (along, across) = match self.pos.edge {
+            Anchor::TOP | Anchor::BOTTOM => (x, y),
+            _ => (y, x),
+        };
+        let along = if self.pos.close_to == Anchor::TOP || self.pos.close_to == Anchor::LEFT {
+            along
+        } else {
+            self.bar.length as f32 - along
+        };
+        let across = if self.pos.edge == Anchor::TOP || self.pos.edge == Anchor::LEFT {
+            across
+        } else {
+            self.bar.thickness as f32 - across
+        };
+
+        (along, across)
+    }
+
+    /// The length of the spans put together.
+    fn length(&self) -> f32 {
+        self.spans.iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// How far a pixel is inside a gap between spans, 0 outside them.
+    pub fn gap_depth(&self, x: u32, y: u32) -> f32 {
+        let (along, _) = self.window_local(x, y);
+        self.spans
+            .windows(2)
+            .map(|pair| (along - pair[0].1).min(pair[1].0 - along))
+            .fold(0., f32::max)
+    }
+
+    /// Which indicator a point along the bar belongs to.
+    fn segment(&self, along: f32) -> usize {
+        let segment = along.max(0.) as usize * self.colors.len() / (self.length() as usize).max(1);
+        segment.min(self.colors.len() - 1)
+    }
+
+    /// An indicator's color at `fraction` of the way through it, dimmed past its progress.
+    fn filled(&self, segment: usize, fraction: f32) -> u32 {
+        match self.progress.get(segment) {
+            Some(Some(progress)) if fraction > *progress => {
+                animation::fade(self.colors[segment], UNFILLED)
+            }
+            _ => self.colors[segment],
+        }
+    }
+
+    /// The color along the bar, with a gap between segments so they read as separate indicators.
+    fn segment_color(&self, along: f32) -> u32 {
+        let segment = self.segment(along);
+        if along >= 1. && self.segment(along - 1.) != segment {
+            return 0;
+        }
+
+        let part = self.length() / self.colors.len() as f32;
+        self.filled(segment, along / part - segment as f32)
+    }
+
+    /// How far a point is outside the bar.
+    fn bar_distance(&self, along: f32, across: f32) -> f32 {
+        let outside = |value: f32, size: f32| (-value).max(value - size).max(0.);
+        let dx = outside(along, self.length());
+        let dy = outside(across, self.bar.thickness as f32);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// The circle in the middle of an indicator's part of the bar, as its center along the bar
+    /// and its radius.
+    fn circle(&self, segment: usize) -> (f32, f32) {
+        let part = self.length() / self.colors.len() as f32;
+        let radius = part.min(self.bar.thickness as f32) / 2.;
+        (part * (segment as f32 + 0.5), radius)
+    }
+
+    /// The indicator a pixel belongs to, with how far the pixel is from the middle of its circle
+    /// across and down the window, and the circle's radius.
+    pub fn mark(&self, x: u32, y: u32) -> (usize, f32, f32, f32) {
+        let (along, across) = self.local(x, y);
+        let segment = self.segment(along);
+        let (center, radius) = self.circle(segment);
+
+        // Back from bar coordinates to the window's directions.
+        let along = if self.pos.close_to == Anchor::TOP || self.pos.close_to == Anchor::LEFT {
+            along - center
+        } else {
+            center - along
+        };
+        let across = across - self.bar.thickness as f32 / 2.;
+        let across = if self.pos.edge == Anchor::TOP || self.pos.edge == Anchor::LEFT {
+            across
+        } else {
+            -across
+        };
+        let (dx, dy) = match self.pos.edge {
+            Anchor::TOP | Anchor::BOTTOM => (along, across),
+            _ => (across, along),
+        };
+
+        (segment, dx, dy, radius)
+    }
+
+    /// Distance from a point to the middle of its indicator's circle, with that indicator.
+    fn circle_distance(&self, along: f32, across: f32) -> (usize, f32, f32) {
+        let segment = self.segment(along);
+        let (center, radius) = self.circle(segment);
+        let (dx, dy) = (along - center, across - self.bar.thickness as f32 / 2.);
+        (segment, (dx * dx + dy * dy).sqrt(), radius)
+    }
+
+    /// The color of a point in its indicator's circle, which fills up clockwise from the side
+    /// facing the screen edge.
+    fn circle_color(&self, along: f32, across: f32) -> u32 {
+        let segment = self.segment(along);
+        let (center, _) = self.circle(segment);
+        let (dx, dy) = (along - center, across - self.bar.thickness as f32 / 2.);
+        let turn = dx.atan2(-dy).rem_euclid(TAU) / TAU;
+        self.filled(segment, turn)
+    }
+}
+
+/// The pieces of a bar of `bar`'s size that are drawn, as where each starts and ends along it:
+/// the geometry's `segments`, or the whole bar if it has none or none of them fit, with the notch
+/// cut out of the middle. Written to `spans`, which keeps its room from frame to frame. Empty only
+/// for a bar of no length.
+pub fn spans(pos: &PositionInfo, bar: BarSize, spans: &mut Vec<(f32, f32)>) {
+    let length = bar.length;
+    let clamp =
+        |&(offset, segment): &(u32, u32)| (offset.min(length), (offset + segment).min(length));
+
+    spans.clear();
+    cut_notch(pos.notch, length, pos.segments.iter().map(clamp), spans);
+    // Segments that don't fit, or that all fall inside the notch.
+    if spans.is_empty() {
+        cut_notch(pos.notch, length, [(0, length)].into_iter(), spans);
+    }
+}
+
+/// Adds the segments to `spans` with the notch cut out of the middle of the bar.
+fn cut_notch(
+    notch: u32,
+    length: u32,
+    segments: impl Iterator<Item = (u32, u32)>,
+    spans: &mut Vec<(f32, f32)>,
+) {
+    // Leaving at least a pixel on each side of it.
+    let notch = notch.min(length.saturating_sub(2));
+    let (gap_start, gap_end) = ((length - notch) / 2, (length - notch) / 2 + notch);
+    let pieces = segments.flat_map(|(start, end)| {
+        if notch > 0 {
+            [(start, end.min(gap_start)), (start.max(gap_end), end)]
+        } else {
+            [(start, end), (0, 0)]
+        }
+    });
+
+    // Overlapping segments are drawn as one.
+    for (start, end) in pieces {
+        if start >= end {
+            continue;
+        }
+        let (start, end) = (start as f32, end as f32);
+        match spans.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = last_end.max(end),
+            _ => spans.push((start, end)),
+        }
+    }
+}
+
+pub trait StyleRenderer {
+    /// The premultiplied color of a pixel of the window.
+    fn color(&self, frame: &Frame, x: u32, y: u32) -> u32;
+
+    /// How far a pixel is outside what's drawn, for the shadow. 0 on top of it.
+    fn distance(&self, frame: &Frame, x: u32, y: u32) -> f32;
+
+    /// A part of the window that's fully covered by opaque color, if there is one.
+    fn opaque_rect(&self, _frame: &Frame) -> Option<(u32, u32, u32, u32)> {
+        None
+    }
+}
+
+/// A solid bar, split between the indicators.
+pub struct Bar;
+
+impl StyleRenderer for Bar {
+    fn color(&self, frame: &Frame, x: u32, y: u32) -> u32 {
+        let (along, across) = frame.local(x, y);
+        if frame.bar_distance(along, across) > 0. {
+            return 0;
+        }
+        frame.segment_color(along)
+    }
+
+    fn distance(&self, frame: &Frame, x: u32, y: u32) -> f32 {
+        let (along, across) = frame.local(x, y);
+        frame.bar_distance(along, across)
+    }
+
+    fn opaque_rect(&self, frame: &Frame) -> Option<(u32, u32, u32, u32)> {
+        (frame.colors.len() == 1
+            && frame.colors[0] >> 24 == 0xFF
+            && frame.progress[0].is_none()
+            && frame.spans == [(0., frame.bar.length as f32)])
+        .then(|| frame.pos.bar_rect(frame.bar))
+    }
+}
+
+/// A dot for each indicator, breathing in and out.
+pub struct Dot;
+
+impl Dot {
+    const PERIOD: Duration = Duration::from_millis(1200);
+
+    fn radius(frame: &Frame, radius: f32) -> f32 {
+        let phase = frame.elapsed.as_secs_f32() / Self::PERIOD.as_secs_f32();
+        radius * (0.8 + 0.2 * (phase * TAU).cos())
+    }
+}
+
+impl StyleRenderer for Dot {
+    fn color(&self, frame: &Frame, x: u32, y: u32) -> u32 {
+        let (along, across) = frame.local(x, y);
+        let (_, distance, radius) = frame.circle_distance(along, across);
+        let coverage = (Self::radius(frame, radius) - distance + 0.5).clamp(0., 1.);
+        animation::fade(frame.circle_color(along, across), coverage)
+    }
+
+    fn distance(&self, frame: &Frame, x: u32, y: u32) -> f32 {
+        let (along, across) = frame.local(x, y);
+        let (_, distance, radius) = frame.circle_distance(along, across);
+        (distance - Self::radius(frame, radius)).max(0.)
+    }
+}
+
+/// A ring for each indicator.
+pub struct Ring;
+
+impl Ring {
+    fn width(radius: f32) -> f32 {
+        (radius * 0.3).max(2.)
+    }
+}
+
+impl StyleRenderer for Ring {
+    fn color(&self, frame: &Frame, x: u32, y: u32) -> u32 {
+        let (along, across) = frame.local(x, y);
+        let (_, distance, radius) = frame.circle_distance(along, across);
+        let outer = (radius - distance + 0.5).clamp(0., 1.);
+        let inner = (distance - (radius - Self::width(radius)) + 0.5).clamp(0., 1.);
+        animation::fade(frame.circle_color(along, across), outer.min(inner))
+    }
+
+    fn distance(&self, frame: &Frame, x: u32, y: u32) -> f32 {
+        let (along, across) = frame.local(x, y);
+        let (_, distance, radius) = frame.circle_distance(along, across);
+        let width = Self::width(radius);
+        (distance - radius).max(radius - width - distance).max(0.)
+    }
+}
+
+/// Light spilling in from the screen edge, fading out towards the middle of the screen.
+pub struct Glow;
+
+impl StyleRenderer for Glow {
+    fn color(&self, frame: &Frame, x: u32, y: u32) -> u32 {
+        let (along, across) = frame.local(x, y);
+        if frame.bar_distance(along, across) > 0. {
+            return 0;
+        }
+
+        let strength = (1. - across / frame.bar.thickness as f32).clamp(0., 1.);
+        animation::fade(frame.segment_color(along), strength * strength)
+    }
+
+    fn distance(&self, frame: &Frame, x: u32, y: u32) -> f32 {
+        let (along, across) = frame.local(x, y);
+        frame.bar_distance(along, across)
+    }
+}
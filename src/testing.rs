@@ -0,0 +1,237 @@
+//! Stand-ins for fprintd and fprint-prompt, for testing what's built on this crate without a
+//! reader, a compositor or either of them running.
+//!
+//! A [`FakeSource`] plays events to anything that takes a [`PromptSource`], like a locker built on
+//! the monitor. A [`FakeServer`] answers on a control socket like fprint-prompt does, with a
+//! [`State`] the test sets, for widgets that speak [`rpc`](crate::rpc).
+//!
+//! ```
+//! use fprint_prompt::{testing::FakeSource, MonitorEvent, PromptSource};
+//! use std::time::Duration;
+//!
+//! let source = FakeSource::new();
+//! source.verify("/net/reactivated/Fprint/Device/0", "right-index-finger", true);
+//!
+//! let mut finished = false;
+//! while let Some((_, event)) = source.next_timeout(Duration::ZERO) {
+//!     if let MonitorEvent::Fprint(fprint_prompt::FprintEvent::VerifyStatus { done, .. }) = event {
+//!         finished = done == Some(true);
+//!     }
+//! }
+//! assert!(finished);
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path as FsPath, PathBuf},
+    time::Duration,
+};
+
+use dbus::Path;
+
+use crate::{
+    monitor::{MonitorEvent, PromptSource},
+    rpc::{self, Method, State},
+    signals::FprintEvent,
+};
+
+/// Events queued by the test, handed out in order. Never waits: once they're all out, there are
+/// no more.
+#[derive(Default)]
+pub struct FakeSource {
+    events: RefCell<VecDeque<(Path<'static>, MonitorEvent)>>,
+}
+
+impl FakeSource {
+    pub fn new() -> FakeSource {
+        FakeSource::default()
+    }
+
+    /// Queues an event from `device`, an fprintd object path.
+    ///
+    /// # Panics
+    ///
+    /// If `device` isn't a valid object path.
+    pub fn push(&self, device: &str, event: MonitorEvent) {
+        let device = Path::new(device.to_string()).expect("not an object path");
+        self.events.borrow_mut().push_back((device, event));
+    }
+
+    /// Queues a whole verification, as fprintd sends it: a finger is asked for, then scanned.
+    pub fn verify(&self, device: &str, finger: &str, matched: bool) {
+        self.push(device, MonitorEvent::FingerNeeded(true));
+        self.push(
+            device,
+            MonitorEvent::Fprint(FprintEvent::VerifyFingerSelected {
+                finger_name: Some(finger.to_string()),
+            }),
+        );
+        let result = if matched {
+            "verify-match"
+        } else {
+            "verify-no-match"
+        };
+        self.push(
+            device,
+            MonitorEvent::Fprint(FprintEvent::VerifyStatus {
+                result: Some(result.to_string()),
+                done: Some(true),
+            }),
+        );
+        self.push(device, MonitorEvent::FingerNeeded(false));
+    }
+
+    /// Queues a whole enrollment of `stages` scans, each of them accepted.
+    pub fn enroll(&self, device: &str, stages: u32) {
+        for stage in 1..=stages {
+            let result = if stage == stages {
+                "enroll-completed"
+            } else {
+                "enroll-stage-passed"
+            };
+            self.push(
+                device,
+                MonitorEvent::Fprint(FprintEvent::EnrollStatus {
+                    result: Some(result.to_string()),
+                    done: Some(stage == stages),
+                }),
+            );
+        }
+    }
+
+    /// Whether every queued event has been handed out.
+    pub fn is_empty(&self) -> bool {
+        self.events.borrow().is_empty()
+    }
+}
+
+impl PromptSource for FakeSource {
+    fn next_timeout(&self, _timeout: Duration) -> Option<(Path<'static>, MonitorEvent)> {
+        self.events.borrow_mut().pop_front()
+    }
+}
+
+/// A control socket that answers requests like fprint-prompt's, from a state the test sets. `show`
+/// and `hide` change whether it's visible, and every request is kept for the test to look at.
+///
+/// ```
+/// use std::io::{BufRead, BufReader, Write};
+/// use std::os::unix::net::UnixStream;
+///
+/// use fprint_prompt::rpc::{Method, Request, State};
+/// use fprint_prompt::testing::FakeServer;
+///
+/// let path = std::env::temp_dir().join(format!("fprint-prompt-doctest-{}.sock", std::process::id()));
+/// let mut server = FakeServer::bind(&path).unwrap();
+///
+/// let mut widget = UnixStream::connect(&path).unwrap();
+/// server.accept().unwrap();
+/// writeln!(widget, "{}", Request::new(1, Method::Hide).to_json()).unwrap();
+/// assert!(server.answer().unwrap());
+///
+/// let mut reply = String::new();
+/// BufReader::new(&widget).read_line(&mut reply).unwrap();
+/// assert!(reply.contains("\"result\":true"));
+/// assert_eq!(server.requests(), [Method::Hide]);
+/// assert!(!server.state().visible);
+/// ```
+pub struct FakeServer {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<(BufReader<UnixStream>, bool)>,
+    state: State,
+    requests: Vec<Method>,
+}
+
+impl FakeServer {
+    /// Listens at `path`, showing an empty state.
+    pub fn bind(path: &FsPath) -> io::Result<FakeServer> {
+        let listener = UnixListener::bind(path)?;
+        Ok(FakeServer {
+            listener,
+            path: path.to_path_buf(),
+            clients: vec![],
+            state: State {
+                visible: true,
+                ..State::default()
+            },
+            requests: vec![],
+        })
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Changes the state, notifying the clients that subscribed.
+    pub fn set_state(&mut self, state: State) -> io::Result<()> {
+        self.state = state;
+        self.notify()
+    }
+
+    /// The requests answered so far, oldest first.
+    pub fn requests(&self) -> &[Method] {
+        &self.requests
+    }
+
+    /// Waits for a client to connect.
+    pub fn accept(&mut self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        self.clients.push((BufReader::new(stream), false));
+        Ok(())
+    }
+
+    /// Answers the next request of the client that connected last, waiting for it. False if the
+    /// client hung up instead.
+    pub fn answer(&mut self) -> io::Result<bool> {
+        let Some((reader, subscribed)) = self.clients.last_mut() else {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "no client yet"));
+        };
+
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            self.clients.pop();
+            return Ok(false);
+        }
+
+        let (method, reply) = rpc::answer(&line, &self.state);
+        if let Some(reply) = reply {
+            writeln!(reader.get_mut(), "{reply}")?;
+        }
+        let Some(method) = method else {
+            return Ok(true);
+        };
+        self.requests.push(method);
+
+        let was_visible = self.state.visible;
+        match method {
+            Method::Show | Method::Hide => self.state.visible = method == Method::Show,
+            Method::GetState => (),
+            Method::Subscribe => *subscribed = true,
+        }
+
+        if self.state.visible != was_visible {
+            self.notify()?;
+        }
+        Ok(true)
+    }
+
+    fn notify(&mut self) -> io::Result<()> {
+        let line = self.state.notification().to_string();
+        for (reader, subscribed) in &mut self.clients {
+            if *subscribed {
+                writeln!(reader.get_mut(), "{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,304 @@
+// Built-in color palettes, and themes from assets. A theme can start from another with
+// `inherit = "name"` and only say what it changes.
+//
+// The colorblind ones lean on the Okabe-Ito colors, which stay apart under each kind of color
+// vision deficiency. Success and failure also never rely on color alone: success holds and fades
+// out, failure blinks, and these palettes turn on the [shapes](crate::shape) that mark them.
+
+use std::{borrow::Cow, path::PathBuf};
+
+use crate::{
+    assets::{self, Kind},
+    config::{self, Colors, Entry, Shadow, ShadowStyle, MAX_FLASHES},
+    style::{self, StyleRenderer},
+};
+
+/// Shifts a color towards orange by dimming green a little and blue a lot, like night light
+/// does. Works on premultiplied colors, since alpha is left alone.
+pub fn warm(argb: u32, warmth: f32) -> u32 {
+    let [blue, green, red, alpha] = argb.to_le_bytes();
+    let scale = |channel: u8, by: f32| (channel as f32 * (1. - warmth * by)).round() as u8;
+
+    u32::from_le_bytes([scale(blue, 0.6), scale(green, 0.25), red, alpha])
+}
+
+/// Converts a straight-alpha color, as written in the config, to premultiplied.
+pub fn premultiply(argb: u32) -> u32 {
+    let [blue, green, red, alpha] = argb.to_le_bytes();
+    let scale = |channel: u8| (channel as u32 * alpha as u32 / 255) as u8;
+
+    u32::from_le_bytes([scale(blue), scale(green), scale(red), alpha])
+}
+
+/// Draws a premultiplied color over another.
+pub fn over(top: u32, bottom: u32) -> u32 {
+    let top = top.to_le_bytes();
+    let bottom = bottom.to_le_bytes();
+    let behind = 255 - top[3] as u32;
+
+    u32::from_le_bytes(std::array::from_fn(|i| {
+        (top[i] as u32 + (bottom[i] as u32 * behind + 127) / 255) as u8
+    }))
+}
+
+/// How strong the shadow is at a distance from the bar, from 0 to 1.
+pub fn shadow_opacity(shadow: &Shadow, distance: f32) -> f32 {
+    let size = shadow.size as f32;
+    match shadow.style {
+        ShadowStyle::None => 0.,
+        ShadowStyle::Drop => (1. - distance / size).max(0.).powi(2),
+        // Anti-aliased at the outer edge.
+        ShadowStyle::Outline => (size + 0.5 - distance).clamp(0., 1.),
+    }
+}
+
+/// The shape the prompt is drawn as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    Bar,
+    Dot,
+    Ring,
+    Glow,
+}
+
+impl Style {
+    pub const ALL: [Style; 4] = [Style::Bar, Style::Dot, Style::Ring, Style::Glow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Style::Bar => "bar",
+            Style::Dot => "dot",
+            Style::Ring => "ring",
+            Style::Glow => "glow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Style> {
+        Self::ALL.into_iter().find(|style| style.name() == name)
+    }
+
+    /// Whether it changes over time by itself, like the dot breathing.
+    pub fn moves(self) -> bool {
+        self == Style::Dot
+    }
+
+    pub fn renderer(self) -> &'static dyn StyleRenderer {
+        match self {
+            Style::Bar => &style::Bar,
+            Style::Dot => &style::Dot,
+            Style::Ring => &style::Ring,
+            Style::Glow => &style::Glow,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    Monochrome,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 5] = [
+        Palette::Default,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::Tritanopia,
+        Palette::Monochrome,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Default => "default",
+            Palette::Deuteranopia => "deuteranopia",
+            Palette::Protanopia => "protanopia",
+            Palette::Tritanopia => "tritanopia",
+            Palette::Monochrome => "monochrome",
+        }
+    }
+
+    pub fn colors(self) -> Colors {
+        match self {
+            Palette::Default => Colors {
+                prompt: 0xFFFFFFFF,
+                enroll: 0xFF3584E4,
+                success: 0xFF33D17A,
+                failure: 0xFFE01B24,
+            },
+            // Red and green look alike to both, blue and orange don't.
+            Palette::Deuteranopia | Palette::Protanopia => Colors {
+                prompt: 0xFFFFFFFF,
+                enroll: 0xFF0072B2,
+                success: 0xFF56B4E9,
+                failure: 0xFFE69F00,
+            },
+            // Blue and yellow are the ones that get confused here.
+            Palette::Tritanopia => Colors {
+                prompt: 0xFFFFFFFF,
+                enroll: 0xFF009E73,
+                success: 0xFF009E73,
+                failure: 0xFFD55E00,
+            },
+            Palette::Monochrome => Colors {
+                prompt: 0xFFFFFFFF,
+                enroll: 0xFFAAAAAA,
+                success: 0xFFFFFFFF,
+                failure: 0xFF777777,
+            },
+        }
+    }
+}
+
+/// What a theme sets: every color, and the look of the prompt where it says so. Those go under
+/// the config's own settings, which are applied on top.
+#[derive(Clone)]
+pub struct Theme {
+    pub colors: Colors,
+    pub style: Option<Style>,
+    /// Like `attention.flashes` and `attention.intensity`.
+    pub flashes: Option<u32>,
+    pub intensity: Option<f32>,
+    pub shapes: Option<bool>,
+}
+
+impl From<Palette> for Theme {
+    fn from(palette: Palette) -> Theme {
+        Theme {
+            colors: palette.colors(),
+            style: None,
+            flashes: None,
+            intensity: None,
+            shapes: (palette != Palette::Default).then_some(true),
+        }
+    }
+}
+
+/// Every theme that can be picked with `palette`: the built-in palettes, then themes installed
+/// as assets, then those compiled in. An installed theme replaces a compiled-in one with the same
+/// name, but not a built-in palette.
+pub struct ThemeRegistry {
+    themes: Vec<(String, ThemeSource)>,
+}
+
+enum ThemeSource {
+    Palette(Palette),
+    File(PathBuf),
+    Embedded(&'static [u8]),
+}
+
+impl ThemeRegistry {
+    pub fn discover() -> ThemeRegistry {
+        let mut registry = ThemeRegistry {
+            themes: Palette::ALL
+                .iter()
+                .map(|palette| (palette.name().to_string(), ThemeSource::Palette(*palette)))
+                .collect(),
+        };
+
+        let installed = assets::installed(Kind::Themes)
+            .into_iter()
+            .map(|(name, path)| (name, ThemeSource::File(path)));
+        let embedded = assets::embedded(Kind::Themes)
+            .map(|(name, data)| (name.to_string(), ThemeSource::Embedded(data)));
+        for (file_name, source) in installed.chain(embedded) {
+            let Some(name) = file_name.strip_suffix(".theme") else {
+                continue;
+            };
+            if !registry.themes.iter().any(|(seen, _)| seen == name) {
+                registry.themes.push((name.to_string(), source));
+            }
+        }
+
+        registry
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// A theme, with whatever it inherits, or None if there's no theme with that name.
+    pub fn resolve(&self, name: &str) -> Option<Result<Theme, String>> {
+        self.resolve_from(name, &mut vec![])
+    }
+
+    /// Like [`ThemeRegistry::resolve`], `inheriting` the themes that led to this one.
+    fn resolve_from(
+        &self,
+        name: &str,
+        inheriting: &mut Vec<String>,
+    ) -> Option<Result<Theme, String>> {
+        let (_, source) = self.themes.iter().find(|(theme, _)| theme == name)?;
+        let data = match source {
+            ThemeSource::Palette(palette) => return Some(Ok(Theme::from(*palette))),
+            ThemeSource::File(path) => match std::fs::read(path) {
+                Ok(data) => Cow::Owned(data),
+                Err(e) => return Some(Err(format!("could not read {}: {e}", path.display()))),
+            },
+            ThemeSource::Embedded(data) => Cow::Borrowed(*data),
+        };
+
+        inheriting.push(name.to_string());
+        Some(self.parse_theme(&data, inheriting))
+    }
+
+    /// A theme file: `prompt`, `enroll`, `success` and `failure` like in `[colors]`, `style`, and
+    /// `flashes` and `intensity` like in `[attention]`. What's left out comes from the theme
+    /// named by `inherit`, or the default palette.
+    fn parse_theme(&self, data: &[u8], inheriting: &mut Vec<String>) -> Result<Theme, String> {
+        let text = std::str::from_utf8(data).map_err(|_| "not UTF-8".to_string())?;
+
+        let (entries, errors) = config::parse_entries(text);
+        if let Some(error) = errors.first() {
+            return Err(error.to_string());
+        }
+
+        // Inherited first, wherever in the file it's named, for the rest to go on top.
+        let mut theme = match entries.iter().find(|entry| entry.key == ["inherit"]) {
+            Some(entry) => self.inherit(entry, inheriting)?,
+            None => Theme::from(Palette::Default),
+        };
+        for entry in &entries {
+            apply(&mut theme, entry).map_err(|e| e.to_string())?;
+        }
+
+        Ok(theme)
+    }
+
+    fn inherit(&self, entry: &Entry, inheriting: &mut Vec<String>) -> Result<Theme, String> {
+        let parent = entry.string().map_err(|e| e.to_string())?;
+        if inheriting.contains(&parent) {
+            return Err(format!(
+                "it inherits from itself, through {} -> {parent}",
+                inheriting.join(" -> ")
+            ));
+        }
+
+        match self.resolve_from(&parent, inheriting) {
+            Some(Ok(theme)) => Ok(theme),
+            Some(Err(e)) => Err(format!("{parent:?}, which it inherits, is broken: {e}")),
+            None => Err(entry
+                .error(format!("there's no theme {parent:?} to inherit"))
+                .to_string()),
+        }
+    }
+}
+
+fn apply(theme: &mut Theme, entry: &Entry) -> Result<(), config::ParseError> {
+    let colors = &mut theme.colors;
+    match entry.key.join(".").as_str() {
+        "inherit" => (),
+        "prompt" => colors.prompt = entry.color()?,
+        "enroll" => colors.enroll = entry.color()?,
+        "success" => colors.success = entry.color()?,
+        "failure" => colors.failure = entry.color()?,
+        "style" => theme.style = Some(entry.style()?),
+        "flashes" => theme.flashes = Some(entry.u32_in(0..=MAX_FLASHES)?),
+        "intensity" => theme.intensity = Some(entry.fraction()?),
+        "shapes" => theme.shapes = Some(entry.bool()?),
+        _ => return Err(entry.unknown_key()),
+    }
+    Ok(())
+}
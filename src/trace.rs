@@ -0,0 +1,38 @@
+// `--wayland-trace`: logs the Wayland events that decide where the bar ends up and how big it is,
+// and how long a signal from fprintd takes to be committed, one line each with `key=value` fields.
+// A lot quieter than WAYLAND_DEBUG.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// `wayland_trace!("configure", surface = id, width = 8)` logs `configure surface=... width=8`.
+macro_rules! wayland_trace {
+    ($event:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::trace::enabled() {
+            log::info!(
+                target: "wayland",
+                concat!("{}" $(, " ", stringify!($key), "={}")*),
+                $event
+                $(, $value)*
+            );
+        } else {
+            // Still worth having in a crash report.
+            $crate::crash::record(format!(
+                concat!("wayland: {}" $(, " ", stringify!($key), "={}")*),
+                $event
+                $(, $value)*
+            ));
+        }
+    };
+}
+
+pub(crate) use wayland_trace;
@@ -0,0 +1,160 @@
+// What each fprintd signal does to the prompts. The overlay's loop and the one without Wayland
+// both follow fprintd through here, so the sinks show the same prompts whichever loop feeds them,
+// and the loops only add what's theirs, like picking the `[app]` settings or the lock screen.
+
+use std::{collections::HashMap, time::Duration};
+
+use dbus::{ffidisp::Connection as DbusConnection, Path};
+use fprint_prompt::signals::{FprintEvent, ScanResult};
+
+use crate::{
+    config::Config,
+    fprint::{self, EnrolledFingers},
+    prompts::{Outcome, Prompts, SessionKind},
+    reader_quirks::ReaderQuirks,
+    session,
+};
+
+/// What the prompts are followed with.
+pub struct Context<'a> {
+    pub dbus: &'a DbusConnection,
+    pub config: &'a Config,
+    pub seat_id: &'a str,
+    /// Whether verifications are PAM's, which asks for the password after too many tries. The
+    /// lock screen keeps asking for a finger instead.
+    pub for_pam: bool,
+}
+
+/// What a signal did to the prompts.
+pub enum Transition {
+    /// Nothing, like for another user's verification.
+    Ignored,
+    /// A verification asked for `finger`, which is None with `privacy`. `first` if no other
+    /// prompt was up.
+    Started { finger: Option<String>, first: bool },
+    /// A verification ended, after `duration`, with the outcome shown if it had a prompt.
+    Ended {
+        outcome: Option<Outcome>,
+        duration: Option<Duration>,
+    },
+    /// A verification or enrollment went on.
+    Changed,
+}
+
+/// Follows a signal from `device`. Readers have their quirks learned the first time they prompt.
+pub fn follow(
+    prompts: &mut Prompts,
+    enrolled_fingers: &mut EnrolledFingers,
+    reader_quirks: &mut HashMap<Path<'static>, ReaderQuirks>,
+    context: &Context,
+    device: &Path<'static>,
+    event: &FprintEvent,
+) -> Transition {
+    let Context {
+        dbus,
+        config,
+        seat_id,
+        for_pam,
+    } = *context;
+
+    match event {
+        FprintEvent::VerifyFingerSelected { finger_name } => {
+            if config.privacy {
+                log::info!("Finger selected");
+            } else {
+                log::info!("Finger selected: {:?}", finger_name);
+            }
+
+            if !config.show_other_users && !session::is_own_verification(dbus) {
+                log::info!("Ignoring verification for another user");
+                return Transition::Ignored;
+            }
+            if !session::is_device_on_seat(dbus, config, device, seat_id) {
+                log::info!("Ignoring verification on another seat");
+                return Transition::Ignored;
+            }
+            let Some(finger_name) = finger_name else {
+                return Transition::Ignored;
+            };
+
+            let text = if config.privacy {
+                fprint::PRIVATE_HINT.to_string()
+            } else {
+                enrolled_fingers.hint(dbus, device, finger_name)
+            };
+            crate::reader_quirks::learn(reader_quirks, dbus, config, device);
+
+            let first = prompts.is_empty();
+            prompts.start(device.clone(), SessionKind::Verify, text);
+            log::info!(
+                "Prompt: {:?}",
+                prompts.primary().map(|session| &session.text)
+            );
+            Transition::Started {
+                finger: (!config.privacy).then(|| finger_name.clone()),
+                first,
+            }
+        }
+        FprintEvent::VerifyStatus { result, done } => {
+            log::info!("Result: {:?}, Done: {:?}", result, done);
+            let scan = ScanResult::parse(result.as_deref());
+
+            if *done != Some(true) {
+                // A retry means a finger was on the reader, so the prompt isn't ignored.
+                if scan == ScanResult::Retry {
+                    prompts.retry(device, SessionKind::Verify);
+                } else {
+                    prompts.touch(device, SessionKind::Verify);
+                }
+                return Transition::Changed;
+            }
+
+            let duration = prompts.age(device, SessionKind::Verify);
+            let outcome = prompts.finish(device, SessionKind::Verify).then(|| {
+                if for_pam {
+                    return prompts.end_for_pam(scan, &config.attention);
+                }
+                let outcome = match scan {
+                    ScanResult::Match => Outcome::Success,
+                    _ => Outcome::Failure,
+                };
+                prompts.set_outcome(outcome);
+                outcome
+            });
+            Transition::Ended { outcome, duration }
+        }
+        FprintEvent::EnrollStatus { result, done } => {
+            log::info!("Enroll result: {:?}, Done: {:?}", result, done);
+
+            if *done == Some(true) {
+                prompts.finish(device, SessionKind::Enroll);
+                enrolled_fingers.invalidate(device);
+                return Transition::Changed;
+            }
+
+            // A reader can only do one at a time, so this wasn't a verification after all if it
+            // was picked up at startup.
+            prompts.finish(device, SessionKind::Verify);
+            prompts.start(
+                device.clone(),
+                SessionKind::Enroll,
+                "Touch the sensor again to enroll".to_string(),
+            );
+
+            if !prompts.has_stages(device) {
+                match fprint::Device::new(dbus, device.clone()).enroll_stages() {
+                    Ok(Some(total)) => prompts.set_stages(device, total),
+                    Ok(None) => (),
+                    Err(e) => log::warn!("could not get the enroll stages of {device}: {e}"),
+                }
+            }
+            // Retries don't count, so the progress holds until a scan is good.
+            match ScanResult::parse(result.as_deref()) {
+                ScanResult::StagePassed => prompts.stage_passed(device),
+                ScanResult::Retry => prompts.retry(device, SessionKind::Enroll),
+                _ => (),
+            }
+            Transition::Changed
+        }
+    }
+}
@@ -0,0 +1,301 @@
+// Optional StatusNotifierItem tray icon, with a small com.canonical.dbusmenu menu.
+//
+// Both interfaces are served by hand on a private session bus connection, polled from the main
+// loop. See https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/ and
+// https://github.com/AyatanaIndicators/libdbusmenu/blob/master/libdbusmenu-glib/dbus-menu.xml.
+
+use std::collections::HashMap;
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    ffidisp::Connection as DbusConnection,
+    Message, MessageType,
+};
+
+use crate::{
+    assets::{self, Kind},
+    ipc::{error, PROPERTIES_INTERFACE},
+};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+const ICON: &str = "auth-fingerprint-symbolic";
+/// Used instead of [`ICON`] when installed as an asset, as an SVG or PNG.
+const INSTALLED_ICON: &str = "fprint-prompt";
+/// For hosts that can't find either icon, which is always compiled in.
+const PIXMAP: &str = "fprint-prompt-32.argb";
+const PIXMAP_SIZE: i32 = 32;
+const TIMEOUT_MS: i32 = 1000;
+
+const MENU_PAUSE: i32 = 1;
+const MENU_PREVIEW: i32 = 2;
+const MENU_QUIT: i32 = 4;
+
+/// Something picked from the tray.
+pub enum TrayAction {
+    SetPaused(bool),
+    Preview,
+    Quit,
+}
+
+pub struct Tray {
+    dbus: DbusConnection,
+    prompting: bool,
+    paused: bool,
+    /// Bumped every time the menu changes, as dbusmenu wants.
+    revision: u32,
+}
+
+type MenuItem = (i32, PropMap, Vec<Variant<Box<dyn RefArg>>>);
+
+impl Tray {
+    /// Puts the icon in the tray. Fails if there's no session bus; a missing tray host is only
+    /// logged, since one may show up later.
+    pub fn new() -> Result<Tray, dbus::Error> {
+        let dbus = DbusConnection::new_session()?;
+
+        let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        dbus.register_name(&name, 0)?;
+        dbus.register_object_path(ITEM_PATH)?;
+        dbus.register_object_path(MENU_PATH)?;
+
+        let registered: Result<(), dbus::Error> = dbus
+            .with_path(WATCHER, WATCHER_PATH, TIMEOUT_MS)
+            .method_call(WATCHER, "RegisterStatusNotifierItem", (&name,));
+        if let Err(e) = registered {
+            log::warn!("could not register the tray icon: {e}");
+        }
+
+        Ok(Tray {
+            dbus,
+            prompting: false,
+            paused: false,
+            revision: 1,
+        })
+    }
+
+    /// Updates the icon's status and menu, notifying the tray host if anything changed.
+    pub fn set_state(&mut self, prompting: bool, paused: bool) {
+        if prompting != self.prompting {
+            self.prompting = prompting;
+            self.signal(ITEM_PATH, ITEM_INTERFACE, "NewStatus", |msg| {
+                msg.append1(self.status())
+            });
+        }
+
+        if paused != self.paused {
+            self.paused = paused;
+            self.revision += 1;
+            self.signal(MENU_PATH, MENU_INTERFACE, "LayoutUpdated", |msg| {
+                msg.append2(self.revision, 0i32)
+            });
+        }
+    }
+
+    /// Answers the tray host's calls, returning the first action picked by the user.
+    pub fn poll(&mut self) -> Option<TrayAction> {
+        let mut action = None;
+
+        while let Some(msg) = self.dbus.incoming(0).next() {
+            if msg.msg_type() != MessageType::MethodCall {
+                continue;
+            }
+
+            let (reply, picked) = self.handle(&msg);
+            action = action.or(picked);
+
+            if !msg.get_no_reply() {
+                let _ = self.dbus.send(reply);
+            }
+        }
+
+        action
+    }
+
+    fn handle(&self, msg: &Message) -> (Message, Option<TrayAction>) {
+        let path = msg.path().map(|path| path.to_string()).unwrap_or_default();
+        let interface = msg.interface().map(|i| i.to_string()).unwrap_or_default();
+        let member = msg.member().map(|m| m.to_string()).unwrap_or_default();
+
+        let reply = match (path.as_str(), interface.as_str(), member.as_str()) {
+            (_, PROPERTIES_INTERFACE, "GetAll") => {
+                msg.method_return().append1(self.properties(&path))
+            }
+            (_, PROPERTIES_INTERFACE, "Get") => {
+                let name: &str = msg.read2::<&str, &str>().map_or("", |(_, name)| name);
+                match self.properties(&path).remove(name) {
+                    Some(value) => msg.method_return().append1(value),
+                    None => error(msg, "org.freedesktop.DBus.Error.UnknownProperty", name),
+                }
+            }
+
+            (ITEM_PATH, ITEM_INTERFACE, "Activate" | "SecondaryActivate") => {
+                return (
+                    msg.method_return(),
+                    Some(TrayAction::SetPaused(!self.paused)),
+                );
+            }
+            (ITEM_PATH, ITEM_INTERFACE, "ContextMenu" | "Scroll") => msg.method_return(),
+
+            (MENU_PATH, MENU_INTERFACE, "GetLayout") => {
+                msg.method_return().append2(self.revision, self.layout())
+            }
+            (MENU_PATH, MENU_INTERFACE, "GetGroupProperties") => {
+                let ids: Vec<i32> = msg.read1().unwrap_or_default();
+                let properties: Vec<(i32, PropMap)> = self
+                    .menu_items()
+                    .into_iter()
+                    .filter(|(id, _, _)| ids.is_empty() || ids.contains(id))
+                    .map(|(id, properties, _)| (id, properties))
+                    .collect();
+                msg.method_return().append1(properties)
+            }
+            (MENU_PATH, MENU_INTERFACE, "Event") => {
+                let picked = match msg.read2::<i32, &str>() {
+                    Ok((MENU_PAUSE, "clicked")) => Some(TrayAction::SetPaused(!self.paused)),
+                    Ok((MENU_PREVIEW, "clicked")) => Some(TrayAction::Preview),
+                    Ok((MENU_QUIT, "clicked")) => Some(TrayAction::Quit),
+                    _ => None,
+                };
+                return (msg.method_return(), picked);
+            }
+            (MENU_PATH, MENU_INTERFACE, "AboutToShow") => msg.method_return().append1(false),
+
+            _ => error(msg, "org.freedesktop.DBus.Error.UnknownMethod", &member),
+        };
+
+        (reply, None)
+    }
+
+    fn status(&self) -> &'static str {
+        if self.prompting {
+            "NeedsAttention"
+        } else {
+            "Passive"
+        }
+    }
+
+    fn properties(&self, path: &str) -> PropMap {
+        let mut properties: PropMap = HashMap::new();
+        let mut set = |name: &str, value: Box<dyn RefArg>| {
+            properties.insert(name.to_string(), Variant(value));
+        };
+
+        match path {
+            ITEM_PATH => {
+                let tooltip = if self.prompting {
+                    "Waiting for a fingerprint"
+                } else {
+                    "Idle"
+                };
+
+                set("Category", Box::new("ApplicationStatus".to_string()));
+                set("Id", Box::new("fprint-prompt".to_string()));
+                set("Title", Box::new("fprint-prompt".to_string()));
+                set("Status", Box::new(self.status().to_string()));
+                let (icon, theme_path) = icon();
+                set("IconName", Box::new(icon.clone()));
+                set("AttentionIconName", Box::new(icon.clone()));
+                set("IconThemePath", Box::new(theme_path));
+                set("IconPixmap", Box::new(pixmap()));
+                set(
+                    "ToolTip",
+                    Box::new((
+                        icon,
+                        Vec::<(i32, i32, Vec<u8>)>::new(),
+                        "fprint-prompt".to_string(),
+                        tooltip.to_string(),
+                    )),
+                );
+                set("ItemIsMenu", Box::new(false));
+                set("Menu", Box::new(dbus::Path::from(MENU_PATH)));
+            }
+            MENU_PATH => {
+                set("Version", Box::new(3u32));
+                set("TextDirection", Box::new("ltr".to_string()));
+                set("Status", Box::new("normal".to_string()));
+                set("IconThemePath", Box::<Vec<String>>::default());
+            }
+            _ => (),
+        }
+
+        properties
+    }
+
+    fn menu_items(&self) -> Vec<MenuItem> {
+        let pause = if self.paused {
+            "Resume overlay"
+        } else {
+            "Pause overlay"
+        };
+
+        vec![
+            menu_item(MENU_PAUSE, &[("label", pause)]),
+            menu_item(MENU_PREVIEW, &[("label", "Preview geometry")]),
+            menu_item(3, &[("type", "separator")]),
+            menu_item(MENU_QUIT, &[("label", "Quit")]),
+        ]
+    }
+
+    /// The whole menu, as the root item with the others as its children.
+    fn layout(&self) -> MenuItem {
+        let mut root = menu_item(0, &[("children-display", "submenu")]);
+        root.2 = self
+            .menu_items()
+            .into_iter()
+            .map(|item| Variant(Box::new(item) as Box<dyn RefArg>))
+            .collect();
+        root
+    }
+
+    fn signal(
+        &self,
+        path: &str,
+        interface: &str,
+        member: &str,
+        append: impl FnOnce(Message) -> Message,
+    ) {
+        match Message::new_signal(path, interface, member) {
+            Ok(msg) => {
+                let _ = self.dbus.send(append(msg));
+            }
+            Err(e) => log::warn!("could not create {member} signal: {e}"),
+        }
+    }
+}
+
+fn menu_item(id: i32, properties: &[(&str, &str)]) -> MenuItem {
+    let properties = properties
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                Variant(Box::new(value.to_string()) as Box<dyn RefArg>),
+            )
+        })
+        .collect();
+
+    (id, properties, vec![])
+}
+
+/// The icon name, and the directory the tray host should look for it in first.
+fn icon() -> (String, String) {
+    ["svg", "png"]
+        .iter()
+        .find_map(|extension| assets::find(Kind::Icons, &format!("{INSTALLED_ICON}.{extension}")))
+        .and_then(|path| Some(path.parent()?.to_str()?.to_string()))
+        .map_or((ICON.to_string(), String::new()), |dir| {
+            (INSTALLED_ICON.to_string(), dir)
+        })
+}
+
+fn pixmap() -> Vec<(i32, i32, Vec<u8>)> {
+    assets::load(Kind::Icons, PIXMAP)
+        .filter(|data| data.len() == (PIXMAP_SIZE * PIXMAP_SIZE * 4) as usize)
+        .map(|data| vec![(PIXMAP_SIZE, PIXMAP_SIZE, data.into_owned())])
+        .unwrap_or_default()
+}
@@ -0,0 +1,54 @@
+// `fprint-prompt test`: starts a verification on the default reader and shows the overlay for it,
+// so the whole pipeline can be tried without going through PAM.
+
+use crate::{bus, exit, fprint, sandbox};
+
+pub fn run() -> i32 {
+    // The claim is tied to this connection, so it has to outlive the overlay.
+    let bus = bus::selected();
+    let dbus = match bus.connect() {
+        Ok(dbus) => dbus,
+        Err(e) => {
+            eprintln!("could not connect to {bus}: {e}");
+            return exit::FAILURE;
+        }
+    };
+
+    let device = match fprint::get_default_device(&dbus) {
+        Ok(device) => device,
+        Err(e) => {
+            eprintln!("no fingerprint reader available: {e}");
+            if let Some(hint) = sandbox::permission_hint() {
+                eprintln!("{hint}");
+            }
+            return exit::FAILURE;
+        }
+    };
+
+    if let Err(e) = device.claim(&fprint::current_username()) {
+        eprintln!("could not claim {}: {e}", device.path);
+        return exit::FAILURE;
+    }
+
+    println!("Touch the fingerprint sensor...");
+
+    let result = crate::run_overlay(crate::Mode::Test(&device));
+
+    let _ = device.verify_stop();
+    let _ = device.release();
+
+    match result.as_deref() {
+        Some("verify-match") => {
+            println!("Verification succeeded.");
+            exit::SUCCESS
+        }
+        Some(other) => {
+            println!("Verification ended with {other}.");
+            exit::FAILURE
+        }
+        None => {
+            println!("Verification was interrupted.");
+            exit::FAILURE
+        }
+    }
+}
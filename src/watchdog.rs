@@ -0,0 +1,113 @@
+// Noticing when the main loop hangs. It comes around at least every quarter of a second, since it
+// waits on the compositor and fprintd with a timeout, so when it hasn't for `watchdog` seconds
+// something blocked: a compositor that stopped reading from us, or a D-Bus call that never got
+// its answer. A prompt that hangs without a word is the worst kind of broken, as verifications
+// go on without anyone seeing them.
+//
+// The loop says which stage it's in, and a thread checks that it keeps moving. When it doesn't,
+// what the loop was stuck on is logged, and with `crash_reports` written to a report. Then we
+// start over: under systemd by exiting with [`exit::STALLED`] for `Restart=on-failure` to restart
+// us, otherwise by running ourselves again, which reconnects to everything. That's only tried
+// once, a second stall exits.
+
+use std::{
+    os::unix::process::CommandExt,
+    process::Command,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{crash, exit::STALLED};
+
+/// Set when we ran ourselves again after a stall.
+const RESTARTED_VAR: &str = "FPRINT_PROMPT_WATCHDOG_RESTARTED";
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// Seconds a stage may take, or 0 to never step in.
+    timeout: AtomicU32,
+    /// The stage the loop is in and when it got there.
+    stage: Mutex<(&'static str, Instant)>,
+}
+
+impl Watchdog {
+    pub fn start(timeout: u32) -> Watchdog {
+        let shared = Arc::new(Shared {
+            timeout: AtomicU32::new(timeout),
+            stage: Mutex::new(("starting", Instant::now())),
+        });
+
+        let watched = shared.clone();
+        let spawned = std::thread::Builder::new()
+            .name("watchdog".to_string())
+            .spawn(move || watch(&watched));
+        if let Err(e) = spawned {
+            log::warn!("could not start the watchdog: {e}");
+        }
+
+        Watchdog { shared }
+    }
+
+    /// Follows a change of the `watchdog` setting.
+    pub fn set_timeout(&self, timeout: u32) {
+        self.shared.timeout.store(timeout, Ordering::Relaxed);
+    }
+
+    /// Notes that the loop moved on to `stage`, e.g. "waiting on the compositor".
+    pub fn stage(&self, stage: &'static str) {
+        if let Ok(mut current) = self.shared.stage.lock() {
+            *current = (stage, Instant::now());
+        }
+    }
+}
+
+fn watch(shared: &Shared) {
+    loop {
+        std::thread::sleep(CHECK_INTERVAL);
+
+        let timeout = shared.timeout.load(Ordering::Relaxed);
+        let Ok((stage, since)) = shared.stage.lock().map(|stage| *stage) else {
+            return;
+        };
+        let stuck_for = since.elapsed();
+        if timeout == 0 || stuck_for < Duration::from_secs(timeout.into()) {
+            continue;
+        }
+
+        let description = format!(
+            "The main loop has been stuck {stage} for {} seconds",
+            stuck_for.as_secs()
+        );
+        log::error!("{description}");
+        crash::report_stall(&description);
+        recover();
+    }
+}
+
+/// Starts over, one way or another.
+fn recover() -> ! {
+    // systemd sets this for everything it starts, and knows better when to restart us.
+    let supervised = std::env::var_os("INVOCATION_ID").is_some();
+    if supervised || std::env::var_os(RESTARTED_VAR).is_some() {
+        log::error!("Exiting with status {STALLED}");
+        std::process::exit(STALLED);
+    }
+
+    log::error!("Starting over");
+    let error = match std::env::current_exe() {
+        Ok(exe) => Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .env(RESTARTED_VAR, "1")
+            .exec(),
+        Err(e) => e,
+    };
+    log::error!("could not start over, exiting with status {STALLED}: {error}");
+    std::process::exit(STALLED);
+}